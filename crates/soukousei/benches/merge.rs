@@ -0,0 +1,85 @@
+//! Merging many sources over a config with 500 fields
+
+#![allow(dead_code)]
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use soukousei::Layer;
+
+macro_rules! section {
+    ($name:ident { $($field:ident),* }) => {
+        #[derive(Debug, Layer)]
+        struct $name {
+            $(
+                #[layer(default = "0")]
+                $field: u64,
+            )*
+        }
+    };
+}
+
+#[rustfmt::skip]
+section!(Section {
+    f00, f01, f02, f03, f04, f05, f06, f07, f08, f09,
+    f10, f11, f12, f13, f14, f15, f16, f17, f18, f19,
+    f20, f21, f22, f23, f24, f25, f26, f27, f28, f29,
+    f30, f31, f32, f33, f34, f35, f36, f37, f38, f39,
+    f40, f41, f42, f43, f44, f45, f46, f47, f48, f49
+});
+
+#[derive(Debug, Layer)]
+struct Config {
+    #[layer(nested)]
+    s0: Section,
+    #[layer(nested)]
+    s1: Section,
+    #[layer(nested)]
+    s2: Section,
+    #[layer(nested)]
+    s3: Section,
+    #[layer(nested)]
+    s4: Section,
+    #[layer(nested)]
+    s5: Section,
+    #[layer(nested)]
+    s6: Section,
+    #[layer(nested)]
+    s7: Section,
+    #[layer(nested)]
+    s8: Section,
+    #[layer(nested)]
+    s9: Section,
+}
+
+fn sources() -> Vec<ConfigLayer> {
+    (0..10).map(|_| ConfigLayer::default()).collect()
+}
+
+fn merge(c: &mut Criterion) {
+    c.bench_function("merge 10 sources", |b| {
+        b.iter_batched(
+            sources,
+            |sources| {
+                let layer = sources.into_iter().fold(ConfigLayer::new(), Layer::merge);
+                black_box(layer)
+            },
+            criterion::BatchSize::SmallInput,
+        )
+    });
+
+    c.bench_function("merge_from 10 sources", |b| {
+        b.iter_batched(
+            sources,
+            |sources| {
+                let mut layer = ConfigLayer::new();
+                for source in sources {
+                    layer.merge_from(source);
+                }
+                black_box(layer)
+            },
+            criterion::BatchSize::SmallInput,
+        )
+    });
+}
+
+criterion_group!(benches, merge);
+criterion_main!(benches);
@@ -0,0 +1,163 @@
+//! [Parsers](crate::parse::FieldParser) of common third-party types, enabled with the `uuid`,
+//! `semver` and `regex` features.
+//!
+//! ```ignore
+//! #[derive(Layer)]
+//! struct Plugin {
+//!     #[layer(parse = "soukousei::adapters::UuidParser")]
+//!     id: uuid::Uuid,
+//!     #[layer(env = "PLUGIN_API", parse = "soukousei::adapters::SemverVersionReq")]
+//!     api: semver::VersionReq,
+//!     #[layer(parse = "soukousei::adapters::RegexPattern")]
+//!     include: regex::Regex,
+//! }
+//! ```
+
+use crate::parse::FieldParser;
+#[cfg(any(feature = "uuid", feature = "semver"))]
+use crate::parse::InvalidValueError;
+use crate::Report;
+
+/// Parser of [`uuid::Uuid`] in any of the formats accepted by [`uuid::Uuid::parse_str`]
+#[cfg(feature = "uuid")]
+pub struct UuidParser;
+
+#[cfg(feature = "uuid")]
+impl FieldParser for UuidParser {
+    type Value = uuid::Uuid;
+
+    fn parse(value: &str) -> Result<Self::Value, Report> {
+        uuid::Uuid::parse_str(value.trim()).map_err(|_| {
+            InvalidValueError::new(value, "UUID, e.g. `67e55044-10b1-426f-9247-bb680e5fe0c8`")
+                .into()
+        })
+    }
+
+    fn format(value: &Self::Value) -> String {
+        value.hyphenated().to_string()
+    }
+}
+
+/// Parser of [`semver::Version`]
+#[cfg(feature = "semver")]
+pub struct SemverVersion;
+
+#[cfg(feature = "semver")]
+impl FieldParser for SemverVersion {
+    type Value = semver::Version;
+
+    fn parse(value: &str) -> Result<Self::Value, Report> {
+        semver::Version::parse(value.trim())
+            .map_err(|_| InvalidValueError::new(value, "semantic version, e.g. `1.2.3`").into())
+    }
+
+    fn format(value: &Self::Value) -> String {
+        value.to_string()
+    }
+}
+
+/// Parser of [`semver::VersionReq`]
+#[cfg(feature = "semver")]
+pub struct SemverVersionReq;
+
+#[cfg(feature = "semver")]
+impl FieldParser for SemverVersionReq {
+    type Value = semver::VersionReq;
+
+    fn parse(value: &str) -> Result<Self::Value, Report> {
+        semver::VersionReq::parse(value.trim()).map_err(|_| {
+            InvalidValueError::new(value, "semantic version requirement, e.g. `>=1.2, <2`").into()
+        })
+    }
+
+    fn format(value: &Self::Value) -> String {
+        value.to_string()
+    }
+}
+
+/// Parser of [`regex::Regex`]. Invalid patterns are reported with [`InvalidRegexError`], which
+/// points to the invalid part of the pattern.
+#[cfg(feature = "regex")]
+pub struct RegexPattern;
+
+#[cfg(feature = "regex")]
+impl FieldParser for RegexPattern {
+    type Value = regex::Regex;
+
+    fn parse(value: &str) -> Result<Self::Value, Report> {
+        // `regex::Error` doesn't expose the span, so the syntax is checked separately
+        if let Err(err) = regex_syntax::Parser::new().parse(value) {
+            let (message, span) = match &err {
+                regex_syntax::Error::Parse(err) => (err.kind().to_string(), Some(*err.span())),
+                regex_syntax::Error::Translate(err) => (err.kind().to_string(), Some(*err.span())),
+                _ => (err.to_string(), None),
+            };
+            return Err(InvalidRegexError {
+                pattern: value.to_owned(),
+                message,
+                span: span.map(|span| span.start.offset..span.end.offset),
+            }
+            .into());
+        }
+        regex::Regex::new(value).map_err(|err| {
+            InvalidRegexError {
+                pattern: value.to_owned(),
+                message: err.to_string(),
+                span: None,
+            }
+            .into()
+        })
+    }
+
+    fn format(value: &Self::Value) -> String {
+        value.as_str().to_owned()
+    }
+}
+
+#[cfg(feature = "regex")]
+#[derive(Debug)]
+pub struct InvalidRegexError {
+    pattern: String,
+    message: String,
+    span: Option<std::ops::Range<usize>>,
+}
+
+#[cfg(feature = "regex")]
+impl InvalidRegexError {
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Byte range of the invalid part of the pattern, if known
+    pub fn span(&self) -> Option<std::ops::Range<usize>> {
+        self.span.clone()
+    }
+}
+
+#[cfg(feature = "regex")]
+impl std::fmt::Display for InvalidRegexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid regex `{}`: {}", self.pattern, self.message)?;
+        if let Some(span) = &self.span {
+            write!(f, " at position {}", span.start)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "regex")]
+impl std::error::Error for InvalidRegexError {}
+
+#[cfg(all(feature = "regex", feature = "miette"))]
+impl miette::Diagnostic for InvalidRegexError {
+    fn source_code(&self) -> Option<&dyn miette::SourceCode> {
+        Some(&self.pattern)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = miette::LabeledSpan> + '_>> {
+        let span = self.span.clone()?;
+        Some(Box::new(std::iter::once(
+            miette::LabeledSpan::new_with_span(Some(self.message.clone()), span),
+        )))
+    }
+}
@@ -0,0 +1,124 @@
+//! Recording every override during merging, see [`MergeAudit`].
+//!
+//! [`Provenance`] only keeps the source which won. When a value is ignored, the audit tells
+//! which source overrode it and with what:
+//!
+//! ```ignore
+//! let builder = ConfigBuilder::<ConfigLayer>::new()
+//!     .with_merge_audit()
+//!     .with_defaults()
+//!     .with_file("config.toml")?
+//!     .with_env(&StdEnv::new())?;
+//!
+//! // `db.port`: 5432 from config.toml is overridden by 6432 from env
+//! eprintln!("{}", builder.merge_audit().unwrap());
+//! ```
+//!
+//! Values of `#[layer(secret)]` fields are redacted.
+
+use crate::meta::{self, FieldMeta};
+use crate::provenance::Provenance;
+use std::fmt::{Display, Formatter};
+
+const REDACTED: &str = "<redacted>";
+
+/// Overrides in the order they happened
+#[derive(Debug, Clone, Default)]
+pub struct MergeAudit {
+    events: Vec<OverrideEvent>,
+}
+
+/// A field provided by a source replaced the value of an earlier one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverrideEvent {
+    /// Dot-separated path of the field
+    pub field: String,
+    /// Source whose value is lost
+    pub previous_source: String,
+    /// Source whose value wins, unless it is overridden again later
+    pub source: String,
+    /// The lost value, rendered as TOML, if the layer could be serialized
+    pub previous_value: Option<String>,
+    /// The winning value, rendered as TOML, if the layer could be serialized
+    pub value: Option<String>,
+}
+
+impl MergeAudit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn events(&self) -> &[OverrideEvent] {
+        &self.events
+    }
+
+    /// Overrides of a single field, e.g. to find out why its value is ignored
+    pub fn events_for<'a>(&'a self, field: &'a str) -> impl Iterator<Item = &'a OverrideEvent> {
+        self.events.iter().filter(move |x| x.field == field)
+    }
+
+    /// Record overrides of `provided` fields by `source`, before they are merged and recorded
+    /// in `provenance`. `merged` and `incoming` are serialized layers.
+    pub(crate) fn record(
+        &mut self,
+        source: &str,
+        provided: &[String],
+        provenance: &Provenance,
+        merged: Option<&toml::Value>,
+        incoming: Option<&toml::Value>,
+        fields: &'static [FieldMeta],
+    ) {
+        for field in provided {
+            let Some(previous_source) = provenance.source_of(field) else {
+                continue;
+            };
+            let secret = meta::find(fields, field).is_some_and(|x| x.secret);
+            let render = |value: Option<&toml::Value>| {
+                let value = lookup(value?, field)?;
+                Some(if secret {
+                    REDACTED.to_owned()
+                } else {
+                    value.to_string()
+                })
+            };
+            self.events.push(OverrideEvent {
+                field: field.clone(),
+                previous_source: previous_source.to_owned(),
+                source: source.to_owned(),
+                previous_value: render(merged),
+                value: render(incoming),
+            });
+        }
+    }
+}
+
+/// Renders as lines like `` `db.port`: 5432 from config.toml is overridden by 6432 from env ``
+impl Display for MergeAudit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for event in self.events.iter() {
+            writeln!(f, "{event}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for OverrideEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}`: ", self.field)?;
+        if let Some(value) = &self.previous_value {
+            write!(f, "{value} ")?;
+        }
+        write!(f, "from {} is overridden by ", self.previous_source)?;
+        if let Some(value) = &self.value {
+            write!(f, "{value} ")?;
+        }
+        write!(f, "from {}", self.source)
+    }
+}
+
+fn lookup<'a>(value: &'a toml::Value, path: &str) -> Option<&'a toml::Value> {
+    if path.is_empty() {
+        return Some(value);
+    }
+    path.split('.').try_fold(value, |value, key| value.get(key))
+}
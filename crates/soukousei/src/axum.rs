@@ -0,0 +1,88 @@
+//! Integration with [axum](https://docs.rs/axum): the config is kept in the router state as
+//! [`ConfigState`], and handlers take a per-request [`Snapshot`] of it.
+//!
+//! ```ignore
+//! let state = ConfigState::new(Shared::new(config));
+//! let app = Router::new()
+//!     .route("/", get(index))
+//!     .route("/admin/config", get(render_config::<AppConfig>))
+//!     .with_state(state.clone());
+//!
+//! async fn index(Snapshot(config): Snapshot<AppConfig>) -> String {
+//!     format!("listening on {}", config.port)
+//! }
+//! ```
+//!
+//! With a custom app state, implement `FromRef<AppState> for ConfigState<AppConfig>`.
+
+use crate::shared::Shared;
+use crate::tree::RenderTree;
+use ::axum::extract::{FromRef, FromRequestParts, State};
+use ::axum::http::request::Parts;
+use std::convert::Infallible;
+use std::fmt::{Debug, Formatter};
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// Router state with a handle to the current config
+pub struct ConfigState<T> {
+    shared: Shared<T>,
+}
+
+impl<T> ConfigState<T> {
+    pub fn new(shared: Shared<T>) -> Self {
+        Self { shared }
+    }
+
+    /// Handle to swap the config, e.g. on reload
+    pub fn shared(&self) -> &Shared<T> {
+        &self.shared
+    }
+}
+
+impl<T> Clone for ConfigState<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T: Debug> Debug for ConfigState<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ConfigState").field(&self.shared).finish()
+    }
+}
+
+/// Snapshot of the config taken at the start of a request, so that a handler sees the same
+/// config even if it is reloaded meanwhile
+#[derive(Debug, Clone)]
+pub struct Snapshot<T>(pub Arc<T>);
+
+impl<T> Deref for Snapshot<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+#[::axum::async_trait]
+impl<S, T> FromRequestParts<S> for Snapshot<T>
+where
+    ConfigState<T>: FromRef<S>,
+    S: Send + Sync,
+    T: Send + Sync + 'static,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(_parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self(ConfigState::<T>::from_ref(state).shared.get()))
+    }
+}
+
+/// Admin handler rendering the current config as a tree, with secrets redacted, see
+/// [`RenderTree`]
+pub async fn render_config<T: RenderTree>(State(state): State<ConfigState<T>>) -> String {
+    state.shared.get().render_tree()
+}
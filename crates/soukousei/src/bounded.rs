@@ -0,0 +1,243 @@
+//! Numbers restricted to a range, see [`Bounded`] and [`Ratio`].
+//!
+//! ```ignore
+//! #[derive(Layer)]
+//! struct Server {
+//!     #[layer(env = "WORKERS", default = "Bounded::new(4).unwrap()")]
+//!     workers: Bounded<u32, 1, 256>,
+//!     /// Set as `0.25` or `"25%"`
+//!     #[layer(env = "SAMPLE_RATE")]
+//!     sample_rate: Ratio,
+//!     /// Plain numbers are checked on completion with `#[layer(range = "...")]`
+//!     #[layer(range = "1..=65535")]
+//!     port: u16,
+//! }
+//! ```
+//!
+//! Out of range values are rejected with errors like "expected 0.0..=1.0, got 1.4".
+
+use crate::Report;
+#[cfg(feature = "miette")]
+use miette::Diagnostic;
+use std::fmt::{Display, Formatter};
+use std::ops::{Deref, RangeBounds};
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+#[error("expected {expected}, got {got}")]
+pub struct OutOfRangeError {
+    expected: String,
+    got: String,
+}
+
+impl OutOfRangeError {
+    pub fn new(expected: impl Into<String>, got: impl Display) -> Self {
+        Self {
+            expected: expected.into(),
+            got: got.to_string(),
+        }
+    }
+
+    /// The range as it is written, e.g. `1..=65535`
+    pub fn expected(&self) -> &str {
+        &self.expected
+    }
+}
+
+/// Check a value against `range`, written as `expected` in the error. Used by
+/// `#[layer(range = "...")]`.
+pub fn check_range<T, R>(value: &T, range: R, expected: &str) -> Result<(), Report>
+where
+    T: PartialOrd + Display,
+    R: RangeBounds<T>,
+{
+    if range.contains(value) {
+        Ok(())
+    } else {
+        Err(OutOfRangeError::new(expected, value).into())
+    }
+}
+
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+pub enum ParseBoundedError {
+    #[error("invalid number `{value}`: {message}")]
+    Invalid { value: String, message: String },
+    #[error(transparent)]
+    #[cfg_attr(feature = "miette", diagnostic(transparent))]
+    OutOfRange(#[from] OutOfRangeError),
+}
+
+/// Integer within `MIN..=MAX`, e.g. `Bounded<u16, 1, 65535>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Bounded<T, const MIN: i128, const MAX: i128>(T);
+
+impl<T, const MIN: i128, const MAX: i128> Bounded<T, MIN, MAX>
+where
+    T: Copy + Into<i128> + Display,
+{
+    pub fn new(value: T) -> Result<Self, OutOfRangeError> {
+        if (MIN..=MAX).contains(&value.into()) {
+            Ok(Self(value))
+        } else {
+            Err(OutOfRangeError::new(format!("{MIN}..={MAX}"), value))
+        }
+    }
+
+    pub fn get(self) -> T {
+        self.0
+    }
+}
+
+impl<T, const MIN: i128, const MAX: i128> Deref for Bounded<T, MIN, MAX> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T: Display, const MIN: i128, const MAX: i128> Display for Bounded<T, MIN, MAX> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<T, const MIN: i128, const MAX: i128> FromStr for Bounded<T, MIN, MAX>
+where
+    T: Copy + Into<i128> + Display + FromStr,
+    T::Err: Display,
+{
+    type Err = ParseBoundedError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = s
+            .trim()
+            .parse::<T>()
+            .map_err(|err| ParseBoundedError::Invalid {
+                value: s.to_owned(),
+                message: err.to_string(),
+            })?;
+        Ok(Self::new(value)?)
+    }
+}
+
+/// Fraction within `0.0..=1.0`, which might also be written as a percentage, e.g. `"25%"`
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct Ratio(f64);
+
+impl Ratio {
+    pub fn new(value: f64) -> Result<Self, OutOfRangeError> {
+        if (0.0..=1.0).contains(&value) {
+            Ok(Self(value))
+        } else {
+            Err(OutOfRangeError::new("0.0..=1.0", value))
+        }
+    }
+
+    pub fn get(self) -> f64 {
+        self.0
+    }
+
+    pub fn as_percent(self) -> f64 {
+        self.0 * 100.0
+    }
+}
+
+impl Display for Ratio {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl FromStr for Ratio {
+    type Err = ParseBoundedError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |message: String| ParseBoundedError::Invalid {
+            value: s.to_owned(),
+            message,
+        };
+        let value = match s.trim().strip_suffix('%') {
+            Some(percent) => {
+                let percent = percent
+                    .trim()
+                    .parse::<f64>()
+                    .map_err(|err| invalid(err.to_string()))?;
+                if !(0.0..=100.0).contains(&percent) {
+                    return Err(OutOfRangeError::new("0%..=100%", s.trim()).into());
+                }
+                percent / 100.0
+            }
+            None => s
+                .trim()
+                .parse::<f64>()
+                .map_err(|err| invalid(err.to_string()))?,
+        };
+        Ok(Self::new(value)?)
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::{Bounded, Ratio};
+    use serde::de::{Error, Visitor};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::fmt::{Display, Formatter};
+
+    impl<T: Serialize, const MIN: i128, const MAX: i128> Serialize for Bounded<T, MIN, MAX> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            self.0.serialize(serializer)
+        }
+    }
+
+    impl<'de, T, const MIN: i128, const MAX: i128> Deserialize<'de> for Bounded<T, MIN, MAX>
+    where
+        T: Deserialize<'de> + Copy + Into<i128> + Display,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let value = T::deserialize(deserializer)?;
+            Self::new(value).map_err(D::Error::custom)
+        }
+    }
+
+    impl Serialize for Ratio {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_f64(self.0)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Ratio {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct RatioVisitor;
+
+            impl<'de> Visitor<'de> for RatioVisitor {
+                type Value = Ratio;
+
+                fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                    f.write_str("a number within 0.0..=1.0 or a percentage string")
+                }
+
+                fn visit_f64<E: Error>(self, value: f64) -> Result<Self::Value, E> {
+                    Ratio::new(value).map_err(E::custom)
+                }
+
+                fn visit_i64<E: Error>(self, value: i64) -> Result<Self::Value, E> {
+                    Ratio::new(value as f64).map_err(E::custom)
+                }
+
+                fn visit_u64<E: Error>(self, value: u64) -> Result<Self::Value, E> {
+                    Ratio::new(value as f64).map_err(E::custom)
+                }
+
+                fn visit_str<E: Error>(self, value: &str) -> Result<Self::Value, E> {
+                    value.parse().map_err(E::custom)
+                }
+            }
+
+            deserializer.deserialize_any(RatioVisitor)
+        }
+    }
+}
@@ -0,0 +1,1359 @@
+//! Composing a config from multiple sources.
+//!
+//! Sources are merged in the order they are added, so that later ones take precedence:
+//!
+//! ```ignore
+//! let config: Config = ConfigBuilder::<ConfigLayer>::new()
+//!     .with_defaults()
+//!     .with_file("config.toml")?
+//!     .with_env(&StdEnv::new())?
+//!     .build()?;
+//! ```
+//!
+//! A single file might also contain multiple profiles, see [`ConfigBuilder::with_profile_file`].
+
+#[cfg(feature = "toml")]
+use crate::audit::MergeAudit;
+#[cfg(feature = "serde")]
+use crate::conflict::{SourceConflictError, SourceShapes};
+use crate::env::{EnvProvider, FieldFromEnvError, FromEnv};
+#[cfg(feature = "toml")]
+use crate::freeze::{Freeze, FreezeError};
+#[cfg(feature = "serde")]
+use crate::jail::{PathEscapeError, PathJail};
+#[cfg(feature = "serde")]
+use crate::limits::Limits;
+#[cfg(feature = "serde")]
+use crate::lint::{LintIssue, LintReport};
+use crate::meta;
+use crate::metrics::ConfigMetricsSink;
+#[cfg(feature = "serde")]
+use crate::normalize::KeyNormalizer;
+use crate::provenance::Provenance;
+#[cfg(feature = "serde")]
+use crate::schema::{self, RemoteSource, SchemaMismatchError};
+#[cfg(all(feature = "regex", feature = "serde"))]
+use crate::secret_scan::SecretScan;
+use crate::shared::Shared;
+#[cfg(feature = "toml")]
+use crate::source::Embedded;
+#[cfg(feature = "serde")]
+use crate::source::{Format, Parsed, SourceError};
+use crate::{CompleteErrorDiagnostic, ErrorPolicy, FieldsErrorBunch, Layer};
+#[cfg(feature = "miette")]
+use miette::Diagnostic;
+#[cfg(feature = "serde")]
+use serde::de::DeserializeOwned;
+use std::cell::RefCell;
+#[cfg(feature = "serde")]
+use std::collections::HashMap;
+use std::ffi::OsString;
+#[cfg(feature = "serde")]
+use std::io::Read;
+#[cfg(feature = "serde")]
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+use thiserror::Error;
+
+/// Serializes a layer for [`MergeAudit`], as `L` is only required to be `Serialize` by
+/// [`ConfigBuilder::with_merge_audit`]
+#[cfg(feature = "toml")]
+type Snapshot<L> = fn(&L) -> Option<toml::Value>;
+
+pub struct ConfigBuilder<L> {
+    layer: L,
+    provenance: Provenance,
+    warnings: Vec<BuildWarning>,
+    /// Merged last, see [`ConfigBuilder::with_overrides`]
+    overrides: Option<L>,
+    /// See [`ConfigBuilder::with_lazy_defaults`]
+    lazy_defaults: bool,
+    /// See [`ConfigBuilder::with_secret_scan`]
+    #[cfg(all(feature = "regex", feature = "serde"))]
+    secret_scan: Option<SecretScan>,
+    /// See [`ConfigBuilder::with_key_normalizer`]
+    #[cfg(feature = "serde")]
+    key_normalizer: Option<Arc<dyn KeyNormalizer>>,
+    /// See [`ConfigBuilder::with_merge_audit`]
+    #[cfg(feature = "toml")]
+    audit: Option<(MergeAudit, Snapshot<L>)>,
+    /// See [`ConfigBuilder::env_allowlist`]
+    env_allowlist: Option<Vec<String>>,
+    /// See [`ConfigBuilder::with_metrics`]
+    metrics: Option<Arc<dyn ConfigMetricsSink>>,
+    /// See [`ConfigBuilder::deny_ambiguous_env`]
+    deny_ambiguous_env: bool,
+    /// See [`ConfigBuilder::with_path_jail`]
+    #[cfg(feature = "serde")]
+    jail: Option<PathJail>,
+    /// See [`ConfigBuilder::allow_latin1`]
+    #[cfg(feature = "serde")]
+    latin1: bool,
+    /// See [`ConfigBuilder::with_limits`]
+    #[cfg(feature = "serde")]
+    limits: Option<Limits>,
+    /// See [`ConfigBuilder::deny_source_conflicts`]
+    #[cfg(feature = "serde")]
+    source_shapes: Option<SourceShapes>,
+    /// See [`ConfigBuilder::fail_fast`]
+    error_policy: ErrorPolicy,
+}
+
+impl<L: Layer> ConfigBuilder<L> {
+    /// Start with an empty layer
+    pub fn new() -> Self {
+        Self {
+            layer: L::new(),
+            provenance: Provenance::new(),
+            warnings: Vec::new(),
+            overrides: None,
+            lazy_defaults: false,
+            #[cfg(all(feature = "regex", feature = "serde"))]
+            secret_scan: None,
+            #[cfg(feature = "serde")]
+            key_normalizer: None,
+            #[cfg(feature = "toml")]
+            audit: None,
+            env_allowlist: None,
+            metrics: None,
+            deny_ambiguous_env: false,
+            #[cfg(feature = "serde")]
+            jail: None,
+            #[cfg(feature = "serde")]
+            latin1: false,
+            #[cfg(feature = "serde")]
+            limits: None,
+            #[cfg(feature = "serde")]
+            source_shapes: None,
+            error_policy: ErrorPolicy::CollectAll,
+        }
+    }
+
+    /// Merge default values of the layer
+    pub fn with_defaults(self) -> Self
+    where
+        L: Default,
+    {
+        self.with_named_layer(DEFAULTS_SOURCE, L::default())
+    }
+
+    /// Same as [`Self::with_defaults`], but defaults are filled in only for the fields which
+    /// are still missing once all sources are merged, see [`Layer::fill_defaults`]. Useful when
+    /// default expressions are expensive, e.g. read machine-specific values.
+    ///
+    /// Defaults always come before other sources, regardless of the order in which they are
+    /// added.
+    pub fn with_lazy_defaults(mut self) -> Self {
+        self.lazy_defaults = true;
+        self
+    }
+
+    /// Set values programmatically, e.g. in tests. Overrides take precedence over all other
+    /// sources, regardless of the order in which they are added, and are recorded as
+    /// [`PROGRAMMATIC_SOURCE`] in [`Provenance`] once the config is built.
+    pub fn with_overrides(mut self, f: impl FnOnce(&mut L)) -> Self {
+        f(self.overrides.get_or_insert_with(L::new));
+        self
+    }
+
+    /// Record every override by sources added afterwards, see [`crate::audit`]. Overrides
+    /// from [`Self::with_overrides`] are merged on build, so they don't appear in
+    /// [`Self::merge_audit`].
+    #[cfg(feature = "toml")]
+    pub fn with_merge_audit(mut self) -> Self
+    where
+        L: serde::Serialize,
+    {
+        fn snapshot<L: serde::Serialize>(layer: &L) -> Option<toml::Value> {
+            toml::Value::try_from(layer).ok()
+        }
+
+        self.audit = Some((MergeAudit::new(), snapshot::<L>));
+        self
+    }
+
+    /// Overrides recorded so far, if [`Self::with_merge_audit`] is enabled
+    #[cfg(feature = "toml")]
+    pub fn merge_audit(&self) -> Option<&MergeAudit> {
+        self.audit.as_ref().map(|(audit, _)| audit)
+    }
+
+    /// Map keys of file-like sources added afterwards onto field names, e.g. with
+    /// [`SnakeCase`](crate::normalize::SnakeCase) for `max-connections` or `maxConnections`
+    /// keys, see [`crate::normalize`]
+    #[cfg(feature = "serde")]
+    pub fn with_key_normalizer(mut self, normalizer: impl KeyNormalizer + 'static) -> Self {
+        self.key_normalizer = Some(Arc::new(normalizer));
+        self
+    }
+
+    /// Read keys of sources added afterwards as they are
+    #[cfg(feature = "serde")]
+    pub fn without_key_normalizer(mut self) -> Self {
+        self.key_normalizer = None;
+        self
+    }
+
+    /// Report how long each source added afterwards takes to load, see [`crate::metrics`]
+    pub fn with_metrics(mut self, sink: Arc<dyn ConfigMetricsSink>) -> Self {
+        self.metrics = Some(sink);
+        self
+    }
+
+    fn record_load(&self, source: &str, started: Instant) {
+        if let Some(metrics) = &self.metrics {
+            metrics.source_loaded(source, started.elapsed());
+        }
+    }
+
+    /// Scan string values of sources added afterwards for literal secrets, e.g. AWS keys or
+    /// private keys, see [`crate::secret_scan`]. Findings fail the source with their locations,
+    /// or are recorded as warnings with [`SecretScan::warn_only`].
+    #[cfg(all(feature = "regex", feature = "serde"))]
+    pub fn with_secret_scan(mut self, scan: SecretScan) -> Self {
+        self.secret_scan = Some(scan);
+        self
+    }
+
+    /// Reject sources added afterwards which exceed `limits`, e.g. when configs are uploaded by
+    /// semi-trusted parties, see [`crate::limits`]
+    #[cfg(feature = "serde")]
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+
+    /// Parse a source, stopping at the first value which exceeds the limits
+    #[cfg(feature = "serde")]
+    fn parse_limited<T: DeserializeOwned>(
+        &self,
+        name: &str,
+        format: Format,
+        contents: &str,
+    ) -> Result<Parsed<T>, BuildError> {
+        let limit_error = |err| {
+            BuildError::Source(LintReport::new(
+                name.to_owned(),
+                contents.to_owned(),
+                vec![LintIssue::Limit(err)],
+            ))
+        };
+        let check = match &self.limits {
+            Some(limits) => {
+                limits.check_size(contents).map_err(limit_error)?;
+                limits.checker()
+            }
+            None => None,
+        };
+        let parsed = format.parse_limited(contents, self.key_normalizer.as_deref(), check.as_ref());
+        if let Some(check) = check {
+            check.into_result().map_err(limit_error)?;
+        }
+        parsed.map_err(|err| {
+            BuildError::Source(LintReport::new(
+                name.to_owned(),
+                contents.to_owned(),
+                vec![LintIssue::Source(err.into_source_error(name))],
+            ))
+        })
+    }
+
+    #[cfg(all(feature = "regex", feature = "serde"))]
+    fn scan_secrets(
+        &mut self,
+        name: &str,
+        format: Format,
+        contents: &str,
+    ) -> Result<(), BuildError> {
+        let Some(scan) = &self.secret_scan else {
+            return Ok(());
+        };
+        let found = scan.scan(format, contents);
+        if found.is_empty() {
+            return Ok(());
+        }
+        if scan.is_deny() {
+            return Err(BuildError::Source(LintReport::new(
+                name.to_owned(),
+                contents.to_owned(),
+                found.into_iter().map(LintIssue::LiteralSecret).collect(),
+            )));
+        }
+        for secret in found {
+            let location = match secret.line_column(contents) {
+                Some((line, column)) => format!("{name}:{line}:{column}"),
+                None => name.to_owned(),
+            };
+            self.warnings.push(BuildWarning::LiteralSecret {
+                key: secret.key().to_owned(),
+                pattern: secret.pattern().to_owned(),
+                location,
+            });
+        }
+        Ok(())
+    }
+
+    /// Merge overrides, which always come last, and fill in lazy defaults
+    fn finish(mut self) -> Self {
+        if let Some(overrides) = self.overrides.take() {
+            self = self.with_named_layer(PROGRAMMATIC_SOURCE, overrides);
+        }
+        if self.lazy_defaults {
+            let provided = self.layer.provided_fields();
+            self.layer.fill_defaults();
+            let filled = self
+                .layer
+                .provided_fields()
+                .into_iter()
+                .filter(|x| !provided.contains(x));
+            self.provenance.record(DEFAULTS_SOURCE, filled);
+        }
+        self
+    }
+
+    pub fn with_layer(self, layer: L) -> Self {
+        self.with_named_layer("layer", layer)
+    }
+
+    /// Merge a layer, recording `source` as the provenance of the fields it provides
+    pub fn with_named_layer(mut self, source: &str, layer: L) -> Self {
+        let provided = layer.provided_fields();
+
+        for field in provided.iter() {
+            let opaque = meta::find(L::FIELDS, field).is_some_and(|x| x.opaque);
+            match self.provenance.source_of(field) {
+                Some(previous) if opaque && previous != DEFAULTS_SOURCE => {
+                    self.warnings.push(BuildWarning::OpaqueOverride {
+                        field: field.clone(),
+                        source_name: source.to_owned(),
+                        previous: previous.to_owned(),
+                    })
+                }
+                _ => {}
+            }
+        }
+
+        #[cfg(feature = "toml")]
+        if let Some((audit, snapshot)) = &mut self.audit {
+            audit.record(
+                source,
+                &provided,
+                &self.provenance,
+                snapshot(&self.layer).as_ref(),
+                snapshot(&layer).as_ref(),
+                L::FIELDS,
+            );
+        }
+
+        self.provenance.record(source, provided);
+        self.layer.merge_from(layer);
+        self
+    }
+
+    pub fn with_env(self, provider: &impl EnvProvider) -> Result<Self, BuildError>
+    where
+        L: FromEnv,
+    {
+        let started = Instant::now();
+
+        if self.deny_ambiguous_env {
+            let mut conflicts = Vec::new();
+            find_ambiguous_env(L::FIELDS, "", provider, &mut conflicts)?;
+            if !conflicts.is_empty() {
+                return Err(BuildError::AmbiguousEnv(AmbiguousEnvError { conflicts }));
+            }
+        }
+
+        let provider = RecordingEnv::new(provider);
+        let layer = L::from_env(&provider).map_err(|err| BuildError::Env(err.into_diagnostic()))?;
+
+        if let Some(allowed) = &self.env_allowlist {
+            let denied: Vec<_> = layer
+                .provided_fields()
+                .into_iter()
+                .filter(|field| !env_allowed(allowed, field))
+                .map(|field| {
+                    let variables = env_vars_of::<L>(&field);
+                    (field, variables)
+                })
+                .collect();
+            if !denied.is_empty() {
+                return Err(BuildError::EnvNotAllowed(EnvNotAllowedError {
+                    fields: denied,
+                    help: format!(
+                        "only {} might be set with ENV, set other fields in a config file",
+                        allowed
+                            .iter()
+                            .map(|x| format!("`{x}`"))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                }));
+            }
+        }
+
+        // fields with several variables take the first set one
+        let variables: Vec<_> = layer
+            .provided_fields()
+            .into_iter()
+            .filter_map(|field| {
+                let variable = env_vars_of::<L>(&field)
+                    .into_iter()
+                    .find(|x| provider.was_set(x))?;
+                Some((field, variable))
+            })
+            .collect();
+
+        let mut this = self.with_named_layer(ENV_SOURCE, layer);
+        for (field, variable) in variables {
+            this.provenance.record_variable(&field, &variable);
+        }
+        this.record_load(ENV_SOURCE, started);
+        Ok(this)
+    }
+
+    /// Allow ENV vars to set only the listed fields or sections, e.g.
+    /// `["db.password", "log.level"]`, so that config files stay authoritative for the rest.
+    /// [`Self::with_env`] fails if a variable of any other field is set.
+    ///
+    /// The path file of a `#[layer(sensitive_file)]` field is allowed along with the field.
+    pub fn env_allowlist<I, S>(mut self, fields: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.env_allowlist = Some(fields.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Fail [`Self::with_env`] if several variables of a field with
+    /// `#[layer(env = ["A", "B"])]` are set to different values. By default, the first one set
+    /// in the listing order is taken.
+    pub fn deny_ambiguous_env(mut self) -> Self {
+        self.deny_ambiguous_env = true;
+        self
+    }
+
+    /// Fail adding a source which disagrees with an earlier one on the shape of a key, e.g. a
+    /// table in one and a string in the other, naming both sources, see [`crate::conflict`]. By
+    /// default, the later source fails to decode on its own, or replaces the value if the field
+    /// accepts both shapes.
+    ///
+    /// Only sources parsed from text are compared, e.g. [`Self::with_file`] and
+    /// [`Self::with_str`], and only with the ones added after this call.
+    #[cfg(feature = "serde")]
+    pub fn deny_source_conflicts(mut self) -> Self {
+        self.source_shapes = Some(SourceShapes::new());
+        self
+    }
+
+    /// Stop completion at the first missing or invalid field instead of reporting all of them,
+    /// see [`ErrorPolicy::FailFast`]
+    pub fn fail_fast(mut self) -> Self {
+        self.error_policy = ErrorPolicy::FailFast;
+        self
+    }
+
+    /// Same as [`Self::with_env`], but also look for variables starting with `prefix` which
+    /// match no field, e.g. a typo like `MYAPP_DB_PROT`. They are either reported as warnings or
+    /// fail the build, depending on `unknown`.
+    ///
+    /// The provider must support [`EnvProvider::iter_prefixed`].
+    pub fn with_env_strict(
+        mut self,
+        provider: &impl EnvProvider,
+        prefix: &str,
+        unknown: UnknownEnv,
+    ) -> Result<Self, BuildError>
+    where
+        L: FromEnv,
+    {
+        let known = meta::env_vars(L::FIELDS);
+        let patterns = meta::env_patterns(L::FIELDS);
+        let variables: Vec<_> = provider
+            .iter_prefixed(prefix)
+            .map_err(BuildError::EnvIter)?
+            .into_iter()
+            .map(|(key, _)| key)
+            .filter(|key| !known.contains(&key.as_str()))
+            .filter(|key| {
+                !patterns
+                    .iter()
+                    .any(|pattern| meta::matches_env_pattern(pattern, key))
+            })
+            .map(|key| {
+                let suggestion = suggest(&key, &known).map(ToOwned::to_owned);
+                (key, suggestion)
+            })
+            .collect();
+
+        match unknown {
+            UnknownEnv::Deny if !variables.is_empty() => {
+                return Err(BuildError::UnknownEnv(UnknownEnvError::new(variables)))
+            }
+            UnknownEnv::Deny => {}
+            UnknownEnv::Warn => {
+                self.warnings
+                    .extend(variables.into_iter().map(|(variable, suggestion)| {
+                        BuildWarning::UnknownEnv {
+                            variable,
+                            help: suggestion.map(|x| format!("did you mean `{x}`?")),
+                        }
+                    }))
+            }
+        }
+
+        self.with_env(provider)
+    }
+
+    /// Read a config file. The format is guessed by the file extension.
+    ///
+    /// Unknown keys are ignored, use [`crate::lint`] to report them. A UTF-8 byte order mark,
+    /// which some Windows editors write, is skipped.
+    #[cfg(feature = "serde")]
+    pub fn with_file(mut self, path: impl AsRef<Path>) -> Result<Self, BuildError>
+    where
+        L: DeserializeOwned,
+    {
+        let started = Instant::now();
+        let (name, format, contents) = self.read_file(path.as_ref())?;
+        self.with_str_since(name, format, contents, started)
+    }
+
+    /// Restrict files read afterwards to the `jail` directory, rejecting paths which lead
+    /// outside of it, see [`crate::jail`]
+    #[cfg(feature = "serde")]
+    pub fn with_path_jail(mut self, jail: PathJail) -> Self {
+        self.jail = Some(jail);
+        self
+    }
+
+    /// Read files afterwards as Latin-1 if they are not valid UTF-8, e.g. configs written by
+    /// legacy Windows editors, with a [`BuildWarning::NotUtf8`]. Otherwise such files fail with
+    /// [`SourceError::Io`] of [`std::io::ErrorKind::InvalidData`].
+    #[cfg(feature = "serde")]
+    pub fn allow_latin1(mut self) -> Self {
+        self.latin1 = true;
+        self
+    }
+
+    #[cfg(feature = "serde")]
+    fn read_file(&mut self, path: &Path) -> Result<(String, Format, String), BuildError> {
+        let path = match &self.jail {
+            Some(jail) => jail.resolve(path).map_err(BuildError::PathEscape)?,
+            None => path.to_owned(),
+        };
+        let size_limit = self.limits.as_ref().and_then(Limits::size_limit);
+        let (name, format, contents, encoding) = read_file_as(&path, self.latin1, size_limit)?;
+        if encoding == Encoding::Latin1 {
+            self.warnings.push(BuildWarning::NotUtf8 {
+                source_name: name.clone(),
+            });
+        }
+        Ok((name, format, contents))
+    }
+
+    /// Same as [`Self::with_file`], but with [`OnError::Skip`] a file which fails to be read or
+    /// parsed, e.g. a malformed user override in `~/.config`, is skipped with a
+    /// [`BuildWarning::SourceSkipped`] instead of failing the build
+    #[cfg(feature = "serde")]
+    pub fn with_file_on_error(
+        mut self,
+        path: impl AsRef<Path>,
+        on_error: OnError,
+    ) -> Result<Self, BuildError>
+    where
+        L: DeserializeOwned,
+    {
+        let started = Instant::now();
+        let loaded = self
+            .read_file(path.as_ref())
+            .and_then(|(name, format, contents)| {
+                let layer = self.parse_str(&name, format, contents)?;
+                Ok((name, layer))
+            });
+        match (loaded, on_error) {
+            (Ok((name, layer)), _) => {
+                let this = self.with_named_layer(&name, layer);
+                this.record_load(&name, started);
+                Ok(this)
+            }
+            (Err(BuildError::Source(report)), OnError::Skip) => {
+                self.warnings.push(BuildWarning::SourceSkipped {
+                    source_name: report.name().to_owned(),
+                    report,
+                });
+                Ok(self)
+            }
+            (Err(err), _) => Err(err),
+        }
+    }
+
+    /// Read a config file whose path is set in the ENV var `variable`, e.g. `MYAPP_CONFIG`.
+    /// The file is required if the var is set, otherwise nothing is read and a
+    /// [`BuildWarning::FileNotSelected`] note is recorded.
+    ///
+    /// The source is named after both the file and the var, e.g.
+    /// `/etc/myapp.toml (from MYAPP_CONFIG)`.
+    #[cfg(feature = "serde")]
+    pub fn with_file_from_env(
+        mut self,
+        provider: &impl EnvProvider,
+        variable: &str,
+    ) -> Result<Self, BuildError>
+    where
+        L: DeserializeOwned,
+    {
+        let path = provider.fetch_os(variable).map_err(|report| {
+            BuildError::EnvFetch(FieldFromEnvError::new(report, variable.to_owned()))
+        })?;
+        let Some(path) = path else {
+            self.warnings.push(BuildWarning::FileNotSelected {
+                variable: variable.to_owned(),
+            });
+            return Ok(self);
+        };
+        let started = Instant::now();
+        let (name, format, contents) = self.read_file(Path::new(&path))?;
+        self.with_str_since(
+            format!("{name} (from {variable})"),
+            format,
+            contents,
+            started,
+        )
+    }
+
+    /// Read a config piped into the process, e.g. `cat config.toml | app --config -`. The
+    /// source is named [`STDIN_SOURCE`].
+    #[cfg(feature = "serde")]
+    pub fn with_stdin(self, format: Format) -> Result<Self, BuildError>
+    where
+        L: DeserializeOwned,
+    {
+        self.with_reader(STDIN_SOURCE, format, std::io::stdin().lock())
+    }
+
+    /// Read config contents from `reader` until EOF. `name` is used in diagnostics.
+    #[cfg(feature = "serde")]
+    pub fn with_reader(
+        self,
+        name: impl Into<String>,
+        format: Format,
+        reader: impl std::io::Read,
+    ) -> Result<Self, BuildError>
+    where
+        L: DeserializeOwned,
+    {
+        let name = name.into();
+        let io_error = |err| {
+            BuildError::Source(LintReport::new(
+                name.clone(),
+                String::new(),
+                vec![LintIssue::Source(SourceError::Io {
+                    path: name.clone(),
+                    source: err,
+                })],
+            ))
+        };
+        let size_limit = self.limits.as_ref().and_then(Limits::size_limit);
+        let bytes = read_limited(reader, size_limit).map_err(io_error)?;
+        let contents = match String::from_utf8(bytes) {
+            Ok(contents) => contents,
+            // the stream might be cut in the middle of a char, but it is rejected anyway
+            Err(err) if exceeds(err.as_bytes(), size_limit) => {
+                String::from_utf8_lossy(err.as_bytes()).into_owned()
+            }
+            Err(err) => {
+                return Err(io_error(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    err,
+                )))
+            }
+        };
+        self.with_str(name, format, contents)
+    }
+
+    /// Parse config contents, e.g. embedded defaults, test fixtures or a config downloaded by
+    /// other means. `name` labels the source in [`Provenance`] and diagnostics, which point into
+    /// `contents`.
+    #[cfg(feature = "serde")]
+    pub fn with_str(
+        self,
+        name: impl Into<String>,
+        format: Format,
+        contents: impl Into<String>,
+    ) -> Result<Self, BuildError>
+    where
+        L: DeserializeOwned,
+    {
+        self.with_str_since(name.into(), format, contents.into(), Instant::now())
+    }
+
+    /// [`Self::with_str`] of a source which started loading at `started`, e.g. before reading
+    /// a file
+    #[cfg(feature = "serde")]
+    fn with_str_since(
+        mut self,
+        name: String,
+        format: Format,
+        contents: String,
+        started: Instant,
+    ) -> Result<Self, BuildError>
+    where
+        L: DeserializeOwned,
+    {
+        let layer = self.parse_str(&name, format, contents)?;
+        let this = self.with_named_layer(&name, layer);
+        this.record_load(&name, started);
+        Ok(this)
+    }
+
+    /// Parse a source without merging it, so that the builder is kept if it fails
+    #[cfg(feature = "serde")]
+    fn parse_str(&mut self, name: &str, format: Format, contents: String) -> Result<L, BuildError>
+    where
+        L: DeserializeOwned,
+    {
+        let shape = match &self.source_shapes {
+            Some(shapes) => shapes
+                .check(name, format, &contents, self.key_normalizer.as_deref())
+                .map_err(BuildError::SourceConflict)?,
+            None => None,
+        };
+        let parsed = self.parse_limited::<L>(name, format, &contents)?;
+        #[cfg(feature = "regex")]
+        self.scan_secrets(name, format, &contents)?;
+        if let (Some(shapes), Some(shape)) = (&mut self.source_shapes, shape) {
+            shapes.push(name.to_owned(), shape);
+        }
+        Ok(parsed.value)
+    }
+
+    /// Fetch a document from a remote source, e.g. a config server, for the schema fingerprint
+    /// of `L`. A document produced for another schema fails with
+    /// [`BuildError::SchemaMismatch`] instead of missing field errors, see [`crate::schema`].
+    #[cfg(feature = "serde")]
+    pub fn with_remote(
+        self,
+        name: impl Into<String>,
+        remote: &dyn RemoteSource,
+    ) -> Result<Self, BuildError>
+    where
+        L: DeserializeOwned,
+    {
+        let started = Instant::now();
+        let name = name.into();
+        let document = match remote.fetch(schema::fingerprint(L::fields())) {
+            Ok(document) => document,
+            Err(error) => return Err(BuildError::Fetch { name, error }),
+        };
+        schema::verify::<L>(document.fingerprint).map_err(BuildError::SchemaMismatch)?;
+        self.with_str_since(name, document.format, document.contents, started)
+    }
+
+    /// Merge a config embedded with [`crate::include_config`]
+    #[cfg(feature = "toml")]
+    pub fn with_embedded(self, embedded: Embedded<L>) -> Result<Self, BuildError>
+    where
+        L: DeserializeOwned,
+    {
+        self.with_str(embedded.name(), Format::Toml, embedded.contents())
+    }
+
+    /// Read a config file with multiple profiles, e.g.:
+    ///
+    /// ```toml
+    /// [default]
+    /// port = 8080
+    ///
+    /// [production]
+    /// port = 80
+    /// ```
+    ///
+    /// The [`DEFAULT_PROFILE`] section is merged first, then the `profile` one. Other sections
+    /// are parsed as well, so that errors in them are not hidden until deployment.
+    #[cfg(feature = "serde")]
+    pub fn with_profile_file(
+        mut self,
+        path: impl AsRef<Path>,
+        profile: &str,
+    ) -> Result<Self, BuildError>
+    where
+        L: DeserializeOwned,
+    {
+        let (name, format, contents) = self.read_file(path.as_ref())?;
+        self.with_profile_str(name, format, contents, profile)
+    }
+
+    /// Parse config contents with multiple profiles, see [`Self::with_profile_file`]
+    #[cfg(feature = "serde")]
+    pub fn with_profile_str(
+        #[allow(unused_mut)] mut self,
+        name: impl Into<String>,
+        format: Format,
+        contents: impl Into<String>,
+        profile: &str,
+    ) -> Result<Self, BuildError>
+    where
+        L: DeserializeOwned,
+    {
+        let started = Instant::now();
+        let name = name.into();
+        let contents = contents.into();
+        let parsed = self.parse_limited::<HashMap<String, L>>(&name, format, &contents)?;
+        #[cfg(feature = "regex")]
+        self.scan_secrets(&name, format, &contents)?;
+        let mut profiles = parsed.value;
+        let default = profiles
+            .remove(DEFAULT_PROFILE)
+            .map(|x| (format!("{name} [{DEFAULT_PROFILE}]"), x));
+        let selected = profiles
+            .remove(profile)
+            .map(|x| (format!("{name} [{profile}]"), x));
+        let this = default
+            .into_iter()
+            .chain(selected)
+            .fold(self, |acc, (source, layer)| {
+                acc.with_named_layer(&source, layer)
+            });
+        this.record_load(&name, started);
+        Ok(this)
+    }
+
+    /// Merged layer, without completing it
+    pub fn layer(self) -> L {
+        self.finish().layer
+    }
+
+    /// Where each field comes from so far
+    pub fn provenance(&self) -> &Provenance {
+        &self.provenance
+    }
+
+    /// Issues which don't prevent building the config, but are likely mistakes
+    pub fn warnings(&self) -> &[BuildWarning] {
+        &self.warnings
+    }
+
+    /// Anonymized export of the config merged so far, with provenance and warnings, which can
+    /// be attached to bug reports, see [`crate::support`]
+    #[cfg(feature = "json")]
+    pub fn export_support_bundle(&self) -> Result<serde_json::Value, serde_json::Error>
+    where
+        L: serde::Serialize,
+    {
+        crate::support::support_bundle(&self.layer, &self.provenance, &self.warnings)
+    }
+
+    /// Dump the config merged so far, so that it can be read back with [`Freeze::read_file`],
+    /// see [`crate::freeze`]
+    #[cfg(feature = "toml")]
+    pub fn freeze(&self, freeze: &Freeze) -> Result<String, FreezeError>
+    where
+        L: serde::Serialize,
+    {
+        freeze.to_string(&self.layer)
+    }
+
+    pub fn build(self) -> Result<L::Complete, BuildError> {
+        self.build_with_provenance().map(|(complete, _)| complete)
+    }
+
+    /// Same as [`Self::build`], but also returns where each field comes from
+    pub fn build_with_provenance(self) -> Result<(L::Complete, Provenance), BuildError> {
+        let Self {
+            layer,
+            provenance,
+            error_policy,
+            ..
+        } = self.finish();
+        let complete = layer
+            .complete_with(error_policy)
+            .map_err(|err| BuildError::Complete(err.into()))?;
+        Ok((complete, provenance))
+    }
+
+    /// Same as [`Self::build`], but wraps the config into a [`Shared`] handle, which can be
+    /// swapped on reload
+    pub fn build_shared(self) -> Result<Shared<L::Complete>, BuildError> {
+        self.build().map(Shared::new)
+    }
+}
+
+/// Source name of [`ConfigBuilder::with_defaults`] in [`Provenance`]
+pub const DEFAULTS_SOURCE: &str = "defaults";
+
+/// Source name of [`ConfigBuilder::with_env`] in [`Provenance`]
+pub const ENV_SOURCE: &str = "env";
+
+/// Source name of [`ConfigBuilder::with_overrides`] in [`Provenance`]
+pub const PROGRAMMATIC_SOURCE: &str = "programmatic";
+
+/// Source name of [`ConfigBuilder::with_stdin`] in [`Provenance`]
+pub const STDIN_SOURCE: &str = "stdin";
+
+/// Profile which is merged under the selected one
+pub const DEFAULT_PROFILE: &str = "default";
+
+#[cfg(feature = "serde")]
+pub(crate) fn read_file(path: &Path) -> Result<(String, Format, String), BuildError> {
+    read_file_as(path, false, None).map(|(name, format, contents, _)| (name, format, contents))
+}
+
+/// Encoding a file was read in
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Utf8,
+    /// See [`ConfigBuilder::allow_latin1`]
+    Latin1,
+}
+
+/// Same as [`read_file`], but decodes contents which are not valid UTF-8 as Latin-1 if `latin1`,
+/// and reads at most a byte more than `size_limit`, see [`read_limited`]
+#[cfg(feature = "serde")]
+fn read_file_as(
+    path: &Path,
+    latin1: bool,
+    size_limit: Option<usize>,
+) -> Result<(String, Format, String, Encoding), BuildError> {
+    let name = path.display().to_string();
+    let io_error = |err| {
+        BuildError::Source(LintReport::new(
+            name.clone(),
+            String::new(),
+            vec![LintIssue::Source(SourceError::Io {
+                path: name.clone(),
+                source: err,
+            })],
+        ))
+    };
+
+    let file = std::fs::File::open(path).map_err(io_error)?;
+    let bytes = read_limited(file, size_limit).map_err(io_error)?;
+    let bytes = bytes.strip_prefix(UTF8_BOM).unwrap_or(&bytes);
+    let (contents, encoding) = match std::str::from_utf8(bytes) {
+        Ok(contents) => (contents.to_owned(), Encoding::Utf8),
+        // the file might be cut in the middle of a char, but it is rejected anyway
+        Err(_) if exceeds(bytes, size_limit) => {
+            (String::from_utf8_lossy(bytes).into_owned(), Encoding::Utf8)
+        }
+        // every byte is a Latin-1 char with the same code point
+        Err(_) if latin1 => (
+            bytes.iter().map(|&x| char::from(x)).collect(),
+            Encoding::Latin1,
+        ),
+        Err(err) => {
+            return Err(io_error(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                err,
+            )))
+        }
+    };
+
+    let Some(format) = Format::from_path(path) else {
+        return Err(BuildError::Source(LintReport::new(
+            name,
+            contents,
+            vec![LintIssue::UnknownFormat],
+        )));
+    };
+
+    Ok((name, format, contents, encoding))
+}
+
+#[cfg(feature = "serde")]
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Read until EOF, but at most a byte more than `size_limit`, which is enough to reject an
+/// endless or huge source without reading it whole
+#[cfg(feature = "serde")]
+fn read_limited(reader: impl Read, size_limit: Option<usize>) -> std::io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader
+        .take(size_limit.map_or(u64::MAX, |x| x as u64 + 1))
+        .read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(feature = "serde")]
+fn exceeds(bytes: &[u8], size_limit: Option<usize>) -> bool {
+    size_limit.is_some_and(|x| bytes.len() > x)
+}
+
+/// Whether `field` is in `allowed` or in a section from it
+fn env_allowed(allowed: &[String], field: &str) -> bool {
+    let field = field.strip_suffix("_file").unwrap_or(field);
+    allowed.iter().any(|x| {
+        field == x
+            || field
+                .strip_prefix(x.as_str())
+                .is_some_and(|rest| rest.starts_with('.'))
+    })
+}
+
+/// ENV vars of a provided `field`, for diagnostics
+fn env_vars_of<L: Layer>(field: &str) -> Vec<String> {
+    if let Some(meta) = meta::find(L::FIELDS, field) {
+        return meta.env.iter().map(|x| (*x).to_owned()).collect();
+    }
+    field
+        .strip_suffix("_file")
+        .and_then(|x| meta::find(L::FIELDS, x))
+        .map(|meta| {
+            meta.env
+                .iter()
+                .map(|x| format!("{x}{}", crate::env::FILE_SUFFIX))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Collect fields which several variables are set for, with different values
+fn find_ambiguous_env(
+    fields: &[meta::FieldMeta],
+    prefix: &str,
+    provider: &impl EnvProvider,
+    out: &mut Vec<AmbiguousEnv>,
+) -> Result<(), BuildError> {
+    for field in fields {
+        let path = format!("{prefix}{}", field.name);
+        if field.env.len() > 1 {
+            let mut values = Vec::new();
+            for variable in field.env {
+                let value = provider.fetch_os(variable).map_err(|report| {
+                    BuildError::EnvFetch(FieldFromEnvError::new(report, (*variable).to_owned()))
+                })?;
+                if let Some(value) = value {
+                    values.push((*variable, value));
+                }
+            }
+            if values.windows(2).any(|pair| pair[0].1 != pair[1].1) {
+                out.push(AmbiguousEnv {
+                    values: values
+                        .into_iter()
+                        .map(|(variable, value)| {
+                            let value = if field.secret {
+                                "<redacted>".to_owned()
+                            } else {
+                                format!("{:?}", value.to_string_lossy())
+                            };
+                            (variable.to_owned(), value)
+                        })
+                        .collect(),
+                    field: path.clone(),
+                });
+            }
+        }
+        if let Some(nested) = field.nested {
+            find_ambiguous_env(nested, &format!("{path}."), provider, out)?;
+        }
+    }
+    Ok(())
+}
+
+/// Provider which remembers the variables that were set, so that fields can be traced to them
+struct RecordingEnv<'a, P> {
+    inner: &'a P,
+    set: RefCell<Vec<String>>,
+}
+
+impl<'a, P: EnvProvider> RecordingEnv<'a, P> {
+    fn new(inner: &'a P) -> Self {
+        Self {
+            inner,
+            set: RefCell::default(),
+        }
+    }
+
+    fn was_set(&self, key: &str) -> bool {
+        self.set.borrow().iter().any(|x| x == key)
+    }
+
+    fn record<T>(&self, key: &str, value: &Option<T>) {
+        if value.is_some() && !self.was_set(key) {
+            self.set.borrow_mut().push(key.to_owned());
+        }
+    }
+}
+
+impl<P: EnvProvider> EnvProvider for RecordingEnv<'_, P> {
+    fn fetch(&self, key: impl AsRef<str>) -> Result<Option<String>, crate::Report> {
+        let value = self.inner.fetch(key.as_ref())?;
+        self.record(key.as_ref(), &value);
+        Ok(value)
+    }
+
+    fn fetch_os(&self, key: impl AsRef<str>) -> Result<Option<OsString>, crate::Report> {
+        let value = self.inner.fetch_os(key.as_ref())?;
+        self.record(key.as_ref(), &value);
+        Ok(value)
+    }
+
+    fn iter_prefixed(&self, prefix: &str) -> Result<Vec<(String, String)>, crate::Report> {
+        self.inner.iter_prefixed(prefix)
+    }
+}
+
+/// What to do with a source which fails to load, see [`ConfigBuilder::with_file_on_error`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnError {
+    Fail,
+    Skip,
+}
+
+/// What to do with unknown ENV vars, see [`ConfigBuilder::with_env_strict`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnknownEnv {
+    Warn,
+    Deny,
+}
+
+/// The closest known name, if it looks like a typo
+fn suggest<'a>(name: &str, known: &[&'a str]) -> Option<&'a str> {
+    known
+        .iter()
+        .map(|x| (edit_distance(name, x), *x))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, x)| x)
+}
+
+/// Levenshtein distance, where a swap of adjacent chars counts as 2
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, x) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = if x == *y {
+                prev
+            } else {
+                1 + prev.min(row[j]).min(current)
+            };
+            prev = current;
+        }
+    }
+    row[b.len()]
+}
+
+impl<L: Layer> Default for ConfigBuilder<L> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+pub enum BuildError {
+    #[cfg(feature = "serde")]
+    #[error(transparent)]
+    #[cfg_attr(feature = "miette", diagnostic(transparent))]
+    Source(LintReport),
+    #[error(transparent)]
+    #[cfg_attr(feature = "miette", diagnostic(transparent))]
+    Env(FieldsErrorBunch<FieldFromEnvError>),
+    #[error(transparent)]
+    #[cfg_attr(feature = "miette", diagnostic(transparent))]
+    Complete(CompleteErrorDiagnostic),
+    #[error("Failed to enumerate ENV vars: {0}")]
+    EnvIter(crate::Report),
+    /// Failed to fetch a variable before reading fields, e.g. into [`crate::env::Snapshot`]
+    #[error(transparent)]
+    #[cfg_attr(feature = "miette", diagnostic(transparent))]
+    EnvFetch(FieldFromEnvError),
+    #[error(transparent)]
+    #[cfg_attr(feature = "miette", diagnostic(transparent))]
+    UnknownEnv(UnknownEnvError),
+    /// See [`ConfigBuilder::env_allowlist`]
+    #[error(transparent)]
+    #[cfg_attr(feature = "miette", diagnostic(transparent))]
+    EnvNotAllowed(EnvNotAllowedError),
+    /// See [`ConfigBuilder::deny_ambiguous_env`]
+    #[error(transparent)]
+    #[cfg_attr(feature = "miette", diagnostic(transparent))]
+    AmbiguousEnv(AmbiguousEnvError),
+    /// See [`ConfigBuilder::with_path_jail`]
+    #[cfg(feature = "serde")]
+    #[error(transparent)]
+    #[cfg_attr(feature = "miette", diagnostic(transparent))]
+    PathEscape(PathEscapeError),
+    /// See [`ConfigBuilder::deny_source_conflicts`]
+    #[cfg(feature = "serde")]
+    #[error(transparent)]
+    #[cfg_attr(feature = "miette", diagnostic(transparent))]
+    SourceConflict(SourceConflictError),
+    /// See [`ConfigBuilder::with_remote`]
+    #[cfg(feature = "serde")]
+    #[error("Failed to fetch `{name}`: {error}")]
+    Fetch { name: String, error: crate::Report },
+    /// See [`ConfigBuilder::with_remote`]
+    #[cfg(feature = "serde")]
+    #[error(transparent)]
+    #[cfg_attr(feature = "miette", diagnostic(transparent))]
+    SchemaMismatch(SchemaMismatchError),
+}
+
+impl BuildError {
+    /// The source error, if a config source could not be read, parsed or decoded
+    #[cfg(feature = "serde")]
+    pub fn source_error(&self) -> Option<&SourceError> {
+        match self {
+            Self::Source(report) => report.source_error(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+#[error("Unknown ENV vars: {}", variables.join(", "))]
+pub struct UnknownEnvError {
+    variables: Vec<String>,
+    #[cfg_attr(feature = "miette", help)]
+    help: Option<String>,
+}
+
+impl UnknownEnvError {
+    fn new(variables: Vec<(String, Option<String>)>) -> Self {
+        let suggestions: Vec<_> = variables
+            .iter()
+            .filter_map(|(variable, suggestion)| {
+                suggestion
+                    .as_ref()
+                    .map(|x| format!("`{x}` instead of `{variable}`"))
+            })
+            .collect();
+        Self {
+            variables: variables.into_iter().map(|(x, _)| x).collect(),
+            help: (!suggestions.is_empty())
+                .then(|| format!("did you mean {}?", suggestions.join(", "))),
+        }
+    }
+
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+}
+
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+#[error("ENV vars are not allowed to set {}", render_denied(fields))]
+pub struct EnvNotAllowedError {
+    fields: Vec<(String, Vec<String>)>,
+    #[cfg_attr(feature = "miette", help)]
+    help: String,
+}
+
+impl EnvNotAllowedError {
+    /// Paths of the denied fields
+    pub fn fields(&self) -> impl Iterator<Item = &str> {
+        self.fields.iter().map(|(x, _)| x.as_str())
+    }
+}
+
+fn render_denied(fields: &[(String, Vec<String>)]) -> String {
+    fields
+        .iter()
+        .map(|(field, variables)| match variables.as_slice() {
+            [] => format!("`{field}`"),
+            variables => format!("`{field}` (with {})", variables.join(" or ")),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+#[error("ENV vars disagree on {}", render_ambiguous(conflicts))]
+#[cfg_attr(
+    feature = "miette",
+    diagnostic(help("set only one of the variables, or set them to the same value"))
+)]
+pub struct AmbiguousEnvError {
+    conflicts: Vec<AmbiguousEnv>,
+}
+
+impl AmbiguousEnvError {
+    /// Paths of the fields with disagreeing variables
+    pub fn fields(&self) -> impl Iterator<Item = &str> {
+        self.conflicts.iter().map(|x| x.field.as_str())
+    }
+}
+
+#[derive(Debug)]
+struct AmbiguousEnv {
+    field: String,
+    /// Set variables with rendered values, in the listing order
+    values: Vec<(String, String)>,
+}
+
+fn render_ambiguous(conflicts: &[AmbiguousEnv]) -> String {
+    conflicts
+        .iter()
+        .map(|conflict| {
+            let values = conflict
+                .values
+                .iter()
+                .map(|(variable, value)| format!("{variable}={value}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("`{}` ({values})", conflict.field)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+pub enum BuildWarning {
+    #[error(
+        "`{field}` from {source_name} replaces the whole value from {previous}, as it is opaque"
+    )]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(help("set all keys of `{field}` in a single source"))
+    )]
+    OpaqueOverride {
+        field: String,
+        source_name: String,
+        previous: String,
+    },
+    #[error("ENV var `{variable}` is not set, so no config file is read from it")]
+    FileNotSelected { variable: String },
+    #[error("ENV var `{variable}` matches no field")]
+    UnknownEnv {
+        variable: String,
+        #[cfg_attr(feature = "miette", help)]
+        help: Option<String>,
+    },
+    /// See [`ConfigBuilder::with_secret_scan`]
+    #[cfg(all(feature = "regex", feature = "serde"))]
+    #[error("`{key}` at {location} contains a literal {pattern}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(help("provide it with ENV, a secret file or a secret manager instead"))
+    )]
+    LiteralSecret {
+        key: String,
+        pattern: String,
+        location: String,
+    },
+    /// See [`ConfigBuilder::allow_latin1`]
+    #[cfg(feature = "serde")]
+    #[error("{source_name} is not valid UTF-8, so it is read as Latin-1")]
+    #[cfg_attr(feature = "miette", diagnostic(help("save it in UTF-8")))]
+    NotUtf8 { source_name: String },
+    /// See [`ConfigBuilder::with_file_on_error`]
+    #[cfg(feature = "serde")]
+    #[error("{source_name} is skipped, as it failed to load")]
+    SourceSkipped {
+        source_name: String,
+        #[source]
+        #[cfg_attr(feature = "miette", diagnostic_source)]
+        report: LintReport,
+    },
+}
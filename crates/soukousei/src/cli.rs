@@ -0,0 +1,214 @@
+//! Library-level entry point for a companion config CLI.
+//!
+//! Applications can wrap [`run`] into a subcommand (e.g. `myapp config lint config.toml`) or a
+//! separate binary:
+//!
+//! ```text
+//! lint <file>            validate a config file
+//! schema --json          print JSON schema of the config
+//! example --toml         print an example TOML config
+//! env-docs --markdown    print a table of ENV variables
+//! ```
+
+use crate::lint::{validate_file, LintReport};
+use crate::meta::{self, FieldMeta};
+use crate::{HasLayer, Layer};
+#[cfg(feature = "miette")]
+use miette::Diagnostic;
+use serde::de::DeserializeOwned;
+use std::fmt::Write;
+use thiserror::Error;
+
+pub const USAGE: &str = "\
+usage:
+  lint <file>            validate a config file
+  schema --json          print JSON schema of the config
+  example --toml         print an example TOML config
+  env-docs --markdown    print a table of ENV variables";
+
+/// Run a command against the config type `T`, returning its output.
+///
+/// `args` should not include the program name.
+pub fn run<T>(args: impl IntoIterator<Item = String>) -> Result<String, CliError>
+where
+    T: HasLayer,
+    T::Layer: DeserializeOwned,
+{
+    let args: Vec<_> = args.into_iter().collect();
+    let args: Vec<_> = args.iter().map(String::as_str).collect();
+    let fields = T::Layer::fields();
+
+    match args.as_slice() {
+        ["lint", file] => {
+            validate_file::<T>(file)?;
+            Ok(format!("`{file}` is valid\n"))
+        }
+        ["schema", "--json"] => Ok(json_schema(fields)),
+        ["example", "--toml"] => Ok(example_toml(fields)),
+        ["env-docs", "--markdown"] => Ok(env_docs_markdown(fields)),
+        _ => Err(CliError::Usage),
+    }
+}
+
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+pub enum CliError {
+    #[error("Invalid arguments\n{}", USAGE)]
+    Usage,
+    #[error(transparent)]
+    #[cfg_attr(feature = "miette", diagnostic(transparent))]
+    Lint(#[from] LintReport),
+}
+
+/// Render a JSON schema (draft 2020-12) of the config, based on the fields metadata
+pub fn json_schema(fields: &[FieldMeta]) -> String {
+    let mut out = String::new();
+    out.push_str(r#"{"$schema":"https://json-schema.org/draft/2020-12/schema","#);
+    json_schema_object(&mut out, fields);
+    out.push_str("}\n");
+    out
+}
+
+fn json_schema_object(out: &mut String, fields: &[FieldMeta]) {
+    out.push_str(r#""type":"object","properties":{"#);
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write!(out, "{}:{{", json_string(field.name)).unwrap();
+        if let Some(doc) = field.doc {
+            write!(out, r#""description":{},"#, json_string(doc)).unwrap();
+        }
+        match field.nested_fields() {
+            Some(nested) => json_schema_object(out, nested),
+            None => {
+                if let Some(ty) = json_type(field.ty) {
+                    write!(out, r#""type":{},"#, json_string(ty)).unwrap();
+                }
+                write!(out, r#""x-rust-type":{}"#, json_string(field.ty)).unwrap();
+            }
+        }
+        out.push('}');
+    }
+    out.push('}');
+}
+
+fn json_type(ty: &str) -> Option<&'static str> {
+    let ty = ty
+        .strip_prefix("Option<")
+        .and_then(|x| x.strip_suffix('>'))
+        .unwrap_or(ty);
+
+    match ty {
+        "bool" => Some("boolean"),
+        "String" | "PathBuf" | "std::path::PathBuf" => Some("string"),
+        "f32" | "f64" => Some("number"),
+        "u8" | "u16" | "u32" | "u64" | "u128" | "usize" | "i8" | "i16" | "i32" | "i64" | "i128"
+        | "isize" => Some("integer"),
+        x if x.starts_with("Vec<") => Some("array"),
+        _ => None,
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Render an example TOML config with all fields commented out, documented with their doc
+/// comments, defaults and ENV variables
+pub fn example_toml(fields: &[FieldMeta]) -> String {
+    let mut out = String::new();
+    example_toml_table(&mut out, fields, &mut Vec::new());
+    out
+}
+
+fn example_toml_table(out: &mut String, fields: &[FieldMeta], path: &mut Vec<&'static str>) {
+    for field in fields.iter().filter(|x| x.nested.is_none()) {
+        if let Some(doc) = field.doc {
+            for line in doc.lines() {
+                writeln!(out, "# {line}").unwrap();
+            }
+        }
+        if !field.env.is_empty() {
+            writeln!(out, "# env: {}", field.env.join(", ")).unwrap();
+        }
+        match field.default {
+            Some(default) => writeln!(out, "# {} = {default}", field.name).unwrap(),
+            None => writeln!(out, "# {} = <{}>", field.name, field.ty).unwrap(),
+        }
+        out.push('\n');
+    }
+
+    for field in fields.iter() {
+        let Some(nested) = field.nested_fields() else {
+            continue;
+        };
+        path.push(field.name);
+        if let Some(doc) = field.doc {
+            for line in doc.lines() {
+                writeln!(out, "# {line}").unwrap();
+            }
+        }
+        writeln!(out, "[{}]\n", path.join(".")).unwrap();
+        example_toml_table(out, nested, path);
+        path.pop();
+    }
+}
+
+/// Render a markdown table of all ENV variables that can provide the config
+pub fn env_docs_markdown(fields: &[FieldMeta]) -> String {
+    let mut out = String::from("| Variable | Field | Type | Description |\n|---|---|---|---|\n");
+    env_docs_rows(&mut out, fields, &mut Vec::new(), "");
+    out
+}
+
+/// `env_prefix` is prepended to ENV vars of indexed collection elements, see
+/// [`meta::indexed_env`]
+fn env_docs_rows(
+    out: &mut String,
+    fields: &[FieldMeta],
+    path: &mut Vec<&'static str>,
+    env_prefix: &str,
+) {
+    for field in fields {
+        path.push(field.name);
+        match field.nested_fields() {
+            Some(nested) => {
+                let env_prefix = match field.env_indexed {
+                    Some(indexed) => meta::indexed_env(
+                        &format!("{env_prefix}{indexed}"),
+                        meta::INDEX_PLACEHOLDER,
+                        "",
+                    ),
+                    None => env_prefix.to_owned(),
+                };
+                env_docs_rows(out, nested, path, &env_prefix)
+            }
+            None => {
+                let doc = field.doc.unwrap_or_default().replace('\n', " ");
+                for env in field.env {
+                    writeln!(
+                        out,
+                        "| `{env_prefix}{env}` | `{}` | `{}` | {doc} |",
+                        path.join("."),
+                        field.ty
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        path.pop();
+    }
+}
@@ -0,0 +1,254 @@
+//! Layers for arrays and maps of nested configs, so that `#[layer(nested)]` works with
+//! `Vec<T>`, `BTreeMap<String, T>` and `HashMap<String, T>` fields where `T: HasLayer`.
+//!
+//! Collections are merged as a whole, i.e. a newer source replaces all the elements. Each
+//! element is merged over its defaults and completed in order, and errors are reported with
+//! indexed paths, e.g. `servers[1].port` or `tenants["acme"].quota`.
+//!
+//! Map entries are completed and serialized in order of their keys, even for `HashMap`, so that
+//! errors and dumps are deterministic.
+
+use crate::env::{EnvProvider, FieldFromEnvError, FromEnv, Prefixed};
+use crate::meta::{self, FieldMeta};
+use crate::{
+    CompleteError, CompleteFieldError, ErrorPolicy, HasLayer, Layer, MultipleFieldsError,
+    PathSegment, ResultExt,
+};
+use std::collections::{BTreeMap, HashMap};
+
+/// Complete each element, accumulating errors under its path segment made by `segment`
+pub(crate) fn complete_each<K, L, C>(
+    entries: impl Iterator<Item = (K, L)>,
+    segment: impl Fn(&K) -> PathSegment,
+    policy: ErrorPolicy,
+) -> Result<C, CompleteError>
+where
+    L: Layer + Default,
+    C: FromIterator<(K, L::Complete)>,
+{
+    let mut errors = MultipleFieldsError::<CompleteFieldError>::new().with_policy(policy);
+    let mut complete = Vec::new();
+
+    for (key, layer) in entries {
+        let (value, errors_next) = L::default()
+            .merge(layer)
+            .complete_with(policy)
+            .nest_if_err(errors, segment(&key));
+        errors = errors_next;
+        if errors.should_stop() {
+            break;
+        }
+        complete.extend(value.map(|value| (key, value)));
+    }
+
+    errors.result()?;
+    Ok(complete.into_iter().collect())
+}
+
+/// Layer of `Vec<T>`
+#[derive(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct VecLayer<L>(pub Option<Vec<L>>);
+
+impl<L> Default for VecLayer<L> {
+    fn default() -> Self {
+        Self(None)
+    }
+}
+
+impl<L: Layer + Default> Layer for VecLayer<L> {
+    type Complete = Vec<L::Complete>;
+
+    fn new() -> Self {
+        Self(None)
+    }
+
+    fn merge(self, other: Self) -> Self {
+        Self(other.0.or(self.0))
+    }
+
+    fn merge_from(&mut self, other: Self) {
+        if other.0.is_some() {
+            self.0 = other.0;
+        }
+    }
+
+    fn complete(self) -> Result<Self::Complete, CompleteError> {
+        self.complete_with(ErrorPolicy::CollectAll)
+    }
+
+    fn complete_with(self, policy: ErrorPolicy) -> Result<Self::Complete, CompleteError> {
+        let items = self.0.ok_or(CompleteError::MissingData)?;
+        let items: Vec<(usize, L::Complete)> = complete_each(
+            items.into_iter().enumerate(),
+            |&i| PathSegment::Index(i),
+            policy,
+        )?;
+        Ok(items.into_iter().map(|(_, x)| x).collect())
+    }
+
+    const FIELDS: &'static [FieldMeta] = L::FIELDS;
+
+    fn provided_fields(&self) -> Vec<String> {
+        self.0.iter().map(|_| String::new()).collect()
+    }
+}
+
+impl<T> HasLayer for Vec<T>
+where
+    T: HasLayer,
+    T::Layer: Default,
+{
+    type Layer = VecLayer<T::Layer>;
+}
+
+/// Elements are not read from ENV, unless the field has `#[layer(env_indexed = "...")]`, see
+/// [`IndexedFromEnv`]
+impl<L> FromEnv for VecLayer<L> {
+    fn from_env(
+        _provider: &impl EnvProvider,
+    ) -> Result<Self, MultipleFieldsError<FieldFromEnvError>> {
+        Ok(Self(None))
+    }
+}
+
+/// Collections which elements are read from indexed ENV vars, e.g. `MYAPP_UPSTREAMS_0_ADDR` and
+/// `MYAPP_UPSTREAMS_1_ADDR` for `#[layer(nested, env_indexed = "MYAPP_UPSTREAMS")]` over a
+/// `Vec<Upstream>` with `#[layer(env = "ADDR")]` on its field.
+///
+/// Indexes start from `0` and are read until one which has no vars set. Like other sources,
+/// ENV replaces the elements from files as a whole.
+pub trait IndexedFromEnv: Sized {
+    fn from_env_indexed(
+        provider: &impl EnvProvider,
+        prefix: &str,
+    ) -> Result<Self, MultipleFieldsError<FieldFromEnvError>>;
+}
+
+impl<L: Layer + FromEnv> IndexedFromEnv for VecLayer<L> {
+    fn from_env_indexed(
+        provider: &impl EnvProvider,
+        prefix: &str,
+    ) -> Result<Self, MultipleFieldsError<FieldFromEnvError>> {
+        let mut items = Vec::new();
+        let mut errors = MultipleFieldsError::new();
+
+        for index in 0.. {
+            let element = Prefixed::new(provider, meta::indexed_env(prefix, index, ""));
+            let (item, errors_next) = errors.nest_if_err(L::from_env(&element), index);
+            errors = errors_next;
+            match item {
+                Some(item) if item.provided_fields().is_empty() => break,
+                Some(item) => items.push(item),
+                // the element is set, as it failed to parse
+                None => {}
+            }
+        }
+
+        errors.result()?;
+        Ok(Self((!items.is_empty()).then_some(items)))
+    }
+}
+
+#[cfg(feature = "serde")]
+fn serialize_sorted<'a, M, L, S>(map: &'a Option<M>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    &'a M: IntoIterator<Item = (&'a String, &'a L)>,
+    L: serde::Serialize + 'a,
+    S: serde::Serializer,
+{
+    use serde::Serialize;
+
+    map.as_ref()
+        .map(|map| map.into_iter().collect::<BTreeMap<_, _>>())
+        .serialize(serializer)
+}
+
+macro_rules! map_layer {
+    ($(#[$meta:meta])* $layer:ident, $map:ident) => {
+        $(#[$meta])*
+        #[derive(Debug)]
+        #[cfg_attr(
+            feature = "serde",
+            derive(serde::Serialize, serde::Deserialize),
+            serde(transparent, bound(serialize = "L: serde::Serialize"))
+        )]
+        pub struct $layer<L>(
+            #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_sorted"))]
+            pub Option<$map<String, L>>,
+        );
+
+        impl<L> Default for $layer<L> {
+            fn default() -> Self {
+                Self(None)
+            }
+        }
+
+        impl<L: Layer + Default> Layer for $layer<L> {
+            type Complete = $map<String, L::Complete>;
+
+            fn new() -> Self {
+                Self(None)
+            }
+
+            fn merge(self, other: Self) -> Self {
+                Self(other.0.or(self.0))
+            }
+
+            fn merge_from(&mut self, other: Self) {
+                if other.0.is_some() {
+                    self.0 = other.0;
+                }
+            }
+
+            fn complete(self) -> Result<Self::Complete, CompleteError> {
+                self.complete_with(ErrorPolicy::CollectAll)
+            }
+
+            fn complete_with(self, policy: ErrorPolicy) -> Result<Self::Complete, CompleteError> {
+                let entries = self.0.ok_or(CompleteError::MissingData)?;
+                let mut entries: Vec<_> = entries.into_iter().collect();
+                entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+                complete_each(entries.into_iter(), |key| PathSegment::key(key.as_str()), policy)
+            }
+
+            const FIELDS: &'static [FieldMeta] = L::FIELDS;
+
+            fn provided_fields(&self) -> Vec<String> {
+                self.0.iter().map(|_| String::new()).collect()
+            }
+        }
+
+        impl<T> HasLayer for $map<String, T>
+        where
+            T: HasLayer,
+            T::Layer: Default,
+        {
+            type Layer = $layer<T::Layer>;
+        }
+
+        /// Entries are not read from ENV
+        impl<L> FromEnv for $layer<L> {
+            fn from_env(
+                _provider: &impl EnvProvider,
+            ) -> Result<Self, MultipleFieldsError<FieldFromEnvError>> {
+                Ok(Self(None))
+            }
+        }
+    };
+}
+
+map_layer!(
+    /// Layer of `BTreeMap<String, T>`
+    BTreeMapLayer,
+    BTreeMap
+);
+map_layer!(
+    /// Layer of `HashMap<String, T>`
+    HashMapLayer,
+    HashMap
+);
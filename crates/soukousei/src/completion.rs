@@ -0,0 +1,88 @@
+//! Shell completions of config paths for `--set key=value` overrides, based on the fields
+//! metadata.
+//!
+//! ```ignore
+//! // myapp completions bash > /etc/bash_completion.d/myapp
+//! print!("{}", AppLayer::completion_script(Shell::Bash, "myapp"));
+//! ```
+
+use crate::meta::FieldMeta;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// Render a completion script of `program`, which completes paths of all fields after `--set`
+pub fn completion_script(fields: &[FieldMeta], shell: Shell, program: &str) -> String {
+    let mut paths = Vec::new();
+    collect_paths(&mut paths, fields, "");
+    let function = format!(
+        "_{}_set",
+        program.replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+    );
+    let keys = paths
+        .iter()
+        .map(|(path, _)| path.as_str())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut out = String::new();
+    match shell {
+        Shell::Bash => {
+            writeln!(out, "{function}() {{").unwrap();
+            writeln!(out, "    local cur=\"${{COMP_WORDS[COMP_CWORD]}}\"").unwrap();
+            writeln!(out, "    local prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"").unwrap();
+            writeln!(out, "    if [[ \"$prev\" == \"--set\" ]]; then").unwrap();
+            writeln!(
+                out,
+                "        COMPREPLY=($(compgen -S '=' -W \"{keys}\" -- \"$cur\"))"
+            )
+            .unwrap();
+            writeln!(out, "        compopt -o nospace").unwrap();
+            writeln!(out, "    fi").unwrap();
+            writeln!(out, "}}").unwrap();
+            writeln!(out, "complete -F {function} {program}").unwrap();
+        }
+        Shell::Zsh => {
+            writeln!(out, "#compdef {program}").unwrap();
+            writeln!(out, "{function}() {{").unwrap();
+            writeln!(out, "    if [[ ${{words[CURRENT-1]}} == --set ]]; then").unwrap();
+            writeln!(out, "        compadd -S '=' -- {keys}").unwrap();
+            writeln!(out, "    fi").unwrap();
+            writeln!(out, "}}").unwrap();
+            writeln!(out, "compdef {function} {program}").unwrap();
+        }
+        Shell::Fish => {
+            for (path, doc) in paths.iter() {
+                write!(out, "complete -c {program} -l set -x -a '{path}='").unwrap();
+                if let Some(doc) = doc {
+                    write!(out, " -d '{}'", doc.replace('\'', "\\'")).unwrap();
+                }
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+/// Dot-separated paths of all non-nested fields, with the first line of their docs
+fn collect_paths(
+    out: &mut Vec<(String, Option<&'static str>)>,
+    fields: &[FieldMeta],
+    prefix: &str,
+) {
+    for field in fields {
+        let path = format!("{prefix}{}", field.name);
+        match field.nested_fields() {
+            Some(nested) => collect_paths(out, nested, &format!("{path}.")),
+            None => out.push((
+                path,
+                field.doc.and_then(|x| x.lines().next()).map(str::trim),
+            )),
+        }
+    }
+}
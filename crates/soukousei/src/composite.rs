@@ -0,0 +1,161 @@
+//! Loading several independent root configs from the same sources at once, e.g. when a binary
+//! is composed of libraries with their own configs:
+//!
+//! ```ignore
+//! let (app, telemetry, db) = CompositeBuilder::<(AppConfig, TelemetryConfig, DbConfig)>::new()
+//!     .with_defaults()
+//!     .with_file("config.toml")?
+//!     .with_env(&StdEnv::new())?
+//!     .build()?;
+//! ```
+//!
+//! A file is read once and parsed for each root, ignoring keys of the others. ENV vars of all
+//! roots are fetched once into an [`env::Snapshot`].
+
+use crate::builder::{read_file, BuildError, ConfigBuilder};
+use crate::env::{self, EnvProvider, FromEnv};
+use crate::source::Format;
+use crate::{meta, HasLayer, Layer};
+use serde::de::DeserializeOwned;
+use std::path::Path;
+
+/// Tuple of root config types, implemented for tuples of up to 4 elements
+pub trait Roots {
+    /// Builder of each root
+    type Builders;
+    /// Tuple of complete configs
+    type Complete;
+
+    fn new_builders() -> Self::Builders;
+
+    /// ENV vars of all roots
+    fn env_vars() -> Vec<&'static str>;
+
+    fn with_defaults(builders: Self::Builders) -> Self::Builders;
+
+    fn with_env(
+        builders: Self::Builders,
+        provider: &impl EnvProvider,
+    ) -> Result<Self::Builders, BuildError>;
+
+    fn with_str(
+        builders: Self::Builders,
+        name: &str,
+        format: Format,
+        contents: &str,
+    ) -> Result<Self::Builders, BuildError>;
+
+    fn build(builders: Self::Builders) -> Result<Self::Complete, BuildError>;
+}
+
+macro_rules! impl_roots {
+    ($($root:ident),+) => {
+        #[allow(non_snake_case)]
+        impl<$($root),+> Roots for ($($root,)+)
+        where
+            $(
+                $root: HasLayer,
+                $root::Layer: Default + FromEnv + DeserializeOwned,
+            )+
+        {
+            type Builders = ($(ConfigBuilder<$root::Layer>,)+);
+            type Complete = ($($root,)+);
+
+            fn new_builders() -> Self::Builders {
+                ($(ConfigBuilder::<$root::Layer>::new(),)+)
+            }
+
+            fn env_vars() -> Vec<&'static str> {
+                let mut vars = Vec::new();
+                $(vars.extend(meta::env_vars(<$root::Layer as Layer>::FIELDS));)+
+                vars
+            }
+
+            fn with_defaults(builders: Self::Builders) -> Self::Builders {
+                let ($($root,)+) = builders;
+                ($($root.with_defaults(),)+)
+            }
+
+            fn with_env(
+                builders: Self::Builders,
+                provider: &impl EnvProvider,
+            ) -> Result<Self::Builders, BuildError> {
+                let ($($root,)+) = builders;
+                Ok(($($root.with_env(provider)?,)+))
+            }
+
+            fn with_str(
+                builders: Self::Builders,
+                name: &str,
+                format: Format,
+                contents: &str,
+            ) -> Result<Self::Builders, BuildError> {
+                let ($($root,)+) = builders;
+                Ok(($($root.with_str(name, format, contents)?,)+))
+            }
+
+            fn build(builders: Self::Builders) -> Result<Self::Complete, BuildError> {
+                let ($($root,)+) = builders;
+                Ok(($($root.build()?,)+))
+            }
+        }
+    };
+}
+
+impl_roots!(A);
+impl_roots!(A, B);
+impl_roots!(A, B, C);
+impl_roots!(A, B, C, D);
+
+/// Same as [`ConfigBuilder`], but for a tuple of root configs `R`, see the [module](self) docs
+pub struct CompositeBuilder<R: Roots> {
+    builders: R::Builders,
+}
+
+impl<R: Roots> CompositeBuilder<R> {
+    pub fn new() -> Self {
+        Self {
+            builders: R::new_builders(),
+        }
+    }
+
+    /// Merge default values of each root
+    pub fn with_defaults(self) -> Self {
+        Self {
+            builders: R::with_defaults(self.builders),
+        }
+    }
+
+    /// Fetch ENV vars of all roots once, then read each root from them
+    pub fn with_env(self, provider: &impl EnvProvider) -> Result<Self, BuildError> {
+        let snapshot =
+            env::Snapshot::capture(provider, R::env_vars()).map_err(BuildError::EnvFetch)?;
+        Ok(Self {
+            builders: R::with_env(self.builders, &snapshot)?,
+        })
+    }
+
+    /// Read a config file once and parse it for each root. The format is guessed by the file
+    /// extension.
+    pub fn with_file(self, path: impl AsRef<Path>) -> Result<Self, BuildError> {
+        let (name, format, contents) = read_file(path.as_ref())?;
+        self.with_str(&name, format, &contents)
+    }
+
+    /// Parse config contents for each root. `name` is used in diagnostics.
+    pub fn with_str(self, name: &str, format: Format, contents: &str) -> Result<Self, BuildError> {
+        Ok(Self {
+            builders: R::with_str(self.builders, name, format, contents)?,
+        })
+    }
+
+    pub fn build(self) -> Result<R::Complete, BuildError> {
+        R::build(self.builders)
+    }
+}
+
+impl<R: Roots> Default for CompositeBuilder<R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
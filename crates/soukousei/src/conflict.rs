@@ -0,0 +1,272 @@
+//! Detection of sources which disagree on the shape of a key, e.g. a table in one and a string
+//! in another.
+//!
+//! Each source is deserialized into a layer on its own, so such a source either fails to decode
+//! or replaces the value, without naming the source it disagrees with.
+
+use crate::normalize::KeyNormalizer;
+use crate::source::Format;
+#[cfg(feature = "miette")]
+use miette::Diagnostic;
+use serde::de::{self, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::fmt;
+use thiserror::Error;
+
+/// Key of the map TOML deserializes datetimes into
+const TOML_DATETIME_KEY: &str = "$__toml_private_datetime";
+
+/// Shapes of the sources added so far
+#[derive(Debug, Default)]
+pub struct SourceShapes {
+    sources: Vec<(String, Shape)>,
+}
+
+impl SourceShapes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a source, unless it disagrees with an earlier one on the shape of a key. Each key is
+    /// compared with the latest earlier source which sets it, so a `null` in between unsets the
+    /// key.
+    ///
+    /// Contents which fail to parse are skipped, as deserializing the layer reports them.
+    pub fn add(
+        &mut self,
+        name: impl Into<String>,
+        format: Format,
+        contents: &str,
+    ) -> Result<(), SourceConflictError> {
+        self.add_with(name, format, contents, None)
+    }
+
+    /// Same as [`Self::add`], but keys are mapped onto field names with `normalizer`, see
+    /// [`Format::parse_with`]
+    pub fn add_with(
+        &mut self,
+        name: impl Into<String>,
+        format: Format,
+        contents: &str,
+        normalizer: Option<&dyn KeyNormalizer>,
+    ) -> Result<(), SourceConflictError> {
+        let name = name.into();
+        if let Some(shape) = self.check(&name, format, contents, normalizer)? {
+            self.push(name, shape);
+        }
+        Ok(())
+    }
+
+    /// Compare a source with the earlier ones without adding it, so that it is added with
+    /// [`Self::push`] only once the layer is deserialized from it
+    pub(crate) fn check(
+        &self,
+        name: &str,
+        format: Format,
+        contents: &str,
+        normalizer: Option<&dyn KeyNormalizer>,
+    ) -> Result<Option<Shape>, SourceConflictError> {
+        let Ok(parsed) = format.parse_with::<Shape>(contents, normalizer) else {
+            return Ok(None);
+        };
+        let mut conflicts = Vec::new();
+        self.find_conflicts(&parsed.value, &mut Vec::new(), &mut conflicts);
+        if conflicts.is_empty() {
+            Ok(Some(parsed.value))
+        } else {
+            Err(SourceConflictError {
+                source_name: name.to_owned(),
+                conflicts,
+            })
+        }
+    }
+
+    pub(crate) fn push(&mut self, name: String, shape: Shape) {
+        self.sources.push((name, shape));
+    }
+
+    fn find_conflicts<'a>(
+        &self,
+        shape: &'a Shape,
+        path: &mut Vec<&'a str>,
+        conflicts: &mut Vec<SourceConflict>,
+    ) {
+        let Shape::Table(table) = shape else {
+            return;
+        };
+        for (key, value) in table {
+            path.push(key);
+            let previous = self
+                .sources
+                .iter()
+                .rev()
+                .find_map(|(source, tree)| Some((source, tree.get(path)?)));
+            match previous {
+                Some((source, previous)) if previous.conflicts_with(value) => {
+                    conflicts.push(SourceConflict {
+                        path: path.join("."),
+                        previous: source.clone(),
+                        previous_type: previous.type_name(),
+                        type_name: value.type_name(),
+                    });
+                }
+                _ => self.find_conflicts(value, path, conflicts),
+            }
+            path.pop();
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+#[error(
+    "`{source_name}` disagrees with earlier sources on {}",
+    render_conflicts(source_name, conflicts)
+)]
+#[cfg_attr(
+    feature = "miette",
+    diagnostic(help("use the same structure for the key in every source"))
+)]
+pub struct SourceConflictError {
+    source_name: String,
+    conflicts: Vec<SourceConflict>,
+}
+
+impl SourceConflictError {
+    /// Name of the source which failed to be added
+    pub fn source_name(&self) -> &str {
+        &self.source_name
+    }
+
+    /// Dot-separated paths of the conflicting keys
+    pub fn fields(&self) -> impl Iterator<Item = &str> {
+        self.conflicts.iter().map(|x| x.path.as_str())
+    }
+}
+
+#[derive(Debug)]
+struct SourceConflict {
+    path: String,
+    /// The latest earlier source which sets the key
+    previous: String,
+    previous_type: &'static str,
+    type_name: &'static str,
+}
+
+fn render_conflicts(source_name: &str, conflicts: &[SourceConflict]) -> String {
+    conflicts
+        .iter()
+        .map(|conflict| {
+            format!(
+                "`{}` ({} in `{}`, {} in `{source_name}`)",
+                conflict.path, conflict.previous_type, conflict.previous, conflict.type_name
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Structure of a source without the values of its scalars
+#[derive(Debug)]
+pub(crate) enum Shape {
+    Null,
+    Scalar(&'static str),
+    Array,
+    Table(BTreeMap<String, Shape>),
+}
+
+impl Shape {
+    fn get(&self, path: &[&str]) -> Option<&Shape> {
+        path.iter().try_fold(self, |shape, key| match shape {
+            Self::Table(table) => table.get(*key),
+            _ => None,
+        })
+    }
+
+    fn type_name(&self) -> &'static str {
+        match self {
+            Self::Null => "null",
+            Self::Scalar(type_name) => type_name,
+            Self::Array => "array",
+            Self::Table(_) => "table",
+        }
+    }
+
+    /// Scalars of different types are not conflicts, as the field decides whether it accepts
+    /// them, and null unsets the value
+    fn conflicts_with(&self, other: &Shape) -> bool {
+        let kind = |shape: &Shape| match shape {
+            Self::Null => None,
+            Self::Scalar(_) => Some("scalar"),
+            Self::Array => Some("array"),
+            Self::Table(_) => Some("table"),
+        };
+        matches!((kind(self), kind(other)), (Some(a), Some(b)) if a != b)
+    }
+}
+
+impl<'de> Deserialize<'de> for Shape {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ShapeVisitor)
+    }
+}
+
+struct ShapeVisitor;
+
+impl<'de> Visitor<'de> for ShapeVisitor {
+    type Value = Shape;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any value")
+    }
+
+    fn visit_bool<E: de::Error>(self, _: bool) -> Result<Shape, E> {
+        Ok(Shape::Scalar("boolean"))
+    }
+
+    fn visit_i64<E: de::Error>(self, _: i64) -> Result<Shape, E> {
+        Ok(Shape::Scalar("integer"))
+    }
+
+    fn visit_u64<E: de::Error>(self, _: u64) -> Result<Shape, E> {
+        Ok(Shape::Scalar("integer"))
+    }
+
+    fn visit_f64<E: de::Error>(self, _: f64) -> Result<Shape, E> {
+        Ok(Shape::Scalar("float"))
+    }
+
+    fn visit_str<E: de::Error>(self, _: &str) -> Result<Shape, E> {
+        Ok(Shape::Scalar("string"))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Shape, E> {
+        Ok(Shape::Null)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Shape, E> {
+        Ok(Shape::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Shape, D::Error> {
+        Shape::deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Shape, A::Error> {
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(Shape::Array)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Shape, A::Error> {
+        let mut table = BTreeMap::new();
+        while let Some(key) = map.next_key::<String>()? {
+            if key == TOML_DATETIME_KEY {
+                map.next_value::<IgnoredAny>()?;
+                return Ok(Shape::Scalar("datetime"));
+            }
+            table.insert(key, map.next_value()?);
+        }
+        Ok(Shape::Table(table))
+    }
+}
@@ -0,0 +1,78 @@
+//! HTTP server section: bind address, timeouts and optional TLS.
+//!
+//! ```toml
+//! [server]
+//! host = "127.0.0.1"
+//! port = 8443
+//!
+//! [server.tls]
+//! enabled = true
+//! cert = "/etc/app/cert.pem"
+//! key = "/etc/app/key.pem"
+//! ```
+
+use super::InvalidSectionError;
+use crate::toggle::Toggle;
+use crate::Layer;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Layer)]
+#[layer(crate = "crate")]
+pub struct HttpServerConfig {
+    /// Address to bind to
+    #[layer(default = "IpAddr::from([0, 0, 0, 0])")]
+    pub host: IpAddr,
+    /// Port to listen on
+    #[layer(default = "8080")]
+    pub port: u16,
+    /// Maximum duration of a request, in seconds
+    #[layer(default = "30")]
+    pub request_timeout_secs: u64,
+    /// Maximum size of a request body, in bytes
+    #[layer(default = "2 * 1024 * 1024")]
+    pub body_limit_bytes: usize,
+    #[layer(toggle)]
+    pub tls: Toggle<TlsConfig>,
+}
+
+#[derive(Debug, Clone, Layer)]
+#[layer(crate = "crate")]
+pub struct TlsConfig {
+    /// PEM certificate chain
+    pub cert: PathBuf,
+    /// PEM private key
+    pub key: PathBuf,
+}
+
+impl HttpServerConfig {
+    pub fn socket_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.host, self.port)
+    }
+
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout_secs)
+    }
+
+    /// Check values which can't be checked by their types, and that TLS files exist
+    pub fn validate(&self) -> Result<(), InvalidSectionError> {
+        if self.request_timeout_secs == 0 {
+            return Err(InvalidSectionError::new(
+                "request_timeout_secs",
+                "must be positive",
+            ));
+        }
+        if let Some(tls) = self.tls.enabled() {
+            for (field, path) in [("tls.cert", &tls.cert), ("tls.key", &tls.key)] {
+                if !path.is_file() {
+                    return Err(InvalidSectionError::new(
+                        field,
+                        format!("`{}` is not a file", path.display()),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+//! Logging section, which configures a `tracing-subscriber` directly. Enabled with the
+//! `logging` feature.
+//!
+//! ```ignore
+//! #[derive(Layer)]
+//! struct Config {
+//!     #[layer(nested)]
+//!     logging: LoggingConfig,
+//! }
+//!
+//! init_tracing(&config.logging)?;
+//! ```
+//!
+//! ```toml
+//! [logging]
+//! level = "debug"
+//! format = "json"
+//! file = "/var/log/app.log"
+//!
+//! [logging.targets]
+//! hyper = "warn"
+//! ```
+
+use crate::Layer;
+#[cfg(feature = "miette")]
+use miette::Diagnostic;
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use thiserror::Error;
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer as _;
+
+#[derive(Debug, Clone, Layer)]
+#[layer(crate = "crate")]
+pub struct LoggingConfig {
+    /// Minimal level of events
+    #[layer(default = "Level::Info")]
+    pub level: Level,
+    /// Format of events
+    #[layer(default = "LogFormat::Pretty")]
+    pub format: LogFormat,
+    /// File to append events to, instead of stderr
+    pub file: Option<PathBuf>,
+    /// Levels of specific targets, e.g. `hyper = "warn"`, overriding `level`
+    #[layer(default = "BTreeMap::new()")]
+    pub targets: BTreeMap<String, Level>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<Level> for LevelFilter {
+    fn from(value: Level) -> Self {
+        match value {
+            Level::Off => LevelFilter::OFF,
+            Level::Error => LevelFilter::ERROR,
+            Level::Warn => LevelFilter::WARN,
+            Level::Info => LevelFilter::INFO,
+            Level::Debug => LevelFilter::DEBUG,
+            Level::Trace => LevelFilter::TRACE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Multi-line, human-readable
+    Pretty,
+    /// Single-line, human-readable
+    Compact,
+    /// Newline-delimited JSON
+    Json,
+}
+
+impl LoggingConfig {
+    /// Filter of events by `level` and `targets`
+    pub fn targets(&self) -> Targets {
+        self.targets.iter().fold(
+            Targets::new().with_default(self.level),
+            |acc, (target, level)| acc.with_target(target, *level),
+        )
+    }
+}
+
+/// Install a global subscriber configured by the section
+pub fn init_tracing(config: &LoggingConfig) -> Result<(), LoggingError> {
+    let writer = match &config.file {
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|source| LoggingError::File {
+                    path: path.clone(),
+                    source,
+                })?;
+            BoxMakeWriter::new(Mutex::new(file))
+        }
+        None => BoxMakeWriter::new(std::io::stderr),
+    };
+    let ansi = config.file.is_none();
+    let filter = config.targets();
+
+    let fmt = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi);
+    let layer = match config.format {
+        LogFormat::Pretty => fmt.pretty().with_filter(filter).boxed(),
+        LogFormat::Compact => fmt.compact().with_filter(filter).boxed(),
+        LogFormat::Json => fmt.json().with_filter(filter).boxed(),
+    };
+
+    tracing_subscriber::registry()
+        .with(layer)
+        .try_init()
+        .map_err(|err| LoggingError::Init(err.to_string()))
+}
+
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+pub enum LoggingError {
+    #[error("failed to open log file `{}`", path.display())]
+    File {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("failed to install the global subscriber: {0}")]
+    Init(String),
+}
@@ -0,0 +1,37 @@
+//! Ready-made config sections for common concerns, to be nested into app configs with
+//! `#[layer(nested)]`. Logging is behind the `logging` feature, other sections are behind the
+//! `contrib` one.
+
+#[cfg(feature = "miette")]
+use miette::Diagnostic;
+use thiserror::Error;
+
+#[cfg(feature = "contrib")]
+pub mod database;
+#[cfg(feature = "contrib")]
+pub mod http;
+#[cfg(feature = "logging")]
+pub mod logging;
+
+/// A complete section with values which are inconsistent, e.g. a pool with `min > max`
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+#[error("`{field}` {message}")]
+pub struct InvalidSectionError {
+    field: &'static str,
+    message: String,
+}
+
+impl InvalidSectionError {
+    #[cfg(feature = "contrib")]
+    pub(crate) fn new(field: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            field,
+            message: message.into(),
+        }
+    }
+
+    pub fn field(&self) -> &'static str {
+        self.field
+    }
+}
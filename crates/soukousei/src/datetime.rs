@@ -0,0 +1,107 @@
+//! [Parsers](crate::parse::FieldParser) of `chrono` and `time` datetimes, enabled with the
+//! `chrono` and `time` features.
+//!
+//! Besides RFC 3339, they accept `YYYY-MM-DD HH:MM:SS`, `YYYY-MM-DDTHH:MM:SS` and `YYYY-MM-DD`,
+//! which are treated as UTC.
+
+use crate::parse::{FieldParser, InvalidValueError};
+use crate::Report;
+
+#[cfg(any(feature = "chrono", feature = "time"))]
+const EXPECTED_DATETIME: &str =
+    "RFC 3339 datetime, `YYYY-MM-DD HH:MM:SS`, `YYYY-MM-DDTHH:MM:SS` or `YYYY-MM-DD` (UTC)";
+
+#[cfg(feature = "chrono")]
+const EXPECTED_DATE: &str = "date in `YYYY-MM-DD`, `YYYY/MM/DD` or `DD.MM.YYYY` format";
+
+/// Parser of [`chrono::DateTime<chrono::Utc>`]
+#[cfg(feature = "chrono")]
+pub struct ChronoUtc;
+
+#[cfg(feature = "chrono")]
+impl FieldParser for ChronoUtc {
+    type Value = chrono::DateTime<chrono::Utc>;
+
+    fn parse(value: &str) -> Result<Self::Value, Report> {
+        use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+        let value = value.trim();
+        if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+            return Ok(datetime.with_timezone(&Utc));
+        }
+        ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"]
+            .into_iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+            .or_else(|| {
+                NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                    .ok()
+                    .and_then(|date| date.and_hms_opt(0, 0, 0))
+            })
+            .map(|datetime| Utc.from_utc_datetime(&datetime))
+            .ok_or_else(|| InvalidValueError::new(value, EXPECTED_DATETIME).into())
+    }
+
+    fn format(value: &Self::Value) -> String {
+        value.to_rfc3339()
+    }
+}
+
+/// Parser of [`chrono::NaiveDate`]
+#[cfg(feature = "chrono")]
+pub struct ChronoNaiveDate;
+
+#[cfg(feature = "chrono")]
+impl FieldParser for ChronoNaiveDate {
+    type Value = chrono::NaiveDate;
+
+    fn parse(value: &str) -> Result<Self::Value, Report> {
+        let value = value.trim();
+        ["%Y-%m-%d", "%Y/%m/%d", "%d.%m.%Y"]
+            .into_iter()
+            .find_map(|format| chrono::NaiveDate::parse_from_str(value, format).ok())
+            .ok_or_else(|| InvalidValueError::new(value, EXPECTED_DATE).into())
+    }
+
+    fn format(value: &Self::Value) -> String {
+        value.format("%Y-%m-%d").to_string()
+    }
+}
+
+/// Parser of [`time::OffsetDateTime`]
+#[cfg(feature = "time")]
+pub struct TimeOffsetDateTime;
+
+#[cfg(feature = "time")]
+impl FieldParser for TimeOffsetDateTime {
+    type Value = time::OffsetDateTime;
+
+    fn parse(value: &str) -> Result<Self::Value, Report> {
+        use time::format_description::well_known::Rfc3339;
+        use time::macros::format_description;
+        use time::{Date, OffsetDateTime, PrimitiveDateTime, Time};
+
+        let value = value.trim();
+        if let Ok(datetime) = OffsetDateTime::parse(value, &Rfc3339) {
+            return Ok(datetime);
+        }
+        [
+            format_description!("[year]-[month]-[day] [hour]:[minute]:[second]"),
+            format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]"),
+        ]
+        .into_iter()
+        .find_map(|format| PrimitiveDateTime::parse(value, format).ok())
+        .or_else(|| {
+            Date::parse(value, format_description!("[year]-[month]-[day]"))
+                .ok()
+                .map(|date| date.with_time(Time::MIDNIGHT))
+        })
+        .map(PrimitiveDateTime::assume_utc)
+        .ok_or_else(|| InvalidValueError::new(value, EXPECTED_DATETIME).into())
+    }
+
+    fn format(value: &Self::Value) -> String {
+        use time::format_description::well_known::Rfc3339;
+
+        value.format(&Rfc3339).unwrap_or_else(|_| value.to_string())
+    }
+}
@@ -0,0 +1,42 @@
+//! Partial completion, so that a service can start degraded when some of its config sections
+//! are broken, e.g. metrics, instead of refusing to boot.
+//!
+//! Enabled with `#[layer(degradable)]`, which generates `complete_partial` for the layer and a
+//! `<Struct>Degraded` type, in which every nested section is an `Option`. Top-level plain fields
+//! are still required.
+//!
+//! Sections are completed with the error policy of the struct, see `complete_partial_with`, and
+//! references are resolved as by `complete`, except for the ones in or to a failed section.
+
+use crate::{CompleteError, CompleteErrorDiagnostic};
+#[cfg(feature = "miette")]
+use miette::Diagnostic;
+use thiserror::Error;
+
+/// A nested section which failed to complete
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+#[error("Section `{name}` is disabled, as it failed to complete")]
+pub struct FailedSection {
+    name: &'static str,
+    #[source]
+    #[cfg_attr(feature = "miette", diagnostic_source)]
+    error: CompleteErrorDiagnostic,
+}
+
+impl FailedSection {
+    pub fn new(name: &'static str, error: CompleteError) -> Self {
+        Self {
+            name,
+            error: error.into(),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn error(&self) -> &CompleteErrorDiagnostic {
+        &self.error
+    }
+}
@@ -0,0 +1,109 @@
+//! Rewriting TOML configs in place, keeping comments and formatting of the existing document.
+//! Enabled with the `edit` feature.
+//!
+//! ```ignore
+//! let mut layer = ConfigLayer::new();
+//! layer.port = Some(3000);
+//! edit::apply_layer_to_file("config.toml", &layer)?;
+//! ```
+//!
+//! Only keys provided by the layer are touched. A replaced value keeps the comments around it,
+//! and new sections are appended as regular tables.
+
+#[cfg(feature = "miette")]
+use miette::Diagnostic;
+use serde::Serialize;
+use std::path::Path;
+use thiserror::Error;
+use toml_edit::{Document, Item, TableLike, Value};
+
+/// Set every key provided by `layer` in `document`
+pub fn apply_layer_to_document<T: Serialize>(
+    document: &mut Document,
+    layer: &T,
+) -> Result<(), EditError> {
+    let source = toml_edit::ser::to_document(layer)?;
+    merge(document.as_table_mut(), source.as_table(), false);
+    Ok(())
+}
+
+/// Same as [`apply_layer_to_document`], but for a TOML string
+pub fn apply_layer_to_str<T: Serialize>(contents: &str, layer: &T) -> Result<String, EditError> {
+    let mut document: Document = contents.parse()?;
+    apply_layer_to_document(&mut document, layer)?;
+    Ok(document.to_string())
+}
+
+/// Same as [`apply_layer_to_document`], but for a TOML file, which is created if it is missing
+pub fn apply_layer_to_file<T: Serialize>(
+    path: impl AsRef<Path>,
+    layer: &T,
+) -> Result<(), EditError> {
+    let path = path.as_ref();
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(EditError::Io(err)),
+    };
+    let contents = apply_layer_to_str(&contents, layer)?;
+    std::fs::write(path, contents).map_err(EditError::Io)
+}
+
+fn merge(target: &mut dyn TableLike, source: &dyn TableLike, inline: bool) {
+    for (key, item) in source.iter() {
+        match target.get_mut(key) {
+            Some(existing) => merge_item(existing, item),
+            // a section without provided keys
+            None if item.as_table_like().is_some_and(|table| table.is_empty()) => {}
+            None => {
+                let item = if inline {
+                    item.clone()
+                } else {
+                    into_block(item.clone())
+                };
+                target.insert(key, item);
+            }
+        }
+    }
+}
+
+fn merge_item(target: &mut Item, source: &Item) {
+    let inline = target.is_inline_table();
+    if let (Some(target), Some(source)) = (target.as_table_like_mut(), source.as_table_like()) {
+        merge(target, source, inline);
+        return;
+    }
+    match (target.as_value_mut(), source.as_value()) {
+        (Some(existing), Some(value)) => {
+            let decor = existing.decor().clone();
+            *existing = value.clone();
+            *existing.decor_mut() = decor;
+        }
+        _ => *target = source.clone(),
+    }
+}
+
+/// Serialized sections are inline tables, while regular ones read better in config files
+fn into_block(item: Item) -> Item {
+    match item {
+        Item::Value(Value::InlineTable(inline)) => {
+            let mut table = inline.into_table();
+            for (_, child) in table.iter_mut() {
+                *child = into_block(std::mem::take(child));
+            }
+            Item::Table(table)
+        }
+        item => item,
+    }
+}
+
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+pub enum EditError {
+    #[error("Failed to parse the document")]
+    Parse(#[from] toml_edit::TomlError),
+    #[error("Failed to serialize the layer")]
+    Serialize(#[from] toml_edit::ser::Error),
+    #[error("Failed to read or write the file")]
+    Io(#[source] std::io::Error),
+}
@@ -0,0 +1,157 @@
+//! Exporting a complete config as ENV vars which would reproduce it, e.g. for `Environment=`
+//! lines of a systemd unit or a docker-compose snippet.
+//!
+//! Implemented by the derive with `#[layer(env_export)]`. Only fields with `#[layer(env)]` are
+//! exported, under the first of their names. Secret fields are skipped unless requested
+//! explicitly:
+//!
+//! ```ignore
+//! #[derive(Layer)]
+//! #[layer(env_export)]
+//! struct Config {
+//!     #[layer(env = "PORT")]
+//!     port: u16,
+//!     #[layer(env = "DB_PASSWORD", secret)]
+//!     db_password: String,
+//! }
+//!
+//! // [("PORT", "8080")]
+//! let vars = config.to_env_vars("");
+//! ```
+
+use crate::bounded::{Bounded, Ratio};
+use crate::env::EnvEnum;
+use crate::net::CidrList;
+use crate::toggle::Toggle;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// Implemented by the derive for complete structs, see [module-level docs](self)
+pub trait EnvExport {
+    /// Push variables with names prefixed with `prefix` into `out`, in order of fields
+    fn env_vars_into(&self, prefix: &str, secrets: bool, out: &mut Vec<(String, String)>);
+
+    /// Variables without secrets
+    fn to_env_vars(&self, prefix: &str) -> Vec<(String, String)> {
+        let mut out = Vec::new();
+        self.env_vars_into(prefix, false, &mut out);
+        out
+    }
+
+    /// Variables including secrets
+    fn to_env_vars_with_secrets(&self, prefix: &str) -> Vec<(String, String)> {
+        let mut out = Vec::new();
+        self.env_vars_into(prefix, true, &mut out);
+        out
+    }
+}
+
+impl<T: EnvExport> EnvExport for Box<T> {
+    fn env_vars_into(&self, prefix: &str, secrets: bool, out: &mut Vec<(String, String)>) {
+        (**self).env_vars_into(prefix, secrets, out)
+    }
+}
+
+impl<T: EnvExport> EnvExport for Arc<T> {
+    fn env_vars_into(&self, prefix: &str, secrets: bool, out: &mut Vec<(String, String)>) {
+        (**self).env_vars_into(prefix, secrets, out)
+    }
+}
+
+impl<T: EnvExport> EnvExport for Rc<T> {
+    fn env_vars_into(&self, prefix: &str, secrets: bool, out: &mut Vec<(String, String)>) {
+        (**self).env_vars_into(prefix, secrets, out)
+    }
+}
+
+/// Variables of an enabled section. `enabled` itself is not read from ENV.
+impl<T: EnvExport> EnvExport for Toggle<T> {
+    fn env_vars_into(&self, prefix: &str, secrets: bool, out: &mut Vec<(String, String)>) {
+        if let Self::Enabled(value) = self {
+            value.env_vars_into(prefix, secrets, out)
+        }
+    }
+}
+
+impl<T: EnvExport> EnvExport for Option<T> {
+    fn env_vars_into(&self, prefix: &str, secrets: bool, out: &mut Vec<(String, String)>) {
+        if let Some(value) = self {
+            value.env_vars_into(prefix, secrets, out)
+        }
+    }
+}
+
+/// Collections are not read from ENV, so nothing is exported
+impl<T> EnvExport for Vec<T> {
+    fn env_vars_into(&self, _prefix: &str, _secrets: bool, _out: &mut Vec<(String, String)>) {}
+}
+
+impl<T> EnvExport for BTreeMap<String, T> {
+    fn env_vars_into(&self, _prefix: &str, _secrets: bool, _out: &mut Vec<(String, String)>) {}
+}
+
+impl<T, S> EnvExport for HashMap<String, T, S> {
+    fn env_vars_into(&self, _prefix: &str, _secrets: bool, _out: &mut Vec<(String, String)>) {}
+}
+
+#[cfg(feature = "toml")]
+impl<T> EnvExport for crate::instances::Instances<T> {
+    fn env_vars_into(&self, _prefix: &str, _secrets: bool, _out: &mut Vec<(String, String)>) {}
+}
+
+/// Value of a field as it is parsed from ENV
+pub trait EnvValue {
+    fn to_env_value(&self) -> String;
+}
+
+macro_rules! env_value_via_to_string {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl EnvValue for $ty {
+                fn to_env_value(&self) -> String {
+                    self.to_string()
+                }
+            }
+        )*
+    };
+}
+
+env_value_via_to_string!(
+    String, bool, char, u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64,
+    IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, CidrList, Ratio,
+);
+
+impl<T: Display, const MIN: i128, const MAX: i128> EnvValue for Bounded<T, MIN, MAX> {
+    fn to_env_value(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl EnvValue for Cow<'_, str> {
+    fn to_env_value(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl EnvValue for PathBuf {
+    fn to_env_value(&self) -> String {
+        self.to_string_lossy().into_owned()
+    }
+}
+
+impl EnvValue for OsString {
+    fn to_env_value(&self) -> String {
+        self.to_string_lossy().into_owned()
+    }
+}
+
+/// Value of an `#[layer(env_enum)]` field
+pub fn enum_value<T: EnvEnum>(value: &T) -> String {
+    T::NAMES[value.index()].to_owned()
+}
@@ -0,0 +1,199 @@
+//! Deterministic placeholder layers for tests, enabled with the `fuzz` or `proptest` feature.
+//!
+//! Integration tests often need a valid config, but don't care about most of its values.
+//! Instead of writing every field, a layer might be filled with placeholders derived from a
+//! seed and field paths, and the fields that matter are set on top of it:
+//!
+//! ```ignore
+//! let config = ConfigLayer::arbitrary(42)
+//!     .merge(soukousei::layer!(Config { db.port: 5432u16 }))
+//!     .complete()?;
+//! ```
+//!
+//! The same seed always gives the same layer, on every platform. Strings are made of the field
+//! name and a hash, e.g. `host-3f2a`, and numbers are small positive ones. Fields of types
+//! without placeholders, e.g. enums, are left unset, so give them defaults or set them as well.
+//!
+//! Fuzz targets based on the `arbitrary` crate can produce layers with `from_unstructured`, and
+//! property tests with the strategies of [`crate::strategy`].
+
+use crate::meta::{self, FieldMeta};
+use crate::schema::Fnv64;
+use crate::value::Value;
+use crate::Layer;
+#[cfg(feature = "fuzz")]
+use arbitrary::{Arbitrary, Unstructured};
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+
+/// Layer with every field of a supported type set to a placeholder, see the [module](self) docs
+pub fn arbitrary<L>(seed: u64) -> Result<L, serde::de::value::Error>
+where
+    L: Layer + DeserializeOwned,
+{
+    placeholders(L::FIELDS, seed).deserialize_into()
+}
+
+/// Same as [`arbitrary`], but the seed is taken from `u`, e.g. to implement `Arbitrary`:
+///
+/// ```ignore
+/// impl<'a> Arbitrary<'a> for ConfigLayer {
+///     fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+///         soukousei::fixture::from_unstructured(u)
+///     }
+/// }
+/// ```
+#[cfg(feature = "fuzz")]
+pub fn from_unstructured<L>(u: &mut Unstructured<'_>) -> arbitrary::Result<L>
+where
+    L: Layer + DeserializeOwned,
+{
+    arbitrary(u64::arbitrary(u)?).map_err(|_| arbitrary::Error::IncorrectFormat)
+}
+
+/// Value tree of placeholders for `fields`, which layers are deserialized from
+pub fn placeholders(fields: &[FieldMeta], seed: u64) -> Value {
+    sparse_placeholders(fields, seed, &mut |_| true)
+}
+
+/// Same as [`placeholders`], but only with fields for whose paths `present` is true. Nested
+/// fields are asked about after their section, in declaration order.
+pub fn sparse_placeholders(
+    fields: &[FieldMeta],
+    seed: u64,
+    present: &mut dyn FnMut(&str) -> bool,
+) -> Value {
+    table(fields, "", seed, present)
+}
+
+fn table(
+    fields: &[FieldMeta],
+    path: &str,
+    seed: u64,
+    present: &mut dyn FnMut(&str) -> bool,
+) -> Value {
+    let entries = fields
+        .iter()
+        .filter(|field| !field.catch_all)
+        .filter_map(|field| {
+            let path = join(path, field.name);
+            if !present(&path) {
+                return None;
+            }
+            let value = match field.nested {
+                Some(nested) => nested_placeholder(field, nested, &path, seed, present),
+                None => placeholder(field.ty, field.name, &path, seed),
+            }?;
+            Some((field.name.to_owned(), value))
+        })
+        .collect();
+    Value::Table(entries)
+}
+
+fn nested_placeholder(
+    field: &FieldMeta,
+    nested: &[FieldMeta],
+    path: &str,
+    seed: u64,
+    present: &mut dyn FnMut(&str) -> bool,
+) -> Option<Value> {
+    let mut ty = field.ty;
+    while let ("Option" | "Box" | "Toggle", Some(inner)) =
+        (meta::type_base_name(ty), type_args(ty).first().copied())
+    {
+        ty = inner;
+    }
+    let mut section = |path: &str| {
+        let mut section = table(nested, path, seed, present);
+        if let (true, Value::Table(entries)) = (field.toggle, &mut section) {
+            entries.insert("enabled".to_owned(), Value::Bool(true));
+        }
+        section
+    };
+    Some(match meta::type_base_name(ty) {
+        "Vec" => Value::Array(vec![section(&join(path, "0"))]),
+        "BTreeMap" | "HashMap" => {
+            let key = format!("{}_0", field.name);
+            let value = section(&join(path, &key));
+            Value::Table(BTreeMap::from([(key, value)]))
+        }
+        _ => section(path),
+    })
+}
+
+/// Placeholder of a value of type `ty`, or `None` if there is none for the type
+fn placeholder(ty: &str, name: &str, path: &str, seed: u64) -> Option<Value> {
+    let hash = hash(seed, path);
+    let args = type_args(ty);
+    let value = match meta::type_base_name(ty) {
+        "Option" | "Box" | "Rc" | "Arc" => return placeholder(args.first()?, name, path, seed),
+        "bool" => Value::Bool(hash.is_multiple_of(2)),
+        "u8" | "u16" | "u32" | "u64" | "u128" | "usize" | "i8" | "i16" | "i32" | "i64" | "i128"
+        | "isize" => Value::Integer(i128::from(hash % 100 + 1)),
+        x if x.starts_with("NonZero") => Value::Integer(i128::from(hash % 100 + 1)),
+        "f32" | "f64" => Value::Float((hash % 1000 + 1) as f64 / 10.0),
+        "char" => Value::String(char::from(b'a' + (hash % 26) as u8).to_string()),
+        "String" | "PathBuf" | "OsString" => Value::String(format!("{name}-{:04x}", hash & 0xffff)),
+        "IpAddr" | "Ipv4Addr" => Value::String(ip(hash)),
+        "SocketAddr" | "SocketAddrV4" => {
+            Value::String(format!("{}:{}", ip(hash), 1024 + (hash >> 16) % 60000))
+        }
+        "Vec" | "VecDeque" | "HashSet" | "BTreeSet" => Value::Array(vec![placeholder(
+            args.first()?,
+            name,
+            &join(path, "0"),
+            seed,
+        )?]),
+        "BTreeMap" | "HashMap" if meta::type_base_name(args.first()?) == "String" => {
+            let key = format!("{name}_0");
+            let value = placeholder(args.get(1)?, name, &join(path, &key), seed)?;
+            Value::Table(BTreeMap::from([(key, value)]))
+        }
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn ip(hash: u64) -> String {
+    format!("10.0.{}.{}", (hash >> 8) % 256, hash % 254 + 1)
+}
+
+fn hash(seed: u64, path: &str) -> u64 {
+    let mut hasher = Fnv64::new();
+    hasher.write(&seed.to_le_bytes());
+    hasher.write(path.as_bytes());
+    hasher.finish()
+}
+
+/// Generic arguments of a type, e.g. `String` and `u16` for `HashMap<String, u16>`. Lifetimes
+/// are skipped.
+fn type_args(ty: &str) -> Vec<&str> {
+    let (Some(start), Some(end)) = (ty.find('<'), ty.rfind('>')) else {
+        return Vec::new();
+    };
+    let inner = &ty[start + 1..end];
+    let mut args = Vec::new();
+    let (mut depth, mut from) = (0, 0);
+    for (i, c) in inner.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ',' if depth == 0 => {
+                args.push(inner[from..i].trim());
+                from = i + 1;
+            }
+            _ => {}
+        }
+    }
+    args.push(inner[from..].trim());
+    args.retain(|x| !x.is_empty() && !x.starts_with('\''));
+    args
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{path}.{key}")
+    }
+}
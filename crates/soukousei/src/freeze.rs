@@ -0,0 +1,278 @@
+//! Dumps of merged layers, e.g. to cache a config resolved from slow sources on disk and read
+//! it back on the next start. Enabled with the `toml` feature.
+//!
+//! Values of `#[layer(secret)]` fields are encrypted with a user-provided [`Cipher`], so that
+//! the core doesn't depend on a crypto library. Without a cipher, writing a secret is refused,
+//! unless plaintext secrets are explicitly allowed:
+//!
+//! ```ignore
+//! let freeze = Freeze::new().with_cipher(MyAesGcm::new(key));
+//! let builder = ConfigBuilder::<ConfigLayer>::new().with_file("config.toml")?;
+//! std::fs::write("config.frozen.toml", builder.freeze(&freeze)?)?;
+//!
+//! // on the next start
+//! let config = ConfigBuilder::<ConfigLayer>::new()
+//!     .with_named_layer("config.frozen.toml", freeze.read_file("config.frozen.toml")?)
+//!     .build()?;
+//! ```
+//!
+//! An encrypted value is stored as a string with the [`ENCRYPTED_PREFIX`], followed by the
+//! ciphertext in hex. The dot-separated path of the field is passed to the cipher as associated
+//! data, so that an encrypted value can't be moved to another field.
+
+use crate::meta::{self, FieldMeta};
+use crate::{Layer, Report};
+#[cfg(feature = "miette")]
+use miette::Diagnostic;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Write;
+use std::path::Path;
+use thiserror::Error;
+use toml::Value;
+
+/// Prefix of encrypted values in dumps
+pub const ENCRYPTED_PREFIX: &str = "enc:v1:";
+
+/// Authenticated encryption with associated data, such as AES-GCM or ChaCha20-Poly1305
+pub trait Cipher {
+    /// Encrypt `plaintext`. The output must contain everything needed to decrypt it except the
+    /// key, e.g. a random nonce.
+    fn encrypt(&self, plaintext: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Report>;
+
+    /// Decrypt an output of [`Cipher::encrypt`], failing if it or `associated_data` was
+    /// tampered with
+    fn decrypt(&self, ciphertext: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Report>;
+}
+
+/// Settings of writing and reading dumps, see the [module](self) docs
+#[derive(Default)]
+pub struct Freeze {
+    cipher: Option<Box<dyn Cipher>>,
+    allow_plaintext_secrets: bool,
+}
+
+impl Freeze {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Encrypt secret values on write and decrypt them on read
+    pub fn with_cipher(mut self, cipher: impl Cipher + 'static) -> Self {
+        self.cipher = Some(Box::new(cipher));
+        self
+    }
+
+    /// Write secret values as is when there is no cipher, instead of failing with
+    /// [`FreezeError::PlaintextSecret`], and accept them on read instead of failing with
+    /// [`FreezeError::UnencryptedSecret`]
+    pub fn allow_plaintext_secrets(mut self) -> Self {
+        self.allow_plaintext_secrets = true;
+        self
+    }
+
+    /// Dump `layer` into a TOML string
+    pub fn to_string<L: Layer + Serialize>(&self, layer: &L) -> Result<String, FreezeError> {
+        let mut value = Value::try_from(layer)?;
+        visit_secrets(&mut value, Some(L::FIELDS), "", &mut |path, value| {
+            self.seal(path, value)
+        })?;
+        Ok(toml::to_string(&value)?)
+    }
+
+    /// Same as [`Self::to_string`], but writes the dump into a file
+    pub fn write_file<L: Layer + Serialize>(
+        &self,
+        path: impl AsRef<Path>,
+        layer: &L,
+    ) -> Result<(), FreezeError> {
+        let contents = self.to_string(layer)?;
+        std::fs::write(path, contents).map_err(FreezeError::Io)
+    }
+
+    /// Read a layer from a dump made by [`Self::to_string`]
+    pub fn from_str<L: Layer + DeserializeOwned>(&self, contents: &str) -> Result<L, FreezeError> {
+        let mut value: Value = toml::from_str(contents)?;
+        visit_secrets(&mut value, Some(L::FIELDS), "", &mut |path, value| {
+            self.unseal(path, value)
+        })?;
+        Ok(value.try_into()?)
+    }
+
+    /// Same as [`Self::from_str`], but reads the dump from a file
+    pub fn read_file<L: Layer + DeserializeOwned>(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<L, FreezeError> {
+        let contents = std::fs::read_to_string(path).map_err(FreezeError::Io)?;
+        self.from_str(&contents)
+    }
+
+    fn seal(&self, path: &str, value: &mut Value) -> Result<(), FreezeError> {
+        let Some(cipher) = &self.cipher else {
+            return if self.allow_plaintext_secrets {
+                Ok(())
+            } else {
+                Err(FreezeError::PlaintextSecret {
+                    path: path.to_owned(),
+                })
+            };
+        };
+        let ciphertext = cipher
+            .encrypt(value.to_string().as_bytes(), path.as_bytes())
+            .map_err(|report| FreezeError::Encrypt {
+                path: path.to_owned(),
+                report,
+            })?;
+        *value = Value::String(format!("{ENCRYPTED_PREFIX}{}", to_hex(&ciphertext)));
+        Ok(())
+    }
+
+    fn unseal(&self, path: &str, value: &mut Value) -> Result<(), FreezeError> {
+        let Some(hex) = value
+            .as_str()
+            .and_then(|x| x.strip_prefix(ENCRYPTED_PREFIX))
+        else {
+            // otherwise anyone who can write the dump could replace an encrypted secret
+            return if self.allow_plaintext_secrets {
+                Ok(())
+            } else {
+                Err(FreezeError::UnencryptedSecret {
+                    path: path.to_owned(),
+                })
+            };
+        };
+        let Some(cipher) = &self.cipher else {
+            return Err(FreezeError::MissingCipher {
+                path: path.to_owned(),
+            });
+        };
+        let decrypt_error = |report| FreezeError::Decrypt {
+            path: path.to_owned(),
+            report,
+        };
+        let ciphertext = from_hex(hex).ok_or_else(|| decrypt_error(InvalidHexError.into()))?;
+        let plaintext = cipher
+            .decrypt(&ciphertext, path.as_bytes())
+            .map_err(decrypt_error)?;
+        *value = String::from_utf8(plaintext)
+            .ok()
+            .and_then(|plaintext| parse_value(&plaintext))
+            .ok_or_else(|| decrypt_error(InvalidPlaintextError.into()))?;
+        Ok(())
+    }
+}
+
+/// Call `f` with each value of a secret field and its dot-separated path
+fn visit_secrets(
+    value: &mut Value,
+    fields: Option<&[FieldMeta]>,
+    path: &str,
+    f: &mut dyn FnMut(&str, &mut Value) -> Result<(), FreezeError>,
+) -> Result<(), FreezeError> {
+    let Some(fields) = fields else {
+        return Ok(());
+    };
+    match value {
+        Value::Array(items) => {
+            for (index, item) in items.iter_mut().enumerate() {
+                visit_secrets(item, Some(fields), &join(path, &index.to_string()), f)?;
+            }
+        }
+        Value::Table(entries) => {
+            for (key, value) in entries.iter_mut() {
+                let Some(field) = fields.iter().find(|x| x.name == key.as_str()) else {
+                    continue;
+                };
+                let path = join(path, key);
+                if field.secret {
+                    f(&path, value)?;
+                } else if let (true, Value::Table(entries)) = (is_map(field.ty), &mut *value) {
+                    for (key, value) in entries.iter_mut() {
+                        visit_secrets(value, field.nested, &join(&path, key), f)?;
+                    }
+                } else {
+                    visit_secrets(value, field.nested, &path, f)?;
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+/// Map sections are keyed by arbitrary names instead of fields
+fn is_map(ty: &str) -> bool {
+    matches!(meta::type_base_name(ty), "BTreeMap" | "HashMap")
+}
+
+/// Inverse of [`Value::to_string`], which produces an inline TOML value
+fn parse_value(repr: &str) -> Option<Value> {
+    let mut table: toml::Table = toml::from_str(&format!("value = {repr}")).ok()?;
+    table.remove("value")
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        write!(hex, "{byte:02x}").unwrap();
+    }
+    hex
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+#[error("The ciphertext is not valid hex")]
+struct InvalidHexError;
+
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+#[error("The decrypted value is not a valid TOML value")]
+struct InvalidPlaintextError;
+
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+pub enum FreezeError {
+    #[error("Secret field `{path}` would be written in plaintext")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(help("set a cipher with `Freeze::with_cipher`, or allow plaintext secrets"))
+    )]
+    PlaintextSecret { path: String },
+    #[error("Secret field `{path}` is not encrypted in the dump")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(help("allow plaintext secrets if the dump was written with them"))
+    )]
+    UnencryptedSecret { path: String },
+    #[error("Secret field `{path}` is encrypted, but there is no cipher to decrypt it")]
+    MissingCipher { path: String },
+    #[error("Failed to encrypt secret field `{path}`: {report}")]
+    Encrypt { path: String, report: Report },
+    #[error("Failed to decrypt secret field `{path}`: {report}")]
+    Decrypt { path: String, report: Report },
+    #[error("Failed to serialize the layer")]
+    Serialize(#[from] toml::ser::Error),
+    #[error("Failed to parse the dump")]
+    Parse(#[from] toml::de::Error),
+    #[error("Failed to read or write the file")]
+    Io(#[source] std::io::Error),
+}
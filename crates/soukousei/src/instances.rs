@@ -0,0 +1,334 @@
+//! Sections declared once and expanded into several entries, e.g. for sharded worker pools.
+//!
+//! ```ignore
+//! #[derive(Layer)]
+//! struct Config {
+//!     #[layer(nested)]
+//!     workers: Instances<Worker>,
+//! }
+//! ```
+//!
+//! ```toml
+//! [workers]
+//! instances = 3
+//! vars = { base_port = 9000 }
+//!
+//! [workers.template]
+//! name = "worker-${index}"
+//! port = "${base_port + index}"
+//! ```
+//!
+//! The template is expanded at completion time for each `index` from `0` to `instances - 1`.
+//! A string which is a single `${...}` becomes a number, otherwise the numbers are
+//! interpolated. Expressions support integers, variables, `+`, `-`, `*` and parentheses. The
+//! entries might also be listed explicitly, as an array.
+//!
+//! Like other collections, the section is replaced as a whole on merge and isn't read from ENV.
+
+use crate::collection::complete_each;
+use crate::env::{EnvProvider, FieldFromEnvError, FromEnv};
+use crate::meta::FieldMeta;
+use crate::{CompleteError, ErrorPolicy, HasLayer, Layer, MultipleFieldsError, PathSegment};
+#[cfg(feature = "miette")]
+use miette::Diagnostic;
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+use std::ops::Deref;
+use thiserror::Error;
+use toml::{Table, Value};
+
+/// Entries of a section which might be expanded from a template, see
+/// [module-level docs](self)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instances<T>(pub Vec<T>);
+
+impl<T> Deref for Instances<T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Layer of [`Instances`]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct InstancesLayer<L>(pub Option<InstancesSource<L>>);
+
+/// Entries as they are written in a source
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum InstancesSource<L> {
+    List(Vec<L>),
+    Template {
+        instances: usize,
+        #[serde(default)]
+        vars: BTreeMap<String, i64>,
+        template: Table,
+    },
+}
+
+impl<L> Default for InstancesLayer<L> {
+    fn default() -> Self {
+        Self(None)
+    }
+}
+
+impl<L: Layer + Default + DeserializeOwned> Layer for InstancesLayer<L> {
+    type Complete = Instances<L::Complete>;
+
+    fn new() -> Self {
+        Self(None)
+    }
+
+    fn merge(self, other: Self) -> Self {
+        Self(other.0.or(self.0))
+    }
+
+    fn merge_from(&mut self, other: Self) {
+        if other.0.is_some() {
+            self.0 = other.0;
+        }
+    }
+
+    fn complete(self) -> Result<Self::Complete, CompleteError> {
+        self.complete_with(ErrorPolicy::CollectAll)
+    }
+
+    fn complete_with(self, policy: ErrorPolicy) -> Result<Self::Complete, CompleteError> {
+        let items = match self.0.ok_or(CompleteError::MissingData)? {
+            InstancesSource::List(items) => items,
+            InstancesSource::Template {
+                instances,
+                vars,
+                template,
+            } => expand(instances, &vars, &template)
+                .map_err(|err| CompleteError::Invalid(err.into()))?,
+        };
+        let items: Vec<(usize, L::Complete)> = complete_each(
+            items.into_iter().enumerate(),
+            |&i| PathSegment::Index(i),
+            policy,
+        )?;
+        Ok(Instances(items.into_iter().map(|(_, x)| x).collect()))
+    }
+
+    const FIELDS: &'static [FieldMeta] = L::FIELDS;
+
+    fn provided_fields(&self) -> Vec<String> {
+        match &self.0 {
+            Some(_) => vec![String::new()],
+            None => Vec::new(),
+        }
+    }
+}
+
+impl<T> HasLayer for Instances<T>
+where
+    T: HasLayer,
+    T::Layer: Default + DeserializeOwned,
+{
+    type Layer = InstancesLayer<T::Layer>;
+}
+
+/// Entries are not read from ENV
+impl<L> FromEnv for InstancesLayer<L> {
+    fn from_env(
+        _provider: &impl EnvProvider,
+    ) -> Result<Self, MultipleFieldsError<FieldFromEnvError>> {
+        Ok(Self(None))
+    }
+}
+
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+#[error("failed to expand the template for instance {index}: {message}")]
+pub struct TemplateError {
+    index: usize,
+    message: String,
+}
+
+fn expand<L: DeserializeOwned>(
+    instances: usize,
+    vars: &BTreeMap<String, i64>,
+    template: &Table,
+) -> Result<Vec<L>, TemplateError> {
+    (0..instances)
+        .map(|index| {
+            let err = |message: String| TemplateError { index, message };
+            let mut vars = vars.clone();
+            vars.insert("index".to_owned(), index as i64);
+            let value = expand_value(&Value::Table(template.clone()), &vars).map_err(err)?;
+            value
+                .try_into()
+                .map_err(|x: toml::de::Error| err(x.message().to_owned()))
+        })
+        .collect()
+}
+
+fn expand_value(value: &Value, vars: &BTreeMap<String, i64>) -> Result<Value, String> {
+    match value {
+        Value::String(s) => expand_str(s, vars),
+        Value::Array(items) => items
+            .iter()
+            .map(|x| expand_value(x, vars))
+            .collect::<Result<_, _>>()
+            .map(Value::Array),
+        Value::Table(table) => table
+            .iter()
+            .map(|(k, v)| Ok((k.clone(), expand_value(v, vars)?)))
+            .collect::<Result<_, String>>()
+            .map(Value::Table),
+        other => Ok(other.clone()),
+    }
+}
+
+fn expand_str(s: &str, vars: &BTreeMap<String, i64>) -> Result<Value, String> {
+    if let Some(expr) = s.strip_prefix("${").and_then(|x| x.strip_suffix('}')) {
+        if !expr.contains("${") {
+            return eval(expr, vars).map(Value::Integer);
+        }
+    }
+    let mut out = String::new();
+    let mut rest = s;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("unclosed `${{` in `{s}`"))?;
+        out.push_str(&eval(&rest[start + 2..start + end], vars)?.to_string());
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    Ok(Value::String(out))
+}
+
+/// Evaluate an integer expression with `+`, `-`, `*`, parentheses and variables
+fn eval(expr: &str, vars: &BTreeMap<String, i64>) -> Result<i64, String> {
+    let tokens = tokenize(expr)?;
+    let mut parser = Parser {
+        tokens: &tokens,
+        pos: 0,
+        vars,
+    };
+    let value = parser.sum()?;
+    match parser.tokens.get(parser.pos) {
+        None => Ok(value),
+        Some(token) => Err(format!("unexpected `{token}` in `{expr}`")),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Ident(String),
+    Op(char),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Number(x) => write!(f, "{x}"),
+            Self::Ident(x) => write!(f, "{x}"),
+            Self::Op(x) => write!(f, "{x}"),
+        }
+    }
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = expr.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() {
+            let mut number = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                number.push(c);
+                chars.next();
+            }
+            let number = number
+                .parse()
+                .map_err(|_| format!("`{number}` is too large"))?;
+            tokens.push(Token::Number(number));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut ident = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_alphanumeric() || **c == '_') {
+                ident.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Ident(ident));
+        } else if "+-*()".contains(c) {
+            tokens.push(Token::Op(c));
+            chars.next();
+        } else {
+            return Err(format!("unexpected `{c}` in `{expr}`"));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    vars: &'a BTreeMap<String, i64>,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.pos);
+        self.pos += 1;
+        token
+    }
+
+    fn peek_op(&self) -> Option<char> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) => Some(*op),
+            _ => None,
+        }
+    }
+
+    fn sum(&mut self) -> Result<i64, String> {
+        let mut value = self.product()?;
+        while let Some(op @ ('+' | '-')) = self.peek_op() {
+            self.pos += 1;
+            let rhs = self.product()?;
+            value = match op {
+                '+' => value.checked_add(rhs),
+                _ => value.checked_sub(rhs),
+            }
+            .ok_or("overflow")?;
+        }
+        Ok(value)
+    }
+
+    fn product(&mut self) -> Result<i64, String> {
+        let mut value = self.atom()?;
+        while let Some('*') = self.peek_op() {
+            self.pos += 1;
+            value = value.checked_mul(self.atom()?).ok_or("overflow")?;
+        }
+        Ok(value)
+    }
+
+    fn atom(&mut self) -> Result<i64, String> {
+        match self.next().cloned() {
+            Some(Token::Number(x)) => Ok(x),
+            Some(Token::Ident(name)) => self
+                .vars
+                .get(&name)
+                .copied()
+                .ok_or_else(|| format!("unknown variable `{name}`")),
+            Some(Token::Op('-')) => self.atom()?.checked_neg().ok_or("overflow".to_owned()),
+            Some(Token::Op('(')) => {
+                let value = self.sum()?;
+                match self.next() {
+                    Some(Token::Op(')')) => Ok(value),
+                    _ => Err("expected `)`".to_owned()),
+                }
+            }
+            Some(token) => Err(format!("unexpected `{token}`")),
+            None => Err("unexpected end of expression".to_owned()),
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! Restricting config files to a base directory.
+//!
+//! When paths of config files come from outside, e.g. from an ENV var with
+//! [`ConfigBuilder::with_file_from_env`](crate::builder::ConfigBuilder::with_file_from_env) or
+//! from a request of a semi-trusted client, a path like `../../etc/passwd` could make the
+//! service read and report any file it has access to. With
+//! [`ConfigBuilder::with_path_jail`](crate::builder::ConfigBuilder::with_path_jail), every
+//! file path is resolved against the base directory and rejected if it points outside of it,
+//! including through symlinks.
+//!
+//! There are no include directives in config files yet; once they appear, their paths go
+//! through the same jail.
+
+#[cfg(feature = "miette")]
+use miette::Diagnostic;
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathJail {
+    base: PathBuf,
+    allow_absolute: bool,
+}
+
+impl PathJail {
+    /// Relative paths are resolved against `base`, absolute paths are rejected
+    pub fn new(base: impl Into<PathBuf>) -> Self {
+        Self {
+            base: base.into(),
+            allow_absolute: false,
+        }
+    }
+
+    /// Accept absolute paths, as long as they point inside the base directory
+    pub fn allow_absolute(mut self) -> Self {
+        self.allow_absolute = true;
+        self
+    }
+
+    pub fn base(&self) -> &Path {
+        &self.base
+    }
+
+    /// Path to read instead of `path`, or an error if it is outside of the base directory
+    pub fn resolve(&self, path: &Path) -> Result<PathBuf, PathEscapeError> {
+        let escape = |reason| PathEscapeError {
+            path: path.to_owned(),
+            base: self.base.clone(),
+            reason,
+        };
+
+        let relative = if path.is_absolute() {
+            if !self.allow_absolute {
+                return Err(escape(EscapeReason::Absolute));
+            }
+            let base = if self.base.is_absolute() {
+                self.base.clone()
+            } else {
+                std::env::current_dir()
+                    .map_err(|_| escape(EscapeReason::Outside))?
+                    .join(&self.base)
+            };
+            normalize(path)
+                .strip_prefix(normalize(&base))
+                .map_err(|_| escape(EscapeReason::Outside))?
+                .to_owned()
+        } else {
+            path.to_owned()
+        };
+
+        let mut depth = 0usize;
+        for component in relative.components() {
+            match component {
+                Component::CurDir => {}
+                Component::Normal(_) => depth += 1,
+                Component::ParentDir if depth > 0 => depth -= 1,
+                _ => return Err(escape(EscapeReason::Outside)),
+            }
+        }
+        let joined = self.base.join(relative);
+
+        // symlinks might point anywhere; a missing file is left to fail on reading
+        if let (Ok(base), Ok(target)) = (self.base.canonicalize(), joined.canonicalize()) {
+            if !target.starts_with(base) {
+                return Err(escape(EscapeReason::Symlink));
+            }
+        }
+
+        Ok(joined)
+    }
+}
+
+/// Resolve `.` and `..` of an absolute path lexically, without touching the file system
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            component => out.push(component),
+        }
+    }
+    out
+}
+
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+#[error("Refused to read `{}`: {reason} `{}`", path.display(), base.display())]
+#[cfg_attr(
+    feature = "miette",
+    diagnostic(help(
+        "config files are restricted to this directory; a path leading outside of it might be an attempt of path traversal"
+    ))
+)]
+pub struct PathEscapeError {
+    path: PathBuf,
+    base: PathBuf,
+    reason: EscapeReason,
+}
+
+impl PathEscapeError {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EscapeReason {
+    Absolute,
+    Outside,
+    Symlink,
+}
+
+impl std::fmt::Display for EscapeReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Absolute => write!(f, "absolute paths are not allowed in"),
+            Self::Outside => write!(f, "it is outside of"),
+            Self::Symlink => write!(f, "it links outside of"),
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! Fields that are validated at completion, but materialized only on first access.
+//!
+//! Useful for expensive fields, e.g. a certificate referenced by a path, in rarely used
+//! subsystems. Use it as a nested layer:
+//!
+//! ```ignore
+//! #[derive(Layer)]
+//! struct Tls {
+//!     #[layer(nested)]
+//!     cert: Lazy<CertFile>,
+//! }
+//! ```
+
+use crate::env::{EnvProvider, FieldFromEnvError, FromEnv};
+use crate::{CompleteError, HasLayer, Layer, MultipleFieldsError, Report};
+use std::fmt::{Debug, Formatter};
+use std::sync::OnceLock;
+
+/// A recipe of how to load a value, e.g. a file path.
+pub trait Load {
+    type Output;
+
+    /// Cheap check performed at completion, e.g. that the file exists
+    fn validate(&self) -> Result<(), Report> {
+        Ok(())
+    }
+
+    fn load(&self) -> Result<Self::Output, Report>;
+}
+
+pub struct Lazy<L: Load> {
+    loader: L,
+    value: OnceLock<Result<L::Output, Report>>,
+}
+
+impl<L: Load> Lazy<L> {
+    pub fn new(loader: L) -> Self {
+        Self {
+            loader,
+            value: OnceLock::new(),
+        }
+    }
+
+    pub fn loader(&self) -> &L {
+        &self.loader
+    }
+
+    /// Load the value on first access. The result, including an error, is cached.
+    pub fn get(&self) -> Result<&L::Output, &Report> {
+        self.value.get_or_init(|| self.loader.load()).as_ref()
+    }
+}
+
+impl<L> Debug for Lazy<L>
+where
+    L: Load + Debug,
+    L::Output: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Lazy")
+            .field("loader", &self.loader)
+            .field("value", &self.value.get())
+            .finish()
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct LazyLayer<L>(pub Option<L>);
+
+impl<L> Default for LazyLayer<L> {
+    fn default() -> Self {
+        Self(None)
+    }
+}
+
+impl<L: Load> Layer for LazyLayer<L> {
+    type Complete = Lazy<L>;
+
+    fn new() -> Self {
+        Self(None)
+    }
+
+    fn merge(self, other: Self) -> Self {
+        Self(other.0.or(self.0))
+    }
+
+    fn merge_from(&mut self, other: Self) {
+        if other.0.is_some() {
+            self.0 = other.0;
+        }
+    }
+
+    fn complete(self) -> Result<Self::Complete, CompleteError> {
+        let loader = self.0.ok_or(CompleteError::MissingData)?;
+        loader.validate().map_err(CompleteError::Invalid)?;
+        Ok(Lazy::new(loader))
+    }
+
+    fn provided_fields(&self) -> Vec<String> {
+        self.0.iter().map(|_| String::new()).collect()
+    }
+}
+
+/// Loaders are not read from ENV
+impl<L> FromEnv for LazyLayer<L> {
+    fn from_env(
+        _provider: &impl EnvProvider,
+    ) -> Result<Self, MultipleFieldsError<FieldFromEnvError>> {
+        Ok(Self(None))
+    }
+}
+
+impl<L: Load> HasLayer for Lazy<L> {
+    type Layer = LazyLayer<L>;
+}
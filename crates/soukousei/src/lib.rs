@@ -1,28 +1,198 @@
-use crate::env::EnvProvider;
-use miette::{Diagnostic, IntoDiagnostic, Report};
-use serde::{Deserialize, Deserializer};
-use std::fmt::{Display, Formatter, Write};
-use std::ops::{Deref, Mul};
+#[cfg(feature = "miette")]
+use miette::Diagnostic;
+use std::borrow::Cow;
+use std::fmt::{Display, Formatter};
 use thiserror::Error;
 
+#[cfg(feature = "miette")]
 pub use miette;
+#[cfg(feature = "serde")]
+pub use serde;
+#[cfg(feature = "toml")]
+pub use soukousei_derive::include_config;
+pub use soukousei_derive::EnvEnum;
+pub use soukousei_derive::Layer;
+
+/// Type-erased error returned by user-provided extension points, such as ENV providers and
+/// parsers.
+///
+/// It is [`miette::Report`] when the `miette` feature is enabled, and a boxed
+/// [`std::error::Error`] otherwise.
+#[cfg(feature = "miette")]
+pub type Report = miette::Report;
+
+/// Type-erased error returned by user-provided extension points, such as ENV providers and
+/// parsers.
+///
+/// It is [`miette::Report`] when the `miette` feature is enabled, and a boxed
+/// [`std::error::Error`] otherwise.
+#[cfg(not(feature = "miette"))]
+pub type Report = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+#[cfg(any(feature = "uuid", feature = "semver", feature = "regex"))]
+pub mod adapters;
+#[cfg(feature = "toml")]
+pub mod audit;
+#[cfg(feature = "axum")]
+pub mod axum;
+pub mod bounded;
+pub mod builder;
+#[cfg(feature = "cli")]
+pub mod cli;
+pub mod collection;
+pub mod completion;
+#[cfg(any(feature = "contrib", feature = "logging"))]
+pub mod contrib;
+#[cfg(feature = "serde")]
+pub mod composite;
+#[cfg(feature = "serde")]
+pub mod conflict;
+#[cfg(any(feature = "chrono", feature = "time"))]
+pub mod datetime;
+pub mod degraded;
+#[cfg(feature = "edit")]
+pub mod edit;
+pub mod env_export;
+#[cfg(any(feature = "fuzz", feature = "proptest"))]
+pub mod fixture;
+#[cfg(feature = "toml")]
+pub mod freeze;
+#[cfg(feature = "toml")]
+pub mod instances;
+pub mod jail;
+pub mod lazy;
+#[cfg(feature = "serde")]
+// the deserializer adapter is only used by formats
+#[cfg_attr(not(any(feature = "toml", feature = "json")), allow(dead_code))]
+pub mod limits;
+#[cfg(feature = "serde")]
+pub mod lint;
+pub mod merge;
+pub mod meta;
+pub mod metrics;
+pub mod net;
+#[cfg(feature = "serde")]
+// the deserializer adapter is only used by formats
+#[cfg_attr(not(any(feature = "toml", feature = "json")), allow(dead_code))]
+pub mod normalize;
+pub mod optional;
+pub mod parse;
+pub mod pointer;
+pub mod provenance;
+pub mod reference;
+pub mod reload;
+pub mod schema;
+#[cfg(all(feature = "regex", feature = "serde"))]
+pub mod secret_scan;
+pub mod sensitive_file;
+pub mod shared;
+#[cfg(feature = "serde")]
+pub mod source;
+#[cfg(feature = "proptest")]
+pub mod strategy;
+#[cfg(feature = "json")]
+pub mod support;
+pub mod telemetry;
+pub mod testing;
+pub mod toggle;
+pub mod tree;
+pub mod tuple;
+#[cfg(feature = "serde")]
+pub mod value;
+#[cfg(feature = "wizard")]
+pub mod wizard;
 
 pub mod env {
-    use crate::{FieldsAcc, MultipleFieldsError};
-    use miette::{miette, Report};
+    use crate::sensitive_file::FromFileContents;
+    use crate::{MultipleFieldsError, PathSegment, Report};
+    #[cfg(feature = "miette")]
+    use miette::Diagnostic;
     use std::ffi::OsString;
-    use std::ops::Deref;
+    use std::path::PathBuf;
     use std::str::FromStr;
+    use thiserror::Error;
 
     pub fn default_env_parse<T, E>(value: &str) -> Result<T, Report>
     where
         T: FromStr<Err = E>,
         E: std::error::Error,
     {
-        FromStr::from_str(value)
-            .map_err(|err| miette!("Failed to parse value from string: {}", err))
+        T::from_str(value).map_err(|err: E| Report::from(ParseEnvError::new(err.to_string())))
+    }
+
+    /// Error of a `#[layer(compile_time)]` var which is baked in with a value failing to parse,
+    /// if no source sets the field instead. Used by the derive.
+    pub fn compile_time_error<T>(
+        value: &Option<T>,
+        raw: Option<&str>,
+        parse: impl FnOnce(&str) -> Result<T, Report>,
+    ) -> Option<Report> {
+        match (value, raw) {
+            (None, Some(raw)) => parse(raw).err(),
+            _ => None,
+        }
+    }
+
+    #[derive(Debug, Error)]
+    #[cfg_attr(feature = "miette", derive(Diagnostic))]
+    #[error("Failed to parse value from string: {message}")]
+    pub struct ParseEnvError {
+        message: String,
+    }
+
+    impl ParseEnvError {
+        pub fn new(message: String) -> Self {
+            Self { message }
+        }
     }
 
+    /// Enum with unit variants which might be parsed from ENV by variant names, see
+    /// `#[layer(env_enum)]`. Usually derived with [`crate::EnvEnum`].
+    pub trait EnvEnum: Sized {
+        /// Names of the variants, in lowercase
+        const NAMES: &'static [&'static str];
+
+        /// Variant by its index in [`Self::NAMES`]
+        fn from_index(index: usize) -> Self;
+
+        /// Index of the variant in [`Self::NAMES`]
+        fn index(&self) -> usize;
+    }
+
+    /// Parse an [`EnvEnum`] variant by its name, case-insensitively
+    pub fn parse_enum<T: EnvEnum>(value: &str) -> Result<T, Report> {
+        T::NAMES
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(value))
+            .map(T::from_index)
+            .ok_or_else(|| {
+                UnknownVariantError {
+                    value: value.to_owned(),
+                    expected: T::NAMES,
+                }
+                .into()
+            })
+    }
+
+    #[derive(Debug, Error)]
+    #[cfg_attr(feature = "miette", derive(Diagnostic))]
+    #[error("unknown value `{value}`, expected one of: {}", expected.join(", "))]
+    pub struct UnknownVariantError {
+        value: String,
+        expected: &'static [&'static str],
+    }
+
+    #[derive(Debug, Error)]
+    #[cfg_attr(feature = "miette", derive(Diagnostic))]
+    #[error("ENV var `{variable}` is not a valid utf-8 string: {value:?}")]
+    pub struct NotUnicodeEnvError {
+        variable: String,
+        value: OsString,
+    }
+
+    #[derive(Debug, Error)]
+    #[cfg_attr(feature = "miette", derive(Diagnostic))]
+    #[error("Failed to read ENV var `{variable}`: {report}")]
     pub struct FieldFromEnvError {
         variable: String,
         report: Report,
@@ -47,7 +217,7 @@ pub mod env {
     impl MultipleFieldsError<FieldFromEnvError> {
         pub fn add_if_err<T>(
             self,
-            loc: &'static str,
+            loc: impl Into<PathSegment>,
             result: Result<Option<T>, FieldFromEnvError>,
         ) -> (Option<T>, Self) {
             match result {
@@ -60,6 +230,22 @@ pub mod env {
     pub trait EnvProvider {
         fn fetch(&self, key: impl AsRef<str>) -> Result<Option<String>, Report>;
 
+        /// Same as [`Self::fetch`], but the value doesn't have to be a valid unicode. Used for
+        /// `PathBuf` and `OsString` fields.
+        fn fetch_os(&self, key: impl AsRef<str>) -> Result<Option<OsString>, Report> {
+            self.fetch(key).map(|value| value.map(OsString::from))
+        }
+
+        /// All variables which names start with `prefix`, sorted by name.
+        ///
+        /// Not every provider can enumerate its variables, the default implementation fails.
+        fn iter_prefixed(&self, prefix: &str) -> Result<Vec<(String, String)>, Report> {
+            Err(IterUnsupportedError {
+                prefix: prefix.to_owned(),
+            }
+            .into())
+        }
+
         fn fetch_and_parse<T, F>(
             &self,
             key: &'static str,
@@ -94,8 +280,272 @@ pub mod env {
             }
             Ok(None)
         }
+
+        fn try_fetch_multiple_os<T>(
+            &self,
+            keys: impl Iterator<Item = &'static str>,
+        ) -> Result<Option<T>, FieldFromEnvError>
+        where
+            T: From<OsString>,
+        {
+            for key in keys {
+                let value = self
+                    .fetch_os(key)
+                    .map_err(|report| FieldFromEnvError::new(report, key.to_owned()))?;
+                if let Some(value) = value {
+                    return Ok(Some(T::from(value)));
+                }
+            }
+            Ok(None)
+        }
+    }
+
+    impl<P: EnvProvider> EnvProvider for &P {
+        fn fetch(&self, key: impl AsRef<str>) -> Result<Option<String>, Report> {
+            (**self).fetch(key)
+        }
+
+        fn fetch_os(&self, key: impl AsRef<str>) -> Result<Option<OsString>, Report> {
+            (**self).fetch_os(key)
+        }
+
+        fn iter_prefixed(&self, prefix: &str) -> Result<Vec<(String, String)>, Report> {
+            (**self).iter_prefixed(prefix)
+        }
+    }
+
+    /// Composite provider, which looks up variables in `A` first, then in `B`
+    pub struct Chain<A, B>(pub A, pub B);
+
+    impl<A: EnvProvider, B: EnvProvider> EnvProvider for Chain<A, B> {
+        fn fetch(&self, key: impl AsRef<str>) -> Result<Option<String>, Report> {
+            match self.0.fetch(key.as_ref())? {
+                Some(value) => Ok(Some(value)),
+                None => self.1.fetch(key),
+            }
+        }
+
+        fn fetch_os(&self, key: impl AsRef<str>) -> Result<Option<OsString>, Report> {
+            match self.0.fetch_os(key.as_ref())? {
+                Some(value) => Ok(Some(value)),
+                None => self.1.fetch_os(key),
+            }
+        }
+
+        /// Variables of both providers, `A` takes precedence
+        fn iter_prefixed(&self, prefix: &str) -> Result<Vec<(String, String)>, Report> {
+            let mut vars = self.0.iter_prefixed(prefix)?;
+            for (key, value) in self.1.iter_prefixed(prefix)? {
+                if !vars.iter().any(|(x, _)| *x == key) {
+                    vars.push((key, value));
+                }
+            }
+            vars.sort();
+            Ok(vars)
+        }
+    }
+
+    /// Provider which looks up variables with `prefix` prepended to their names, e.g. `ADDR` as
+    /// `MYAPP_UPSTREAMS_0_ADDR` for an element of `#[layer(env_indexed = "MYAPP_UPSTREAMS")]`
+    pub struct Prefixed<P> {
+        inner: P,
+        prefix: String,
+    }
+
+    impl<P> Prefixed<P> {
+        pub fn new(inner: P, prefix: impl Into<String>) -> Self {
+            Self {
+                inner,
+                prefix: prefix.into(),
+            }
+        }
+    }
+
+    impl<P: EnvProvider> EnvProvider for Prefixed<P> {
+        fn fetch(&self, key: impl AsRef<str>) -> Result<Option<String>, Report> {
+            self.inner.fetch(format!("{}{}", self.prefix, key.as_ref()))
+        }
+
+        fn fetch_os(&self, key: impl AsRef<str>) -> Result<Option<OsString>, Report> {
+            self.inner
+                .fetch_os(format!("{}{}", self.prefix, key.as_ref()))
+        }
+
+        /// Variables are listed without the prefix
+        fn iter_prefixed(&self, prefix: &str) -> Result<Vec<(String, String)>, Report> {
+            Ok(self
+                .inner
+                .iter_prefixed(&format!("{}{prefix}", self.prefix))?
+                .into_iter()
+                .filter_map(|(key, value)| {
+                    Some((key.strip_prefix(&self.prefix)?.to_owned(), value))
+                })
+                .collect())
+        }
+    }
+
+    /// Variables captured from another provider once, e.g. to read them for several configs
+    /// without querying the provider again
+    pub struct Snapshot {
+        vars: Vec<(String, OsString)>,
+    }
+
+    impl Snapshot {
+        /// Capture `keys` which are set in `provider`
+        pub fn capture<'a>(
+            provider: &impl EnvProvider,
+            keys: impl IntoIterator<Item = &'a str>,
+        ) -> Result<Self, FieldFromEnvError> {
+            let mut vars = Vec::new();
+            for key in keys {
+                if vars.iter().any(|(x, _)| x == key) {
+                    continue;
+                }
+                let value = provider
+                    .fetch_os(key)
+                    .map_err(|report| FieldFromEnvError::new(report, key.to_owned()))?;
+                if let Some(value) = value {
+                    vars.push((key.to_owned(), value));
+                }
+            }
+            Ok(Self { vars })
+        }
     }
 
+    impl EnvProvider for Snapshot {
+        fn fetch(&self, key: impl AsRef<str>) -> Result<Option<String>, Report> {
+            match self.fetch_os(key.as_ref())? {
+                Some(value) => value.into_string().map(Some).map_err(|value| {
+                    NotUnicodeEnvError {
+                        variable: key.as_ref().to_owned(),
+                        value,
+                    }
+                    .into()
+                }),
+                None => Ok(None),
+            }
+        }
+
+        fn fetch_os(&self, key: impl AsRef<str>) -> Result<Option<OsString>, Report> {
+            Ok(self
+                .vars
+                .iter()
+                .find(|(x, _)| x == key.as_ref())
+                .map(|(_, value)| value.clone()))
+        }
+
+        /// Only captured variables, with non-unicode values skipped
+        fn iter_prefixed(&self, prefix: &str) -> Result<Vec<(String, String)>, Report> {
+            let mut vars: Vec<_> = self
+                .vars
+                .iter()
+                .filter(|(key, _)| key.starts_with(prefix))
+                .filter_map(|(key, value)| Some((key.clone(), value.to_str()?.to_owned())))
+                .collect();
+            vars.sort();
+            Ok(vars)
+        }
+    }
+
+    /// Provider which resolves the `_FILE` suffix convention of Docker and Compose secrets:
+    /// if `DB_PASSWORD` is not set, but `DB_PASSWORD_FILE=/run/secrets/db` is, the value is read
+    /// from that file, without a trailing newline. Setting both is an error.
+    pub struct FileSuffix<P>(pub P);
+
+    impl<P: EnvProvider> FileSuffix<P> {
+        fn read_file(&self, key: &str) -> Result<Option<String>, Report> {
+            let file_key = format!("{key}{FILE_SUFFIX}");
+            let Some(path) = self.0.fetch_os(&file_key)? else {
+                return Ok(None);
+            };
+            let path = PathBuf::from(path);
+            let contents = std::fs::read(&path).map_err(|source| FileEnvError::Read {
+                variable: file_key,
+                path: path.clone(),
+                source,
+            })?;
+            String::from_contents(&path, contents).map(Some)
+        }
+    }
+
+    impl<P: EnvProvider> EnvProvider for FileSuffix<P> {
+        fn fetch(&self, key: impl AsRef<str>) -> Result<Option<String>, Report> {
+            let key = key.as_ref();
+            let value = self.0.fetch(key)?;
+            let file = self.read_file(key)?;
+            match (value, file) {
+                (Some(_), Some(_)) => Err(FileEnvError::Conflict {
+                    variable: key.to_owned(),
+                    file_variable: format!("{key}{FILE_SUFFIX}"),
+                }
+                .into()),
+                (value, file) => Ok(value.or(file)),
+            }
+        }
+
+        fn fetch_os(&self, key: impl AsRef<str>) -> Result<Option<OsString>, Report> {
+            let key = key.as_ref();
+            match self.0.fetch_os(key)? {
+                Some(value) if self.0.fetch_os(format!("{key}{FILE_SUFFIX}"))?.is_none() => {
+                    Ok(Some(value))
+                }
+                _ => self.fetch(key).map(|value| value.map(OsString::from)),
+            }
+        }
+
+        /// Variables with the suffix are listed under their base names, with values read from
+        /// the files
+        fn iter_prefixed(&self, prefix: &str) -> Result<Vec<(String, String)>, Report> {
+            let mut vars = Vec::new();
+            for (key, value) in self.0.iter_prefixed(prefix)? {
+                match key.strip_suffix(FILE_SUFFIX) {
+                    Some(base) if !base.is_empty() => {
+                        if !vars.iter().any(|(x, _)| x == base) {
+                            let value = self.fetch(base)?.unwrap_or_default();
+                            vars.push((base.to_owned(), value));
+                        }
+                    }
+                    _ => {
+                        if !vars.iter().any(|(x, _)| *x == key) {
+                            vars.push((key, value));
+                        }
+                    }
+                }
+            }
+            vars.sort();
+            Ok(vars)
+        }
+    }
+
+    /// Suffix of variables with paths, see [`FileSuffix`]
+    pub const FILE_SUFFIX: &str = "_FILE";
+
+    #[derive(Debug, Error)]
+    #[cfg_attr(feature = "miette", derive(Diagnostic))]
+    pub enum FileEnvError {
+        #[error("both `{variable}` and `{file_variable}` are set")]
+        #[cfg_attr(feature = "miette", diagnostic(help("remove one of them")))]
+        Conflict {
+            variable: String,
+            file_variable: String,
+        },
+        #[error("failed to read `{}` set in `{variable}`", path.display())]
+        Read {
+            variable: String,
+            path: PathBuf,
+            #[source]
+            source: std::io::Error,
+        },
+    }
+
+    #[derive(Debug, Error)]
+    #[cfg_attr(feature = "miette", derive(Diagnostic))]
+    #[error("ENV provider cannot enumerate variables with prefix `{prefix}`")]
+    pub struct IterUnsupportedError {
+        prefix: String,
+    }
+
+    #[derive(Default)]
     pub struct StdEnv;
 
     impl StdEnv {
@@ -111,22 +561,53 @@ pub mod env {
             match var(key.as_ref()) {
                 Ok(x) => Ok(Some(x)),
                 Err(VarError::NotPresent) => Ok(None),
-                Err(VarError::NotUnicode(os)) => {
-                    // TODO: make a special diagnostic for this error
-                    Err(miette!(
-                        "ENV var `{}` is not a valid utf-8 string: {:?}",
-                        key.as_ref(),
-                        os
-                    ))
+                Err(VarError::NotUnicode(value)) => Err(NotUnicodeEnvError {
+                    variable: key.as_ref().to_owned(),
+                    value,
                 }
+                .into()),
             }
         }
+
+        fn fetch_os(&self, key: impl AsRef<str>) -> Result<Option<OsString>, Report> {
+            Ok(std::env::var_os(key.as_ref()))
+        }
+
+        /// Variables with non-unicode names or values are skipped
+        fn iter_prefixed(&self, prefix: &str) -> Result<Vec<(String, String)>, Report> {
+            let mut vars: Vec<_> = std::env::vars_os()
+                .filter_map(|(key, value)| {
+                    Some((key.into_string().ok()?, value.into_string().ok()?))
+                })
+                .filter(|(key, _)| key.starts_with(prefix))
+                .collect();
+            vars.sort();
+            Ok(vars)
+        }
     }
 }
 
-#[derive(Error, Debug, Diagnostic)]
+#[derive(Error, Debug, Default)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
 #[error("Missing field")]
-pub struct MissingFieldError;
+pub struct MissingFieldError {
+    /// ENV variables which could have provided the field
+    env: &'static [&'static str],
+}
+
+impl MissingFieldError {
+    pub fn new() -> Self {
+        Self { env: &[] }
+    }
+
+    pub fn with_env(env: &'static [&'static str]) -> Self {
+        Self { env }
+    }
+
+    pub fn env(&self) -> &'static [&'static str] {
+        self.env
+    }
+}
 
 pub trait Layer {
     type Complete;
@@ -135,8 +616,101 @@ pub trait Layer {
 
     fn merge(self, other: Self) -> Self;
 
+    /// Same as [`Self::merge`], but in place. Derived layers implement it without moving the
+    /// whole struct, which matters for large configs merged from many sources.
+    fn merge_from(&mut self, other: Self)
+    where
+        Self: Sized,
+    {
+        let this = std::mem::replace(self, Self::new());
+        *self = this.merge(other);
+    }
+
+    /// Set missing fields to their defaults in place, evaluating default expressions only for
+    /// the fields which are actually missing. Same as merging `self` over the default layer,
+    /// but without constructing it, see [`builder::ConfigBuilder::with_lazy_defaults`].
+    ///
+    /// Does nothing unless the layer is derived.
+    fn fill_defaults(&mut self) {}
+
     fn complete(self) -> Result<Self::Complete, CompleteError>;
 
+    /// Same as [`Self::complete`], but with an explicit [`ErrorPolicy`]. Derived layers and
+    /// collections stop at the first error with [`ErrorPolicy::FailFast`], other layers
+    /// complete as usual.
+    fn complete_with(self, policy: ErrorPolicy) -> Result<Self::Complete, CompleteError>
+    where
+        Self: Sized,
+    {
+        self.complete().map_err(|err| err.with_policy(policy))
+    }
+
+    /// Metadata of the layer fields, in declaration order. Empty unless the layer is derived.
+    ///
+    /// It is a constant, so that it might be inspected at compile time. Outputs based on it,
+    /// such as help, schemas and fingerprints, are stable across builds.
+    const FIELDS: &'static [meta::FieldMeta] = &[];
+
+    /// Same as [`Self::FIELDS`]
+    fn fields() -> &'static [meta::FieldMeta]
+    where
+        Self: Sized,
+    {
+        Self::FIELDS
+    }
+
+    /// Paths of fields which are set in the layer, e.g. `db.port`. An empty path stands for the
+    /// layer itself, e.g. a whole array. Used to track where values come from, see
+    /// [`provenance`].
+    fn provided_fields(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Paths of unknown keys captured by `#[layer(flatten)]` maps, e.g. `plugin_key`. They are
+    /// reported by [`lint`] like other unknown keys.
+    fn captured_keys(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Render help listing every option of the layer, see [`meta::render_help`].
+    fn render_help(colored: bool) -> String
+    where
+        Self: Sized,
+    {
+        meta::render_help(Self::fields(), colored)
+    }
+
+    /// Render a shell completion script for `--set key=value` overrides of `program`, see
+    /// [`completion::completion_script`].
+    fn completion_script(shell: completion::Shell, program: &str) -> String
+    where
+        Self: Sized,
+    {
+        completion::completion_script(Self::fields(), shell, program)
+    }
+
+    /// Check that the default layer survives serialization and completes, see
+    /// [`testing::verify_roundtrip`]
+    #[cfg(feature = "toml")]
+    fn verify_roundtrip() -> Result<(), testing::RoundtripError>
+    where
+        Self: Sized + Default + serde::Serialize + serde::de::DeserializeOwned,
+    {
+        testing::verify_roundtrip::<Self>()
+    }
+
+    /// Layer with placeholder values derived from `seed` and field names, see
+    /// [`fixture::arbitrary`]. Panics if the layer fails to deserialize from the placeholders,
+    /// e.g. because of a custom `deserialize_with`.
+    #[cfg(feature = "fuzz")]
+    fn arbitrary(seed: u64) -> Self
+    where
+        Self: Sized + serde::de::DeserializeOwned,
+    {
+        fixture::arbitrary(seed)
+            .unwrap_or_else(|err| panic!("failed to fill the layer with placeholders: {err}"))
+    }
+
     fn complete_and_report(self) -> Result<Self::Complete, CompleteErrorDiagnostic>
     where
         Self: Sized,
@@ -145,57 +719,283 @@ pub trait Layer {
     }
 }
 
-#[derive(Debug, Error, Diagnostic)]
+/// Error of a single field during completion
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+pub enum CompleteFieldError {
+    #[error(transparent)]
+    #[cfg_attr(feature = "miette", diagnostic(transparent))]
+    Missing(#[from] MissingFieldError),
+    #[error("Invalid value: {0}")]
+    Invalid(Report),
+    /// See [`CompleteError::Custom`]
+    #[error("{0}")]
+    Custom(Report),
+}
+
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
 pub enum CompleteErrorDiagnostic {
     #[error("Missing data")]
     MissingData,
-    #[error("Ooops, there are missing fields")]
-    MissingFields {
+    #[error("Invalid data: {0}")]
+    Invalid(Report),
+    #[error("Ooops, there are missing or invalid fields")]
+    Fields {
         // TODO display the whole source string with labels attached to a whole config with missing fields?
-        #[related]
-        fields: Vec<MissingFieldErrorDiagnostic>,
+        #[cfg_attr(feature = "miette", related)]
+        fields: Vec<FieldErrorDiagnostic>,
     },
+    #[error(transparent)]
+    #[cfg_attr(feature = "miette", diagnostic(transparent))]
+    Custom(CustomErrorDiagnostic),
 }
 
 impl From<CompleteError> for CompleteErrorDiagnostic {
     fn from(value: CompleteError) -> Self {
         match value {
             CompleteError::MissingData => CompleteErrorDiagnostic::MissingData,
-            CompleteError::MissingFields(MultipleFieldsError {
+            CompleteError::Invalid(report) => CompleteErrorDiagnostic::Invalid(report),
+            CompleteError::Custom(report) => {
+                CompleteErrorDiagnostic::Custom(CustomErrorDiagnostic { path: None, report })
+            }
+            CompleteError::Fields(MultipleFieldsError {
                 fields: FieldsAcc { paths },
+                ..
             }) => {
                 let fields = paths
                     .into_iter()
-                    .map(|WithPath { mut path, .. }| {
-                        path.iter()
-                            .rev()
-                            .map(|x| (*x).to_owned())
-                            .collect::<Vec<_>>()
-                            .join(".")
+                    .map(|WithPath { path, value }| {
+                        let path = join_path(&path);
+                        match value {
+                            CompleteFieldError::Missing(err) => FieldErrorDiagnostic::Missing(
+                                MissingFieldErrorDiagnostic::new(path, err.env()),
+                            ),
+                            CompleteFieldError::Invalid(report) => {
+                                FieldErrorDiagnostic::Invalid(InvalidFieldErrorDiagnostic {
+                                    path,
+                                    report,
+                                })
+                            }
+                            CompleteFieldError::Custom(report) => {
+                                FieldErrorDiagnostic::Custom(CustomErrorDiagnostic {
+                                    path: Some(path),
+                                    report,
+                                })
+                            }
+                        }
                     })
-                    .map(|path| MissingFieldErrorDiagnostic { path })
                     .collect();
-                Self::MissingFields { fields }
+                Self::Fields { fields }
             }
         }
     }
 }
 
-#[derive(Debug, Error, Diagnostic)]
+#[derive(Debug, Error)]
+pub enum FieldErrorDiagnostic {
+    #[error(transparent)]
+    Missing(MissingFieldErrorDiagnostic),
+    #[error(transparent)]
+    Invalid(InvalidFieldErrorDiagnostic),
+    #[error(transparent)]
+    Custom(CustomErrorDiagnostic),
+}
+
+#[cfg(feature = "miette")]
+impl FieldErrorDiagnostic {
+    fn inner(&self) -> &dyn Diagnostic {
+        match self {
+            Self::Missing(x) => x,
+            Self::Invalid(x) => x,
+            Self::Custom(x) => x,
+        }
+    }
+}
+
+/// Forwards to the inner diagnostic, like `#[diagnostic(transparent)]`
+#[cfg(feature = "miette")]
+impl Diagnostic for FieldErrorDiagnostic {
+    fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        self.inner().code()
+    }
+
+    fn severity(&self) -> Option<miette::Severity> {
+        self.inner().severity()
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        self.inner().help()
+    }
+
+    fn url<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        self.inner().url()
+    }
+
+    fn source_code(&self) -> Option<&dyn miette::SourceCode> {
+        self.inner().source_code()
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = miette::LabeledSpan> + '_>> {
+        self.inner().labels()
+    }
+
+    fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+        self.inner().related()
+    }
+
+    fn diagnostic_source(&self) -> Option<&dyn Diagnostic> {
+        self.inner().diagnostic_source()
+    }
+}
+
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+#[error("`{path}`: invalid value: {report}")]
+pub struct InvalidFieldErrorDiagnostic {
+    path: String,
+    report: Report,
+}
+
+/// Report of a custom layer, see [`CompleteError::Custom`]. Its diagnostic, such as help, is
+/// forwarded as is.
+#[derive(Debug)]
+pub struct CustomErrorDiagnostic {
+    /// `None` when the layer is completed by itself, not as a field
+    path: Option<String>,
+    report: Report,
+}
+
+impl CustomErrorDiagnostic {
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    pub fn report(&self) -> &Report {
+        &self.report
+    }
+}
+
+impl Display for CustomErrorDiagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "`{path}`: {}", self.report),
+            None => write!(f, "{}", self.report),
+        }
+    }
+}
+
+impl std::error::Error for CustomErrorDiagnostic {}
+
+#[cfg(feature = "miette")]
+impl Diagnostic for CustomErrorDiagnostic {
+    fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        self.report.code()
+    }
+
+    fn severity(&self) -> Option<miette::Severity> {
+        self.report.severity()
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        self.report.help()
+    }
+
+    fn url<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        self.report.url()
+    }
+
+    fn source_code(&self) -> Option<&dyn miette::SourceCode> {
+        self.report.source_code()
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = miette::LabeledSpan> + '_>> {
+        self.report.labels()
+    }
+
+    fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+        self.report.related()
+    }
+
+    fn diagnostic_source(&self) -> Option<&dyn Diagnostic> {
+        self.report.diagnostic_source()
+    }
+}
+
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
 #[error("`{path}`: missing field")]
 pub struct MissingFieldErrorDiagnostic {
     path: String,
+    #[cfg_attr(feature = "miette", help)]
+    help: String,
+}
+
+impl MissingFieldErrorDiagnostic {
+    fn new(path: String, env: &[&str]) -> Self {
+        let help = match env {
+            [] => format!("set `{path}` in a config file"),
+            env => {
+                let env = env
+                    .iter()
+                    .map(|x| format!("`{x}`"))
+                    .collect::<Vec<_>>()
+                    .join(" or ");
+                format!("set `{path}` in a config file or export {env}")
+            }
+        };
+        Self { path, help }
+    }
+
+    pub fn help(&self) -> &str {
+        &self.help
+    }
 }
 
 #[derive(Debug)]
 pub enum CompleteError {
     MissingData,
-    MissingFields(MultipleFieldsError<MissingFieldError>),
+    /// The layer has data, but it is invalid, e.g. failed validation
+    Invalid(Report),
+    Fields(MultipleFieldsError<CompleteFieldError>),
+    /// A custom layer reports its own diagnostic, e.g. the expected format and which key or ENV
+    /// var to set. Unlike [`Self::Invalid`], its help is kept, and when the layer is a field,
+    /// the error is reported at the full path of the field.
+    Custom(Report),
 }
 
-impl From<MultipleFieldsError<MissingFieldError>> for CompleteError {
-    fn from(value: MultipleFieldsError<MissingFieldError>) -> Self {
-        Self::MissingFields(value)
+impl CompleteError {
+    /// Policy the field errors are collected with. `None` for [`Self::MissingData`],
+    /// [`Self::Invalid`] and [`Self::Custom`], which are single errors either way.
+    pub fn policy(&self) -> Option<ErrorPolicy> {
+        match self {
+            Self::Fields(errors) => Some(errors.policy()),
+            Self::MissingData | Self::Invalid(_) | Self::Custom(_) => None,
+        }
+    }
+
+    fn with_policy(self, policy: ErrorPolicy) -> Self {
+        match self {
+            Self::Fields(errors) => Self::Fields(errors.with_policy(policy)),
+            other => other,
+        }
+    }
+}
+
+/// Whether completion reports all errors or only the first one, see [`Layer::complete_with`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    /// Traverse the whole layer and report every missing or invalid field
+    #[default]
+    CollectAll,
+    /// Stop at the first missing or invalid field, e.g. for giant configs where the full
+    /// traversal is expensive
+    FailFast,
+}
+
+impl From<MultipleFieldsError<CompleteFieldError>> for CompleteError {
+    fn from(value: MultipleFieldsError<CompleteFieldError>) -> Self {
+        Self::Fields(value)
     }
 }
 
@@ -203,25 +1003,31 @@ pub trait ResultExt<T, E> {
     fn nest_if_err(
         self,
         errors: MultipleFieldsError<E>,
-        loc: &'static str,
+        loc: impl Into<PathSegment>,
     ) -> (Option<T>, MultipleFieldsError<E>);
 }
 
-impl<T> ResultExt<T, MissingFieldError> for Result<T, CompleteError> {
+impl<T> ResultExt<T, CompleteFieldError> for Result<T, CompleteError> {
     fn nest_if_err(
         self,
-        mut errors: MultipleFieldsError<MissingFieldError>,
-        loc: &'static str,
-    ) -> (Option<T>, MultipleFieldsError<MissingFieldError>) {
+        mut errors: MultipleFieldsError<CompleteFieldError>,
+        loc: impl Into<PathSegment>,
+    ) -> (Option<T>, MultipleFieldsError<CompleteFieldError>) {
         match self {
             Ok(value) => (Some(value), errors),
             Err(err) => {
                 let errors = match err {
-                    CompleteError::MissingFields(acc) => {
+                    CompleteError::Fields(acc) => {
                         errors.fields.nest(acc.fields, loc);
                         errors
                     }
-                    CompleteError::MissingData => errors.add(MissingFieldError, loc),
+                    CompleteError::MissingData => errors.add(MissingFieldError::new().into(), loc),
+                    CompleteError::Invalid(report) => {
+                        errors.add(CompleteFieldError::Invalid(report), loc)
+                    }
+                    CompleteError::Custom(report) => {
+                        errors.add(CompleteFieldError::Custom(report), loc)
+                    }
                 };
                 (None, errors)
             }
@@ -238,52 +1044,86 @@ pub struct FieldsAcc<T> {
     paths: Vec<WithPath<T>>,
 }
 
+impl<T> Default for FieldsAcc<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T> FieldsAcc<T> {
     pub fn new() -> Self {
         Self { paths: Vec::new() }
     }
 
-    pub fn add_field(&mut self, value: T, loc: &'static str) {
+    pub fn add_field(&mut self, value: T, loc: impl Into<PathSegment>) {
         self.paths.push(WithPath::new(value).add_loc(loc));
     }
 
-    pub fn nest(&mut self, other: Self, loc: &'static str) {
-        for mut nested_path in other.paths.into_iter() {
-            self.paths.push(nested_path.add_loc(loc));
+    pub fn nest(&mut self, other: Self, loc: impl Into<PathSegment>) {
+        let loc = loc.into();
+        for nested_path in other.paths.into_iter() {
+            self.paths.push(nested_path.add_loc(loc.clone()));
         }
     }
 
     pub fn is_empty(&self) -> bool {
         self.paths.len() == 0
     }
+
+    pub fn iter(&self) -> impl Iterator<Item = &WithPath<T>> {
+        self.paths.iter()
+    }
 }
 
 #[derive(Debug)]
 pub struct MultipleFieldsError<T> {
     fields: FieldsAcc<T>,
+    policy: ErrorPolicy,
+}
+
+impl<T> Default for MultipleFieldsError<T> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<T> MultipleFieldsError<T> {
     pub fn new() -> Self {
         Self {
             fields: FieldsAcc::new(),
+            policy: ErrorPolicy::CollectAll,
         }
     }
 
-    pub fn add(mut self, err: T, loc: &'static str) -> Self {
+    pub fn with_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn policy(&self) -> ErrorPolicy {
+        self.policy
+    }
+
+    /// Whether the traversal should stop, as there is an error and the policy is
+    /// [`ErrorPolicy::FailFast`]
+    pub fn should_stop(&self) -> bool {
+        self.policy == ErrorPolicy::FailFast && !self.fields.is_empty()
+    }
+
+    pub fn add(mut self, err: T, loc: impl Into<PathSegment>) -> Self {
         self.fields.add_field(err, loc);
         self
     }
 
-    pub fn nest(mut self, other: Self, loc: &'static str) -> Self {
+    pub fn nest(mut self, other: Self, loc: impl Into<PathSegment>) -> Self {
         self.fields.nest(other.fields, loc);
         self
     }
 
     pub fn nest_if_err<U>(
-        mut self,
+        self,
         result: Result<U, Self>,
-        loc: &'static str,
+        loc: impl Into<PathSegment>,
     ) -> (Option<U>, Self) {
         match result {
             Ok(value) => (Some(value), self),
@@ -291,6 +1131,11 @@ impl<T> MultipleFieldsError<T> {
         }
     }
 
+    /// Accumulated errors with their paths
+    pub fn iter(&self) -> impl Iterator<Item = &WithPath<T>> {
+        self.fields.iter()
+    }
+
     pub fn result(self) -> Result<(), Self> {
         if self.fields.is_empty() {
             Ok(())
@@ -302,56 +1147,245 @@ impl<T> MultipleFieldsError<T> {
 
 impl<T> MultipleFieldsError<T>
 where
-    T: Diagnostic,
+    T: std::error::Error,
 {
     pub fn into_diagnostic(self) -> FieldsErrorBunch<T> {
-        let items = self
+        let mut items: Vec<_> = self
             .fields
             .paths
             .into_iter()
             .map(|WithPath { path, value }| FieldError {
-                path: path.join("."),
+                section: path
+                    .last()
+                    .map(|x| join_path(std::slice::from_ref(x)))
+                    .unwrap_or_default(),
+                path: join_path(&path),
                 main: value,
             })
             .collect();
 
-        FieldsErrorBunch { items }
+        // group by top-level section, preserving the order in which sections appeared
+        let mut sections: Vec<String> = Vec::new();
+        for item in items.iter() {
+            if !sections.iter().any(|x| x == item.section()) {
+                sections.push(item.section().to_owned());
+            }
+        }
+        items.sort_by_key(|item| sections.iter().position(|x| x == item.section()));
+
+        FieldsErrorBunch {
+            items,
+            max_rendered: usize::MAX,
+            truncated: None,
+        }
     }
 }
 
-#[derive(Debug, Error, Diagnostic)]
-#[error("FieldsErrorBunch")]
+/// A bunch of field-level errors, grouped by top-level section.
+///
+/// Displays as a summary header, e.g. `17 configuration errors (nested: 12, server: 5)`. Each
+/// error is rendered as a related diagnostic; use [`FieldsErrorBunch::max_rendered`] to cap
+/// their amount.
+#[derive(Debug)]
 pub struct FieldsErrorBunch<T>
 where
-    T: Diagnostic,
+    T: std::error::Error,
 {
-    #[related]
     items: Vec<FieldError<T>>,
+    max_rendered: usize,
+    truncated: Option<TruncatedErrors>,
 }
 
-#[derive(Debug, Error, Diagnostic)]
+impl<T> FieldsErrorBunch<T>
+where
+    T: std::error::Error,
+{
+    /// Render at most `max` related errors, followed by "… and N more".
+    pub fn max_rendered(mut self, max: usize) -> Self {
+        self.max_rendered = max;
+        self.truncated = Some(self.items.len().saturating_sub(max))
+            .filter(|more| *more > 0)
+            .map(TruncatedErrors);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Top-level sections with amounts of errors in them, in order of appearance.
+    pub fn sections(&self) -> Vec<(&str, usize)> {
+        let mut sections: Vec<(&str, usize)> = Vec::new();
+        for item in self.items.iter() {
+            match sections.last_mut() {
+                Some((section, count)) if *section == item.section() => *count += 1,
+                _ => sections.push((item.section(), 1)),
+            }
+        }
+        sections
+    }
+}
+
+impl<T> Display for FieldsErrorBunch<T>
+where
+    T: std::error::Error,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let count = self.items.len();
+        write!(
+            f,
+            "{count} configuration error{}",
+            if count == 1 { "" } else { "s" }
+        )?;
+
+        let sections = self.sections();
+        if sections.len() > 1 {
+            let sections = sections
+                .into_iter()
+                .map(|(section, count)| format!("{section}: {count}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            write!(f, " ({sections})")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<T> std::error::Error for FieldsErrorBunch<T> where T: std::error::Error {}
+
+#[cfg(feature = "miette")]
+impl<T> Diagnostic for FieldsErrorBunch<T>
+where
+    T: Diagnostic + 'static,
+{
+    fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+        let rendered = self
+            .items
+            .iter()
+            .take(self.max_rendered)
+            .map(|x| x as &dyn Diagnostic);
+        let truncated = self.truncated.iter().map(|x| x as &dyn Diagnostic);
+        Some(Box::new(rendered.chain(truncated)))
+    }
+}
+
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+#[error("… and {0} more")]
+pub struct TruncatedErrors(usize);
+
+#[derive(Debug, Error)]
 #[error("{path}: {main}")]
 pub struct FieldError<T>
 where
-    T: Diagnostic,
+    T: std::error::Error,
 {
     path: String,
-    #[diagnostic_source]
+    /// Outermost segment of the path
+    section: String,
     main: T,
 }
 
-impl MultipleFieldsError<MissingFieldError> {
-    pub fn add_if_none<T>(self, option: &Option<T>, loc: &'static str) -> Self {
+impl<T> FieldError<T>
+where
+    T: std::error::Error,
+{
+    fn section(&self) -> &str {
+        &self.section
+    }
+}
+
+#[cfg(feature = "miette")]
+impl<T> Diagnostic for FieldError<T>
+where
+    T: Diagnostic + 'static,
+{
+    fn diagnostic_source(&self) -> Option<&dyn Diagnostic> {
+        Some(&self.main)
+    }
+}
+
+impl MultipleFieldsError<CompleteFieldError> {
+    pub fn add_if_none<T>(self, option: &Option<T>, loc: impl Into<PathSegment>) -> Self {
+        self.add_if_none_with_env(option, loc, &[])
+    }
+
+    /// Same as [`Self::add_if_none`], but also remembers ENV variables that could have provided
+    /// the field, so that they are suggested in diagnostics.
+    pub fn add_if_none_with_env<T>(
+        self,
+        option: &Option<T>,
+        loc: impl Into<PathSegment>,
+        env: &'static [&'static str],
+    ) -> Self {
         if option.is_none() {
-            return self.add(MissingFieldError, loc);
+            return self.add(MissingFieldError::with_env(env).into(), loc);
         }
         self
     }
 }
 
+/// Join a path, stored from the innermost segment to the outermost one, e.g.
+/// `servers[1].port` or `tenants["acme"].quota`
+fn join_path(path: &[PathSegment]) -> String {
+    let mut joined = String::new();
+    for segment in path.iter().rev() {
+        match segment {
+            PathSegment::Field(name) if joined.is_empty() => joined.push_str(name),
+            PathSegment::Field(name) => {
+                joined.push('.');
+                joined.push_str(name);
+            }
+            PathSegment::Index(index) => joined.push_str(&format!("[{index}]")),
+            PathSegment::Key(key) => joined.push_str(&format!("[{key:?}]")),
+        }
+    }
+    joined
+}
+
+/// Segment of a path to a field
+///
+/// Segments are owned when needed, so that paths might include runtime data, e.g. map keys
+/// or names of fields of a custom layer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Field(Cow<'static, str>),
+    /// Index of an array element
+    Index(usize),
+    /// Key of a map entry
+    Key(String),
+}
+
+impl PathSegment {
+    pub fn field(name: impl Into<Cow<'static, str>>) -> Self {
+        Self::Field(name.into())
+    }
+
+    pub fn key(key: impl Into<String>) -> Self {
+        Self::Key(key.into())
+    }
+}
+
+impl From<&'static str> for PathSegment {
+    fn from(value: &'static str) -> Self {
+        Self::Field(Cow::Borrowed(value))
+    }
+}
+
+impl From<usize> for PathSegment {
+    fn from(value: usize) -> Self {
+        Self::Index(value)
+    }
+}
+
 #[derive(Debug)]
 pub struct WithPath<T> {
-    path: Vec<&'static str>,
+    path: Vec<PathSegment>,
     value: T,
 }
 
@@ -363,8 +1397,22 @@ impl<T> WithPath<T> {
         }
     }
 
-    pub fn add_loc(mut self, loc: &'static str) -> Self {
-        self.path.push(loc);
+    pub fn add_loc(mut self, loc: impl Into<PathSegment>) -> Self {
+        self.path.push(loc.into());
         self
     }
+
+    /// Path segments, from the outermost to the innermost one
+    pub fn segments(&self) -> impl Iterator<Item = &PathSegment> {
+        self.path.iter().rev()
+    }
+
+    /// Path joined for display, e.g. `servers[1].port`
+    pub fn joined_path(&self) -> String {
+        join_path(&self.path)
+    }
+
+    pub fn value(&self) -> &T {
+        &self.value
+    }
 }
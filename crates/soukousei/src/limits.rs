@@ -0,0 +1,586 @@
+//! Limits of config inputs from semi-trusted sources, see [`Limits`].
+//!
+//! Services which accept configs from the outside, e.g. uploaded by tenants, should bound the
+//! work done on a single document, so that a pathological one, such as a gigabyte of nested
+//! arrays, is rejected early:
+//!
+//! ```ignore
+//! let config = ConfigBuilder::<ConfigLayer>::new()
+//!     .with_limits(Limits::new().max_size(64 * 1024).max_depth(8).max_array_len(1000))
+//!     .with_str("upload", Format::Toml, body)?
+//!     .build()?;
+//! ```
+//!
+//! The size is checked before parsing. The depth and array lengths are checked while a layer
+//! is deserialized from the document, which stops at the first violation, so that the document
+//! is parsed once. Still, only the size bounds the work of the parser itself, as TOML is parsed
+//! in full before it is deserialized.
+
+use crate::source::Format;
+use crate::value::Value;
+#[cfg(feature = "miette")]
+use miette::Diagnostic;
+use serde::de::{
+    self, DeserializeSeed, Deserializer, EnumAccess, IgnoredAny, MapAccess, SeqAccess,
+    VariantAccess, Visitor,
+};
+use std::cell::RefCell;
+use std::fmt::{Display, Formatter};
+use thiserror::Error;
+
+/// Key under which TOML deserializers expose datetimes, which are not tables
+const TOML_DATETIME_KEY: &str = "$__toml_private_datetime";
+
+/// Bounds of a single document. There are no limits by default.
+#[derive(Debug, Clone, Default)]
+pub struct Limits {
+    max_size: Option<usize>,
+    max_depth: Option<usize>,
+    max_array_len: Option<usize>,
+}
+
+impl Limits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maximum length of a document in bytes
+    pub fn max_size(mut self, bytes: usize) -> Self {
+        self.max_size = Some(bytes);
+        self
+    }
+
+    /// Maximum nesting of tables and arrays. Values at the top level of a document are at
+    /// depth 0, so `[server]` is at depth 1, while both `[server.tls]` and an array in
+    /// `[server]` are at depth 2.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Maximum number of items in an array
+    pub fn max_array_len(mut self, len: usize) -> Self {
+        self.max_array_len = Some(len);
+        self
+    }
+
+    /// See [`Self::max_size`]
+    pub fn size_limit(&self) -> Option<usize> {
+        self.max_size
+    }
+
+    /// Check `contents` against the limits. Contents which fail to parse are not checked
+    /// further than the violations found before the error, as parse errors are reported anyway.
+    pub fn check_str(&self, format: Format, contents: &str) -> Result<(), LimitError> {
+        self.check_size(contents)?;
+        let Some(check) = self.checker() else {
+            return Ok(());
+        };
+        let _ = format.parse_limited::<IgnoredAny>(contents, None, Some(&check));
+        check.into_result()
+    }
+
+    pub(crate) fn check_size(&self, contents: &str) -> Result<(), LimitError> {
+        match self.max_size {
+            Some(limit) if contents.len() > limit => Err(LimitError::Size { limit }),
+            _ => Ok(()),
+        }
+    }
+
+    /// State of checking a document while it is deserialized, if there are limits besides
+    /// the size, see [`Limited`]
+    pub(crate) fn checker(&self) -> Option<LimitCheck> {
+        (self.max_depth.is_some() || self.max_array_len.is_some()).then(|| LimitCheck {
+            limits: self.clone(),
+            error: RefCell::new(None),
+            key: RefCell::new(String::new()),
+        })
+    }
+
+    /// Check the depth and array lengths of `value`, which is already parsed
+    pub fn check(&self, value: &Value) -> Result<(), LimitError> {
+        self.check_at(value, "", 0)
+    }
+
+    fn check_at(&self, value: &Value, path: &str, depth: usize) -> Result<(), LimitError> {
+        if !matches!(value, Value::Array(_) | Value::Table(_)) {
+            return Ok(());
+        }
+        if let Some(limit) = self.max_depth {
+            if depth > limit {
+                return Err(LimitError::Depth {
+                    path: path.to_owned(),
+                    limit,
+                });
+            }
+        }
+        match value {
+            Value::Array(items) => {
+                if let Some(limit) = self.max_array_len {
+                    if items.len() > limit {
+                        return Err(LimitError::ArrayLength {
+                            path: path.to_owned(),
+                            limit,
+                        });
+                    }
+                }
+                for (index, item) in items.iter().enumerate() {
+                    self.check_at(item, &join(path, &index.to_string()), depth + 1)?;
+                }
+            }
+            Value::Table(entries) => {
+                for (key, entry) in entries {
+                    self.check_at(entry, &join(path, key), depth + 1)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+pub enum LimitError {
+    #[error("The document exceeds the limit of {limit} bytes")]
+    Size { limit: usize },
+    #[error("`{path}` is nested deeper than the limit of {limit} levels")]
+    #[cfg_attr(feature = "miette", diagnostic(help("flatten the structure")))]
+    Depth { path: String, limit: usize },
+    #[error("`{path}` has more items than the limit of {limit}")]
+    ArrayLength { path: String, limit: usize },
+}
+
+/// The first violation found by [`Limited`], which deserializers report as a custom error
+#[derive(Debug)]
+pub(crate) struct LimitCheck {
+    limits: Limits,
+    error: RefCell<Option<LimitError>>,
+    /// The latest map key, which the value after it is at
+    key: RefCell<String>,
+}
+
+impl LimitCheck {
+    pub(crate) fn into_result(self) -> Result<(), LimitError> {
+        match self.error.into_inner() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    fn depth<E: de::Error>(&self, path: &str, depth: usize) -> Result<(), E> {
+        match self.limits.max_depth {
+            Some(limit) if depth > limit => Err(self.fail(LimitError::Depth {
+                path: path.to_owned(),
+                limit,
+            })),
+            _ => Ok(()),
+        }
+    }
+
+    fn array_len<E: de::Error>(&self, path: &str, len: usize) -> Result<(), E> {
+        match self.limits.max_array_len {
+            Some(limit) if len > limit => Err(self.fail(LimitError::ArrayLength {
+                path: path.to_owned(),
+                limit,
+            })),
+            _ => Ok(()),
+        }
+    }
+
+    fn fail<E: de::Error>(&self, err: LimitError) -> E {
+        let custom = E::custom(&err);
+        self.error.borrow_mut().get_or_insert(err);
+        custom
+    }
+}
+
+/// Deserializer which checks `inner` against the limits as values are deserialized from it.
+/// Parts which are ignored, e.g. unknown keys, are visited and checked too.
+pub(crate) struct Limited<'c, D> {
+    inner: D,
+    check: &'c LimitCheck,
+    path: String,
+    depth: usize,
+    /// Whether it is a map key, which is recorded in [`LimitCheck::key`]
+    key: bool,
+}
+
+impl<'c, D> Limited<'c, D> {
+    pub(crate) fn new(inner: D, check: &'c LimitCheck) -> Self {
+        Self {
+            inner,
+            check,
+            path: String::new(),
+            depth: 0,
+            key: false,
+        }
+    }
+
+    fn wrap<T>(&self, inner: T) -> Wrap<'c, T> {
+        Wrap {
+            inner,
+            check: self.check,
+            path: self.path.clone(),
+            depth: self.depth,
+            key: self.key,
+        }
+    }
+}
+
+macro_rules! forward_deserialize {
+    ($($method:ident($($arg:ident: $ty:ty),*)),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(
+                self,
+                $($arg: $ty,)*
+                visitor: V,
+            ) -> Result<V::Value, Self::Error> {
+                let visitor = self.wrap(visitor);
+                self.inner.$method($($arg,)* visitor)
+            }
+        )*
+    };
+}
+
+impl<'de, D: Deserializer<'de>> Deserializer<'de> for Limited<'_, D> {
+    type Error = D::Error;
+
+    forward_deserialize!(
+        deserialize_any(),
+        deserialize_bool(),
+        deserialize_i8(),
+        deserialize_i16(),
+        deserialize_i32(),
+        deserialize_i64(),
+        deserialize_i128(),
+        deserialize_u8(),
+        deserialize_u16(),
+        deserialize_u32(),
+        deserialize_u64(),
+        deserialize_u128(),
+        deserialize_f32(),
+        deserialize_f64(),
+        deserialize_char(),
+        deserialize_str(),
+        deserialize_string(),
+        deserialize_bytes(),
+        deserialize_byte_buf(),
+        deserialize_option(),
+        deserialize_unit(),
+        deserialize_unit_struct(name: &'static str),
+        deserialize_newtype_struct(name: &'static str),
+        deserialize_seq(),
+        deserialize_tuple(len: usize),
+        deserialize_tuple_struct(name: &'static str, len: usize),
+        deserialize_map(),
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]),
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]),
+        deserialize_identifier(),
+    );
+
+    /// Ignored values are visited too, as some deserializers skip them without any visitor
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, D::Error> {
+        let visitor = self.wrap(visitor);
+        self.inner.deserialize_any(visitor)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+/// Visitor, seed and accessors which wrap nested deserializers into [`Limited`]
+struct Wrap<'c, T> {
+    inner: T,
+    check: &'c LimitCheck,
+    path: String,
+    depth: usize,
+    key: bool,
+}
+
+impl<'c, T> Wrap<'c, T> {
+    fn limited<D>(&self, inner: D, path: String, depth: usize, key: bool) -> Limited<'c, D> {
+        Limited {
+            inner,
+            check: self.check,
+            path,
+            depth,
+            key,
+        }
+    }
+
+    fn rewrap<U>(&self, inner: U) -> Wrap<'c, U> {
+        Wrap {
+            inner,
+            check: self.check,
+            path: self.path.clone(),
+            depth: self.depth,
+            key: self.key,
+        }
+    }
+
+    fn record_key(&self, key: impl Display) {
+        if self.key {
+            *self.check.key.borrow_mut() = key.to_string();
+        }
+    }
+}
+
+fn child_path(path: &str, segment: impl Display) -> String {
+    if path.is_empty() {
+        segment.to_string()
+    } else {
+        format!("{path}.{segment}")
+    }
+}
+
+macro_rules! forward_visit {
+    ($($method:ident($ty:ty)),* $(,)?) => {
+        $(
+            fn $method<E: de::Error>(self, value: $ty) -> Result<Self::Value, E> {
+                self.record_key(&value);
+                self.inner.$method(value)
+            }
+        )*
+    };
+}
+
+impl<'de, V: Visitor<'de>> Visitor<'de> for Wrap<'_, V> {
+    type Value = V::Value;
+
+    fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+        self.inner.expecting(f)
+    }
+
+    forward_visit!(
+        visit_bool(bool),
+        visit_i8(i8),
+        visit_i16(i16),
+        visit_i32(i32),
+        visit_i64(i64),
+        visit_i128(i128),
+        visit_u8(u8),
+        visit_u16(u16),
+        visit_u32(u32),
+        visit_u64(u64),
+        visit_u128(u128),
+        visit_f32(f32),
+        visit_f64(f64),
+        visit_char(char),
+        visit_str(&str),
+        visit_borrowed_str(&'de str),
+        visit_string(String),
+    );
+
+    fn visit_bytes<E: de::Error>(self, value: &[u8]) -> Result<Self::Value, E> {
+        self.inner.visit_bytes(value)
+    }
+
+    fn visit_borrowed_bytes<E: de::Error>(self, value: &'de [u8]) -> Result<Self::Value, E> {
+        self.inner.visit_borrowed_bytes(value)
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, value: Vec<u8>) -> Result<Self::Value, E> {
+        self.inner.visit_byte_buf(value)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        self.inner.visit_none()
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        self.inner.visit_unit()
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        let deserializer = self.limited(deserializer, self.path.clone(), self.depth, self.key);
+        self.inner.visit_some(deserializer)
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        let deserializer = self.limited(deserializer, self.path.clone(), self.depth, self.key);
+        self.inner.visit_newtype_struct(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+        self.check.depth(&self.path, self.depth)?;
+        let seq = Counted {
+            inner: seq,
+            check: self.check,
+            path: self.path,
+            depth: self.depth,
+            len: 0,
+        };
+        self.inner.visit_seq(seq)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        let map = Keyed {
+            inner: map,
+            check: self.check,
+            path: self.path,
+            depth: self.depth,
+            checked: false,
+        };
+        self.inner.visit_map(map)
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+        let data = self.rewrap(data);
+        self.inner.visit_enum(data)
+    }
+}
+
+impl<'de, S: DeserializeSeed<'de>> DeserializeSeed<'de> for Wrap<'_, S> {
+    type Value = S::Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<S::Value, D::Error> {
+        let deserializer = self.limited(deserializer, self.path.clone(), self.depth, self.key);
+        self.inner.deserialize(deserializer)
+    }
+}
+
+/// Elements of an array, which fails once there are more than the limit
+struct Counted<'c, A> {
+    inner: A,
+    check: &'c LimitCheck,
+    path: String,
+    depth: usize,
+    len: usize,
+}
+
+impl<'de, A: SeqAccess<'de>> SeqAccess<'de> for Counted<'_, A> {
+    type Error = A::Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, A::Error> {
+        let seed = Wrap {
+            inner: seed,
+            check: self.check,
+            path: child_path(&self.path, self.len),
+            depth: self.depth + 1,
+            key: false,
+        };
+        let element = self.inner.next_element_seed(seed)?;
+        if element.is_some() {
+            self.len += 1;
+            self.check.array_len(&self.path, self.len)?;
+        }
+        Ok(element)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.inner.size_hint()
+    }
+}
+
+/// Entries of a table, whose depth is checked at the first key, as TOML datetimes are
+/// deserialized from single-entry maps
+struct Keyed<'c, A> {
+    inner: A,
+    check: &'c LimitCheck,
+    path: String,
+    depth: usize,
+    checked: bool,
+}
+
+impl<'de, A: MapAccess<'de>> MapAccess<'de> for Keyed<'_, A> {
+    type Error = A::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, A::Error> {
+        let seed = Wrap {
+            inner: seed,
+            check: self.check,
+            path: self.path.clone(),
+            depth: self.depth,
+            key: true,
+        };
+        let key = self.inner.next_key_seed(seed)?;
+        if !self.checked {
+            self.checked = true;
+            if key.is_none() || *self.check.key.borrow() != TOML_DATETIME_KEY {
+                self.check.depth(&self.path, self.depth)?;
+            }
+        }
+        Ok(key)
+    }
+
+    fn next_value_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<T::Value, A::Error> {
+        let seed = Wrap {
+            inner: seed,
+            check: self.check,
+            path: child_path(&self.path, self.check.key.borrow()),
+            depth: self.depth + 1,
+            key: false,
+        };
+        self.inner.next_value_seed(seed)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.inner.size_hint()
+    }
+}
+
+impl<'de, 'c, A: EnumAccess<'de>> EnumAccess<'de> for Wrap<'c, A> {
+    type Error = A::Error;
+    type Variant = Wrap<'c, A::Variant>;
+
+    fn variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<(T::Value, Self::Variant), A::Error> {
+        let (value, variant) = self.inner.variant_seed(seed)?;
+        let variant = Wrap {
+            inner: variant,
+            check: self.check,
+            path: self.path,
+            depth: self.depth,
+            key: false,
+        };
+        Ok((value, variant))
+    }
+}
+
+impl<'de, A: VariantAccess<'de>> VariantAccess<'de> for Wrap<'_, A> {
+    type Error = A::Error;
+
+    fn unit_variant(self) -> Result<(), A::Error> {
+        self.inner.unit_variant()
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, A::Error> {
+        let seed = self.rewrap(seed);
+        self.inner.newtype_variant_seed(seed)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, A::Error> {
+        let visitor = self.rewrap(visitor);
+        self.inner.tuple_variant(len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, A::Error> {
+        let visitor = self.rewrap(visitor);
+        self.inner.struct_variant(fields, visitor)
+    }
+}
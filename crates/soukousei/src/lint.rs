@@ -0,0 +1,195 @@
+//! Checking config files against a config type without building the app config.
+//!
+//! Missing fields are not reported, as they might be provided by other sources.
+
+use crate::limits::LimitError;
+use crate::source::{Format, SourceError};
+use crate::{HasLayer, Layer};
+#[cfg(feature = "miette")]
+use miette::{Diagnostic, LabeledSpan, NamedSource, SourceCode};
+use serde::de::DeserializeOwned;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use thiserror::Error;
+
+/// Validate a config file for the config type `T`.
+///
+/// The format is guessed by the file extension.
+pub fn validate_file<T>(path: impl AsRef<Path>) -> Result<(), LintReport>
+where
+    T: HasLayer,
+    T::Layer: DeserializeOwned,
+{
+    let path = path.as_ref();
+    let name = path.display().to_string();
+
+    let contents = match std::fs::read_to_string(path) {
+        // skip a byte order mark, as the builder does
+        Ok(contents) if contents.starts_with('\u{feff}') => {
+            contents['\u{feff}'.len_utf8()..].to_owned()
+        }
+        Ok(contents) => contents,
+        Err(err) => {
+            return Err(LintReport::new(
+                name.clone(),
+                String::new(),
+                vec![LintIssue::Source(SourceError::Io {
+                    path: name,
+                    source: err,
+                })],
+            ))
+        }
+    };
+
+    let Some(format) = Format::from_path(path) else {
+        return Err(LintReport::new(
+            name,
+            contents,
+            vec![LintIssue::UnknownFormat],
+        ));
+    };
+
+    validate_str::<T>(name, format, contents)
+}
+
+/// Validate config contents for the config type `T`. `name` is used in diagnostics.
+pub fn validate_str<T>(
+    name: impl Into<String>,
+    format: Format,
+    contents: impl Into<String>,
+) -> Result<(), LintReport>
+where
+    T: HasLayer,
+    T::Layer: DeserializeOwned,
+{
+    let name = name.into();
+    let contents = contents.into();
+
+    let issues = match format.parse::<T::Layer>(&contents) {
+        Ok(parsed) => {
+            let captured = Layer::captured_keys(&parsed.value);
+            parsed
+                .unknown_keys
+                .into_iter()
+                .chain(captured)
+                .map(|key| LintIssue::UnknownKey { key })
+                .collect()
+        }
+        Err(err) => vec![LintIssue::Source(err.into_source_error(name.clone()))],
+    };
+
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(LintReport::new(name, contents, issues))
+    }
+}
+
+/// All issues found in a single config file
+#[derive(Debug)]
+pub struct LintReport {
+    name: String,
+    #[cfg(feature = "miette")]
+    source: NamedSource,
+    issues: Vec<LintIssue>,
+}
+
+impl LintReport {
+    pub(crate) fn new(name: String, contents: String, issues: Vec<LintIssue>) -> Self {
+        #[cfg(not(feature = "miette"))]
+        drop(contents);
+
+        Self {
+            #[cfg(feature = "miette")]
+            source: NamedSource::new(&name, contents),
+            name,
+            issues,
+        }
+    }
+
+    /// Name of the source, e.g. a file path or a label given to
+    /// [`ConfigBuilder::with_str`](crate::builder::ConfigBuilder::with_str)
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn issues(&self) -> &[LintIssue] {
+        &self.issues
+    }
+
+    /// The source error, if the source could not be read, parsed or decoded
+    pub fn source_error(&self) -> Option<&SourceError> {
+        self.issues.iter().find_map(|issue| match issue {
+            LintIssue::Source(err) => Some(err),
+            _ => None,
+        })
+    }
+}
+
+impl Display for LintReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}` is not a valid configuration", self.name)
+    }
+}
+
+impl std::error::Error for LintReport {}
+
+#[cfg(feature = "miette")]
+impl Diagnostic for LintReport {
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        Some(&self.source)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        let labels = self.issues.iter().filter_map(|issue| match issue {
+            LintIssue::Source(
+                SourceError::Parse {
+                    message,
+                    span: Some(span),
+                    ..
+                }
+                | SourceError::Decode {
+                    message,
+                    span: Some(span),
+                    ..
+                },
+            ) => Some(LabeledSpan::new_with_span(
+                Some(message.clone()),
+                span.clone(),
+            )),
+            #[cfg(feature = "regex")]
+            LintIssue::LiteralSecret(secret) => secret
+                .span()
+                .map(|span| LabeledSpan::new_with_span(Some(secret.pattern().to_owned()), span)),
+            _ => None,
+        });
+        Some(Box::new(labels))
+    }
+
+    fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+        Some(Box::new(self.issues.iter().map(|x| x as &dyn Diagnostic)))
+    }
+}
+
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+pub enum LintIssue {
+    /// The source could not be read, parsed or decoded
+    #[error(transparent)]
+    #[cfg_attr(feature = "miette", diagnostic(transparent))]
+    Source(SourceError),
+    #[error("Unknown file format, cannot guess it by the file extension")]
+    UnknownFormat,
+    /// See [`crate::limits`]
+    #[error(transparent)]
+    #[cfg_attr(feature = "miette", diagnostic(transparent))]
+    Limit(LimitError),
+    #[error("Unknown key `{key}`")]
+    #[cfg_attr(feature = "miette", diagnostic(help("check it for typos")))]
+    UnknownKey { key: String },
+    /// See [`crate::secret_scan`]
+    #[cfg(feature = "regex")]
+    #[error(transparent)]
+    #[cfg_attr(feature = "miette", diagnostic(transparent))]
+    LiteralSecret(crate::secret_scan::LiteralSecret),
+}
@@ -0,0 +1,47 @@
+//! Merge functions for `#[layer(merge_with = "...")]`, which override how a single field is
+//! merged instead of the newer value replacing the older one:
+//!
+//! ```ignore
+//! #[derive(Layer)]
+//! struct Config {
+//!     #[layer(merge_with = "soukousei::merge::max")]
+//!     workers: u32,
+//!     #[layer(merge_with = "soukousei::merge::concat")]
+//!     plugins: Vec<String>,
+//! }
+//! ```
+//!
+//! A merge function receives the older value first. A value missing in one of the layers is
+//! taken from the other one as is.
+
+use std::ops::Add;
+
+fn combine<T>(older: Option<T>, newer: Option<T>, f: impl FnOnce(T, T) -> T) -> Option<T> {
+    match (older, newer) {
+        (Some(older), Some(newer)) => Some(f(older, newer)),
+        (older, newer) => newer.or(older),
+    }
+}
+
+/// The greatest of the values
+pub fn max<T: Ord>(older: Option<T>, newer: Option<T>) -> Option<T> {
+    combine(older, newer, std::cmp::max)
+}
+
+/// The least of the values
+pub fn min<T: Ord>(older: Option<T>, newer: Option<T>) -> Option<T> {
+    combine(older, newer, std::cmp::min)
+}
+
+/// Sum of the values, e.g. of budgets granted by several sources
+pub fn sum<T: Add<Output = T>>(older: Option<T>, newer: Option<T>) -> Option<T> {
+    combine(older, newer, Add::add)
+}
+
+/// Items of the older collection followed by items of the newer one
+pub fn concat<T: Extend<T::Item> + IntoIterator>(older: Option<T>, newer: Option<T>) -> Option<T> {
+    combine(older, newer, |mut older, newer| {
+        older.extend(newer);
+        older
+    })
+}
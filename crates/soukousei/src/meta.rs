@@ -0,0 +1,339 @@
+//! Static metadata of layer fields, generated by the derive.
+//!
+//! It is used to produce documentation-like outputs, such as [`render_help`].
+
+use std::fmt::Write;
+
+const INDENT: &str = "  ";
+
+/// Metadata of a single layer field.
+#[derive(Debug, Clone, Copy)]
+pub struct FieldMeta {
+    pub name: &'static str,
+    /// Type of the field in the complete struct, as written in the source
+    pub ty: &'static str,
+    /// Doc comment of the field
+    pub doc: Option<&'static str>,
+    /// Default value expression, as written in the source
+    pub default: Option<&'static str>,
+    /// ENV variables that provide the field, in order of precedence
+    pub env: &'static [&'static str],
+    pub secret: bool,
+    /// The field might remain empty after completion
+    pub optional: bool,
+    /// The field is deserialized and merged as a whole, so its inner keys can't be set by
+    /// different sources
+    pub opaque: bool,
+    /// The field is a nested [`crate::toggle::Toggle`] section, which also has an `enabled` key
+    pub toggle: bool,
+    /// The field is a map capturing unknown keys of its level, set with `#[layer(flatten)]`
+    pub catch_all: bool,
+    /// Fields of a nested layer
+    pub nested: Option<&'static [FieldMeta]>,
+    /// Prefix of indexed ENV vars of collection elements, set with
+    /// `#[layer(env_indexed = "...")]`, see [`indexed_env`]
+    pub env_indexed: Option<&'static str>,
+    /// Group for settings editors, set with `#[layer(group = "...")]`
+    pub group: Option<&'static str>,
+    /// Position for settings editors, set with `#[layer(order = ...)]`
+    pub order: Option<i32>,
+    /// Audience which may see the field in dumps, set with `#[layer(visibility = "...")]`, see
+    /// [`crate::tree::Audience`]
+    pub visibility: Option<&'static str>,
+}
+
+impl FieldMeta {
+    pub fn nested_fields(&self) -> Option<&'static [FieldMeta]> {
+        self.nested
+    }
+}
+
+/// Find a field by a dot-separated path, e.g. `db.port`
+pub fn find(fields: &'static [FieldMeta], path: &str) -> Option<&'static FieldMeta> {
+    let (name, rest) = match path.split_once('.') {
+        Some((name, rest)) => (name, Some(rest)),
+        None => (path, None),
+    };
+    let field = fields.iter().find(|x| x.name == name)?;
+    match rest {
+        None => Some(field),
+        Some(rest) => find(field.nested?, rest),
+    }
+}
+
+/// Fields of a single group, see [`groups`]
+#[derive(Debug, Clone)]
+pub struct FieldGroup {
+    /// `None` for fields without `#[layer(group = "...")]`
+    pub name: Option<&'static str>,
+    pub fields: Vec<&'static FieldMeta>,
+}
+
+/// Fields of a layer (not including nested ones) arranged for settings editors.
+///
+/// Fields are sorted by `order`, and those without it keep the declaration order after the
+/// ordered ones. Groups appear in order of their first field.
+pub fn groups(fields: &'static [FieldMeta]) -> Vec<FieldGroup> {
+    let mut sorted: Vec<_> = fields.iter().collect();
+    sorted.sort_by_key(|field| (field.order.is_none(), field.order));
+
+    let mut groups: Vec<FieldGroup> = Vec::new();
+    for field in sorted {
+        match groups.iter_mut().find(|group| group.name == field.group) {
+            Some(group) => group.fields.push(field),
+            None => groups.push(FieldGroup {
+                name: field.group,
+                fields: vec![field],
+            }),
+        }
+    }
+    groups
+}
+
+/// Name of a type without a path and generic arguments, e.g. `HashMap` for
+/// `std::collections::HashMap<String, T>`
+#[cfg(any(
+    feature = "toml",
+    feature = "json",
+    feature = "wizard",
+    feature = "fuzz",
+    feature = "proptest"
+))]
+pub(crate) fn type_base_name(ty: &str) -> &str {
+    let ty = ty.split('<').next().unwrap_or(ty);
+    ty.rsplit("::").next().unwrap_or(ty).trim()
+}
+
+/// ENV variables of all fields, including nested ones. Variables of indexed collection
+/// elements are listed by [`env_patterns`] instead.
+pub fn env_vars(fields: &[FieldMeta]) -> Vec<&'static str> {
+    let mut vars = Vec::new();
+    for field in fields {
+        vars.extend_from_slice(field.env);
+        if let (Some(nested), None) = (field.nested, field.env_indexed) {
+            vars.extend(env_vars(nested));
+        }
+    }
+    vars
+}
+
+/// Placeholder of an element index in [`env_patterns`]
+pub const INDEX_PLACEHOLDER: &str = "<N>";
+
+/// Name of an ENV var of a collection element, e.g. `MYAPP_UPSTREAMS_0_ADDR` for the
+/// `MYAPP_UPSTREAMS` prefix, the element `0` and its `ADDR` var
+pub fn indexed_env(prefix: &str, index: impl std::fmt::Display, env: &str) -> String {
+    format!("{prefix}_{index}_{env}")
+}
+
+/// ENV variables of indexed collection elements, with [`INDEX_PLACEHOLDER`] in place of
+/// indexes, e.g. `MYAPP_UPSTREAMS_<N>_ADDR`
+pub fn env_patterns(fields: &[FieldMeta]) -> Vec<String> {
+    let mut patterns = Vec::new();
+    env_patterns_into(&mut patterns, fields, None);
+    patterns
+}
+
+fn env_patterns_into(out: &mut Vec<String>, fields: &[FieldMeta], prefix: Option<&str>) {
+    for field in fields {
+        if let Some(prefix) = prefix {
+            out.extend(field.env.iter().map(|env| format!("{prefix}{env}")));
+        }
+        let Some(nested) = field.nested else {
+            continue;
+        };
+        match field.env_indexed {
+            Some(indexed) => {
+                let indexed = indexed_env(
+                    &format!("{}{indexed}", prefix.unwrap_or_default()),
+                    INDEX_PLACEHOLDER,
+                    "",
+                );
+                env_patterns_into(out, nested, Some(&indexed));
+            }
+            None => env_patterns_into(out, nested, prefix),
+        }
+    }
+}
+
+/// Whether `var` matches a pattern of [`env_patterns`]
+pub fn matches_env_pattern(pattern: &str, var: &str) -> bool {
+    let mut parts = pattern.split(INDEX_PLACEHOLDER);
+    let Some(mut rest) = parts.next().and_then(|first| var.strip_prefix(first)) else {
+        return false;
+    };
+    for part in parts {
+        let digits = rest.len() - rest.trim_start_matches(|x: char| x.is_ascii_digit()).len();
+        if digits == 0 {
+            return false;
+        }
+        match rest[digits..].strip_prefix(part) {
+            Some(next) => rest = next,
+            None => return false,
+        }
+    }
+    rest.is_empty()
+}
+
+/// Whether a dot-separated `path` points to a field or a nested section. Any path inside an
+/// opaque field or next to a catch-all map is accepted, as it is checked only by
+/// deserialization.
+///
+/// It is a `const fn`, so that embedded configs are checked at compile time, see
+/// [`crate::include_config`].
+pub const fn has_path(fields: &[FieldMeta], path: &str) -> bool {
+    has_path_bytes(fields, path.as_bytes())
+}
+
+const fn has_path_bytes(fields: &[FieldMeta], path: &[u8]) -> bool {
+    let mut dot = 0;
+    while dot < path.len() && path[dot] != b'.' {
+        dot += 1;
+    }
+    let (segment, rest) = path.split_at(dot);
+
+    let mut i = 0;
+    while i < fields.len() {
+        if bytes_eq(fields[i].name.as_bytes(), segment) {
+            if rest.is_empty() || fields[i].opaque {
+                return true;
+            }
+            if fields[i].toggle && bytes_eq(rest, b".enabled") {
+                return true;
+            }
+            return match fields[i].nested {
+                Some(nested) => has_path_bytes(nested, rest.split_at(1).1),
+                None => false,
+            };
+        }
+        i += 1;
+    }
+
+    // an unknown key is captured by a catch-all map, if there is one
+    let mut i = 0;
+    while i < fields.len() {
+        if fields[i].catch_all {
+            return true;
+        }
+        i += 1;
+    }
+    false
+}
+
+const fn bytes_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Render terminal-friendly help listing every option of a layer, grouped by nested sections.
+///
+/// With `colored`, ANSI escape codes are used.
+pub fn render_help(fields: &[FieldMeta], colored: bool) -> String {
+    let mut out = String::new();
+    render_help_into(&mut out, fields, 0, "", Style { colored });
+    out
+}
+
+#[derive(Clone, Copy)]
+struct Style {
+    colored: bool,
+}
+
+impl Style {
+    fn paint(self, code: &str, text: &str) -> String {
+        if self.colored {
+            format!("\x1b[{code}m{text}\x1b[0m")
+        } else {
+            text.to_owned()
+        }
+    }
+
+    fn name(self, text: &str) -> String {
+        self.paint("1", text)
+    }
+
+    fn ty(self, text: &str) -> String {
+        self.paint("32", text)
+    }
+
+    fn label(self, text: &str) -> String {
+        self.paint("2", text)
+    }
+}
+
+/// `env_prefix` is prepended to ENV vars of indexed collection elements
+fn render_help_into(
+    out: &mut String,
+    fields: &[FieldMeta],
+    depth: usize,
+    env_prefix: &str,
+    style: Style,
+) {
+    let indent = INDENT.repeat(depth);
+
+    for field in fields {
+        if let Some(nested) = field.nested_fields() {
+            writeln!(out, "{indent}{}:", style.name(field.name)).unwrap();
+            if let Some(doc) = field.doc {
+                for line in doc.lines() {
+                    writeln!(out, "{indent}{INDENT}{line}").unwrap();
+                }
+            }
+            let env_prefix = match field.env_indexed {
+                Some(indexed) => {
+                    indexed_env(&format!("{env_prefix}{indexed}"), INDEX_PLACEHOLDER, "")
+                }
+                None => env_prefix.to_owned(),
+            };
+            render_help_into(out, nested, depth + 1, &env_prefix, style);
+            continue;
+        }
+
+        write!(
+            out,
+            "{indent}{}: {}",
+            style.name(field.name),
+            style.ty(field.ty)
+        )
+        .unwrap();
+        if field.optional {
+            write!(out, " {}", style.label("(optional)")).unwrap();
+        }
+        if field.secret {
+            write!(out, " {}", style.label("(secret)")).unwrap();
+        }
+        out.push('\n');
+
+        if let Some(doc) = field.doc {
+            for line in doc.lines() {
+                writeln!(out, "{indent}{INDENT}{line}").unwrap();
+            }
+        }
+        if let Some(default) = field.default {
+            writeln!(out, "{indent}{INDENT}{} {default}", style.label("default:")).unwrap();
+        }
+        if !field.env.is_empty() {
+            writeln!(
+                out,
+                "{indent}{INDENT}{} {}",
+                style.label("env:"),
+                field
+                    .env
+                    .iter()
+                    .map(|env| format!("{env_prefix}{env}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+            .unwrap();
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! Metrics of the config subsystem, e.g. for dashboards tracking config health.
+//!
+//! Implement [`ConfigMetricsSink`] and pass it to
+//! [`ConfigBuilder::with_metrics`](crate::builder::ConfigBuilder::with_metrics) to time the
+//! loading of sources, and to [`Reloader::with_metrics`](crate::reload::Reloader::with_metrics)
+//! to count reloads and label the active config with its [`fingerprint`]. With the `metrics`
+//! feature, [`MetricsAdapter`] reports them to the [metrics](https://docs.rs/metrics) facade.
+
+use crate::reload::ReloadTrigger;
+use crate::schema::Fnv64;
+use crate::tree::RenderTree;
+use std::time::Duration;
+
+/// Receiver of the config metrics. All methods do nothing by default.
+pub trait ConfigMetricsSink: Send + Sync {
+    /// A source is read, parsed and merged, e.g. a file named by its path, or `env`
+    fn source_loaded(&self, _source: &str, _duration: Duration) {}
+
+    /// A reload is started
+    fn reload_attempted(&self, _trigger: ReloadTrigger) {}
+
+    /// A reload has failed, so the previous config stays active
+    fn reload_failed(&self, _trigger: ReloadTrigger) {}
+
+    /// A config becomes active, initially or after a reload. See [`fingerprint`].
+    fn config_activated(&self, _fingerprint: u64) {}
+}
+
+/// Stable fingerprint of config values, e.g. to tell which config is active on which instance.
+///
+/// Values are taken as they are rendered with [`RenderTree`], so secrets don't affect it.
+pub fn fingerprint(config: &dyn RenderTree) -> u64 {
+    let mut hasher = Fnv64::new();
+    for (path, value) in crate::tree::flatten(config) {
+        hasher.write(path.as_bytes());
+        hasher.write(b"=");
+        hasher.write(value.as_bytes());
+        hasher.write(b"\n");
+    }
+    hasher.finish()
+}
+
+/// Reports to the [metrics](https://docs.rs/metrics) facade:
+///
+/// - `soukousei_source_load_seconds` histogram, labeled with `source`
+/// - `soukousei_reload_attempts_total` and `soukousei_reload_failures_total` counters, labeled
+///   with `trigger`
+/// - `soukousei_config_info` gauge, set to 1 for the `fingerprint` of the active config and to
+///   0 for the previously active one
+#[cfg(feature = "metrics")]
+#[derive(Debug, Default)]
+pub struct MetricsAdapter {
+    active: std::sync::Mutex<Option<u64>>,
+}
+
+#[cfg(feature = "metrics")]
+impl ConfigMetricsSink for MetricsAdapter {
+    fn source_loaded(&self, source: &str, duration: Duration) {
+        ::metrics::histogram!("soukousei_source_load_seconds", duration, "source" => source.to_owned());
+    }
+
+    fn reload_attempted(&self, trigger: ReloadTrigger) {
+        ::metrics::increment_counter!("soukousei_reload_attempts_total", "trigger" => trigger.to_string());
+    }
+
+    fn reload_failed(&self, trigger: ReloadTrigger) {
+        ::metrics::increment_counter!("soukousei_reload_failures_total", "trigger" => trigger.to_string());
+    }
+
+    fn config_activated(&self, fingerprint: u64) {
+        let mut active = self.active.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(previous) = active.replace(fingerprint) {
+            if previous != fingerprint {
+                ::metrics::gauge!("soukousei_config_info", 0.0, "fingerprint" => format!("{previous:016x}"));
+            }
+        }
+        ::metrics::gauge!("soukousei_config_info", 1.0, "fingerprint" => format!("{fingerprint:016x}"));
+    }
+}
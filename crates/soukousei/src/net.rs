@@ -0,0 +1,219 @@
+//! Network-related field types, see [`CidrList`].
+//!
+//! ```ignore
+//! #[derive(Layer)]
+//! struct Server {
+//!     #[layer(env = "ALLOWED_IPS", default = "CidrList::default()")]
+//!     allowed_ips: CidrList,
+//! }
+//! ```
+//!
+//! ```toml
+//! allowed_ips = ["10.0.0.0/8", "192.168.1.0/24", "::1"]
+//! ```
+//!
+//! In ENV, the list is comma-separated, e.g. `ALLOWED_IPS=10.0.0.0/8,192.168.1.0/24`.
+
+#[cfg(feature = "miette")]
+use miette::Diagnostic;
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// IP network, e.g. `10.0.0.0/8`. A bare address is a network of a single host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    pub fn new(addr: IpAddr, prefix: u8) -> Result<Self, ParseCidrError> {
+        if prefix > max_prefix(addr) {
+            return Err(ParseCidrError::new(format!("{addr}/{prefix}")));
+        }
+        Ok(Self { addr, prefix })
+    }
+
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    /// Whether the address belongs to the network. Addresses of another family never do.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        match (self.addr, addr) {
+            (IpAddr::V4(net), IpAddr::V4(addr)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u32::from(net) & mask == u32::from(addr) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(addr)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u128::from(net) & mask == u128::from(addr) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+fn max_prefix(addr: IpAddr) -> u8 {
+    match addr {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = ParseCidrError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        let err = || ParseCidrError::new(value.to_owned());
+        match value.split_once('/') {
+            Some((addr, prefix)) => {
+                let addr: IpAddr = addr.parse().map_err(|_| err())?;
+                let prefix: u8 = prefix.parse().map_err(|_| err())?;
+                Self::new(addr, prefix).map_err(|_| err())
+            }
+            None => {
+                let addr: IpAddr = value.parse().map_err(|_| err())?;
+                Ok(Self {
+                    addr,
+                    prefix: max_prefix(addr),
+                })
+            }
+        }
+    }
+}
+
+impl Display for Cidr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+#[error("invalid network `{value}`, expected an IP address or CIDR, e.g. `10.0.0.0/8`")]
+pub struct ParseCidrError {
+    value: String,
+}
+
+impl ParseCidrError {
+    fn new(value: String) -> Self {
+        Self { value }
+    }
+}
+
+/// List of IP networks, e.g. an allow-list. Parsed from an array or a comma-separated string.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CidrList(pub Vec<Cidr>);
+
+impl CidrList {
+    /// Whether the address belongs to any of the networks
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        self.0.iter().any(|cidr| cidr.contains(addr))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Cidr> {
+        self.0.iter()
+    }
+}
+
+impl FromStr for CidrList {
+    type Err = ParseCidrError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        value
+            .split(',')
+            .filter(|item| !item.trim().is_empty())
+            .map(Cidr::from_str)
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+impl Display for CidrList {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, cidr) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{cidr}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromIterator<Cidr> for CidrList {
+    fn from_iter<T: IntoIterator<Item = Cidr>>(iter: T) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::{Cidr, CidrList};
+    use serde::de::{Error, SeqAccess, Visitor};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::fmt::Formatter;
+
+    impl Serialize for Cidr {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_str(self)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Cidr {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let value = String::deserialize(deserializer)?;
+            value.parse().map_err(D::Error::custom)
+        }
+    }
+
+    impl Serialize for CidrList {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(&self.0)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for CidrList {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct ListVisitor;
+
+            impl<'de> Visitor<'de> for ListVisitor {
+                type Value = CidrList;
+
+                fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                    f.write_str("an array of networks or a comma-separated string")
+                }
+
+                fn visit_str<E: Error>(self, value: &str) -> Result<Self::Value, E> {
+                    value.parse().map_err(E::custom)
+                }
+
+                fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                    let mut list = Vec::new();
+                    while let Some(cidr) = seq.next_element()? {
+                        list.push(cidr);
+                    }
+                    Ok(CidrList(list))
+                }
+            }
+
+            deserializer.deserialize_any(ListVisitor)
+        }
+    }
+}
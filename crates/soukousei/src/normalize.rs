@@ -0,0 +1,328 @@
+//! Mapping keys of config files onto field names, see [`KeyNormalizer`].
+//!
+//! Fields are named in `snake_case`, while files might follow other conventions, e.g.
+//! `max-connections` in TOML or `maxConnections` in JSON. Instead of renaming each field, a
+//! normalizer is set for the sources which need it:
+//!
+//! ```ignore
+//! let config = ConfigBuilder::<ConfigLayer>::new()
+//!     .with_key_normalizer(SnakeCase)
+//!     .with_file("config.json")?
+//!     .build()?;
+//! ```
+//!
+//! All keys are normalized, including keys of maps such as `BTreeMap<String, T>`. ENV vars
+//! are not affected, as their names are declared on fields with `#[layer(env = "...")]`.
+
+use serde::de::{
+    DeserializeSeed, Deserializer, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    VariantAccess, Visitor,
+};
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::fmt::Formatter;
+
+/// Maps an incoming key onto a canonical field name
+pub trait KeyNormalizer: Send + Sync {
+    fn normalize<'a>(&self, key: &'a str) -> Cow<'a, str>;
+}
+
+impl<F> KeyNormalizer for F
+where
+    F: Fn(&str) -> String + Send + Sync,
+{
+    fn normalize<'a>(&self, key: &'a str) -> Cow<'a, str> {
+        Cow::Owned(self(key))
+    }
+}
+
+/// Converts `kebab-case`, `camelCase`, `PascalCase` and `SCREAMING_SNAKE_CASE` keys into
+/// `snake_case`, e.g. `HTTPServer` into `http_server`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SnakeCase;
+
+impl KeyNormalizer for SnakeCase {
+    fn normalize<'a>(&self, key: &'a str) -> Cow<'a, str> {
+        if !key.chars().any(|c| c == '-' || c.is_uppercase()) {
+            return Cow::Borrowed(key);
+        }
+
+        let chars: Vec<char> = key.chars().collect();
+        let mut out = String::with_capacity(key.len() + 4);
+        for (i, &c) in chars.iter().enumerate() {
+            if c == '-' {
+                out.push('_');
+                continue;
+            }
+            if c.is_uppercase() && i > 0 {
+                let prev = chars[i - 1];
+                let next_lower = chars.get(i + 1).is_some_and(|x| x.is_lowercase());
+                if prev.is_lowercase()
+                    || prev.is_ascii_digit()
+                    || (prev.is_uppercase() && next_lower)
+                {
+                    out.push('_');
+                }
+            }
+            out.extend(c.to_lowercase());
+        }
+        Cow::Owned(out)
+    }
+}
+
+/// Deserializer which normalizes map keys of `inner` at any depth
+pub(crate) struct Normalized<'n, D> {
+    inner: D,
+    normalizer: &'n dyn KeyNormalizer,
+}
+
+impl<'n, D> Normalized<'n, D> {
+    pub(crate) fn new(inner: D, normalizer: &'n dyn KeyNormalizer) -> Self {
+        Self { inner, normalizer }
+    }
+}
+
+macro_rules! forward_deserialize {
+    ($($method:ident($($arg:ident: $ty:ty),*)),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(
+                self,
+                $($arg: $ty,)*
+                visitor: V,
+            ) -> Result<V::Value, Self::Error> {
+                self.inner.$method($($arg,)* Wrap::new(visitor, self.normalizer))
+            }
+        )*
+    };
+}
+
+impl<'de, D: Deserializer<'de>> Deserializer<'de> for Normalized<'_, D> {
+    type Error = D::Error;
+
+    forward_deserialize!(
+        deserialize_any(),
+        deserialize_bool(),
+        deserialize_i8(),
+        deserialize_i16(),
+        deserialize_i32(),
+        deserialize_i64(),
+        deserialize_i128(),
+        deserialize_u8(),
+        deserialize_u16(),
+        deserialize_u32(),
+        deserialize_u64(),
+        deserialize_u128(),
+        deserialize_f32(),
+        deserialize_f64(),
+        deserialize_char(),
+        deserialize_str(),
+        deserialize_string(),
+        deserialize_bytes(),
+        deserialize_byte_buf(),
+        deserialize_option(),
+        deserialize_unit(),
+        deserialize_unit_struct(name: &'static str),
+        deserialize_newtype_struct(name: &'static str),
+        deserialize_seq(),
+        deserialize_tuple(len: usize),
+        deserialize_tuple_struct(name: &'static str, len: usize),
+        deserialize_map(),
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]),
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]),
+        deserialize_identifier(),
+        deserialize_ignored_any(),
+    );
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+/// Visitor, seed and accessors which wrap nested deserializers into [`Normalized`]
+struct Wrap<'n, T> {
+    inner: T,
+    normalizer: &'n dyn KeyNormalizer,
+}
+
+impl<'n, T> Wrap<'n, T> {
+    fn new(inner: T, normalizer: &'n dyn KeyNormalizer) -> Self {
+        Self { inner, normalizer }
+    }
+}
+
+macro_rules! forward_visit {
+    ($($method:ident($ty:ty)),* $(,)?) => {
+        $(
+            fn $method<E: serde::de::Error>(self, value: $ty) -> Result<Self::Value, E> {
+                self.inner.$method(value)
+            }
+        )*
+    };
+}
+
+impl<'de, V: Visitor<'de>> Visitor<'de> for Wrap<'_, V> {
+    type Value = V::Value;
+
+    fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+        self.inner.expecting(f)
+    }
+
+    forward_visit!(
+        visit_bool(bool),
+        visit_i8(i8),
+        visit_i16(i16),
+        visit_i32(i32),
+        visit_i64(i64),
+        visit_i128(i128),
+        visit_u8(u8),
+        visit_u16(u16),
+        visit_u32(u32),
+        visit_u64(u64),
+        visit_u128(u128),
+        visit_f32(f32),
+        visit_f64(f64),
+        visit_char(char),
+        visit_str(&str),
+        visit_borrowed_str(&'de str),
+        visit_string(String),
+        visit_bytes(&[u8]),
+        visit_borrowed_bytes(&'de [u8]),
+        visit_byte_buf(Vec<u8>),
+    );
+
+    fn visit_none<E: serde::de::Error>(self) -> Result<Self::Value, E> {
+        self.inner.visit_none()
+    }
+
+    fn visit_unit<E: serde::de::Error>(self) -> Result<Self::Value, E> {
+        self.inner.visit_unit()
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        self.inner
+            .visit_some(Normalized::new(deserializer, self.normalizer))
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        self.inner
+            .visit_newtype_struct(Normalized::new(deserializer, self.normalizer))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+        self.inner.visit_seq(Wrap::new(seq, self.normalizer))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        self.inner.visit_map(Wrap::new(map, self.normalizer))
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+        self.inner.visit_enum(Wrap::new(data, self.normalizer))
+    }
+}
+
+impl<'de, S: DeserializeSeed<'de>> DeserializeSeed<'de> for Wrap<'_, S> {
+    type Value = S::Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<S::Value, D::Error> {
+        self.inner
+            .deserialize(Normalized::new(deserializer, self.normalizer))
+    }
+}
+
+impl<'de, A: SeqAccess<'de>> SeqAccess<'de> for Wrap<'_, A> {
+    type Error = A::Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, A::Error> {
+        self.inner
+            .next_element_seed(Wrap::new(seed, self.normalizer))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.inner.size_hint()
+    }
+}
+
+impl<'de, A: MapAccess<'de>> MapAccess<'de> for Wrap<'_, A> {
+    type Error = A::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, A::Error> {
+        self.inner.next_key_seed(KeySeed {
+            inner: seed,
+            normalizer: self.normalizer,
+        })
+    }
+
+    fn next_value_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<T::Value, A::Error> {
+        self.inner.next_value_seed(Wrap::new(seed, self.normalizer))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.inner.size_hint()
+    }
+}
+
+impl<'de, 'n, A: EnumAccess<'de>> EnumAccess<'de> for Wrap<'n, A> {
+    type Error = A::Error;
+    type Variant = Wrap<'n, A::Variant>;
+
+    fn variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<(T::Value, Self::Variant), A::Error> {
+        let (value, variant) = self.inner.variant_seed(seed)?;
+        Ok((value, Wrap::new(variant, self.normalizer)))
+    }
+}
+
+impl<'de, A: VariantAccess<'de>> VariantAccess<'de> for Wrap<'_, A> {
+    type Error = A::Error;
+
+    fn unit_variant(self) -> Result<(), A::Error> {
+        self.inner.unit_variant()
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, A::Error> {
+        self.inner
+            .newtype_variant_seed(Wrap::new(seed, self.normalizer))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, A::Error> {
+        self.inner
+            .tuple_variant(len, Wrap::new(visitor, self.normalizer))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, A::Error> {
+        self.inner
+            .struct_variant(fields, Wrap::new(visitor, self.normalizer))
+    }
+}
+
+/// Deserializes a key as a string and passes the normalized one to the `inner` seed
+struct KeySeed<'n, K> {
+    inner: K,
+    normalizer: &'n dyn KeyNormalizer,
+}
+
+impl<'de, K: DeserializeSeed<'de>> DeserializeSeed<'de> for KeySeed<'_, K> {
+    type Value = K::Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<K::Value, D::Error> {
+        let key = String::deserialize(deserializer)?;
+        let key = self.normalizer.normalize(&key).into_owned();
+        self.inner.deserialize(key.into_deserializer())
+    }
+}
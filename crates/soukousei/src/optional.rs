@@ -0,0 +1,118 @@
+//! Layer of optional sections, so that `#[layer(nested)]` works with `Option<T>` fields where
+//! `T: HasLayer`.
+//!
+//! ```ignore
+//! #[derive(Layer)]
+//! struct Config {
+//!     /// No replica unless the `[replica]` section is set
+//!     #[layer(nested)]
+//!     replica: Option<Database>,
+//! }
+//! ```
+//!
+//! A section is present once any of its fields is set by any source. Then it is merged over
+//! its defaults and must be complete, while an absent section completes into `None`.
+
+use crate::env::{EnvProvider, FieldFromEnvError, FromEnv};
+use crate::meta::FieldMeta;
+use crate::telemetry::Telemetry;
+use crate::tree::{RenderTree, TreeFormatter};
+use crate::{CompleteError, ErrorPolicy, HasLayer, Layer, MultipleFieldsError};
+use std::collections::HashMap;
+
+/// Layer of `Option<T>`
+#[derive(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct OptionLayer<L>(pub Option<L>);
+
+impl<L> Default for OptionLayer<L> {
+    fn default() -> Self {
+        Self(None)
+    }
+}
+
+impl<L: Layer + Default> Layer for OptionLayer<L> {
+    type Complete = Option<L::Complete>;
+
+    fn new() -> Self {
+        Self(None)
+    }
+
+    fn merge(mut self, other: Self) -> Self {
+        self.merge_from(other);
+        self
+    }
+
+    fn merge_from(&mut self, other: Self) {
+        match (&mut self.0, other.0) {
+            (Some(layer), Some(other)) => layer.merge_from(other),
+            (layer, other @ Some(_)) => *layer = other,
+            (_, None) => {}
+        }
+    }
+
+    fn fill_defaults(&mut self) {
+        if let Some(layer) = &mut self.0 {
+            layer.fill_defaults()
+        }
+    }
+
+    fn complete(self) -> Result<Self::Complete, CompleteError> {
+        self.complete_with(ErrorPolicy::CollectAll)
+    }
+
+    fn complete_with(self, policy: ErrorPolicy) -> Result<Self::Complete, CompleteError> {
+        self.0
+            .map(|layer| L::default().merge(layer).complete_with(policy))
+            .transpose()
+    }
+
+    const FIELDS: &'static [FieldMeta] = L::FIELDS;
+
+    fn provided_fields(&self) -> Vec<String> {
+        self.0
+            .as_ref()
+            .map(Layer::provided_fields)
+            .unwrap_or_default()
+    }
+}
+
+impl<T> HasLayer for Option<T>
+where
+    T: HasLayer,
+    T::Layer: Default,
+{
+    type Layer = OptionLayer<T::Layer>;
+}
+
+/// The section is present if ENV sets any of its fields
+impl<L: Layer + FromEnv> FromEnv for OptionLayer<L> {
+    fn from_env(
+        provider: &impl EnvProvider,
+    ) -> Result<Self, MultipleFieldsError<FieldFromEnvError>> {
+        let layer = L::from_env(provider)?;
+        Ok(Self((!layer.provided_fields().is_empty()).then_some(layer)))
+    }
+}
+
+/// Attributes of a present section
+impl<T: Telemetry> Telemetry for Option<T> {
+    fn telemetry_attributes_into(&self, prefix: &str, out: &mut HashMap<String, String>) {
+        if let Some(value) = self {
+            value.telemetry_attributes_into(prefix, out)
+        }
+    }
+}
+
+/// Fields of a present section, an absent one is empty
+impl<T: RenderTree> RenderTree for Option<T> {
+    fn render_fields(&self, f: &mut TreeFormatter<'_>) {
+        if let Some(value) = self {
+            value.render_fields(f)
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! Custom parsers of field values, see [`FieldParser`].
+//!
+//! ```ignore
+//! #[derive(Layer)]
+//! struct Schedule {
+//!     #[layer(env = "START", parse = "soukousei::datetime::ChronoUtc")]
+//!     start: chrono::DateTime<chrono::Utc>,
+//! }
+//! ```
+//!
+//! The parser is used both for ENV vars and for strings in config files, so that the field
+//! doesn't need to implement `FromStr` or `Deserialize` in a particular way.
+
+use crate::Report;
+#[cfg(feature = "miette")]
+use miette::Diagnostic;
+use thiserror::Error;
+
+/// Parses a field value from a string and formats it back, set with
+/// `#[layer(parse = "...")]`
+pub trait FieldParser {
+    type Value;
+
+    fn parse(value: &str) -> Result<Self::Value, Report>;
+
+    /// Format the value so that [`Self::parse`] accepts it back
+    fn format(value: &Self::Value) -> String;
+}
+
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+#[error("invalid value `{value}`, expected {expected}")]
+pub struct InvalidValueError {
+    value: String,
+    expected: &'static str,
+}
+
+impl InvalidValueError {
+    pub fn new(value: impl Into<String>, expected: &'static str) -> Self {
+        Self {
+            value: value.into(),
+            expected,
+        }
+    }
+}
+
+/// Used by `#[layer(parse = "...")]` as `deserialize_with` of the layer field
+#[cfg(feature = "serde")]
+pub fn deserialize_option<'de, P, D>(deserializer: D) -> Result<Option<P::Value>, D::Error>
+where
+    P: FieldParser,
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::Error as _;
+    use serde::Deserialize as _;
+
+    match Option::<String>::deserialize(deserializer)? {
+        Some(value) => P::parse(&value).map(Some).map_err(D::Error::custom),
+        None => Ok(None),
+    }
+}
+
+/// Used by `#[layer(parse = "...")]` as `serialize_with` of the layer field
+#[cfg(feature = "serde")]
+pub fn serialize_option<P, S>(value: &Option<P::Value>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    P: FieldParser,
+    S: serde::Serializer,
+{
+    use serde::Serialize as _;
+
+    value.as_ref().map(P::format).serialize(serializer)
+}
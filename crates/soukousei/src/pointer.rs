@@ -0,0 +1,145 @@
+//! Layers for smart pointers, so that `#[layer(nested)]` works with `Box<T>`, `Arc<T>` and
+//! `Rc<T>` fields where `T: HasLayer`.
+//!
+//! `Box<L>` is a layer by itself. `Arc` and `Rc` are not, because merging shared layers would
+//! require cloning them, so the inner layer is kept unwrapped and is put behind a pointer only
+//! at completion time.
+//!
+//! Pointers don't make recursive configs possible, as the field metadata of a layer includes
+//! the metadata of nested ones. Recursive data, such as a tree of rules, should be an
+//! `#[layer(opaque)]` field.
+
+use crate::env::{EnvProvider, FieldFromEnvError, FromEnv};
+use crate::meta::FieldMeta;
+use crate::{CompleteError, ErrorPolicy, HasLayer, Layer, MultipleFieldsError};
+use std::rc::Rc;
+use std::sync::Arc;
+
+impl<L: Layer> Layer for Box<L> {
+    type Complete = Box<L::Complete>;
+
+    fn new() -> Self {
+        Box::new(L::new())
+    }
+
+    fn merge(self, other: Self) -> Self {
+        Box::new((*self).merge(*other))
+    }
+
+    fn merge_from(&mut self, other: Self) {
+        (**self).merge_from(*other)
+    }
+
+    fn fill_defaults(&mut self) {
+        (**self).fill_defaults()
+    }
+
+    fn complete(self) -> Result<Self::Complete, CompleteError> {
+        (*self).complete().map(Box::new)
+    }
+
+    fn complete_with(self, policy: ErrorPolicy) -> Result<Self::Complete, CompleteError> {
+        (*self).complete_with(policy).map(Box::new)
+    }
+
+    const FIELDS: &'static [FieldMeta] = L::FIELDS;
+
+    fn provided_fields(&self) -> Vec<String> {
+        (**self).provided_fields()
+    }
+
+    fn captured_keys(&self) -> Vec<String> {
+        (**self).captured_keys()
+    }
+}
+
+impl<T: HasLayer> HasLayer for Box<T> {
+    type Layer = Box<T::Layer>;
+}
+
+impl<L: FromEnv> FromEnv for Box<L> {
+    fn from_env(provider: &impl EnvProvider) -> Result<Self, MultipleFieldsError<FieldFromEnvError>>
+    where
+        Self: Sized,
+    {
+        L::from_env(provider).map(Box::new)
+    }
+}
+
+macro_rules! shared_pointer_layer {
+    ($(#[$meta:meta])* $layer:ident, $ptr:ident) => {
+        $(#[$meta])*
+        #[derive(Debug, Default)]
+        #[cfg_attr(
+            feature = "serde",
+            derive(serde::Serialize, serde::Deserialize),
+            serde(transparent)
+        )]
+        pub struct $layer<L>(pub L);
+
+        impl<L: Layer> Layer for $layer<L> {
+            type Complete = $ptr<L::Complete>;
+
+            fn new() -> Self {
+                Self(L::new())
+            }
+
+            fn merge(self, other: Self) -> Self {
+                Self(self.0.merge(other.0))
+            }
+
+            fn merge_from(&mut self, other: Self) {
+                self.0.merge_from(other.0)
+            }
+
+            fn fill_defaults(&mut self) {
+                self.0.fill_defaults()
+            }
+
+            fn complete(self) -> Result<Self::Complete, CompleteError> {
+                self.0.complete().map($ptr::new)
+            }
+
+            fn complete_with(self, policy: ErrorPolicy) -> Result<Self::Complete, CompleteError> {
+                self.0.complete_with(policy).map($ptr::new)
+            }
+
+            const FIELDS: &'static [FieldMeta] = L::FIELDS;
+
+            fn provided_fields(&self) -> Vec<String> {
+                self.0.provided_fields()
+            }
+
+            fn captured_keys(&self) -> Vec<String> {
+                self.0.captured_keys()
+            }
+        }
+
+        impl<T: HasLayer> HasLayer for $ptr<T> {
+            type Layer = $layer<T::Layer>;
+        }
+
+        impl<L: FromEnv> FromEnv for $layer<L> {
+            fn from_env(
+                provider: &impl EnvProvider,
+            ) -> Result<Self, MultipleFieldsError<FieldFromEnvError>>
+            where
+                Self: Sized,
+            {
+                L::from_env(provider).map(Self)
+            }
+        }
+    };
+}
+
+shared_pointer_layer!(
+    /// Layer of [`Arc<T>`], see [module-level docs](self).
+    ArcLayer,
+    Arc
+);
+
+shared_pointer_layer!(
+    /// Layer of [`Rc<T>`], see [module-level docs](self).
+    RcLayer,
+    Rc
+);
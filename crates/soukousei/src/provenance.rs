@@ -0,0 +1,90 @@
+//! Tracking which source each field comes from, e.g. `db.host` from env and `db.port` from
+//! a file.
+//!
+//! Layers themselves don't store it. Instead, after each merge the builder asks the merged
+//! layer which fields it provides, see [`crate::Layer::provided_fields`], and records the
+//! source name for them. Later sources override earlier ones, the same way merge does.
+//!
+//! For fields set with ENV, the exact variable is recorded as well, which matters for fields
+//! with several variables, e.g. `#[layer(env = ["APP_PORT", "PORT"])]`.
+
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Provenance {
+    /// Field paths with source names, in order of first appearance
+    fields: Vec<(String, String)>,
+    /// Field paths with ENV vars which provide them, see [`Provenance::variable_of`]
+    variables: Vec<(String, String)>,
+}
+
+impl Provenance {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that `source` provides the `fields`
+    pub fn record(&mut self, source: &str, fields: impl IntoIterator<Item = String>) {
+        for path in fields {
+            self.variables.retain(|(x, _)| *x != path);
+            match self.fields.iter_mut().find(|(x, _)| *x == path) {
+                Some((_, existing)) => source.clone_into(existing),
+                None => self.fields.push((path, source.to_owned())),
+            }
+        }
+    }
+
+    /// Name of the source which provided the field at `path`
+    pub fn source_of(&self, path: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(x, _)| x == path)
+            .map(|(_, source)| source.as_str())
+    }
+
+    /// Record that the field at `path`, which was just recorded, is provided by the ENV var
+    /// `variable`. It is forgotten once another source overrides the field.
+    pub fn record_variable(&mut self, path: &str, variable: &str) {
+        match self.variables.iter_mut().find(|(x, _)| x == path) {
+            Some((_, existing)) => variable.clone_into(existing),
+            None => self.variables.push((path.to_owned(), variable.to_owned())),
+        }
+    }
+
+    /// ENV var which provided the field at `path`, if it comes from ENV
+    pub fn variable_of(&self, path: &str) -> Option<&str> {
+        self.variables
+            .iter()
+            .find(|(x, _)| x == path)
+            .map(|(_, variable)| variable.as_str())
+    }
+
+    /// Field paths with source names
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.fields.iter().map(|(x, y)| (x.as_str(), y.as_str()))
+    }
+}
+
+/// Renders as lines like `db.host from env (DB_HOST)`
+impl Display for Provenance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (path, source) in self.iter() {
+            match self.variable_of(path) {
+                Some(variable) => writeln!(f, "{path} from {source} ({variable})")?,
+                None => writeln!(f, "{path} from {source}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Prefix provided fields of a nested layer with its name, used by the derive
+pub fn nest(loc: &str, provided: Vec<String>) -> impl Iterator<Item = String> + '_ {
+    provided.into_iter().map(move |path| {
+        if path.is_empty() {
+            loc.to_owned()
+        } else {
+            format!("{loc}.{path}")
+        }
+    })
+}
@@ -0,0 +1,175 @@
+//! References from one section to an entry of another one, resolved on completion.
+//!
+//! Shared sub-configs might be defined once and referenced by name:
+//!
+//! ```ignore
+//! #[derive(Layer)]
+//! #[layer(reference(field = "server.tls", section = "tls_profiles"))]
+//! struct Config {
+//!     #[layer(nested)]
+//!     server: Server,
+//!     #[layer(nested)]
+//!     tls_profiles: BTreeMap<String, TlsProfile>,
+//! }
+//!
+//! #[derive(Layer)]
+//! struct Server {
+//!     /// Set as `tls = "corp-tls"`
+//!     tls: Ref<TlsProfile>,
+//! }
+//! ```
+//!
+//! A reference to a missing entry fails completion of the layer that declares it.
+
+use crate::CompleteFieldError;
+#[cfg(feature = "miette")]
+use miette::Diagnostic;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Debug, Formatter};
+use std::ops::Deref;
+use thiserror::Error;
+
+/// A reference to an entry of another section by its name.
+///
+/// Deserializes from the name. After completion it holds a copy of the referenced entry.
+#[derive(Clone, PartialEq)]
+pub struct Ref<T> {
+    name: String,
+    target: Option<T>,
+}
+
+impl<T> Ref<T> {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            target: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The referenced entry, or `None` if the reference is not resolved yet
+    pub fn get(&self) -> Option<&T> {
+        self.target.as_ref()
+    }
+}
+
+/// Panics if the reference is not resolved, which never happens with completed configs
+impl<T> Deref for Ref<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.target
+            .as_ref()
+            .unwrap_or_else(|| panic!("reference `{}` is not resolved", self.name))
+    }
+}
+
+impl<T: Debug> Debug for Ref<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Ref")
+            .field("name", &self.name)
+            .field("target", &self.target)
+            .finish()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T> serde::Deserialize<'de> for Ref<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::new)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T> serde::Serialize for Ref<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.name.serialize(serializer)
+    }
+}
+
+/// A section with named entries
+pub trait Section<T> {
+    fn entry(&self, name: &str) -> Option<&T>;
+
+    fn names(&self) -> Vec<&str>;
+}
+
+impl<T> Section<T> for BTreeMap<String, T> {
+    fn entry(&self, name: &str) -> Option<&T> {
+        self.get(name)
+    }
+
+    fn names(&self) -> Vec<&str> {
+        self.keys().map(String::as_str).collect()
+    }
+}
+
+impl<T> Section<T> for HashMap<String, T> {
+    fn entry(&self, name: &str) -> Option<&T> {
+        self.get(name)
+    }
+
+    fn names(&self) -> Vec<&str> {
+        let mut names: Vec<_> = self.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+/// A field with references, used by the derive
+pub trait Resolve<T> {
+    fn resolve(
+        &mut self,
+        section: &impl Section<T>,
+        section_name: &'static str,
+    ) -> Result<(), CompleteFieldError>;
+}
+
+impl<T: Clone> Resolve<T> for Ref<T> {
+    fn resolve(
+        &mut self,
+        section: &impl Section<T>,
+        section_name: &'static str,
+    ) -> Result<(), CompleteFieldError> {
+        match section.entry(&self.name) {
+            Some(target) => {
+                self.target = Some(target.clone());
+                Ok(())
+            }
+            None => Err(CompleteFieldError::Invalid(
+                DanglingReferenceError {
+                    name: self.name.clone(),
+                    section: section_name,
+                    available: section.names().join(", "),
+                }
+                .into(),
+            )),
+        }
+    }
+}
+
+impl<T: Clone> Resolve<T> for Option<Ref<T>> {
+    fn resolve(
+        &mut self,
+        section: &impl Section<T>,
+        section_name: &'static str,
+    ) -> Result<(), CompleteFieldError> {
+        match self {
+            Some(reference) => reference.resolve(section, section_name),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+#[error("`{name}` is not defined in `{section}`")]
+#[cfg_attr(feature = "miette", diagnostic(help("defined entries: {available}")))]
+pub struct DanglingReferenceError {
+    name: String,
+    section: &'static str,
+    available: String,
+}
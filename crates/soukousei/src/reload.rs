@@ -0,0 +1,563 @@
+//! Reloading a config at runtime.
+//!
+//! A [`Reloader`] owns the pipeline which produces the config, usually a closure running a
+//! [`ConfigBuilder`](crate::builder::ConfigBuilder), and the [`Shared`] handle which readers
+//! take snapshots from. Each reload re-runs the pipeline and swaps the config on success.
+//!
+//! A failed reload never replaces a valid config: readers holding a [`Reloadable`] handle keep
+//! getting the previous one, and can inspect [`Reloadable::last_error`] meanwhile.
+//!
+//! Reloads are triggered explicitly with [`Reloader::reload`], by changes of config files (see
+//! [`Reloader::watch`]) or by the process receiving `SIGHUP` (see [`Reloader::reload_on_sighup`]).
+//! Results of all of them are delivered to [`Reloader::subscribe`]rs alike:
+//!
+//! [`Reloader::dry_run`] runs the pipeline without swapping anything, e.g. to check whether
+//! the config on disk is currently valid.
+//!
+//! ```ignore
+//! let reloader = Reloader::new(|| {
+//!     ConfigBuilder::<AppConfigLayer>::new()
+//!         .with_defaults()
+//!         .with_file("config.toml")?
+//!         .with_env(&StdEnv)?
+//!         .build()
+//! })?;
+//! let _watcher = reloader.watch(["/etc/app"])?;
+//! let _listener = reloader.reload_on_sighup()?;
+//!
+//! for event in reloader.subscribe() {
+//!     if let Err(err) = event.outcome {
+//!         eprintln!("config reload ({}) failed: {err:?}", event.trigger);
+//!     }
+//! }
+//! ```
+
+use crate::builder::BuildError;
+use crate::metrics::ConfigMetricsSink;
+use crate::shared::Shared;
+use crate::tree::RenderTree;
+use std::fmt::{Debug, Display, Formatter};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+
+type Pipeline<T> = dyn Fn() -> Result<T, BuildError> + Send + Sync;
+
+type FailureHook = dyn Fn(&BuildError) + Send + Sync;
+
+type Fingerprint<T> = fn(&T) -> u64;
+
+pub struct Reloader<T> {
+    shared: Shared<T>,
+    status: Arc<Status>,
+    pipeline: Arc<Pipeline<T>>,
+    /// Held while the pipeline runs, so that concurrent triggers cannot swap in an older config
+    running: Arc<Mutex<()>>,
+    subscribers: Arc<Mutex<Vec<Sender<ReloadEvent>>>>,
+    on_failure: Option<Arc<FailureHook>>,
+    /// See [`Reloader::with_metrics`]
+    metrics: Option<(Arc<dyn ConfigMetricsSink>, Fingerprint<T>)>,
+}
+
+#[derive(Default)]
+struct Status {
+    last_error: Mutex<Option<Arc<BuildError>>>,
+    failures: AtomicU64,
+}
+
+impl Status {
+    fn last_error(&self) -> MutexGuard<'_, Option<Arc<BuildError>>> {
+        // the value is only swapped as a whole
+        self.last_error
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl<T> Reloader<T> {
+    /// Run the pipeline for the initial config. Fails if it fails, as there is no config to
+    /// fall back to yet.
+    pub fn new(
+        pipeline: impl Fn() -> Result<T, BuildError> + Send + Sync + 'static,
+    ) -> Result<Self, BuildError> {
+        let initial = pipeline()?;
+        Ok(Self {
+            shared: Shared::new(initial),
+            status: Arc::default(),
+            pipeline: Arc::new(pipeline),
+            running: Arc::default(),
+            subscribers: Arc::default(),
+            on_failure: None,
+            metrics: None,
+        })
+    }
+
+    /// Call `hook` each time a reload fails, e.g. to log the error or alert on it
+    pub fn on_failure(mut self, hook: impl Fn(&BuildError) + Send + Sync + 'static) -> Self {
+        self.on_failure = Some(Arc::new(hook));
+        self
+    }
+
+    /// Handle with the current config
+    pub fn shared(&self) -> &Shared<T> {
+        &self.shared
+    }
+
+    /// Handle with the current config and the state of reloads
+    pub fn handle(&self) -> Reloadable<T> {
+        Reloadable {
+            shared: self.shared.clone(),
+            status: self.status.clone(),
+        }
+    }
+
+    /// Receive an event for each reload from now on, whatever triggered it
+    pub fn subscribe(&self) -> Receiver<ReloadEvent> {
+        let (tx, rx) = channel();
+        self.lock_subscribers().push(tx);
+        rx
+    }
+
+    /// Re-run the pipeline and swap the config if it succeeds. Otherwise, the previous config
+    /// stays in place.
+    pub fn reload(&self) -> Result<(), Arc<BuildError>> {
+        self.reload_with(ReloadTrigger::Manual)
+    }
+
+    fn reload_with(&self, trigger: ReloadTrigger) -> Result<(), Arc<BuildError>> {
+        let _running = self.running.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some((metrics, _)) = &self.metrics {
+            metrics.reload_attempted(trigger);
+        }
+        let outcome = match (self.pipeline)() {
+            Ok(value) => {
+                if let Some((metrics, fingerprint)) = &self.metrics {
+                    metrics.config_activated(fingerprint(&value));
+                }
+                self.shared.replace(value);
+                *self.status.last_error() = None;
+                Ok(())
+            }
+            Err(err) => {
+                if let Some((metrics, _)) = &self.metrics {
+                    metrics.reload_failed(trigger);
+                }
+                let err = Arc::new(err);
+                *self.status.last_error() = Some(err.clone());
+                self.status.failures.fetch_add(1, Ordering::Relaxed);
+                if let Some(hook) = &self.on_failure {
+                    hook(&err);
+                }
+                Err(err)
+            }
+        };
+
+        self.lock_subscribers().retain(|tx| {
+            tx.send(ReloadEvent {
+                trigger,
+                outcome: outcome.clone(),
+            })
+            .is_ok()
+        });
+
+        outcome
+    }
+
+    fn lock_subscribers(&self) -> MutexGuard<'_, Vec<Sender<ReloadEvent>>> {
+        // senders are only pushed and retained, so a panic cannot leave the list inconsistent
+        self.subscribers
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl<T: Send + Sync + 'static> Reloader<T> {
+    /// Reload when config files change, with [`WatchOptions::default`].
+    ///
+    /// See [`Self::watch_with`].
+    pub fn watch<I, P>(&self, paths: I) -> std::io::Result<Watcher>
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.watch_with(paths, WatchOptions::default())
+    }
+
+    /// Reload when config files change.
+    ///
+    /// Each path is either a file or a directory, in which case all files in it are watched.
+    /// Symlinks are followed, so that the Kubernetes pattern of mounting a ConfigMap, where
+    /// `config.toml` links to `..data/config.toml` and the `..data` link is swapped atomically
+    /// to a new directory, is seen as a change even if the new file has the same size and
+    /// modification time.
+    ///
+    /// Files are polled on a background thread until the returned watcher is stopped or
+    /// dropped. Once a change is seen, the watcher waits until files stay the same for
+    /// [`WatchOptions::debounce`], so that a burst of changes (an editor saving a file in several
+    /// steps, several files updated one by one) results in a single reload.
+    pub fn watch_with<I, P>(&self, paths: I, options: WatchOptions) -> std::io::Result<Watcher>
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        let paths: Vec<PathBuf> = paths.into_iter().map(Into::into).collect();
+        let stopped = Arc::new(AtomicBool::new(false));
+        let reloader = self.clone();
+        let thread_stopped = stopped.clone();
+        let mut last = fingerprint(&paths);
+        std::thread::Builder::new()
+            .name("soukousei-watch".to_owned())
+            .spawn(move || {
+                let sleep = |duration| {
+                    std::thread::sleep(duration);
+                    !thread_stopped.load(Ordering::Relaxed)
+                };
+                while sleep(options.interval) {
+                    let mut current = fingerprint(&paths);
+                    if current == last {
+                        continue;
+                    }
+                    loop {
+                        if !sleep(options.debounce) {
+                            return;
+                        }
+                        let next = fingerprint(&paths);
+                        if next == current {
+                            break;
+                        }
+                        current = next;
+                    }
+                    last = current;
+                    // the outcome is delivered to subscribers
+                    let _ = reloader.reload_with(ReloadTrigger::Watch);
+                }
+            })?;
+
+        Ok(Watcher { stopped })
+    }
+}
+
+impl<T: RenderTree> Reloader<T> {
+    /// Report reloads and the fingerprint of the active config, see [`crate::metrics`]. The
+    /// current config is reported as activated right away.
+    pub fn with_metrics(mut self, sink: Arc<dyn ConfigMetricsSink>) -> Self {
+        fn fingerprint<T: RenderTree>(config: &T) -> u64 {
+            crate::metrics::fingerprint(config)
+        }
+
+        sink.config_activated(fingerprint(&*self.shared.get()));
+        self.metrics = Some((sink, fingerprint::<T>));
+        self
+    }
+
+    /// Run the pipeline as a reload would, but keep the current config in place. Returns how
+    /// the config would change, or why the reload would fail.
+    ///
+    /// Subscribers are not notified, and the failure state of [`Reloadable`] is not affected.
+    /// Changes of secrets are not visible in the diff, as they are redacted.
+    pub fn dry_run(&self) -> Result<ConfigDiff, BuildError> {
+        let next = (self.pipeline)()?;
+        Ok(ConfigDiff::new(&*self.shared.get(), &next))
+    }
+}
+
+#[cfg(all(unix, feature = "signal"))]
+impl<T: Send + Sync + 'static> Reloader<T> {
+    /// Reload each time the process receives `SIGHUP`, e.g. after `kill -HUP <pid>`.
+    ///
+    /// Signals are handled on a background thread until the returned listener is closed or
+    /// dropped.
+    pub fn reload_on_sighup(&self) -> std::io::Result<SignalListener> {
+        use signal_hook::consts::SIGHUP;
+        use signal_hook::iterator::Signals;
+
+        let mut signals = Signals::new([SIGHUP])?;
+        let handle = signals.handle();
+        let reloader = self.clone();
+        std::thread::Builder::new()
+            .name("soukousei-sighup".to_owned())
+            .spawn(move || {
+                for _ in signals.forever() {
+                    // the outcome is delivered to subscribers
+                    let _ = reloader.reload_with(ReloadTrigger::Signal);
+                }
+            })?;
+
+        Ok(SignalListener { handle })
+    }
+}
+
+/// Clones share the same config, pipeline and subscribers
+impl<T> Clone for Reloader<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+            status: self.status.clone(),
+            pipeline: self.pipeline.clone(),
+            running: self.running.clone(),
+            subscribers: self.subscribers.clone(),
+            on_failure: self.on_failure.clone(),
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+impl<T: Debug> Debug for Reloader<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Reloader")
+            .field("shared", &self.shared)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Read side of a [`Reloader`], which always holds the last valid config
+pub struct Reloadable<T> {
+    shared: Shared<T>,
+    status: Arc<Status>,
+}
+
+impl<T> Reloadable<T> {
+    /// Snapshot of the current config
+    pub fn get(&self) -> Arc<T> {
+        self.shared.get()
+    }
+
+    pub fn shared(&self) -> &Shared<T> {
+        &self.shared
+    }
+
+    /// Error of the latest reload, if it failed
+    pub fn last_error(&self) -> Option<Arc<BuildError>> {
+        self.status.last_error().clone()
+    }
+
+    /// Number of failed reloads so far
+    pub fn failures(&self) -> u64 {
+        self.status.failures.load(Ordering::Relaxed)
+    }
+}
+
+/// Clones share the same config
+impl<T> Clone for Reloadable<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+            status: self.status.clone(),
+        }
+    }
+}
+
+impl<T: Debug> Debug for Reloadable<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Reloadable")
+            .field("shared", &self.shared)
+            .field("last_error", &self.last_error())
+            .field("failures", &self.failures())
+            .finish()
+    }
+}
+
+/// What caused a reload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReloadTrigger {
+    /// [`Reloader::reload`]
+    Manual,
+    /// Changed files, see [`Reloader::watch`]
+    Watch,
+    /// `SIGHUP`, see [`Reloader::reload_on_sighup`]
+    Signal,
+}
+
+impl Display for ReloadTrigger {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Manual => write!(f, "manual"),
+            Self::Watch => write!(f, "file change"),
+            Self::Signal => write!(f, "SIGHUP"),
+        }
+    }
+}
+
+/// Result of a reload, see [`Reloader::subscribe`]
+#[derive(Debug, Clone)]
+pub struct ReloadEvent {
+    pub trigger: ReloadTrigger,
+    /// The config is swapped only on success
+    pub outcome: Result<(), Arc<BuildError>>,
+}
+
+/// Fields changed between two configs, see [`Reloader::dry_run`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigDiff {
+    changes: Vec<FieldChange>,
+}
+
+impl ConfigDiff {
+    /// Compares fields rendered with [`RenderTree`]
+    pub fn new(current: &dyn RenderTree, next: &dyn RenderTree) -> Self {
+        let current = crate::tree::flatten(current);
+        let mut next = crate::tree::flatten(next);
+
+        let mut changes = Vec::new();
+        for (path, before) in current {
+            match next.iter().position(|(x, _)| *x == path) {
+                Some(index) => {
+                    let (_, after) = next.remove(index);
+                    if before != after {
+                        changes.push(FieldChange {
+                            path,
+                            before: Some(before),
+                            after: Some(after),
+                        });
+                    }
+                }
+                None => changes.push(FieldChange {
+                    path,
+                    before: Some(before),
+                    after: None,
+                }),
+            }
+        }
+        changes.extend(next.into_iter().map(|(path, after)| FieldChange {
+            path,
+            before: None,
+            after: Some(after),
+        }));
+
+        Self { changes }
+    }
+
+    pub fn changes(&self) -> &[FieldChange] {
+        &self.changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl Display for ConfigDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.changes.is_empty() {
+            return write!(f, "no changes");
+        }
+        for (i, change) in self.changes.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{change}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Rendered values of a field before and after a change. `None` if the field is absent, e.g.
+/// in an optional section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldChange {
+    pub path: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+impl Display for FieldChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let value = |x: &Option<String>| x.clone().unwrap_or_else(|| "<none>".to_owned());
+        write!(
+            f,
+            "`{}`: {} -> {}",
+            self.path,
+            value(&self.before),
+            value(&self.after)
+        )
+    }
+}
+
+/// Polling settings of [`Reloader::watch_with`]
+#[derive(Debug, Clone, Copy)]
+pub struct WatchOptions {
+    /// How often files are checked for changes
+    pub interval: Duration,
+    /// How long files should stay unchanged after a change before reloading
+    pub debounce: Duration,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            debounce: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Stops watching files when stopped or dropped, see [`Reloader::watch`]
+#[derive(Debug)]
+pub struct Watcher {
+    stopped: Arc<AtomicBool>,
+}
+
+impl Watcher {
+    pub fn stop(self) {}
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+/// State of a watched file: where it resolves to through symlinks, and its modification time
+/// and size. `None` if it is missing, e.g. in the middle of a swap.
+type FileState = Option<(PathBuf, Option<SystemTime>, u64)>;
+
+fn fingerprint(paths: &[PathBuf]) -> Vec<(PathBuf, FileState)> {
+    let mut files = Vec::new();
+    for path in paths {
+        match std::fs::read_dir(path) {
+            Ok(entries) => {
+                let mut entries: Vec<_> = entries
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    // `metadata` follows symlinks; nested directories, including the hidden
+                    // ones behind `..data`, are covered by the links to files in them
+                    .filter(|path| path.metadata().is_ok_and(|meta| meta.is_file()))
+                    .collect();
+                entries.sort();
+                files.extend(entries);
+            }
+            Err(_) => files.push(path.clone()),
+        }
+    }
+
+    files
+        .into_iter()
+        .map(|path| {
+            let state = std::fs::canonicalize(&path).ok().and_then(|target| {
+                let meta = target.metadata().ok()?;
+                Some((target, meta.modified().ok(), meta.len()))
+            });
+            (path, state)
+        })
+        .collect()
+}
+
+/// Stops handling `SIGHUP` when closed or dropped, see [`Reloader::reload_on_sighup`]
+#[cfg(all(unix, feature = "signal"))]
+#[derive(Debug)]
+pub struct SignalListener {
+    handle: signal_hook::iterator::Handle,
+}
+
+#[cfg(all(unix, feature = "signal"))]
+impl SignalListener {
+    pub fn close(self) {}
+}
+
+#[cfg(all(unix, feature = "signal"))]
+impl Drop for SignalListener {
+    fn drop(&mut self) {
+        self.handle.close();
+    }
+}
@@ -0,0 +1,132 @@
+//! Fingerprints of config schemas.
+//!
+//! A remote config source (e.g. a config server) might be given the fingerprint of the layer it
+//! serves, and report the fingerprint of the schema its data was produced for. Comparing them
+//! with [`verify`] fails fast with a clear diagnostic instead of a bunch of cryptic missing
+//! field errors. [`crate::builder::ConfigBuilder::with_remote`] does this handshake with a
+//! [`RemoteSource`]:
+//!
+//! ```ignore
+//! let config = ConfigBuilder::<ConfigLayer>::new()
+//!     .with_file("config.toml")?
+//!     .with_remote("config server", &client)?
+//!     .build()?;
+//! ```
+
+use crate::meta::FieldMeta;
+#[cfg(feature = "serde")]
+use crate::source::Format;
+use crate::Layer;
+#[cfg(feature = "miette")]
+use miette::Diagnostic;
+use thiserror::Error;
+
+/// Stable fingerprint of the fields structure: names, types, optionality and nesting.
+///
+/// Types are compared as written without paths and whitespace, so `std::string::String` is
+/// the same as `String`, while a type alias differs from its target. Docs, defaults and ENV
+/// names don't affect it. It doesn't depend on the platform or the compiler version.
+pub fn fingerprint(fields: &[FieldMeta]) -> u64 {
+    let mut hasher = Fnv64::new();
+    hash_fields(&mut hasher, fields);
+    hasher.finish()
+}
+
+fn hash_fields(hasher: &mut Fnv64, fields: &[FieldMeta]) {
+    for field in fields {
+        hasher.write(field.name.as_bytes());
+        hasher.write(b":");
+        hasher.write(normalize_type(field.ty).as_bytes());
+        hasher.write(if field.optional { b"?" } else { b"!" });
+        if field.toggle {
+            hasher.write(b"~");
+        }
+        if let Some(nested) = field.nested_fields() {
+            hasher.write(b"{");
+            hash_fields(hasher, nested);
+            hasher.write(b"}");
+        }
+        hasher.write(b";");
+    }
+}
+
+/// Type without whitespace and paths, e.g. `HashMap<String,Vec<u8>>` for
+/// `std::collections::HashMap<String, ::std::vec::Vec<u8>>`
+fn normalize_type(ty: &str) -> String {
+    let mut out = String::with_capacity(ty.len());
+    let mut segment_start = 0;
+    let mut chars = ty.chars().filter(|x| !x.is_whitespace()).peekable();
+    while let Some(char) = chars.next() {
+        if char == ':' && chars.peek() == Some(&':') {
+            chars.next();
+            out.truncate(segment_start);
+            continue;
+        }
+        if !(char.is_alphanumeric() || char == '_') {
+            segment_start = out.len() + char.len_utf8();
+        }
+        out.push(char);
+    }
+    out
+}
+
+/// Check a fingerprint reported by a remote source against the layer `L`
+pub fn verify<L: Layer>(remote: u64) -> Result<(), SchemaMismatchError> {
+    let expected = fingerprint(L::fields());
+    if expected == remote {
+        Ok(())
+    } else {
+        Err(SchemaMismatchError { expected, remote })
+    }
+}
+
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+#[error("Config server schema mismatch: expected {expected:016x}, got {remote:016x}")]
+#[cfg_attr(
+    feature = "miette",
+    diagnostic(help("the config server serves data for a different version of the config"))
+)]
+pub struct SchemaMismatchError {
+    expected: u64,
+    remote: u64,
+}
+
+/// Remote source, e.g. a config server, which serves documents for the schema fingerprint of
+/// the layer. Implementations do the transport, e.g. HTTP or etcd, while
+/// [`crate::builder::ConfigBuilder::with_remote`] checks the schema of the response.
+#[cfg(feature = "serde")]
+pub trait RemoteSource {
+    /// Fetch the document for the schema with `fingerprint`, see [`fingerprint`]
+    fn fetch(&self, fingerprint: u64) -> Result<RemoteDocument, crate::Report>;
+}
+
+/// Document served by a [`RemoteSource`]
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone)]
+pub struct RemoteDocument {
+    pub format: Format,
+    pub contents: String,
+    /// Fingerprint of the schema the document was produced for, as reported by the source
+    pub fingerprint: u64,
+}
+
+/// FNV-1a, which is stable unlike `std::hash::DefaultHasher`
+pub(crate) struct Fnv64(u64);
+
+impl Fnv64 {
+    pub(crate) fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.0
+    }
+}
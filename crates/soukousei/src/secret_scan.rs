@@ -0,0 +1,364 @@
+//! Detection of literal secrets in config files, see [`SecretScan`].
+//!
+//! Secrets such as cloud credentials and private keys end up in version control and backups
+//! once they are written into config files. With a scan enabled, such values are rejected, so
+//! that they are provided with ENV, `#[layer(sensitive_file)]` or a secret manager instead:
+//!
+//! ```ignore
+//! let config = ConfigBuilder::<ConfigLayer>::new()
+//!     .with_secret_scan(SecretScan::new())
+//!     .with_file("config.toml")?
+//!     .build()?;
+//! ```
+//!
+//! Only string values from file-like sources are scanned, and findings point to their exact
+//! location in the file, which is taken from the span of the value while it is parsed.
+
+use crate::source::Format;
+#[cfg(feature = "miette")]
+use miette::Diagnostic;
+use regex::Regex;
+use serde::de::{DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use std::cell::Cell;
+use std::fmt::Formatter;
+#[cfg(feature = "json")]
+use std::io;
+use std::ops::Range;
+use thiserror::Error;
+
+/// Struct which TOML deserializers expose the span of a value as, see `toml::Spanned`
+const TOML_SPANNED: &str = "$__serde_spanned_private_Spanned";
+const TOML_SPANNED_START: &str = "$__serde_spanned_private_start";
+const TOML_SPANNED_FIELDS: [&str; 3] = [
+    TOML_SPANNED_START,
+    "$__serde_spanned_private_end",
+    "$__serde_spanned_private_value",
+];
+
+/// Patterns of literal secrets, and whether a finding is an error or a warning
+#[derive(Debug, Clone)]
+pub struct SecretScan {
+    patterns: Vec<(String, Regex)>,
+    deny: bool,
+}
+
+impl SecretScan {
+    /// Scan for AWS access keys, GitHub tokens and private keys, denying findings
+    pub fn new() -> Self {
+        Self::empty()
+            .with_builtin("AWS access key", r"\b(AKIA|ASIA)[0-9A-Z]{16}\b")
+            .with_builtin("GitHub token", r"\bgh[pousr]_[A-Za-z0-9]{36}\b")
+            .with_builtin("private key", r"-----BEGIN [A-Z ]*PRIVATE KEY-----")
+    }
+
+    /// Scan without any patterns, to add only custom ones
+    pub fn empty() -> Self {
+        Self {
+            patterns: Vec::new(),
+            deny: true,
+        }
+    }
+
+    fn with_builtin(self, name: &str, pattern: &str) -> Self {
+        self.with_pattern(name, pattern)
+            .expect("built-in patterns are valid")
+    }
+
+    /// Add a pattern, e.g. of internal API tokens. `name` is used in diagnostics.
+    pub fn with_pattern(
+        mut self,
+        name: impl Into<String>,
+        pattern: &str,
+    ) -> Result<Self, regex::Error> {
+        self.patterns.push((name.into(), Regex::new(pattern)?));
+        Ok(self)
+    }
+
+    /// Record findings as [`crate::builder::BuildWarning::LiteralSecret`] instead of failing
+    pub fn warn_only(mut self) -> Self {
+        self.deny = false;
+        self
+    }
+
+    pub fn is_deny(&self) -> bool {
+        self.deny
+    }
+
+    /// Find literal secrets in string values of `contents`. Contents which fail to parse are
+    /// not scanned, as parse errors are reported anyway.
+    pub fn scan(&self, format: Format, contents: &str) -> Vec<LiteralSecret> {
+        let Some(strings) = collect_strings(format, contents) else {
+            return Vec::new();
+        };
+
+        let mut found = Vec::new();
+        for string in strings {
+            // the value is found in its literal only if it has no escapes
+            let start = string.literal.and_then(|literal| {
+                let body = contents.get(literal.clone())?;
+                Some(literal.start + body.find(&string.value)?)
+            });
+            for (name, regex) in self.patterns.iter() {
+                for matched in regex.find_iter(&string.value) {
+                    found.push(LiteralSecret {
+                        key: string.key.clone(),
+                        pattern: name.clone(),
+                        span: start.map(|start| start + matched.start()..start + matched.end()),
+                    });
+                }
+            }
+        }
+        found
+    }
+}
+
+impl Default for SecretScan {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+#[error("`{key}` contains a literal {pattern}")]
+#[cfg_attr(
+    feature = "miette",
+    diagnostic(help("provide it with ENV, a secret file or a secret manager instead"))
+)]
+pub struct LiteralSecret {
+    key: String,
+    pattern: String,
+    span: Option<Range<usize>>,
+}
+
+impl LiteralSecret {
+    /// Dot-separated path of the value, e.g. `database.password` or `keys[1]`
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Name of the matched pattern, e.g. `private key`
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Byte range of the secret in the source. Might be missing if the value is escaped.
+    pub fn span(&self) -> Option<Range<usize>> {
+        self.span.clone()
+    }
+
+    /// 1-based line and column of the secret in `contents`
+    pub fn line_column(&self, contents: &str) -> Option<(usize, usize)> {
+        let start = self.span.as_ref()?.start;
+        let before = contents.get(..start)?;
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |x| x + 1) + 1;
+        Some((line, column))
+    }
+}
+
+/// String value of a document
+struct StringValue {
+    key: String,
+    value: String,
+    /// Byte range of the literal, including quotes and escapes
+    literal: Option<Range<usize>>,
+}
+
+/// All string values of `contents` with their paths, or `None` if it fails to parse
+#[cfg_attr(
+    not(any(feature = "toml", feature = "json")),
+    allow(unused_variables, unused_mut, unreachable_code)
+)]
+fn collect_strings(format: Format, contents: &str) -> Option<Vec<StringValue>> {
+    let mut out = Vec::new();
+    let mut collector = Collector {
+        path: String::new(),
+        out: &mut out,
+        contents,
+        spanned: false,
+        read: None,
+        literal: None,
+    };
+    match format {
+        #[cfg(feature = "toml")]
+        Format::Toml => {
+            collector.spanned = true;
+            toml::Deserializer::new(contents)
+                .deserialize_any(collector)
+                .ok()?;
+        }
+        #[cfg(feature = "json")]
+        Format::Json => {
+            let read = Cell::new(0);
+            collector.read = Some(&read);
+            let reader = CountingReader {
+                remaining: contents.as_bytes(),
+                read: &read,
+            };
+            let mut de = serde_json::Deserializer::from_reader(reader);
+            collector.deserialize(&mut de).ok()?;
+            de.end().ok()?;
+        }
+    }
+    Some(out)
+}
+
+/// Reader which counts the bytes read by the JSON deserializer, which reads them one by one
+#[cfg(feature = "json")]
+struct CountingReader<'a> {
+    remaining: &'a [u8],
+    read: &'a Cell<usize>,
+}
+
+#[cfg(feature = "json")]
+impl io::Read for CountingReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.remaining.read(buf)?;
+        self.read.set(self.read.get() + len);
+        Ok(len)
+    }
+}
+
+/// Byte range of the JSON string literal which ends right before `end`
+fn json_literal(contents: &str, end: usize) -> Option<Range<usize>> {
+    let bytes = contents.as_bytes();
+    if end < 2 || bytes.get(end - 1) != Some(&b'"') {
+        return None;
+    }
+    // the opening quote is the first one before which there is an even number of backslashes
+    let mut quote = end - 1;
+    loop {
+        quote = bytes[..quote].iter().rposition(|&x| x == b'"')?;
+        let backslashes = bytes[..quote]
+            .iter()
+            .rev()
+            .take_while(|&&x| x == b'\\')
+            .count();
+        if backslashes % 2 == 0 {
+            return Some(quote..end);
+        }
+    }
+}
+
+struct Collector<'a> {
+    path: String,
+    out: &'a mut Vec<StringValue>,
+    contents: &'a str,
+    /// Whether values are deserialized as TOML spanned values
+    spanned: bool,
+    /// Number of bytes read by the JSON deserializer
+    read: Option<&'a Cell<usize>>,
+    /// Literal of the value, if it is known beforehand
+    literal: Option<Range<usize>>,
+}
+
+impl<'a> Collector<'a> {
+    fn child(&mut self, path: String) -> Collector<'_> {
+        Collector {
+            path,
+            out: &mut *self.out,
+            contents: self.contents,
+            spanned: self.spanned,
+            read: self.read,
+            literal: None,
+        }
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for Collector<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        // values without a span, e.g. implicit tables, are deserialized as any value
+        if self.spanned && self.literal.is_none() {
+            deserializer.deserialize_struct(TOML_SPANNED, &TOML_SPANNED_FIELDS, self)
+        } else {
+            deserializer.deserialize_any(self)
+        }
+    }
+}
+
+impl<'de> Visitor<'de> for Collector<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+        f.write_str("any value")
+    }
+
+    fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<(), E> {
+        let literal = self.literal.or_else(|| {
+            let read = self.read?;
+            json_literal(self.contents, read.get())
+        });
+        self.out.push(StringValue {
+            key: self.path,
+            value: value.to_owned(),
+            literal,
+        });
+        Ok(())
+    }
+
+    fn visit_bool<E: serde::de::Error>(self, _value: bool) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_i64<E: serde::de::Error>(self, _value: i64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_u64<E: serde::de::Error>(self, _value: u64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_f64<E: serde::de::Error>(self, _value: f64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_unit<E: serde::de::Error>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_none<E: serde::de::Error>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        self.deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+        let mut index = 0;
+        loop {
+            let item = self.child(format!("{}[{index}]", self.path));
+            if seq.next_element_seed(item)?.is_none() {
+                return Ok(());
+            }
+            index += 1;
+        }
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<(), A::Error> {
+        let mut key = map.next_key::<String>()?;
+        if key.as_deref() == Some(TOML_SPANNED_START) {
+            let start: usize = map.next_value()?;
+            map.next_key::<String>()?;
+            let end: usize = map.next_value()?;
+            map.next_key::<String>()?;
+            return map.next_value_seed(Collector {
+                literal: Some(start..end),
+                ..self
+            });
+        }
+        while let Some(name) = key {
+            let path = if self.path.is_empty() {
+                name
+            } else {
+                format!("{}.{name}", self.path)
+            };
+            map.next_value_seed(self.child(path))?;
+            key = map.next_key()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+//! Support of `#[layer(sensitive_file)]` fields.
+//!
+//! Such a field might be set either directly, or with a path to a file with its contents, e.g.
+//! a Docker secret:
+//!
+//! ```ignore
+//! #[derive(Layer)]
+//! struct Database {
+//!     /// Set as `password = "..."` or `password_file = "/run/secrets/db"`
+//!     #[layer(env = "DB_PASSWORD", sensitive_file)]
+//!     password: String,
+//! }
+//! ```
+//!
+//! The path might also be provided with `DB_PASSWORD_FILE` ENV var.
+
+use crate::Report;
+#[cfg(feature = "miette")]
+use miette::Diagnostic;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// A type that might be read from a file
+pub trait FromFileContents: Sized {
+    fn from_contents(path: &Path, contents: Vec<u8>) -> Result<Self, Report>;
+}
+
+/// Contents as is
+impl FromFileContents for Vec<u8> {
+    fn from_contents(_path: &Path, contents: Vec<u8>) -> Result<Self, Report> {
+        Ok(contents)
+    }
+}
+
+/// UTF-8 contents without a trailing newline, which is usually added by editors and `echo`
+impl FromFileContents for String {
+    fn from_contents(path: &Path, contents: Vec<u8>) -> Result<Self, Report> {
+        let mut contents =
+            String::from_utf8(contents).map_err(|_| SensitiveFileError::NotUnicode {
+                path: path.to_owned(),
+            })?;
+        if contents.ends_with('\n') {
+            contents.pop();
+            if contents.ends_with('\r') {
+                contents.pop();
+            }
+        }
+        Ok(contents)
+    }
+}
+
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+pub enum SensitiveFileError {
+    #[error("the value is set both directly and with `{file_key}`")]
+    #[cfg_attr(feature = "miette", diagnostic(help("remove one of them")))]
+    Conflict { file_key: &'static str },
+    #[error("failed to read `{}`", path.display())]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("`{}` is not a valid utf-8 file", path.display())]
+    NotUnicode { path: PathBuf },
+}
+
+/// Resolve a value set either directly or with a path to a file. Setting both is an error.
+///
+/// `file_key` is the name of the sibling field with a path, used in diagnostics.
+pub fn resolve<T: FromFileContents>(
+    value: Option<T>,
+    file: Option<PathBuf>,
+    file_key: &'static str,
+) -> Result<Option<T>, Report> {
+    match (value, file) {
+        (Some(_), Some(_)) => Err(SensitiveFileError::Conflict { file_key }.into()),
+        (value, None) => Ok(value),
+        (None, Some(path)) => {
+            let contents = std::fs::read(&path).map_err(|source| SensitiveFileError::Read {
+                path: path.clone(),
+                source,
+            })?;
+            T::from_contents(&path, contents).map(Some)
+        }
+    }
+}
@@ -0,0 +1,53 @@
+//! Read-mostly sharing of a complete config between threads.
+//!
+//! Readers take cheap snapshots with [`Shared::get`], while a reloader swaps the whole config
+//! with [`Shared::replace`]. A snapshot is never changed in place, so a request handler sees a
+//! consistent config even if it is reloaded in the middle of the request.
+
+use std::fmt::{Debug, Formatter};
+use std::sync::{Arc, RwLock};
+
+pub struct Shared<T> {
+    current: Arc<RwLock<Arc<T>>>,
+}
+
+impl<T> Shared<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            current: Arc::new(RwLock::new(Arc::new(value))),
+        }
+    }
+
+    /// Snapshot of the current config
+    pub fn get(&self) -> Arc<T> {
+        // the lock is never held while running user code, so it cannot be poisoned by a panic
+        // in the middle of an update
+        self.current
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    /// Swap the config, e.g. on reload. Returns the previous one.
+    ///
+    /// Snapshots taken before remain valid and unchanged.
+    pub fn replace(&self, value: T) -> Arc<T> {
+        let mut current = self.current.write().unwrap_or_else(|err| err.into_inner());
+        std::mem::replace(&mut *current, Arc::new(value))
+    }
+}
+
+/// Clones share the same config
+impl<T> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        Self {
+            current: self.current.clone(),
+        }
+    }
+}
+
+impl<T: Debug> Debug for Shared<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Shared").field(&self.get()).finish()
+    }
+}
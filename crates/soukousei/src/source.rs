@@ -0,0 +1,421 @@
+//! File formats that layers can be deserialized from.
+
+use crate::limits::LimitCheck;
+#[cfg(any(feature = "toml", feature = "json"))]
+use crate::limits::Limited;
+use crate::normalize::KeyNormalizer;
+#[cfg(any(feature = "toml", feature = "json"))]
+use crate::normalize::Normalized;
+#[cfg(feature = "miette")]
+use miette::Diagnostic;
+#[cfg(feature = "toml")]
+use serde::de::IgnoredAny;
+use serde::de::{self, DeserializeOwned, Visitor};
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::fmt;
+#[cfg(feature = "toml")]
+use std::marker::PhantomData;
+use std::ops::Range;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    #[cfg(feature = "toml")]
+    Toml,
+    #[cfg(feature = "json")]
+    Json,
+}
+
+impl Format {
+    /// Guess the format by the file extension
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        match path.as_ref().extension()?.to_str()? {
+            #[cfg(feature = "toml")]
+            "toml" => Some(Self::Toml),
+            #[cfg(feature = "json")]
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+
+    pub fn parse<T: DeserializeOwned>(self, contents: &str) -> Result<Parsed<T>, ParseError> {
+        self.parse_with(contents, None)
+    }
+
+    /// Same as [`Self::parse`], but keys are mapped onto field names with `normalizer`, see
+    /// [`crate::normalize`]. Unknown keys are reported normalized.
+    // without a format there are no variants to parse with
+    #[cfg_attr(
+        not(any(feature = "toml", feature = "json")),
+        allow(unused_variables, unreachable_code)
+    )]
+    pub fn parse_with<T: DeserializeOwned>(
+        self,
+        contents: &str,
+        normalizer: Option<&dyn KeyNormalizer>,
+    ) -> Result<Parsed<T>, ParseError> {
+        self.parse_limited(contents, normalizer, None)
+    }
+
+    /// Same as [`Self::parse_with`], but stops at the first value exceeding the limits of
+    /// `limits`, which keeps the violation
+    #[cfg_attr(
+        not(any(feature = "toml", feature = "json")),
+        allow(unused_variables, unreachable_code)
+    )]
+    pub(crate) fn parse_limited<T: DeserializeOwned>(
+        self,
+        contents: &str,
+        normalizer: Option<&dyn KeyNormalizer>,
+        limits: Option<&LimitCheck>,
+    ) -> Result<Parsed<T>, ParseError> {
+        let mut unknown_keys = Vec::new();
+        let on_unknown = |path: serde_ignored::Path<'_>| unknown_keys.push(path.to_string());
+
+        let value = match self {
+            #[cfg(feature = "toml")]
+            Self::Toml => {
+                let de = toml::Deserializer::new(contents);
+                deserialize(de, normalizer, limits, on_unknown).map_err(|err| {
+                    // the document is valid, but doesn't match the layer
+                    let decode = IgnoredAny::deserialize(toml::Deserializer::new(contents)).is_ok();
+                    ParseError {
+                        message: err.message().to_owned(),
+                        span: err.span(),
+                        kind: if decode {
+                            ParseErrorKind::Decode {
+                                field: err.span().and_then(|x| toml_key_at(contents, x.start)),
+                            }
+                        } else {
+                            ParseErrorKind::Syntax
+                        },
+                    }
+                })?
+            }
+            #[cfg(feature = "json")]
+            Self::Json => {
+                let mut de = serde_json::Deserializer::from_str(contents);
+                let parse_error = |err: serde_json::Error| {
+                    let offset = offset_of(contents, err.line(), err.column());
+                    ParseError {
+                        message: err.to_string(),
+                        span: offset.map(|x| x..x),
+                        kind: match err.classify() {
+                            serde_json::error::Category::Data => ParseErrorKind::Decode {
+                                field: offset.and_then(|x| json_key_at(contents, x)),
+                            },
+                            _ => ParseErrorKind::Syntax,
+                        },
+                    }
+                };
+                let value =
+                    deserialize(&mut de, normalizer, limits, on_unknown).map_err(parse_error)?;
+                de.end().map_err(parse_error)?;
+                value
+            }
+        };
+
+        Ok(Parsed {
+            value,
+            unknown_keys,
+        })
+    }
+}
+
+/// Deserialize `T` from `de` with the keys normalized, then checked against the limits
+#[cfg(any(feature = "toml", feature = "json"))]
+fn deserialize<'de, D, T>(
+    de: D,
+    normalizer: Option<&dyn KeyNormalizer>,
+    limits: Option<&LimitCheck>,
+    on_unknown: impl FnMut(serde_ignored::Path<'_>),
+) -> Result<T, D::Error>
+where
+    D: de::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    fn limited<'de, D, T>(
+        de: D,
+        limits: Option<&LimitCheck>,
+        on_unknown: impl FnMut(serde_ignored::Path<'_>),
+    ) -> Result<T, D::Error>
+    where
+        D: de::Deserializer<'de>,
+        T: Deserialize<'de>,
+    {
+        match limits {
+            Some(check) => serde_ignored::deserialize(Limited::new(de, check), on_unknown),
+            None => serde_ignored::deserialize(de, on_unknown),
+        }
+    }
+
+    match normalizer {
+        Some(normalizer) => limited(Normalized::new(de, normalizer), limits, on_unknown),
+        None => limited(de, limits, on_unknown),
+    }
+}
+
+/// Successfully parsed value with keys that were ignored during deserialization
+#[derive(Debug)]
+pub struct Parsed<T> {
+    pub value: T,
+    /// Dot-separated paths of unknown keys
+    pub unknown_keys: Vec<String>,
+}
+
+#[derive(Debug, Error)]
+#[error("{message}")]
+pub struct ParseError {
+    message: String,
+    /// Byte range in the source string
+    span: Option<Range<usize>>,
+    kind: ParseErrorKind,
+}
+
+impl ParseError {
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn span(&self) -> Option<Range<usize>> {
+        self.span.clone()
+    }
+
+    pub fn kind(&self) -> &ParseErrorKind {
+        &self.kind
+    }
+
+    /// Attach the name of the source, e.g. a file path
+    pub fn into_source_error(self, path: impl Into<String>) -> SourceError {
+        let path = path.into();
+        let Self {
+            message,
+            span,
+            kind,
+        } = self;
+        match kind {
+            ParseErrorKind::Syntax => SourceError::Parse {
+                path,
+                message,
+                span,
+            },
+            ParseErrorKind::Decode { field } => SourceError::Decode {
+                path,
+                field,
+                message,
+                span,
+            },
+        }
+    }
+}
+
+/// Whether contents are invalid in their format or don't match the layer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// The contents are not valid in the format, e.g. an unclosed string
+    Syntax,
+    /// The contents are valid, but don't match the layer, e.g. a string in a number field.
+    /// `field` is a dot-separated key of the value, if it can be told by the error location.
+    Decode { field: Option<String> },
+}
+
+/// Typed error of a config source, so that callers might react to it, e.g. create a missing
+/// file. `path` is the name of the source, e.g. a file path.
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+pub enum SourceError {
+    /// The source can't be read, e.g. the file is missing
+    #[error("Failed to read `{path}`")]
+    Io {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+    /// See [`ParseErrorKind::Syntax`]
+    #[error("Failed to parse `{path}`: {message}")]
+    Parse {
+        path: String,
+        message: String,
+        /// Byte range in the source string
+        span: Option<Range<usize>>,
+    },
+    /// See [`ParseErrorKind::Decode`]
+    #[error("Failed to decode `{path}`{}: {message}", at_field(.field))]
+    Decode {
+        path: String,
+        field: Option<String>,
+        message: String,
+        /// Byte range in the source string
+        span: Option<Range<usize>>,
+    },
+}
+
+impl SourceError {
+    pub fn path(&self) -> &str {
+        match self {
+            Self::Io { path, .. } | Self::Parse { path, .. } | Self::Decode { path, .. } => path,
+        }
+    }
+
+    /// Byte range in the source string
+    pub fn span(&self) -> Option<Range<usize>> {
+        match self {
+            Self::Io { .. } => None,
+            Self::Parse { span, .. } | Self::Decode { span, .. } => span.clone(),
+        }
+    }
+}
+
+fn at_field(field: &Option<String>) -> String {
+    field
+        .as_ref()
+        .map(|field| format!(" at `{field}`"))
+        .unwrap_or_default()
+}
+
+/// Deserialize an optional field with a default as `Option<Option<T>>`: an explicit null
+/// becomes `Some(None)` and overrides the default, while an absent key stays `None` (with
+/// `#[serde(default)]`). Used by the derive.
+pub fn deserialize_nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Deserialize an optional string, borrowing it from the source when the format allows it,
+/// e.g. unescaped JSON strings. `Cow<str>` itself always deserializes to `Cow::Owned`. Used by
+/// the derive for `#[layer(borrow)]`.
+pub fn deserialize_borrowed<'de, D>(deserializer: D) -> Result<Option<Cow<'de, str>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    struct CowStr;
+
+    impl<'de> Visitor<'de> for CowStr {
+        type Value = Cow<'de, str>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a string")
+        }
+
+        fn visit_borrowed_str<E: de::Error>(self, value: &'de str) -> Result<Self::Value, E> {
+            Ok(Cow::Borrowed(value))
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+            Ok(Cow::Owned(value.to_owned()))
+        }
+
+        fn visit_string<E: de::Error>(self, value: String) -> Result<Self::Value, E> {
+            Ok(Cow::Owned(value))
+        }
+    }
+
+    struct OptionCowStr;
+
+    impl<'de> Visitor<'de> for OptionCowStr {
+        type Value = Option<Cow<'de, str>>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("an optional string")
+        }
+
+        fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            deserializer.deserialize_str(CowStr).map(Some)
+        }
+    }
+
+    deserializer.deserialize_option(OptionCowStr)
+}
+
+/// TOML config embedded into the binary, with keys checked against the layer `L` at compile
+/// time. Created with [`crate::include_config`].
+#[cfg(feature = "toml")]
+#[derive(Debug, Clone, Copy)]
+pub struct Embedded<L> {
+    name: &'static str,
+    contents: &'static str,
+    layer: PhantomData<fn() -> L>,
+}
+
+#[cfg(feature = "toml")]
+impl<L> Embedded<L> {
+    /// Used by [`crate::include_config`], which performs the checks
+    #[doc(hidden)]
+    pub const fn new(name: &'static str, contents: &'static str) -> Self {
+        Self {
+            name,
+            contents,
+            layer: PhantomData,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn contents(&self) -> &'static str {
+        self.contents
+    }
+}
+
+/// Convert 1-based line and column into a byte offset
+#[cfg(feature = "json")]
+fn offset_of(contents: &str, line: usize, column: usize) -> Option<usize> {
+    let line_start: usize = contents
+        .split_inclusive('\n')
+        .take(line.checked_sub(1)?)
+        .map(str::len)
+        .sum();
+    Some((line_start + column.saturating_sub(1)).min(contents.len()))
+}
+
+/// Dot-separated key of the TOML value at `offset`, e.g. `db.port` for `port = 80` under
+/// `[db]`. It is a guess by the line of the value, so keys of multiline values are unknown.
+#[cfg(feature = "toml")]
+fn toml_key_at(contents: &str, offset: usize) -> Option<String> {
+    let before = contents.get(..offset)?;
+    let line_start = before.rfind('\n').map_or(0, |x| x + 1);
+    let key = before[line_start..]
+        .split_once('=')?
+        .0
+        .trim()
+        .trim_matches('"');
+    if key.is_empty() {
+        return None;
+    }
+    let section = before[..line_start]
+        .lines()
+        .rev()
+        .map(str::trim)
+        .find(|line| line.starts_with('['))
+        .map(|line| line.trim_matches(|c| c == '[' || c == ']').trim());
+    match section {
+        Some(section) if !section.is_empty() => Some(format!("{section}.{key}")),
+        _ => Some(key.to_owned()),
+    }
+}
+
+/// Key of the JSON value at `offset`, i.e. the closest `"key":` before it. Unlike
+/// [`toml_key_at`], it doesn't include the keys of enclosing objects.
+#[cfg(feature = "json")]
+fn json_key_at(contents: &str, offset: usize) -> Option<String> {
+    let before = contents.get(..offset)?;
+    let key = before[..before.rfind(':')?].trim_end().strip_suffix('"')?;
+    Some(key[key.rfind('"')? + 1..].to_owned())
+}
@@ -0,0 +1,91 @@
+//! Property testing of layers with `proptest`, enabled with the `proptest` feature.
+//!
+//! [`layer`] generates layers of a derived type, where each field is independently set or
+//! not, recursively in nested sections. Set fields hold placeholders of [`crate::fixture`].
+//! Shrinking unsets fields, so a failure is reported with a minimal layer:
+//!
+//! ```ignore
+//! proptest! {
+//!     #[test]
+//!     fn completes_with_defaults(layer in strategy::layer::<ConfigLayer>()) {
+//!         ConfigLayer::default().merge(layer).complete().unwrap();
+//!     }
+//! }
+//! ```
+//!
+//! Custom layer impls should obey the same laws as derived ones, which [`assert_merge_laws`]
+//! checks given a strategy of the layer.
+
+use crate::fixture;
+use crate::Layer;
+use proptest::prelude::*;
+use proptest::test_runner::{TestCaseError, TestRunner};
+use serde::de::DeserializeOwned;
+use std::fmt::Debug;
+
+/// Layers with each field independently set to a placeholder or not, see the [module](self)
+/// docs. Panics if the layer fails to deserialize from the placeholders, like
+/// `Layer::arbitrary`.
+pub fn layer<L>() -> BoxedStrategy<L>
+where
+    L: Layer + DeserializeOwned + Debug + 'static,
+{
+    let mut paths = Vec::new();
+    fixture::sparse_placeholders(L::FIELDS, 0, &mut |path| {
+        paths.push(path.to_owned());
+        true
+    });
+    (
+        any::<u64>(),
+        proptest::collection::vec(any::<bool>(), paths.len()),
+    )
+        .prop_map(move |(seed, present)| {
+            fixture::sparse_placeholders(L::FIELDS, seed, &mut |path| {
+                paths.iter().zip(&present).any(|(x, set)| *set && x == path)
+            })
+            .deserialize_into()
+            .unwrap_or_else(|err| panic!("failed to fill the layer with placeholders: {err}"))
+        })
+        .boxed()
+}
+
+/// Check that `new` is an identity of merge on both sides, and that merge is associative:
+///
+/// - `new().merge(a) == a`
+/// - `a.merge(new()) == a`
+/// - `a.merge(b).merge(c) == a.merge(b.merge(c))`
+///
+/// Meant to be called inside `proptest!`, see [`assert_merge_laws`] for a standalone test.
+pub fn check_merge_laws<L>(a: L, b: L, c: L) -> Result<(), TestCaseError>
+where
+    L: Layer + Clone + PartialEq + Debug,
+{
+    prop_assert_eq!(L::new().merge(a.clone()), a.clone(), "left identity");
+    prop_assert_eq!(a.clone().merge(L::new()), a.clone(), "right identity");
+    prop_assert_eq!(
+        a.clone().merge(b.clone()).merge(c.clone()),
+        a.merge(b.merge(c)),
+        "associativity"
+    );
+    Ok(())
+}
+
+/// Run [`check_merge_laws`] on layers generated by `strategy`, e.g. [`layer`] or a strategy of
+/// a custom layer, panicking with a minimal counterexample on failure:
+///
+/// ```ignore
+/// #[test]
+/// fn merge_laws() {
+///     strategy::assert_merge_laws(strategy::layer::<ConfigLayer>());
+/// }
+/// ```
+pub fn assert_merge_laws<L, S>(strategy: S)
+where
+    L: Layer + Clone + PartialEq + Debug,
+    S: Strategy<Value = L> + Clone,
+{
+    let layers = (strategy.clone(), strategy.clone(), strategy);
+    if let Err(err) = TestRunner::default().run(&layers, |(a, b, c)| check_merge_laws(a, b, c)) {
+        panic!("merge laws do not hold: {err}");
+    }
+}
@@ -0,0 +1,88 @@
+//! Anonymized exports of configs, which users can safely attach to bug reports.
+//!
+//! A bundle contains the merged config, where secret fields are redacted and strings are
+//! replaced with their hashes, so that equal values can still be told apart. Numbers and
+//! booleans are kept as is. It also contains where each field comes from and build warnings:
+//!
+//! ```json
+//! {
+//!   "config": { "port": 8080, "host": "<hash:a2f5c1b0e3d4f607>", "token": "<redacted>" },
+//!   "provenance": { "port": "defaults", "host": "config.toml", "token": "env" },
+//!   "warnings": ["ENV var `APP_PROT` matches no field"]
+//! }
+//! ```
+//!
+//! The hash is not cryptographic, so a guessable string might be recovered by trying
+//! candidates. Mark such fields with `#[layer(secret)]`.
+
+use crate::builder::BuildWarning;
+use crate::meta::{self, FieldMeta};
+use crate::provenance::Provenance;
+use crate::schema::Fnv64;
+use crate::Layer;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+const REDACTED: &str = "<redacted>";
+
+/// Build a support bundle of a merged `layer`, see the [module](self) docs
+pub fn support_bundle<L: Layer + Serialize>(
+    layer: &L,
+    provenance: &Provenance,
+    warnings: &[BuildWarning],
+) -> Result<Value, serde_json::Error> {
+    let config = anonymize(serde_json::to_value(layer)?, Some(L::FIELDS));
+    let provenance = provenance
+        .iter()
+        .map(|(path, source)| (path.to_owned(), Value::from(source)))
+        .collect::<Map<_, _>>();
+    let warnings = warnings
+        .iter()
+        .map(|x| Value::from(x.to_string()))
+        .collect();
+
+    let mut bundle = Map::new();
+    bundle.insert("config".to_owned(), config);
+    bundle.insert("provenance".to_owned(), Value::Object(provenance));
+    bundle.insert("warnings".to_owned(), Value::Array(warnings));
+    Ok(Value::Object(bundle))
+}
+
+fn anonymize(value: Value, fields: Option<&[FieldMeta]>) -> Value {
+    match value {
+        Value::String(x) => Value::String(hash(&x)),
+        Value::Array(items) => items.into_iter().map(|x| anonymize(x, fields)).collect(),
+        Value::Object(entries) => entries
+            .into_iter()
+            .map(|(key, value)| {
+                let field = fields.and_then(|x| x.iter().find(|x| x.name == key));
+                let value = match field {
+                    Some(_) if value.is_null() => value,
+                    Some(field) if field.secret => Value::from(REDACTED),
+                    Some(field) if is_map(field.ty) => match value {
+                        Value::Object(entries) => entries
+                            .into_iter()
+                            .map(|(key, value)| (key, anonymize(value, field.nested)))
+                            .collect(),
+                        value => anonymize(value, None),
+                    },
+                    Some(field) => anonymize(value, field.nested),
+                    None => anonymize(value, None),
+                };
+                (key, value)
+            })
+            .collect(),
+        value => value,
+    }
+}
+
+fn hash(value: &str) -> String {
+    let mut hasher = Fnv64::new();
+    hasher.write(value.as_bytes());
+    format!("<hash:{:016x}>", hasher.finish())
+}
+
+/// Map sections are keyed by arbitrary names instead of fields
+fn is_map(ty: &str) -> bool {
+    matches!(meta::type_base_name(ty), "BTreeMap" | "HashMap")
+}
@@ -0,0 +1,35 @@
+//! Exporting the effective config as telemetry attributes, e.g. OpenTelemetry resource
+//! attributes.
+//!
+//! Fields are opted in with `#[layer(telemetry)]` and rendered with `Display`. Secret fields
+//! cannot be exported. On a nested field, the attribute exports the opted in fields of the
+//! section, prefixed with its name:
+//!
+//! ```ignore
+//! #[derive(Layer)]
+//! struct Config {
+//!     #[layer(telemetry)]
+//!     region: String,
+//!     #[layer(nested, telemetry)]
+//!     db: Database,
+//! }
+//!
+//! // {"region": "eu-1", "db.pool_size": "16"}
+//! let attributes = config.telemetry_attributes();
+//! let resource = Resource::new(attributes.into_iter().map(|(k, v)| KeyValue::new(k, v)));
+//! ```
+
+use std::collections::HashMap;
+
+/// Implemented by the derive for complete structs
+pub trait Telemetry {
+    /// Insert attributes with names prefixed with `prefix` into `out`
+    fn telemetry_attributes_into(&self, prefix: &str, out: &mut HashMap<String, String>);
+
+    /// Attributes by dot-separated field paths
+    fn telemetry_attributes(&self) -> HashMap<String, String> {
+        let mut out = HashMap::new();
+        self.telemetry_attributes_into("", &mut out);
+        out
+    }
+}
@@ -0,0 +1,141 @@
+//! Helpers for tests that build and compare layers.
+//!
+//! Layers derive `Clone` and `PartialEq` on request, with `#[layer(derive(Clone, PartialEq))]`.
+//! Nested layers should derive them too.
+
+use crate::Layer;
+#[cfg(feature = "toml")]
+use crate::{CompleteError, CompleteErrorDiagnostic, CompleteFieldError};
+#[cfg(all(feature = "toml", feature = "miette"))]
+use miette::Diagnostic;
+use std::fmt::Debug;
+#[cfg(feature = "toml")]
+use thiserror::Error;
+
+/// Assert that two layers are equal, like `assert_eq!`, but also list the fields which are
+/// provided by only one of them on failure.
+///
+/// ```ignore
+/// #[derive(Layer)]
+/// #[layer(derive(Debug, PartialEq))]
+/// struct Server {
+///     port: u16,
+/// }
+///
+/// assert_layers_eq!(ServerLayer::from_env(&env)?, ServerLayer { port: Some(8080) });
+/// ```
+#[macro_export]
+macro_rules! assert_layers_eq {
+    ($left:expr, $right:expr $(,)?) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if !(*left == *right) {
+                    ::core::panic!("{}", $crate::testing::describe_mismatch(left, right))
+                }
+            }
+        }
+    };
+}
+
+/// Construct a layer of the config type with only the given fields set, e.g.
+/// `layer!(Sample { port: 8080u16, db.host: "localhost" })`.
+///
+/// Nested fields are set by dot-separated paths, and values are converted with `Into`.
+///
+/// Call it by path, i.e. `soukousei::layer!`, since an imported `layer` is ambiguous with the
+/// `#[layer(...)]` attribute of the derive.
+#[macro_export]
+macro_rules! layer {
+    ($ty:ty { $($($field:ident).+ : $value:expr),* $(,)? }) => {{
+        #[allow(unused_mut)]
+        let mut layer = <<$ty as $crate::HasLayer>::Layer as $crate::Layer>::new();
+        $(
+            layer.$($field).+ = ::core::option::Option::Some(::core::convert::Into::into($value));
+        )*
+        layer
+    }};
+}
+
+#[doc(hidden)]
+pub fn describe_mismatch<L: Layer + Debug>(left: &L, right: &L) -> String {
+    let left_fields = left.provided_fields();
+    let right_fields = right.provided_fields();
+
+    let mut message = String::from("assertion failed: layers are not equal");
+    for (side, fields, other) in [
+        ("left", &left_fields, &right_fields),
+        ("right", &right_fields, &left_fields),
+    ] {
+        let only: Vec<_> = fields
+            .iter()
+            .filter(|x| !other.contains(x))
+            .map(String::as_str)
+            .collect();
+        if !only.is_empty() {
+            message.push_str(&format!("\n  only {side} provides: {}", only.join(", ")));
+        }
+    }
+    message.push_str(&format!("\n  left: {left:#?}\n right: {right:#?}"));
+    message
+}
+
+/// Check that the default layer survives `Default → serialize → deserialize → merge → complete`
+/// unchanged, which catches serde attributes that don't match field types and defaults which
+/// fail validation, e.g. `range`. Meant to be called in a test of the config:
+///
+/// ```ignore
+/// #[test]
+/// fn config_roundtrip() {
+///     ConfigLayer::verify_roundtrip().unwrap();
+/// }
+/// ```
+///
+/// Fields without defaults are missing after completion, which is not an error.
+#[cfg(feature = "toml")]
+pub fn verify_roundtrip<L>() -> Result<(), RoundtripError>
+where
+    L: Layer + Default + serde::Serialize + serde::de::DeserializeOwned,
+{
+    let before = toml::Value::try_from(L::default()).map_err(RoundtripError::Serialize)?;
+    let layer: L = before
+        .clone()
+        .try_into()
+        .map_err(RoundtripError::Deserialize)?;
+    let layer = layer.merge(L::new());
+
+    let after = toml::Value::try_from(&layer).map_err(RoundtripError::Serialize)?;
+    if after != before {
+        return Err(RoundtripError::Mismatch {
+            before: before.to_string(),
+            after: after.to_string(),
+        });
+    }
+
+    match layer.complete() {
+        Ok(_) => Ok(()),
+        Err(CompleteError::Fields(errors))
+            if errors
+                .iter()
+                .all(|x| matches!(x.value(), CompleteFieldError::Missing(_))) =>
+        {
+            Ok(())
+        }
+        Err(err) => Err(RoundtripError::Complete(err.into())),
+    }
+}
+
+/// See [`verify_roundtrip`]
+#[cfg(feature = "toml")]
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+pub enum RoundtripError {
+    #[error("Failed to serialize the default layer")]
+    Serialize(#[source] toml::ser::Error),
+    #[error("Failed to deserialize the serialized default layer")]
+    Deserialize(#[source] toml::de::Error),
+    #[error("The default layer changed after a roundtrip\n  before: {before}\n   after: {after}")]
+    Mismatch { before: String, after: String },
+    #[error(transparent)]
+    #[cfg_attr(feature = "miette", diagnostic(transparent))]
+    Complete(CompleteErrorDiagnostic),
+}
@@ -0,0 +1,132 @@
+//! Sections which might be switched off, see [`Toggle`].
+//!
+//! ```ignore
+//! #[derive(Layer)]
+//! struct Config {
+//!     #[layer(toggle)]
+//!     metrics: Toggle<Metrics>,
+//! }
+//! ```
+//!
+//! ```toml
+//! [metrics]
+//! enabled = true
+//! endpoint = "http://localhost:4317"
+//! ```
+//!
+//! A section is disabled unless `enabled = true` is set explicitly. The other fields of a
+//! disabled section are neither required nor validated, while an enabled section must be
+//! complete.
+
+use crate::env::{EnvProvider, FieldFromEnvError, FromEnv};
+use crate::meta::FieldMeta;
+use crate::telemetry::Telemetry;
+use crate::{CompleteError, ErrorPolicy, HasLayer, Layer, MultipleFieldsError};
+use std::collections::HashMap;
+
+/// A section which is either disabled, or enabled with a complete config
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Toggle<T> {
+    Disabled,
+    Enabled(T),
+}
+
+impl<T> Toggle<T> {
+    pub fn is_enabled(&self) -> bool {
+        matches!(self, Self::Enabled(_))
+    }
+
+    /// Config of the section if it is enabled
+    pub fn enabled(&self) -> Option<&T> {
+        match self {
+            Self::Enabled(value) => Some(value),
+            Self::Disabled => None,
+        }
+    }
+}
+
+/// Layer of [`Toggle`]. The `enabled` key is flattened into the section.
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ToggleLayer<L> {
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub enabled: Option<bool>,
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub inner: L,
+}
+
+impl<L: Layer> Layer for ToggleLayer<L> {
+    type Complete = Toggle<L::Complete>;
+
+    fn new() -> Self {
+        Self {
+            enabled: None,
+            inner: L::new(),
+        }
+    }
+
+    fn merge(mut self, other: Self) -> Self {
+        self.merge_from(other);
+        self
+    }
+
+    fn merge_from(&mut self, other: Self) {
+        if other.enabled.is_some() {
+            self.enabled = other.enabled;
+        }
+        self.inner.merge_from(other.inner);
+    }
+
+    fn fill_defaults(&mut self) {
+        self.inner.fill_defaults()
+    }
+
+    fn complete(self) -> Result<Self::Complete, CompleteError> {
+        self.complete_with(ErrorPolicy::CollectAll)
+    }
+
+    fn complete_with(self, policy: ErrorPolicy) -> Result<Self::Complete, CompleteError> {
+        match self.enabled {
+            Some(true) => self.inner.complete_with(policy).map(Toggle::Enabled),
+            _ => Ok(Toggle::Disabled),
+        }
+    }
+
+    const FIELDS: &'static [FieldMeta] = L::FIELDS;
+
+    fn provided_fields(&self) -> Vec<String> {
+        let mut provided = self.inner.provided_fields();
+        if self.enabled.is_some() {
+            provided.push("enabled".to_owned());
+        }
+        provided
+    }
+
+    fn captured_keys(&self) -> Vec<String> {
+        self.inner.captured_keys()
+    }
+}
+
+impl<L: FromEnv> FromEnv for ToggleLayer<L> {
+    fn from_env(
+        provider: &impl EnvProvider,
+    ) -> Result<Self, MultipleFieldsError<FieldFromEnvError>> {
+        Ok(Self {
+            enabled: None,
+            inner: L::from_env(provider)?,
+        })
+    }
+}
+
+impl<T: HasLayer> HasLayer for Toggle<T> {
+    type Layer = ToggleLayer<T::Layer>;
+}
+
+/// Attributes of an enabled section
+impl<T: Telemetry> Telemetry for Toggle<T> {
+    fn telemetry_attributes_into(&self, prefix: &str, out: &mut HashMap<String, String>) {
+        if let Self::Enabled(value) = self {
+            value.telemetry_attributes_into(prefix, out)
+        }
+    }
+}
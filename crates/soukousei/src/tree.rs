@@ -0,0 +1,217 @@
+//! Rendering of complete configurations as an indented tree, e.g. for `--print-config` or
+//! startup logs.
+//!
+//! Implemented by the derive with `#[layer(render_tree)]`. Fields marked with
+//! `#[layer(secret)]` are redacted.
+//!
+//! Fields tagged with `#[layer(visibility = "...")]` are rendered only for the matching
+//! [`Audience`], so that e.g. the admin API dump includes internal tunables, while the public
+//! status page does not:
+//!
+//! ```ignore
+//! #[derive(Layer)]
+//! #[layer(render_tree)]
+//! struct Config {
+//!     port: u16,
+//!     #[layer(visibility = "admin")]
+//!     pool_size: u32,
+//! }
+//!
+//! config.dump_for(&Audience::public().without_secrets()); // port = 8080
+//! config.dump_for(&Audience::new(["admin"])); // port = 8080, pool_size = 16
+//! ```
+
+use std::fmt::Debug;
+use std::rc::Rc;
+use std::sync::Arc;
+
+const INDENT: &str = "  ";
+const REDACTED: &str = "<redacted>";
+
+pub trait RenderTree {
+    fn render_fields(&self, f: &mut TreeFormatter<'_>);
+
+    fn render_tree(&self) -> String
+    where
+        Self: Sized,
+    {
+        let mut f = TreeFormatter::new(None);
+        self.render_fields(&mut f);
+        f.finish()
+    }
+
+    /// Same as [`RenderTree::render_tree`], but each value might be annotated in brackets,
+    /// e.g. with its provenance. The callback receives a dot-separated path of a field.
+    fn render_tree_with(&self, annotate: &dyn Fn(&str) -> Option<String>) -> String
+    where
+        Self: Sized,
+    {
+        let mut f = TreeFormatter::new(Some(annotate));
+        self.render_fields(&mut f);
+        f.finish()
+    }
+
+    /// Same as [`RenderTree::render_tree`], but only with fields visible to `audience`
+    fn dump_for(&self, audience: &Audience) -> String
+    where
+        Self: Sized,
+    {
+        let mut f = TreeFormatter::new(None);
+        f.audience = Some(audience);
+        self.render_fields(&mut f);
+        f.finish()
+    }
+}
+
+/// Viewer of a dump, see [`RenderTree::dump_for`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Audience {
+    visibilities: Vec<String>,
+    secrets: bool,
+}
+
+impl Audience {
+    /// Sees only fields without `#[layer(visibility = "...")]`
+    pub fn public() -> Self {
+        Self {
+            visibilities: Vec::new(),
+            secrets: true,
+        }
+    }
+
+    /// Also sees fields with any of the `visibilities`
+    pub fn new<I, S>(visibilities: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            visibilities: visibilities.into_iter().map(Into::into).collect(),
+            secrets: true,
+        }
+    }
+
+    /// Omit secret fields instead of showing them redacted
+    pub fn without_secrets(mut self) -> Self {
+        self.secrets = false;
+        self
+    }
+
+    /// Whether a field with `#[layer(visibility = "...")]` is visible
+    pub fn reveals(&self, visibility: &str) -> bool {
+        self.visibilities.iter().any(|x| x == visibility)
+    }
+}
+
+impl<T: RenderTree> RenderTree for Box<T> {
+    fn render_fields(&self, f: &mut TreeFormatter<'_>) {
+        (**self).render_fields(f)
+    }
+}
+
+impl<T: RenderTree> RenderTree for Arc<T> {
+    fn render_fields(&self, f: &mut TreeFormatter<'_>) {
+        (**self).render_fields(f)
+    }
+}
+
+impl<T: RenderTree> RenderTree for Rc<T> {
+    fn render_fields(&self, f: &mut TreeFormatter<'_>) {
+        (**self).render_fields(f)
+    }
+}
+
+type Annotate<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+pub struct TreeFormatter<'a> {
+    out: String,
+    path: Vec<&'static str>,
+    annotate: Option<Annotate<'a>>,
+    /// Rendered values by dot-separated paths, see [`flatten`]
+    values: Option<Vec<(String, String)>>,
+    /// Everything is rendered without it, see [`RenderTree::dump_for`]
+    audience: Option<&'a Audience>,
+}
+
+impl<'a> TreeFormatter<'a> {
+    fn new(annotate: Option<Annotate<'a>>) -> Self {
+        Self {
+            out: String::new(),
+            path: Vec::new(),
+            annotate,
+            values: None,
+            audience: None,
+        }
+    }
+
+    pub fn field(&mut self, name: &'static str, value: &dyn Debug) {
+        self.line(name, &format!("{value:?}"));
+    }
+
+    pub fn secret(&mut self, name: &'static str) {
+        if self.audience.is_none_or(|x| x.secrets) {
+            self.line(name, REDACTED);
+        }
+    }
+
+    /// Whether fields with `#[layer(visibility = "...")]` should be rendered
+    pub fn reveals(&self, visibility: &str) -> bool {
+        self.audience.is_none_or(|x| x.reveals(visibility))
+    }
+
+    pub fn section(&mut self, name: &'static str, value: &dyn RenderTree) {
+        self.indent();
+        self.out.push_str(name);
+        self.out.push_str(":\n");
+
+        self.path.push(name);
+        value.render_fields(self);
+        self.path.pop();
+    }
+
+    fn line(&mut self, name: &'static str, value: &str) {
+        self.indent();
+        self.out.push_str(name);
+        self.out.push_str(" = ");
+        self.out.push_str(value);
+
+        if self.annotate.is_some() || self.values.is_some() {
+            let path = self
+                .path
+                .iter()
+                .chain(std::iter::once(&name))
+                .copied()
+                .collect::<Vec<_>>()
+                .join(".");
+            if let Some(values) = &mut self.values {
+                values.push((path.clone(), value.to_owned()));
+            }
+            if let Some(note) = self.annotate.and_then(|annotate| annotate(&path)) {
+                self.out.push_str(" [");
+                self.out.push_str(&note);
+                self.out.push(']');
+            }
+        }
+
+        self.out.push('\n');
+    }
+
+    fn indent(&mut self) {
+        for _ in 0..self.path.len() {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    fn finish(self) -> String {
+        self.out
+    }
+}
+
+/// Rendered values of all fields by their dot-separated paths, in the order of rendering.
+/// Secrets are redacted.
+pub(crate) fn flatten(value: &dyn RenderTree) -> Vec<(String, String)> {
+    let mut f = TreeFormatter::new(None);
+    f.values = Some(Vec::new());
+    value.render_fields(&mut f);
+    f.values.unwrap_or_default()
+}
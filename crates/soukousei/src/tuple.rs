@@ -0,0 +1,76 @@
+//! Layers of tuples, so that sections of different types might be merged and completed
+//! together, e.g. `ConfigBuilder::<(ServerLayer, DatabaseLayer)>` for two independent configs
+//! read from the same sources, or `#[layer(nested)]` on a `(Primary, Fallback)` field.
+//!
+//! Elements are merged pairwise and deserialized from arrays. Errors and provided fields are
+//! reported under element indices, e.g. `[1].url` and `1.url`.
+//!
+//! As metadata of elements can't be concatenated in a constant, tuples have empty
+//! [`Layer::FIELDS`], so their fields are missing from generated help and schemas.
+
+use crate::env::{EnvProvider, FieldFromEnvError, FromEnv};
+use crate::provenance;
+use crate::{
+    CompleteError, CompleteFieldError, HasLayer, Layer, MultipleFieldsError, PathSegment, ResultExt,
+};
+
+macro_rules! tuple_layer {
+    ($($ty:ident $var:ident $idx:tt),+) => {
+        impl<$($ty: Layer),+> Layer for ($($ty,)+) {
+            type Complete = ($($ty::Complete,)+);
+
+            fn new() -> Self {
+                ($($ty::new(),)+)
+            }
+
+            fn merge(mut self, other: Self) -> Self {
+                self.merge_from(other);
+                self
+            }
+
+            fn merge_from(&mut self, other: Self) {
+                $(self.$idx.merge_from(other.$idx);)+
+            }
+
+            fn fill_defaults(&mut self) {
+                $(self.$idx.fill_defaults();)+
+            }
+
+            fn complete(self) -> Result<Self::Complete, CompleteError> {
+                let errors = MultipleFieldsError::<CompleteFieldError>::new();
+                $(let ($var, errors) = self.$idx.complete().nest_if_err(errors, PathSegment::Index($idx));)+
+                match ($($var,)+) {
+                    ($(Some($var),)+) => Ok(($($var,)+)),
+                    _ => Err(CompleteError::Fields(errors)),
+                }
+            }
+
+            fn provided_fields(&self) -> Vec<String> {
+                let mut provided = Vec::new();
+                $(provided.extend(provenance::nest(stringify!($idx), self.$idx.provided_fields()));)+
+                provided
+            }
+        }
+
+        impl<$($ty: HasLayer),+> HasLayer for ($($ty,)+) {
+            type Layer = ($($ty::Layer,)+);
+        }
+
+        impl<$($ty: FromEnv),+> FromEnv for ($($ty,)+) {
+            fn from_env(
+                provider: &impl EnvProvider,
+            ) -> Result<Self, MultipleFieldsError<FieldFromEnvError>> {
+                let errors = MultipleFieldsError::<FieldFromEnvError>::new();
+                $(let ($var, errors) = errors.nest_if_err($ty::from_env(provider), PathSegment::Index($idx));)+
+                match ($($var,)+) {
+                    ($(Some($var),)+) => Ok(($($var,)+)),
+                    _ => Err(errors),
+                }
+            }
+        }
+    };
+}
+
+tuple_layer!(A a 0, B b 1);
+tuple_layer!(A a 0, B b 1, C c 2);
+tuple_layer!(A a 0, B b 1, C c 2, D d 3);
@@ -0,0 +1,257 @@
+//! Format-independent value tree, see [`Value`].
+//!
+//! Sources might be parsed into it first, so that they are inspected and merged before
+//! layers are deserialized from the result. Scalars keep their types, so that a port written
+//! as `"8080"` in one source and as `8080` in another can be told apart, and integers are
+//! stored losslessly in an `i128`, covering both `i64` and `u64`.
+
+use serde::de::value::{MapAccessDeserializer, MapDeserializer, SeqDeserializer};
+use serde::de::{
+    self, DeserializeOwned, Deserializer, IntoDeserializer, MapAccess, SeqAccess, Visitor,
+};
+use serde::{forward_to_deserialize_any, Deserialize, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+/// Key under which TOML deserializers expose datetimes
+const TOML_DATETIME_KEY: &str = "$__toml_private_datetime";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// Explicit null, e.g. in JSON
+    Null,
+    Bool(bool),
+    Integer(i128),
+    Float(f64),
+    String(String),
+    /// Datetime as written in the source, e.g. a TOML offset datetime
+    Datetime(String),
+    Array(Vec<Value>),
+    Table(BTreeMap<String, Value>),
+}
+
+impl Value {
+    /// Name of the type for diagnostics, e.g. `integer`
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Null => "null",
+            Self::Bool(_) => "boolean",
+            Self::Integer(_) => "integer",
+            Self::Float(_) => "float",
+            Self::String(_) => "string",
+            Self::Datetime(_) => "datetime",
+            Self::Array(_) => "array",
+            Self::Table(_) => "table",
+        }
+    }
+
+    /// Value at a dot-separated `path`, e.g. `db.port`
+    pub fn get(&self, path: &str) -> Option<&Value> {
+        path.split('.').try_fold(self, |value, key| match value {
+            Self::Table(table) => table.get(key),
+            _ => None,
+        })
+    }
+
+    /// Deserialize a layer or any other type from the tree
+    pub fn deserialize_into<T: DeserializeOwned>(self) -> Result<T, de::value::Error> {
+        T::deserialize(self)
+    }
+
+    fn unexpected(&self) -> de::Unexpected<'_> {
+        match self {
+            Self::Null => de::Unexpected::Unit,
+            Self::Bool(x) => de::Unexpected::Bool(*x),
+            Self::Integer(x) => match i64::try_from(*x) {
+                Ok(x) => de::Unexpected::Signed(x),
+                Err(_) => de::Unexpected::Other("a large integer"),
+            },
+            Self::Float(x) => de::Unexpected::Float(*x),
+            Self::String(x) | Self::Datetime(x) => de::Unexpected::Str(x),
+            Self::Array(_) => de::Unexpected::Seq,
+            Self::Table(_) => de::Unexpected::Map,
+        }
+    }
+}
+
+/// Renders the type along with the value, e.g. `integer 8080` or `string "8080"`
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Null => f.write_str("null"),
+            Self::Bool(x) => write!(f, "boolean {x}"),
+            Self::Integer(x) => write!(f, "integer {x}"),
+            Self::Float(x) => write!(f, "float {x:?}"),
+            Self::String(x) => write!(f, "string {x:?}"),
+            Self::Datetime(x) => write!(f, "datetime {x}"),
+            Self::Array(x) => write!(f, "array of {} items", x.len()),
+            Self::Table(x) => write!(f, "table of {} keys", x.len()),
+        }
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Null => serializer.serialize_none(),
+            Self::Bool(x) => serializer.serialize_bool(*x),
+            Self::Integer(x) => match (i64::try_from(*x), u64::try_from(*x)) {
+                (Ok(x), _) => serializer.serialize_i64(x),
+                (_, Ok(x)) => serializer.serialize_u64(x),
+                _ => serializer.serialize_i128(*x),
+            },
+            Self::Float(x) => serializer.serialize_f64(*x),
+            Self::String(x) | Self::Datetime(x) => serializer.serialize_str(x),
+            Self::Array(x) => serializer.collect_seq(x),
+            Self::Table(x) => serializer.collect_map(x),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+        f.write_str("any value")
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<Value, E> {
+        Ok(Value::Bool(value))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Value, E> {
+        Ok(Value::Integer(value.into()))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Value, E> {
+        Ok(Value::Integer(value.into()))
+    }
+
+    fn visit_i128<E: de::Error>(self, value: i128) -> Result<Value, E> {
+        Ok(Value::Integer(value))
+    }
+
+    fn visit_u128<E: de::Error>(self, value: u128) -> Result<Value, E> {
+        i128::try_from(value)
+            .map(Value::Integer)
+            .map_err(|_| E::custom(format!("integer {value} is too large")))
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Value, E> {
+        Ok(Value::Float(value))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Value, E> {
+        Ok(Value::String(value.to_owned()))
+    }
+
+    fn visit_string<E: de::Error>(self, value: String) -> Result<Value, E> {
+        Ok(Value::String(value))
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        Value::deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut items = Vec::new();
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut table = BTreeMap::new();
+        while let Some(key) = map.next_key::<String>()? {
+            if key == TOML_DATETIME_KEY {
+                return Ok(Value::Datetime(map.next_value()?));
+            }
+            table.insert(key, map.next_value()?);
+        }
+        Ok(Value::Table(table))
+    }
+}
+
+impl<'de> Deserializer<'de> for Value {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            Self::Null => visitor.visit_unit(),
+            Self::Bool(x) => visitor.visit_bool(x),
+            Self::Integer(x) => match (i64::try_from(x), u64::try_from(x)) {
+                (Ok(x), _) => visitor.visit_i64(x),
+                (_, Ok(x)) => visitor.visit_u64(x),
+                _ => visitor.visit_i128(x),
+            },
+            Self::Float(x) => visitor.visit_f64(x),
+            Self::String(x) | Self::Datetime(x) => visitor.visit_string(x),
+            Self::Array(x) => visitor.visit_seq(SeqDeserializer::new(x.into_iter())),
+            Self::Table(x) => visitor.visit_map(MapDeserializer::new(x.into_iter())),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            Self::Null => visitor.visit_none(),
+            value => visitor.visit_some(value),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self {
+            Self::String(variant) => visitor.visit_enum(variant.into_deserializer()),
+            Self::Table(table) if table.len() == 1 => visitor.visit_enum(
+                MapAccessDeserializer::new(MapDeserializer::new(table.into_iter())),
+            ),
+            other => Err(de::Error::invalid_type(
+                other.unexpected(),
+                &"an enum variant",
+            )),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier
+        ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, de::value::Error> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
@@ -0,0 +1,274 @@
+//! Interactive "first run" wizard, which prompts for every field of a layer and produces a
+//! layer and a TOML config. Enabled with the `wizard` feature.
+//!
+//! ```ignore
+//! let answers = Wizard::new(StdTerminal).run::<ConfigLayer>()?;
+//! answers.write("config.toml")?;
+//! let config = answers.into_layer().complete()?;
+//! ```
+//!
+//! Each answer is validated by deserializing the layer with it, so that a wrong value is asked
+//! again right away. An empty answer leaves the field unset, which is accepted only for optional
+//! fields and fields with defaults. Secret fields are read without echo. Opaque fields and
+//! collections are skipped, as they can't be entered as a single line.
+
+use crate::meta::{self, FieldMeta};
+use crate::Layer;
+#[cfg(feature = "miette")]
+use miette::Diagnostic;
+use serde::de::DeserializeOwned;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use thiserror::Error;
+use toml::{Table, Value};
+
+/// Where the wizard prints prompts and reads answers
+pub trait Terminal {
+    fn print(&mut self, text: &str) -> std::io::Result<()>;
+
+    /// A line without the trailing newline
+    fn read_line(&mut self) -> std::io::Result<String>;
+
+    /// Same as [`Self::read_line`], but the input is not echoed
+    fn read_secret(&mut self) -> std::io::Result<String>;
+}
+
+impl<T: Terminal + ?Sized> Terminal for &mut T {
+    fn print(&mut self, text: &str) -> std::io::Result<()> {
+        (**self).print(text)
+    }
+
+    fn read_line(&mut self) -> std::io::Result<String> {
+        (**self).read_line()
+    }
+
+    fn read_secret(&mut self) -> std::io::Result<String> {
+        (**self).read_secret()
+    }
+}
+
+/// Standard input and output of the process
+pub struct StdTerminal;
+
+impl Terminal for StdTerminal {
+    fn print(&mut self, text: &str) -> std::io::Result<()> {
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(text.as_bytes())?;
+        stdout.flush()
+    }
+
+    fn read_line(&mut self) -> std::io::Result<String> {
+        let mut line = String::new();
+        std::io::stdin().lock().read_line(&mut line)?;
+        Ok(line.trim_end_matches(['\r', '\n']).to_owned())
+    }
+
+    fn read_secret(&mut self) -> std::io::Result<String> {
+        rpassword::read_password()
+    }
+}
+
+pub struct Wizard<T> {
+    terminal: T,
+}
+
+impl<T: Terminal> Wizard<T> {
+    pub fn new(terminal: T) -> Self {
+        Self { terminal }
+    }
+
+    /// Prompt for every field of `L`, including nested sections
+    pub fn run<L>(&mut self) -> Result<Answers<L>, WizardError>
+    where
+        L: Layer + DeserializeOwned,
+    {
+        let mut table = Table::new();
+        self.prompt_fields::<L>(L::FIELDS, &[], &mut table)?;
+        let layer = parse_layer(&table).map_err(WizardError::Invalid)?;
+        Ok(Answers { layer, table })
+    }
+
+    fn prompt_fields<L: DeserializeOwned>(
+        &mut self,
+        fields: &'static [FieldMeta],
+        path: &[&'static str],
+        table: &mut Table,
+    ) -> Result<(), WizardError> {
+        for group in meta::groups(fields) {
+            if let Some(name) = group.name {
+                self.terminal.print(&format!("\n# {name}\n"))?;
+            }
+            for field in group.fields {
+                let mut path = path.to_vec();
+                path.push(field.name);
+                self.prompt_field::<L>(field, &path, table)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn prompt_field<L: DeserializeOwned>(
+        &mut self,
+        field: &'static FieldMeta,
+        path: &[&'static str],
+        table: &mut Table,
+    ) -> Result<(), WizardError> {
+        if field.opaque || is_collection(field.ty) {
+            return Ok(());
+        }
+        let key = path.join(".");
+
+        if let Some(nested) = field.nested {
+            if field.toggle {
+                let enabled = self.prompt_yes_no(&format!("Enable `{key}`?"))?;
+                insert(table, path, "enabled", Value::Boolean(enabled));
+                if !enabled {
+                    return Ok(());
+                }
+            }
+            return self.prompt_fields::<L>(nested, path, table);
+        }
+
+        let required = !field.optional && field.default.is_none();
+        loop {
+            let mut prompt = String::new();
+            if let Some(doc) = field.doc {
+                prompt.push_str(&format!("{doc}\n"));
+            }
+            prompt.push_str(&format!("{key} ({})", field.ty));
+            if let Some(default) = field.default {
+                prompt.push_str(&format!(" [default: {default}]"));
+            }
+            prompt.push_str(": ");
+            self.terminal.print(&prompt)?;
+
+            let answer = if field.secret {
+                self.terminal.read_secret()?
+            } else {
+                self.terminal.read_line()?
+            };
+            let answer = answer.trim();
+
+            if answer.is_empty() {
+                if required {
+                    self.terminal.print("A value is required\n")?;
+                    continue;
+                }
+                return Ok(());
+            }
+
+            match validate::<L>(table, path, answer) {
+                Ok(value) => {
+                    let (name, parent) = path.split_last().expect("path is not empty");
+                    insert(table, parent, name, value);
+                    return Ok(());
+                }
+                Err(message) => self
+                    .terminal
+                    .print(&format!("Invalid value: {message}\n"))?,
+            }
+        }
+    }
+
+    fn prompt_yes_no(&mut self, question: &str) -> Result<bool, WizardError> {
+        loop {
+            self.terminal.print(&format!("{question} [y/N]: "))?;
+            match self.terminal.read_line()?.trim().to_lowercase().as_str() {
+                "y" | "yes" => return Ok(true),
+                "" | "n" | "no" => return Ok(false),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Result of [`Wizard::run`]
+pub struct Answers<L> {
+    layer: L,
+    table: Table,
+}
+
+impl<L> Answers<L> {
+    pub fn layer(&self) -> &L {
+        &self.layer
+    }
+
+    pub fn into_layer(self) -> L {
+        self.layer
+    }
+
+    /// Config with the answered fields
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(&self.table).expect("a table is always serializable")
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_toml())
+    }
+
+    /// Set the answered fields in an existing config, keeping its comments and formatting
+    #[cfg(feature = "edit")]
+    pub fn update(&self, path: impl AsRef<Path>) -> Result<(), crate::edit::EditError> {
+        crate::edit::apply_layer_to_file(path, &self.table)
+    }
+}
+
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "miette", derive(Diagnostic))]
+pub enum WizardError {
+    #[error("Failed to interact with the terminal")]
+    Io(#[from] std::io::Error),
+    #[error("Answers don't form a valid layer: {0}")]
+    Invalid(String),
+}
+
+/// Collections are keyed by arbitrary names or indices instead of fields
+fn is_collection(ty: &str) -> bool {
+    matches!(meta::type_base_name(ty), "Vec" | "BTreeMap" | "HashMap")
+}
+
+fn parse_layer<L: DeserializeOwned>(table: &Table) -> Result<L, String> {
+    Value::Table(table.clone())
+        .try_into()
+        .map_err(|err: toml::de::Error| err.message().to_owned())
+}
+
+/// Interpret the answer as a TOML value, e.g. a number or a boolean, falling back to a string,
+/// and check that the layer accepts it
+fn validate<L: DeserializeOwned>(
+    table: &Table,
+    path: &[&str],
+    answer: &str,
+) -> Result<Value, String> {
+    let (name, parent) = path.split_last().expect("path is not empty");
+    let parsed = format!("value = {answer}")
+        .parse::<Table>()
+        .ok()
+        .and_then(|mut x| x.remove("value"));
+    let mut error = None;
+    for value in parsed.into_iter().chain([Value::String(answer.to_owned())]) {
+        let mut candidate = table.clone();
+        insert(&mut candidate, parent, name, value.clone());
+        match parse_layer::<L>(&candidate) {
+            Ok(_) => return Ok(value),
+            Err(err) => {
+                error.get_or_insert(err);
+            }
+        }
+    }
+    Err(error.unwrap_or_default())
+}
+
+fn insert(table: &mut Table, parent: &[&str], name: &str, value: Value) {
+    let mut table = table;
+    for segment in parent {
+        let entry = table
+            .entry(segment.to_string())
+            .or_insert_with(|| Value::Table(Table::new()));
+        if !entry.is_table() {
+            *entry = Value::Table(Table::new());
+        }
+        table = entry.as_table_mut().expect("checked above");
+    }
+    table.insert(name.to_owned(), value);
+}
@@ -0,0 +1,73 @@
+#![cfg(all(feature = "uuid", feature = "semver", feature = "regex"))]
+
+mod util;
+
+use soukousei::adapters::{
+    InvalidRegexError, RegexPattern, SemverVersion, SemverVersionReq, UuidParser,
+};
+use soukousei::env::FromEnv;
+use soukousei::parse::FieldParser;
+use soukousei::Layer;
+use util::TestEnv;
+
+#[derive(Debug, Layer)]
+struct Plugin {
+    #[layer(env = "PLUGIN_ID", parse = "UuidParser")]
+    id: uuid::Uuid,
+    #[layer(parse = "SemverVersion")]
+    version: semver::Version,
+    #[layer(env = "PLUGIN_API", parse = "SemverVersionReq")]
+    api: semver::VersionReq,
+    #[layer(parse = "RegexPattern")]
+    include: regex::Regex,
+}
+
+#[test]
+fn parsed_from_file_and_env() {
+    let env = TestEnv::new()
+        .add("PLUGIN_ID", "67e55044-10b1-426f-9247-bb680e5fe0c8")
+        .add("PLUGIN_API", ">=1.2, <2");
+    let file: PluginLayer = toml::from_str(
+        r#"
+        version = "1.4.0"
+        include = "^/api/v[0-9]+/"
+        "#,
+    )
+    .unwrap();
+
+    let plugin = PluginLayer::from_env(&env)
+        .unwrap()
+        .merge(file)
+        .complete()
+        .unwrap();
+
+    assert_eq!(
+        plugin.id.to_string(),
+        "67e55044-10b1-426f-9247-bb680e5fe0c8"
+    );
+    assert!(plugin.api.matches(&plugin.version));
+    assert!(plugin.include.is_match("/api/v2/users"));
+}
+
+#[test]
+fn invalid_version_is_rejected() {
+    let Err(err) = toml::from_str::<PluginLayer>(r#"version = "1.x""#) else {
+        panic!("expected an error")
+    };
+
+    assert!(
+        err.to_string()
+            .contains("invalid value `1.x`, expected semantic version, e.g. `1.2.3`"),
+        "{err}"
+    );
+}
+
+#[test]
+fn invalid_regex_points_to_position() {
+    let err = RegexPattern::parse("^/api/(v[0-9]+/").unwrap_err();
+
+    let err = err.downcast_ref::<InvalidRegexError>().unwrap();
+    assert_eq!(err.pattern(), "^/api/(v[0-9]+/");
+    assert_eq!(err.span().map(|span| span.start), Some(6));
+    assert!(err.to_string().ends_with("at position 6"), "{err}");
+}
@@ -0,0 +1,77 @@
+#![allow(dead_code)]
+
+mod util;
+
+use soukousei::builder::{BuildError, ConfigBuilder};
+use soukousei::Layer;
+use util::TestEnv;
+
+#[derive(Debug, Layer)]
+struct Config {
+    #[layer(env = ["APP_PORT", "PORT"])]
+    port: u16,
+    #[layer(nested)]
+    db: Db,
+}
+
+#[derive(Debug, Layer)]
+struct Db {
+    #[layer(env = ["APP_DB_PASSWORD", "DB_PASSWORD"], secret)]
+    password: String,
+}
+
+#[test]
+fn first_variable_wins_by_default() {
+    let config = ConfigBuilder::<ConfigLayer>::new()
+        .with_env(
+            &TestEnv::new()
+                .add("APP_PORT", "3000")
+                .add("PORT", "4000")
+                .add("DB_PASSWORD", "hunter2"),
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(config.port, 3000);
+}
+
+#[test]
+fn disagreeing_variables_are_denied() {
+    let result = ConfigBuilder::<ConfigLayer>::new()
+        .deny_ambiguous_env()
+        .with_env(
+            &TestEnv::new()
+                .add("APP_PORT", "3000")
+                .add("PORT", "4000")
+                .add("APP_DB_PASSWORD", "hunter2")
+                .add("DB_PASSWORD", "letmein"),
+        );
+
+    let Err(BuildError::AmbiguousEnv(err)) = result else {
+        panic!("expected ambiguous ENV")
+    };
+    assert_eq!(err.fields().collect::<Vec<_>>(), ["port", "db.password"]);
+    assert_eq!(
+        err.to_string(),
+        "ENV vars disagree on `port` (APP_PORT=\"3000\", PORT=\"4000\"), \
+         `db.password` (APP_DB_PASSWORD=<redacted>, DB_PASSWORD=<redacted>)"
+    );
+}
+
+#[test]
+fn agreeing_variables_are_accepted() {
+    let config = ConfigBuilder::<ConfigLayer>::new()
+        .deny_ambiguous_env()
+        .with_env(
+            &TestEnv::new()
+                .add("APP_PORT", "3000")
+                .add("PORT", "3000")
+                .add("DB_PASSWORD", "hunter2"),
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(config.port, 3000);
+}
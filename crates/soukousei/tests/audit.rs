@@ -0,0 +1,71 @@
+#![cfg(feature = "toml")]
+#![allow(dead_code)]
+
+mod util;
+
+use soukousei::builder::ConfigBuilder;
+use soukousei::source::Format;
+use soukousei::Layer;
+use util::TestEnv;
+
+#[derive(Debug, Layer)]
+struct Config {
+    #[layer(env = "PORT", default = "8080")]
+    port: u16,
+    #[layer(env = "TOKEN", secret)]
+    token: String,
+    #[layer(nested)]
+    db: Database,
+}
+
+#[derive(Debug, Layer)]
+struct Database {
+    #[layer(env = "DB_HOST")]
+    host: String,
+}
+
+#[test]
+fn overrides_are_recorded_in_order() {
+    let env = TestEnv::new()
+        .add("PORT", "9000")
+        .add("TOKEN", "from-env")
+        .add("DB_HOST", "db.internal");
+
+    let builder = ConfigBuilder::<ConfigLayer>::new()
+        .with_merge_audit()
+        .with_defaults()
+        .with_str(
+            "config.toml",
+            Format::Toml,
+            "port = 3000\ntoken = \"from-file\"\n[db]\nhost = \"localhost\"",
+        )
+        .unwrap()
+        .with_env(&env)
+        .unwrap();
+
+    let audit = builder.merge_audit().unwrap();
+    let port: Vec<_> = audit.events_for("port").map(ToString::to_string).collect();
+    assert_eq!(
+        port,
+        [
+            "`port`: 8080 from defaults is overridden by 3000 from config.toml",
+            "`port`: 3000 from config.toml is overridden by 9000 from env",
+        ]
+    );
+
+    let token = audit.events_for("token").next().unwrap();
+    assert_eq!(token.previous_value.as_deref(), Some("<redacted>"));
+    assert_eq!(token.value.as_deref(), Some("<redacted>"));
+
+    let host = audit.events_for("db.host").next().unwrap();
+    assert_eq!(host.previous_value.as_deref(), Some("\"localhost\""));
+    assert_eq!(host.value.as_deref(), Some("\"db.internal\""));
+    assert_eq!(audit.events().len(), 4);
+}
+
+#[test]
+fn audit_is_off_by_default() {
+    let builder = ConfigBuilder::<ConfigLayer>::new().with_defaults();
+
+    assert!(builder.merge_audit().is_none());
+}
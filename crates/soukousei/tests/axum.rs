@@ -0,0 +1,48 @@
+#![cfg(feature = "axum")]
+#![allow(dead_code)]
+
+use axum::extract::{FromRequestParts, State};
+use axum::http::Request;
+use soukousei::axum::{render_config, ConfigState, Snapshot};
+use soukousei::shared::Shared;
+use soukousei::Layer;
+
+#[derive(Debug, Layer)]
+#[layer(render_tree)]
+struct App {
+    port: u16,
+    #[layer(secret)]
+    token: String,
+}
+
+fn state() -> ConfigState<App> {
+    ConfigState::new(Shared::new(App {
+        port: 8080,
+        token: "hunter2".to_owned(),
+    }))
+}
+
+#[tokio::test]
+async fn snapshot_is_not_affected_by_reload() {
+    let state = state();
+    let (mut parts, _) = Request::new(()).into_parts();
+
+    let Snapshot(snapshot) = Snapshot::<App>::from_request_parts(&mut parts, &state)
+        .await
+        .unwrap();
+    state.shared().replace(App {
+        port: 9090,
+        token: "hunter2".to_owned(),
+    });
+
+    assert_eq!(snapshot.port, 8080);
+    assert_eq!(state.shared().get().port, 9090);
+}
+
+#[tokio::test]
+async fn admin_route_redacts_secrets() {
+    let report = render_config(State(state())).await;
+
+    assert!(report.contains("8080"), "{report}");
+    assert!(!report.contains("hunter2"), "{report}");
+}
@@ -0,0 +1,37 @@
+use soukousei::Layer;
+use std::borrow::Cow;
+
+#[derive(Debug, Layer)]
+struct Server {
+    #[layer(borrow)]
+    host: String,
+    #[layer(borrow)]
+    motd: Option<String>,
+    #[layer(default = "8080")]
+    port: u16,
+}
+
+#[test]
+fn strings_are_borrowed_from_the_source() {
+    let input = r#"{ "host": "localhost", "motd": "hello" }"#;
+
+    let layer: ServerLayer<'_> = serde_json::from_str(input).unwrap();
+
+    assert!(matches!(layer.host, Some(Cow::Borrowed("localhost"))));
+    assert!(matches!(layer.motd, Some(Cow::Borrowed("hello"))));
+}
+
+#[test]
+fn converted_to_owned_on_complete() {
+    let input = String::from(r#"{ "host": "localhost" }"#);
+
+    let server = ServerLayer::default()
+        .merge(serde_json::from_str(&input).unwrap())
+        .complete()
+        .unwrap();
+    drop(input);
+
+    assert_eq!(server.host, "localhost");
+    assert_eq!(server.motd, None);
+    assert_eq!(server.port, 8080);
+}
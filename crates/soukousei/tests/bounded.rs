@@ -0,0 +1,99 @@
+mod util;
+
+use soukousei::bounded::{Bounded, OutOfRangeError, Ratio};
+use soukousei::env::FromEnv;
+use soukousei::{CompleteError, Layer};
+use util::TestEnv;
+
+#[derive(Debug, Layer)]
+struct Config {
+    #[layer(env = "WORKERS")]
+    workers: Bounded<u32, 1, 256>,
+    #[layer(env = "SAMPLE_RATE")]
+    sample_rate: Ratio,
+    #[layer(env = "PORT", range = "1..=65535", default = "8080")]
+    port: u32,
+    #[layer(range = "0.0..1.0")]
+    jitter: Option<f64>,
+}
+
+#[test]
+fn values_are_parsed_from_env() {
+    let env = TestEnv::new()
+        .add("WORKERS", "16")
+        .add("SAMPLE_RATE", "25%")
+        .add("PORT", "443");
+
+    let config = ConfigLayer::default()
+        .merge(ConfigLayer::from_env(&env).unwrap())
+        .complete()
+        .unwrap();
+
+    assert_eq!(config.workers.get(), 16);
+    assert_eq!(config.sample_rate.get(), 0.25);
+    assert_eq!(config.port, 443);
+    assert_eq!(config.jitter, None);
+}
+
+#[test]
+fn out_of_range_values_are_rejected() {
+    let env = TestEnv::new().add("WORKERS", "0").add("SAMPLE_RATE", "0.5");
+
+    assert!(ConfigLayer::from_env(&env).is_err());
+    assert_eq!(
+        Ratio::new(1.4).unwrap_err().to_string(),
+        "expected 0.0..=1.0, got 1.4"
+    );
+    assert_eq!(
+        Bounded::<u8, 1, 10>::new(11).unwrap_err().to_string(),
+        "expected 1..=10, got 11"
+    );
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn ratio_is_deserialized_from_numbers_and_percentages() {
+    #[derive(serde::Deserialize)]
+    struct Doc {
+        a: Ratio,
+        b: Ratio,
+        c: Ratio,
+    }
+
+    let doc: Doc = toml::from_str("a = 0.5\nb = \"10%\"\nc = 1").unwrap();
+
+    assert_eq!((doc.a.get(), doc.b.get(), doc.c.get()), (0.5, 0.1, 1.0));
+    assert!(toml::from_str::<Doc>("a = 1.4\nb = 0\nc = 0").is_err());
+}
+
+#[test]
+fn range_is_checked_on_completion() {
+    let mut layer = ConfigLayer::new();
+    layer.workers = Some(Bounded::new(1).unwrap());
+    layer.sample_rate = Some(Ratio::new(0.1).unwrap());
+    layer.port = Some(70000);
+    layer.jitter = Some(1.0);
+
+    let Err(CompleteError::Fields(errors)) = layer.complete() else {
+        panic!("expected field errors")
+    };
+
+    let errors: Vec<_> = errors
+        .iter()
+        .map(|x| (x.joined_path(), x.value().to_string()))
+        .collect();
+    assert_eq!(
+        errors,
+        [
+            (
+                "port".to_owned(),
+                "Invalid value: expected 1..=65535, got 70000".to_owned()
+            ),
+            (
+                "jitter".to_owned(),
+                "Invalid value: expected 0.0..1.0, got 1".to_owned()
+            ),
+        ]
+    );
+    assert_eq!(OutOfRangeError::new("1..=2", 3).expected(), "1..=2");
+}
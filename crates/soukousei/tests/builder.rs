@@ -0,0 +1,447 @@
+#![allow(dead_code)]
+
+mod util;
+
+use soukousei::builder::{
+    BuildError, BuildWarning, ConfigBuilder, OnError, UnknownEnv, STDIN_SOURCE,
+};
+use soukousei::lint::LintIssue;
+use soukousei::source::Format;
+use soukousei::Layer;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use util::{TempFile, TestEnv};
+
+#[derive(Debug, Layer)]
+struct Sample {
+    #[layer(default = "8080")]
+    port: u16,
+    #[layer(env = "HOST")]
+    host: String,
+}
+
+#[test]
+fn later_sources_take_precedence() {
+    let sample = ConfigBuilder::<SampleLayer>::new()
+        .with_defaults()
+        .with_str("config.toml", Format::Toml, "port = 3000\nhost = \"file\"")
+        .unwrap()
+        .with_env(&TestEnv::new().add("HOST", "env"))
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(sample.port, 3000);
+    assert_eq!(sample.host, "env");
+}
+
+#[test]
+fn parse_error_is_a_source_error() {
+    let err = ConfigBuilder::<SampleLayer>::new()
+        .with_str("config.toml", Format::Toml, "port = \"not a number\"")
+        .err()
+        .unwrap();
+
+    assert!(matches!(err, BuildError::Source(_)), "{err:?}");
+}
+
+#[test]
+fn string_source_is_labeled_and_spanned() {
+    let contents = "port = 3000\nhost = 42";
+
+    let Err(BuildError::Source(report)) =
+        ConfigBuilder::<SampleLayer>::new().with_str("fixture", Format::Toml, contents)
+    else {
+        panic!("expected a source error");
+    };
+
+    assert_eq!(report.name(), "fixture");
+    let [LintIssue::Source(err)] = report.issues() else {
+        panic!("expected a source error, got {:?}", report.issues());
+    };
+    assert_eq!(&contents[err.span().unwrap()], "42");
+}
+
+const PROFILES: &str = r#"
+[default]
+port = 8080
+host = "localhost"
+
+[production]
+host = "example.com"
+"#;
+
+#[test]
+fn profile_is_merged_over_default() {
+    let sample = ConfigBuilder::<SampleLayer>::new()
+        .with_profile_str("Rocket.toml", Format::Toml, PROFILES, "production")
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(sample.port, 8080);
+    assert_eq!(sample.host, "example.com");
+}
+
+#[test]
+fn unknown_profile_falls_back_to_default() {
+    let sample = ConfigBuilder::<SampleLayer>::new()
+        .with_profile_str("Rocket.toml", Format::Toml, PROFILES, "staging")
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(sample.host, "localhost");
+}
+
+#[test]
+fn error_in_other_profile_is_reported() {
+    let contents = format!("{PROFILES}\n[staging]\nport = \"eighty\"");
+
+    let err = ConfigBuilder::<SampleLayer>::new()
+        .with_profile_str("Rocket.toml", Format::Toml, contents.clone(), "production")
+        .err()
+        .unwrap();
+
+    let BuildError::Source(report) = err else {
+        panic!("expected a source error")
+    };
+    let LintIssue::Source(parse) = &report.issues()[0] else {
+        panic!("expected a source error")
+    };
+    let span = parse.span().unwrap();
+    assert!(contents[span].contains("eighty"));
+}
+
+#[test]
+fn shared_snapshots_survive_replace() {
+    let shared = ConfigBuilder::<SampleLayer>::new()
+        .with_defaults()
+        .with_env(&TestEnv::new().add("HOST", "old"))
+        .unwrap()
+        .build_shared()
+        .unwrap();
+
+    let before = shared.get();
+    let previous = shared.clone().replace(Sample {
+        port: 1,
+        host: "new".to_owned(),
+    });
+
+    assert_eq!(before.host, "old");
+    assert_eq!(previous.host, "old");
+    assert_eq!(shared.get().host, "new");
+}
+
+#[test]
+fn provenance_is_tracked_per_field() {
+    let (_, provenance) = ConfigBuilder::<SampleLayer>::new()
+        .with_defaults()
+        .with_str("config.toml", Format::Toml, "host = \"file\"")
+        .unwrap()
+        .with_env(&TestEnv::new().add("HOST", "env"))
+        .unwrap()
+        .build_with_provenance()
+        .unwrap();
+
+    assert_eq!(provenance.source_of("port"), Some("defaults"));
+    assert_eq!(provenance.source_of("host"), Some("env"));
+    assert_eq!(
+        provenance.to_string(),
+        "port from defaults\nhost from env (HOST)\n"
+    );
+}
+
+#[derive(Debug, Layer)]
+struct MultiEnv {
+    #[layer(env = ["APP_PORT", "PORT"])]
+    port: u16,
+    #[layer(env = ["APP_HOST", "HOST"])]
+    host: String,
+}
+
+#[test]
+fn provenance_names_env_var() {
+    let (_, provenance) = ConfigBuilder::<MultiEnvLayer>::new()
+        .with_env(&TestEnv::new().add("PORT", "3000").add("APP_HOST", "env"))
+        .unwrap()
+        .build_with_provenance()
+        .unwrap();
+
+    assert_eq!(provenance.variable_of("port"), Some("PORT"));
+    assert_eq!(provenance.variable_of("host"), Some("APP_HOST"));
+}
+
+#[test]
+fn env_var_is_forgotten_when_overridden() {
+    let (_, provenance) = ConfigBuilder::<MultiEnvLayer>::new()
+        .with_env(&TestEnv::new().add("PORT", "3000").add("HOST", "env"))
+        .unwrap()
+        .with_str("config.toml", Format::Toml, "host = \"file\"")
+        .unwrap()
+        .build_with_provenance()
+        .unwrap();
+
+    assert_eq!(provenance.variable_of("host"), None);
+    assert_eq!(
+        provenance.to_string(),
+        "port from env (PORT)\nhost from config.toml\n"
+    );
+}
+
+#[derive(Debug, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+struct Pool {
+    min: u32,
+    max: u32,
+}
+
+#[derive(Debug, Layer)]
+struct WithPool {
+    #[layer(opaque)]
+    pool: Pool,
+}
+
+#[test]
+fn opaque_field_is_replaced_as_a_whole() {
+    let builder = ConfigBuilder::<WithPoolLayer>::new()
+        .with_defaults()
+        .with_str("base.toml", Format::Toml, "[pool]\nmin = 2\nmax = 10")
+        .unwrap()
+        .with_str("override.toml", Format::Toml, "[pool]\nmax = 20")
+        .unwrap();
+
+    assert_eq!(
+        builder
+            .warnings()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+        vec!["`pool` from override.toml replaces the whole value from base.toml, as it is opaque"]
+    );
+
+    let value = builder.build().unwrap();
+    assert_eq!(value.pool.min, 0);
+    assert_eq!(value.pool.max, 20);
+}
+
+#[derive(Debug, Layer)]
+struct Strict {
+    #[layer(env = "MYAPP_DB_PORT", default = "5432")]
+    db_port: u16,
+}
+
+#[test]
+fn strict_env_denies_unknown_vars() {
+    let env = TestEnv::new()
+        .add("MYAPP_DB_PROT", "6432")
+        .add("OTHER_VAR", "1");
+
+    let err = ConfigBuilder::<StrictLayer>::new()
+        .with_env_strict(&env, "MYAPP_", UnknownEnv::Deny)
+        .err()
+        .unwrap();
+
+    let BuildError::UnknownEnv(err) = err else {
+        panic!("expected unknown ENV error, got {err:?}")
+    };
+    assert_eq!(err.variables(), ["MYAPP_DB_PROT"]);
+    assert_eq!(
+        miette::Diagnostic::help(&err).unwrap().to_string(),
+        "did you mean `MYAPP_DB_PORT` instead of `MYAPP_DB_PROT`?"
+    );
+}
+
+#[test]
+fn strict_env_warns_about_unknown_vars() {
+    let env = TestEnv::new()
+        .add("MYAPP_DB_PORT", "6432")
+        .add("MYAPP_LOG", "debug");
+
+    let builder = ConfigBuilder::<StrictLayer>::new()
+        .with_defaults()
+        .with_env_strict(&env, "MYAPP_", UnknownEnv::Warn)
+        .unwrap();
+
+    assert!(matches!(
+        builder.warnings(),
+        [BuildWarning::UnknownEnv { variable, help: None }] if variable == "MYAPP_LOG"
+    ));
+    assert_eq!(builder.build().unwrap().db_port, 6432);
+}
+
+#[test]
+fn overrides_take_precedence() {
+    let (sample, provenance) = ConfigBuilder::<SampleLayer>::new()
+        .with_defaults()
+        .with_overrides(|layer| layer.port = Some(1))
+        .with_str("config.toml", Format::Toml, "port = 3000\nhost = \"file\"")
+        .unwrap()
+        .build_with_provenance()
+        .unwrap();
+
+    assert_eq!(sample.port, 1);
+    assert_eq!(sample.host, "file");
+    assert_eq!(provenance.source_of("port"), Some("programmatic"));
+    assert_eq!(provenance.source_of("host"), Some("config.toml"));
+}
+
+static HOSTNAME_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+fn hostname() -> String {
+    HOSTNAME_CALLS.fetch_add(1, Ordering::SeqCst);
+    "machine".to_owned()
+}
+
+#[derive(Debug, Layer)]
+struct Expensive {
+    #[layer(default = "8080")]
+    port: u16,
+    #[layer(default = "hostname()")]
+    host: String,
+}
+
+#[test]
+fn lazy_defaults_are_evaluated_only_for_missing_fields() {
+    let (value, provenance) = ConfigBuilder::<ExpensiveLayer>::new()
+        .with_lazy_defaults()
+        .with_str("config.toml", Format::Toml, "host = \"file\"")
+        .unwrap()
+        .build_with_provenance()
+        .unwrap();
+
+    assert_eq!(value.port, 8080);
+    assert_eq!(value.host, "file");
+    assert_eq!(HOSTNAME_CALLS.load(Ordering::SeqCst), 0);
+    assert_eq!(provenance.source_of("port"), Some("defaults"));
+    assert_eq!(provenance.source_of("host"), Some("config.toml"));
+}
+
+#[test]
+fn file_is_selected_by_env_var() {
+    let env = TestEnv::new().add(
+        "APP_CONFIG",
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/sample.toml"),
+    );
+
+    let (sample, provenance) = ConfigBuilder::<SampleLayer>::new()
+        .with_file_from_env(&env, "APP_CONFIG")
+        .unwrap()
+        .build_with_provenance()
+        .unwrap();
+
+    assert_eq!(sample.port, 3000);
+    assert!(
+        provenance
+            .source_of("port")
+            .unwrap()
+            .ends_with("tests/data/sample.toml (from APP_CONFIG)"),
+        "{provenance}"
+    );
+}
+
+#[test]
+fn file_is_skipped_if_env_var_is_not_set() {
+    let builder = ConfigBuilder::<SampleLayer>::new()
+        .with_file_from_env(&TestEnv::new(), "APP_CONFIG")
+        .unwrap();
+
+    assert!(matches!(
+        builder.warnings(),
+        [BuildWarning::FileNotSelected { variable }] if variable == "APP_CONFIG"
+    ));
+}
+
+#[test]
+fn file_selected_by_env_var_is_required() {
+    let env = TestEnv::new().add("APP_CONFIG", "/nonexistent/config.toml");
+
+    let result = ConfigBuilder::<SampleLayer>::new().with_file_from_env(&env, "APP_CONFIG");
+
+    assert!(matches!(result, Err(BuildError::Source(_))));
+}
+
+#[test]
+fn reader_is_read_until_eof() {
+    let input = "port = 3000\nhost = \"piped\"".as_bytes();
+
+    let (sample, provenance) = ConfigBuilder::<SampleLayer>::new()
+        .with_reader(STDIN_SOURCE, Format::Toml, input)
+        .unwrap()
+        .build_with_provenance()
+        .unwrap();
+
+    assert_eq!(sample.host, "piped");
+    assert_eq!(provenance.source_of("host"), Some("stdin"));
+}
+
+#[test]
+fn reader_parse_error_is_labeled() {
+    let input = "port = \"oops\"".as_bytes();
+
+    let Err(BuildError::Source(report)) =
+        ConfigBuilder::<SampleLayer>::new().with_reader(STDIN_SOURCE, Format::Toml, input)
+    else {
+        panic!("expected a source error");
+    };
+
+    assert_eq!(report.to_string(), "`stdin` is not a valid configuration");
+}
+
+#[test]
+fn malformed_optional_file_is_skipped() {
+    let file = TempFile::new("user-override.toml", "port = ");
+    let path = file.path();
+
+    let builder = ConfigBuilder::<SampleLayer>::new()
+        .with_defaults()
+        .with_str("config.toml", Format::Toml, "host = \"file\"")
+        .unwrap()
+        .with_file_on_error(path, OnError::Skip)
+        .unwrap();
+
+    assert_eq!(
+        builder
+            .warnings()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+        vec![format!(
+            "{} is skipped, as it failed to load",
+            path.display()
+        )]
+    );
+    let BuildWarning::SourceSkipped { report, .. } = &builder.warnings()[0] else {
+        panic!("expected a skipped source")
+    };
+    assert!(matches!(report.issues(), [LintIssue::Source(_)]));
+
+    let sample = builder.build().unwrap();
+    assert_eq!(sample.port, 8080);
+    assert_eq!(sample.host, "file");
+}
+
+#[test]
+fn malformed_required_file_fails() {
+    let file = TempFile::new("required.toml", "port = ");
+    let path = file.path();
+
+    let err = ConfigBuilder::<SampleLayer>::new()
+        .with_file_on_error(path, OnError::Fail)
+        .err()
+        .expect("the file is malformed");
+
+    assert!(matches!(err, BuildError::Source(_)));
+}
+
+#[test]
+fn valid_optional_file_is_merged() {
+    let file = TempFile::new("valid-override.toml", "port = 3000\nhost = \"user\"");
+    let path = file.path();
+
+    let builder = ConfigBuilder::<SampleLayer>::new()
+        .with_file_on_error(path, OnError::Skip)
+        .unwrap();
+
+    assert!(builder.warnings().is_empty());
+    assert_eq!(builder.build().unwrap().port, 3000);
+}
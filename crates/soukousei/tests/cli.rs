@@ -0,0 +1,69 @@
+#![cfg(feature = "cli")]
+#![allow(dead_code)]
+
+use soukousei::{cli, Layer};
+
+#[derive(Layer)]
+struct App {
+    /// Port to listen on
+    #[layer(default = "8080", env = "PORT")]
+    port: u16,
+    #[layer(nested)]
+    database: Database,
+}
+
+#[derive(Layer)]
+struct Database {
+    #[layer(env = ["APP_DATABASE_URL", "DATABASE_URL"])]
+    url: String,
+}
+
+fn run(args: &[&str]) -> Result<String, cli::CliError> {
+    cli::run::<App>(args.iter().map(|x| (*x).to_owned()))
+}
+
+#[test]
+fn example_toml() {
+    assert_eq!(
+        run(&["example", "--toml"]).unwrap(),
+        "\
+# Port to listen on
+# env: PORT
+# port = 8080
+
+[database]
+
+# env: APP_DATABASE_URL, DATABASE_URL
+# url = <String>
+
+"
+    );
+}
+
+#[test]
+fn env_docs_markdown() {
+    assert_eq!(
+        run(&["env-docs", "--markdown"]).unwrap(),
+        "\
+| Variable | Field | Type | Description |
+|---|---|---|---|
+| `PORT` | `port` | `u16` | Port to listen on |
+| `APP_DATABASE_URL` | `database.url` | `String` |  |
+| `DATABASE_URL` | `database.url` | `String` |  |
+"
+    );
+}
+
+#[test]
+fn json_schema() {
+    let schema = run(&["schema", "--json"]).unwrap();
+
+    assert!(schema.contains(
+        r#""port":{"description":"Port to listen on","type":"integer","x-rust-type":"u16"}"#
+    ));
+}
+
+#[test]
+fn invalid_arguments() {
+    assert!(matches!(run(&["dump"]), Err(cli::CliError::Usage)));
+}
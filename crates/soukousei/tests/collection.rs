@@ -0,0 +1,140 @@
+#![allow(dead_code)]
+
+use soukousei::{CompleteErrorDiagnostic, HasLayer, Layer};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Layer)]
+struct Cluster {
+    #[layer(nested)]
+    servers: Vec<Server>,
+    #[layer(nested)]
+    tenants: BTreeMap<String, Tenant>,
+}
+
+#[derive(Debug, Layer)]
+struct Server {
+    host: String,
+    #[layer(default = "8080")]
+    port: u16,
+}
+
+#[derive(Debug, Layer)]
+struct Tenant {
+    quota: u32,
+}
+
+fn missing_paths(input: &str) -> Vec<String> {
+    let layer: ClusterLayer = toml::from_str(input).unwrap();
+    let CompleteErrorDiagnostic::Fields { fields } = layer.complete_and_report().unwrap_err()
+    else {
+        panic!("expected field errors")
+    };
+    fields.iter().map(ToString::to_string).collect()
+}
+
+#[test]
+fn elements_are_merged_over_defaults() {
+    let layer: ClusterLayer = toml::from_str(
+        r#"
+        [[servers]]
+        host = "a"
+
+        [[servers]]
+        host = "b"
+        port = 9000
+
+        [tenants.acme]
+        quota = 10
+        "#,
+    )
+    .unwrap();
+
+    let cluster = layer.complete().unwrap();
+
+    let ports: Vec<_> = cluster.servers.iter().map(|x| x.port).collect();
+    assert_eq!(ports, vec![8080, 9000]);
+    assert_eq!(cluster.tenants["acme"].quota, 10);
+}
+
+#[test]
+fn error_paths_are_indexed() {
+    let paths = missing_paths(
+        r#"
+        [[servers]]
+        host = "a"
+
+        [[servers]]
+        port = 9000
+
+        [tenants.acme]
+        "#,
+    );
+
+    assert_eq!(
+        paths,
+        vec![
+            "`servers[1].host`: missing field",
+            "`tenants[\"acme\"].quota`: missing field",
+        ]
+    );
+}
+
+#[test]
+fn newer_source_replaces_elements() {
+    let cluster = <Cluster as HasLayer>::Layer::new()
+        .merge(
+            toml::from_str("servers = [{ host = \"a\" }, { host = \"b\" }]\ntenants = {}").unwrap(),
+        )
+        .merge(toml::from_str("servers = [{ host = \"c\" }]").unwrap())
+        .complete()
+        .unwrap();
+
+    assert_eq!(cluster.servers.len(), 1);
+    assert_eq!(cluster.servers[0].host, "c");
+}
+
+#[test]
+fn provided_fields_are_nested() {
+    let layer: ClusterLayer = toml::from_str("servers = []").unwrap();
+
+    assert_eq!(layer.provided_fields(), vec!["servers"]);
+}
+
+#[derive(Debug, Layer)]
+struct Registry {
+    #[layer(nested)]
+    tenants: std::collections::HashMap<String, Tenant>,
+}
+
+#[test]
+fn hash_map_entries_are_ordered_by_key() {
+    let layer: RegistryLayer = toml::from_str(
+        r#"
+        [tenants.c]
+        [tenants.a]
+        quota = 1
+        [tenants.b]
+        "#,
+    )
+    .unwrap();
+
+    let dump = toml::to_string(&layer).unwrap();
+    let positions: Vec<_> = ["tenants.a", "tenants.b", "tenants.c"]
+        .iter()
+        .map(|x| dump.find(x).unwrap())
+        .collect();
+    assert!(positions.windows(2).all(|x| x[0] < x[1]), "{dump}");
+
+    let CompleteErrorDiagnostic::Fields { fields } = layer.complete_and_report().unwrap_err()
+    else {
+        panic!("expected field errors")
+    };
+    let paths: Vec<_> = fields.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        paths,
+        vec![
+            "`tenants[\"b\"].quota`: missing field",
+            "`tenants[\"c\"].quota`: missing field",
+        ]
+    );
+}
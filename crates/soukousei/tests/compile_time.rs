@@ -0,0 +1,72 @@
+mod util;
+
+use soukousei::env::FromEnv;
+use soukousei::{CompleteError, Layer};
+use util::TestEnv;
+
+#[derive(Debug, Layer)]
+struct Build {
+    #[layer(env = "CARGO_PKG_VERSION", compile_time)]
+    version: String,
+    #[layer(env = "SOUKOUSEI_UNSET_AT_BUILD_TIME", compile_time, default = "7")]
+    number: u32,
+    #[layer(env = "SOUKOUSEI_UNSET_AT_BUILD_TIME", compile_time)]
+    profile: Option<String>,
+}
+
+#[derive(Debug, Layer)]
+struct Invalid {
+    /// The package name is not a number
+    #[layer(env = "CARGO_PKG_NAME", compile_time, default = "1")]
+    number: u32,
+}
+
+#[test]
+fn value_is_baked_into_default() {
+    let build = BuildLayer::default().complete().unwrap();
+
+    assert_eq!(build.version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(build.number, 7);
+    assert_eq!(build.profile, None);
+}
+
+#[test]
+fn var_is_not_read_at_runtime() {
+    let env = TestEnv::new().add("CARGO_PKG_VERSION", "0.0.0-runtime");
+
+    let layer = BuildLayer::from_env(&env).unwrap();
+
+    assert_eq!(layer.version, None);
+}
+
+#[test]
+fn default_is_described_in_help() {
+    let fields = BuildLayer::fields();
+
+    assert_eq!(fields[0].default, Some("`CARGO_PKG_VERSION` at build time"));
+    assert_eq!(fields[0].env, &[] as &[&str]);
+    assert_eq!(
+        fields[1].default,
+        Some("`SOUKOUSEI_UNSET_AT_BUILD_TIME` at build time, or 7")
+    );
+}
+
+#[test]
+fn invalid_value_is_reported() {
+    let Err(CompleteError::Fields(errors)) = InvalidLayer::default().complete() else {
+        panic!("expected field errors")
+    };
+
+    let errors: Vec<_> = errors.iter().map(|x| x.joined_path()).collect();
+    assert_eq!(errors, ["number"]);
+}
+
+#[test]
+fn invalid_value_is_not_reported_when_overridden() {
+    let mut layer = InvalidLayer::new();
+    layer.number = Some(2);
+
+    let invalid = InvalidLayer::default().merge(layer).complete().unwrap();
+
+    assert_eq!(invalid.number, 2);
+}
@@ -0,0 +1,62 @@
+mod util;
+
+use soukousei::composite::CompositeBuilder;
+use soukousei::env::EnvProvider;
+use soukousei::source::Format;
+use soukousei::{Layer, Report};
+use std::cell::RefCell;
+use util::TestEnv;
+
+#[derive(Debug, Layer)]
+struct Http {
+    #[layer(default = "8080")]
+    port: u16,
+    #[layer(env = "LOG_LEVEL")]
+    log_level: String,
+}
+
+#[derive(Debug, Layer)]
+struct Db {
+    #[layer(env = "DB_URL")]
+    db_url: String,
+    #[layer(env = "LOG_LEVEL")]
+    log_level: String,
+}
+
+/// Records fetched variables
+struct Recording {
+    env: TestEnv,
+    fetched: RefCell<Vec<String>>,
+}
+
+impl EnvProvider for Recording {
+    fn fetch(&self, key: impl AsRef<str>) -> Result<Option<String>, Report> {
+        self.fetched.borrow_mut().push(key.as_ref().to_owned());
+        self.env.fetch(key)
+    }
+}
+
+#[test]
+fn roots_are_loaded_from_shared_sources() {
+    let env = Recording {
+        env: TestEnv::new()
+            .add("DB_URL", "postgres://")
+            .add("LOG_LEVEL", "debug"),
+        fetched: RefCell::new(Vec::new()),
+    };
+
+    let (http, db) = CompositeBuilder::<(Http, Db)>::new()
+        .with_defaults()
+        .with_str("config.toml", Format::Toml, "port = 3000")
+        .unwrap()
+        .with_env(&env)
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(http.port, 3000);
+    assert_eq!(http.log_level, "debug");
+    assert_eq!(db.db_url, "postgres://");
+    assert_eq!(db.log_level, "debug");
+    assert_eq!(*env.fetched.borrow(), vec!["LOG_LEVEL", "DB_URL"]);
+}
@@ -0,0 +1,83 @@
+#![cfg(feature = "contrib")]
+
+use soukousei::contrib::database::{DatabaseConfig, DatabaseConfigLayer};
+use soukousei::contrib::http::HttpServerConfig;
+use soukousei::Layer;
+use std::time::Duration;
+
+#[derive(Debug, Layer)]
+struct App {
+    #[layer(nested)]
+    server: HttpServerConfig,
+    #[layer(nested)]
+    database: DatabaseConfig,
+}
+
+fn parse(contents: &str) -> App {
+    let layer: AppLayer = toml::from_str(contents).unwrap();
+    AppLayer::default().merge(layer).complete().unwrap()
+}
+
+#[test]
+fn sections_have_sensible_defaults() {
+    let app = parse(
+        r#"
+        [database]
+        url = "postgres://localhost/app"
+        "#,
+    );
+
+    assert_eq!(app.server.socket_addr().to_string(), "0.0.0.0:8080");
+    assert!(!app.server.tls.is_enabled());
+    assert_eq!(app.database.max_connections, 10);
+    assert_eq!(app.database.idle_timeout(), Some(Duration::from_secs(600)));
+    app.server.validate().unwrap();
+    app.database.validate().unwrap();
+}
+
+#[test]
+fn inconsistent_pool_is_rejected() {
+    let app = parse(
+        r#"
+        [database]
+        url = "postgres://localhost/app"
+        min_connections = 20
+        max_connections = 5
+        "#,
+    );
+
+    let err = app.database.validate().unwrap_err();
+
+    assert_eq!(err.field(), "min_connections");
+}
+
+#[test]
+fn missing_tls_files_are_rejected() {
+    let app = parse(
+        r#"
+        [server.tls]
+        enabled = true
+        cert = "/nonexistent/cert.pem"
+        key = "/nonexistent/key.pem"
+
+        [database]
+        url = "postgres://localhost/app"
+        "#,
+    );
+
+    let err = app.server.validate().unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "`tls.cert` `/nonexistent/cert.pem` is not a file"
+    );
+}
+
+#[test]
+fn database_url_is_secret() {
+    let fields = DatabaseConfigLayer::fields();
+
+    let url = fields.iter().find(|field| field.name == "url").unwrap();
+    assert!(url.secret);
+    assert!(fields.iter().any(|field| field.name == "url_file"));
+}
@@ -0,0 +1,29 @@
+mod framework {
+    pub use soukousei;
+}
+
+use framework::soukousei::Layer;
+
+#[derive(framework::soukousei::Layer)]
+#[layer(crate = "crate::framework::soukousei")]
+struct Config {
+    #[layer(default = "8080")]
+    port: u16,
+    #[layer(nested)]
+    database: Database,
+}
+
+#[derive(framework::soukousei::Layer)]
+#[layer(crate = "crate::framework::soukousei")]
+struct Database {
+    url: String,
+}
+
+#[test]
+fn derive_through_reexport() {
+    let layer: ConfigLayer = toml::from_str("database.url = \"postgres://\"").unwrap();
+    let config = ConfigLayer::default().merge(layer).complete().unwrap();
+
+    assert_eq!(config.port, 8080);
+    assert_eq!(config.database.url, "postgres://");
+}
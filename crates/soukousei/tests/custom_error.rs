@@ -0,0 +1,86 @@
+#![allow(dead_code)]
+
+use miette::{miette, Diagnostic};
+use soukousei::{CompleteError, CompleteErrorDiagnostic, FieldErrorDiagnostic, HasLayer, Layer};
+
+/// Color in the `#rrggbb` format
+#[derive(Debug)]
+struct Color(u32);
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct ColorLayer(Option<String>);
+
+impl Layer for ColorLayer {
+    type Complete = Color;
+
+    fn new() -> Self {
+        Self(None)
+    }
+
+    fn merge(self, other: Self) -> Self {
+        Self(other.0.or(self.0))
+    }
+
+    fn complete(self) -> Result<Self::Complete, CompleteError> {
+        let value = self.0.ok_or(CompleteError::MissingData)?;
+        value
+            .strip_prefix('#')
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .map(Color)
+            .ok_or_else(|| {
+                CompleteError::Custom(miette!(
+                    help = "expected the `#rrggbb` format, e.g. `#ff8800`",
+                    "invalid color `{}`",
+                    value
+                ))
+            })
+    }
+}
+
+impl HasLayer for Color {
+    type Layer = ColorLayer;
+}
+
+#[derive(Debug, Layer)]
+#[layer(no_env)]
+struct Theme {
+    #[layer(nested)]
+    accent: Color,
+}
+
+#[derive(Debug, Layer)]
+#[layer(no_env)]
+struct Sample {
+    #[layer(nested)]
+    theme: Theme,
+}
+
+#[test]
+fn custom_error_has_full_path_and_help() {
+    let layer: SampleLayer = toml::from_str("[theme]\naccent = \"orange\"").unwrap();
+
+    let CompleteErrorDiagnostic::Fields { fields } = layer.complete_and_report().unwrap_err()
+    else {
+        panic!("expected field errors")
+    };
+
+    let [FieldErrorDiagnostic::Custom(custom)] = fields.as_slice() else {
+        panic!("unexpected errors: {fields:?}")
+    };
+    assert_eq!(custom.path(), Some("theme.accent"));
+    assert_eq!(custom.to_string(), "`theme.accent`: invalid color `orange`");
+    assert_eq!(
+        custom.help().unwrap().to_string(),
+        "expected the `#rrggbb` format, e.g. `#ff8800`"
+    );
+}
+
+#[test]
+fn custom_error_of_a_layer_by_itself() {
+    let err = ColorLayer(Some("orange".to_owned()))
+        .complete_and_report()
+        .unwrap_err();
+
+    assert_eq!(err.to_string(), "invalid color `orange`");
+    assert!(err.help().is_some());
+}
@@ -0,0 +1,77 @@
+#![cfg(feature = "chrono")]
+
+mod util;
+
+use chrono::{NaiveDate, TimeZone, Utc};
+use soukousei::datetime::{ChronoNaiveDate, ChronoUtc};
+use soukousei::env::FromEnv;
+use soukousei::Layer;
+use util::TestEnv;
+
+#[derive(Debug, Layer)]
+struct Schedule {
+    #[layer(env = "START", parse = "ChronoUtc")]
+    start: chrono::DateTime<Utc>,
+    #[layer(parse = "ChronoNaiveDate")]
+    until: Option<NaiveDate>,
+}
+
+#[test]
+fn parsed_from_env() {
+    let env = TestEnv::new().add("START", "2023-06-01 12:30:00");
+
+    let schedule = ScheduleLayer::from_env(&env).unwrap().complete().unwrap();
+
+    assert_eq!(
+        schedule.start,
+        Utc.with_ymd_and_hms(2023, 6, 1, 12, 30, 0).unwrap()
+    );
+}
+
+#[test]
+fn parsed_from_file() {
+    let layer: ScheduleLayer = toml::from_str(
+        r#"
+        start = "2023-06-01T12:30:00+03:00"
+        until = "31.12.2023"
+        "#,
+    )
+    .unwrap();
+
+    let schedule = layer.complete().unwrap();
+
+    assert_eq!(
+        schedule.start,
+        Utc.with_ymd_and_hms(2023, 6, 1, 9, 30, 0).unwrap()
+    );
+    assert_eq!(schedule.until, NaiveDate::from_ymd_opt(2023, 12, 31));
+}
+
+#[test]
+fn invalid_value_lists_expected_formats() {
+    let env = TestEnv::new().add("START", "yesterday");
+
+    let Err(err) = ScheduleLayer::from_env(&env) else {
+        panic!("expected an error")
+    };
+
+    let message = err.iter().next().unwrap().value().to_string();
+    assert_eq!(
+        message,
+        "Failed to read ENV var `START`: invalid value `yesterday`, expected RFC 3339 datetime, \
+        `YYYY-MM-DD HH:MM:SS`, `YYYY-MM-DDTHH:MM:SS` or `YYYY-MM-DD` (UTC)"
+    );
+}
+
+#[test]
+fn serialized_back_as_rfc3339() {
+    let mut layer = ScheduleLayer::new();
+    layer.start = Some(Utc.with_ymd_and_hms(2023, 6, 1, 12, 30, 0).unwrap());
+
+    let serialized = toml::to_string(&layer).unwrap();
+
+    assert!(
+        serialized.contains(r#"start = "2023-06-01T12:30:00+00:00""#),
+        "{serialized}"
+    );
+}
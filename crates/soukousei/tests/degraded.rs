@@ -0,0 +1,145 @@
+#![allow(dead_code)]
+
+use soukousei::reference::Ref;
+use soukousei::{CompleteErrorDiagnostic, Layer};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Layer)]
+#[layer(degradable)]
+struct Service {
+    port: u16,
+    #[layer(nested)]
+    metrics: Metrics,
+    #[layer(nested)]
+    storage: Storage,
+}
+
+#[derive(Debug, Layer)]
+struct Metrics {
+    endpoint: String,
+}
+
+#[derive(Debug, Layer)]
+struct Storage {
+    path: String,
+}
+
+#[test]
+fn failed_sections_are_left_empty() {
+    let layer: ServiceLayer = toml::from_str(
+        r#"
+        port = 8080
+        storage.path = "/data"
+        "#,
+    )
+    .unwrap();
+
+    let (service, failed) = layer.complete_partial().unwrap();
+
+    assert_eq!(service.port, 8080);
+    assert!(service.metrics.is_none());
+    assert_eq!(service.storage.unwrap().path, "/data");
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].name(), "metrics");
+    assert!(matches!(
+        failed[0].error(),
+        CompleteErrorDiagnostic::Fields { fields } if fields.len() == 1
+    ));
+}
+
+#[test]
+fn top_level_fields_are_required() {
+    let layer: ServiceLayer = toml::from_str(r#"storage.path = "/data""#).unwrap();
+
+    assert!(layer.complete_partial().is_err());
+}
+
+#[derive(Debug, Layer)]
+#[layer(degradable, fail_fast)]
+struct Strict {
+    #[layer(nested)]
+    storage: Paths,
+}
+
+#[derive(Debug, Layer)]
+struct Paths {
+    data: String,
+    cache: String,
+}
+
+#[test]
+fn sections_follow_error_policy() {
+    let (strict, failed) = StrictLayer::new().complete_partial().unwrap();
+
+    assert!(strict.storage.is_none());
+    assert!(matches!(
+        failed[0].error(),
+        CompleteErrorDiagnostic::Fields { fields } if fields.len() == 1
+    ));
+}
+
+#[derive(Debug, Layer)]
+#[layer(degradable)]
+#[layer(reference(field = "server.tls", section = "tls_profiles"))]
+struct Proxy {
+    #[layer(nested)]
+    server: Server,
+    #[layer(nested)]
+    tls_profiles: BTreeMap<String, TlsProfile>,
+}
+
+#[derive(Debug, Layer)]
+struct Server {
+    tls: Ref<TlsProfile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Layer)]
+struct TlsProfile {
+    cert: String,
+}
+
+#[test]
+fn references_are_resolved() {
+    let layer: ProxyLayer = toml::from_str(
+        r#"
+        server.tls = "corp-tls"
+        tls_profiles.corp-tls.cert = "corp.pem"
+        "#,
+    )
+    .unwrap();
+
+    let (proxy, failed) = layer.complete_partial().unwrap();
+
+    assert!(failed.is_empty());
+    assert_eq!(proxy.server.unwrap().tls.cert, "corp.pem");
+}
+
+#[test]
+fn references_to_failed_sections_are_skipped() {
+    let layer: ProxyLayer = toml::from_str(
+        r#"
+        server.tls = "corp-tls"
+        tls_profiles.corp-tls = {}
+        "#,
+    )
+    .unwrap();
+
+    let (proxy, failed) = layer.complete_partial().unwrap();
+
+    assert!(proxy.tls_profiles.is_none());
+    assert_eq!(proxy.server.unwrap().tls.name(), "corp-tls");
+    assert_eq!(failed[0].name(), "tls_profiles");
+}
+
+#[test]
+fn dangling_reference_is_an_error() {
+    let layer: ProxyLayer = toml::from_str(
+        r#"
+        server.tls = "admin"
+        tls_profiles.corp-tls.cert = "corp.pem"
+        "#,
+    )
+    .unwrap();
+
+    assert!(layer.complete_partial().is_err());
+}
@@ -0,0 +1,196 @@
+#![allow(dead_code)]
+
+use soukousei::{HasLayer, Layer};
+
+#[derive(Debug, Layer)]
+struct Sample {
+    #[layer(default = "100")]
+    with_default_foo: u32,
+    optional_bar: Option<String>,
+    #[layer(nested)]
+    nested: Nested,
+}
+
+#[derive(Debug, Layer)]
+struct Nested {
+    #[layer(env = "BAZ")]
+    required_baz: bool,
+}
+
+type NestedAlias = Nested;
+
+#[derive(Debug, Layer)]
+struct WithAlias {
+    #[layer(nested)]
+    nested: NestedAlias,
+}
+
+#[test]
+fn complete_with_defaults_and_nested() {
+    let sample = <Sample as HasLayer>::Layer::default()
+        .merge(SampleLayer {
+            with_default_foo: None,
+            optional_bar: None,
+            nested: NestedLayer {
+                required_baz: Some(true),
+            },
+        })
+        .complete()
+        .unwrap();
+
+    assert_eq!(sample.with_default_foo, 100);
+    assert_eq!(sample.optional_bar, None);
+    assert!(sample.nested.required_baz);
+}
+
+#[test]
+fn complete_through_type_alias() {
+    let value = WithAliasLayer::new()
+        .merge(WithAliasLayer {
+            nested: NestedLayer {
+                required_baz: Some(false),
+            },
+        })
+        .complete()
+        .unwrap();
+
+    assert!(!value.nested.required_baz);
+}
+
+#[test]
+fn merge_from_in_place() {
+    let mut layer = SampleLayer::default();
+    layer.merge_from(SampleLayer {
+        with_default_foo: None,
+        optional_bar: Some("bar".to_owned()),
+        nested: NestedLayer {
+            required_baz: Some(true),
+        },
+    });
+    layer.merge_from(SampleLayer::new());
+
+    let sample = layer.complete().unwrap();
+
+    assert_eq!(sample.with_default_foo, 100);
+    assert_eq!(sample.optional_bar.as_deref(), Some("bar"));
+    assert!(sample.nested.required_baz);
+}
+
+#[derive(Debug, Layer)]
+#[layer(derive(Clone, Debug, PartialEq))]
+struct Comparable {
+    port: u32,
+}
+
+#[test]
+fn layer_has_extra_derives() {
+    let layer = ComparableLayer { port: Some(42) };
+
+    assert_eq!(layer.clone(), layer);
+}
+
+#[test]
+fn layer_macro_sets_nested_paths() {
+    let layer = soukousei::layer!(Sample {
+        optional_bar: "bar",
+        nested.required_baz: true,
+    });
+
+    assert_eq!(layer.with_default_foo, None);
+    assert_eq!(layer.optional_bar.as_deref(), Some("bar"));
+    assert_eq!(layer.nested.required_baz, Some(true));
+}
+
+#[test]
+fn assert_layers_eq_lists_provided_fields() {
+    soukousei::assert_layers_eq!(
+        ComparableLayer { port: Some(1) },
+        ComparableLayer { port: Some(1) }
+    );
+
+    let message = std::panic::catch_unwind(|| {
+        soukousei::assert_layers_eq!(ComparableLayer { port: Some(1) }, ComparableLayer::new());
+    })
+    .unwrap_err()
+    .downcast::<String>()
+    .unwrap();
+
+    assert!(message.contains("only left provides: port"), "{message}");
+}
+
+#[test]
+fn provided_fields_include_nested_paths() {
+    let layer = SampleLayer {
+        with_default_foo: Some(1),
+        optional_bar: None,
+        nested: NestedLayer {
+            required_baz: Some(true),
+        },
+    };
+
+    assert_eq!(
+        layer.provided_fields(),
+        vec!["with_default_foo", "nested.required_baz"]
+    );
+}
+
+#[derive(Debug, Layer)]
+struct Retries {
+    #[layer(default = "3")]
+    retries: Option<u32>,
+}
+
+#[test]
+fn default_of_optional_applies_when_absent() {
+    let value = RetriesLayer::default()
+        .merge(toml::from_str("").unwrap())
+        .complete()
+        .unwrap();
+
+    assert_eq!(value.retries, Some(3));
+}
+
+#[test]
+fn explicit_null_overrides_default_of_optional() {
+    let value = RetriesLayer::default()
+        .merge(serde_json::from_str(r#"{ "retries": null }"#).unwrap())
+        .complete()
+        .unwrap();
+
+    assert_eq!(value.retries, None);
+}
+
+#[derive(Debug, Layer)]
+#[layer(assert_send_sync)]
+struct AppState {
+    name: String,
+    #[layer(nested)]
+    limits: Limits,
+}
+
+#[derive(Debug, Layer)]
+#[layer(assert_send_sync)]
+struct Limits {
+    #[layer(default = "10")]
+    max_connections: u32,
+}
+
+#[test]
+fn send_sync_config_is_shared_between_threads() {
+    let config = std::sync::Arc::new(AppState {
+        name: "app".to_owned(),
+        limits: Limits {
+            max_connections: 10,
+        },
+    });
+
+    let name = std::thread::spawn({
+        let config = config.clone();
+        move || config.name.clone()
+    })
+    .join()
+    .unwrap();
+
+    assert_eq!(name, "app");
+    assert_eq!(config.limits.max_connections, 10);
+}
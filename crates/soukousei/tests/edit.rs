@@ -0,0 +1,60 @@
+#![cfg(feature = "edit")]
+#![allow(dead_code)]
+
+use soukousei::edit::apply_layer_to_str;
+use soukousei::Layer;
+
+#[derive(Debug, Layer)]
+struct Config {
+    port: u16,
+    host: String,
+    #[layer(nested)]
+    database: Database,
+}
+
+#[derive(Debug, Layer)]
+struct Database {
+    url: String,
+    pool: Option<u32>,
+}
+
+const DOCUMENT: &str = r#"# Server settings
+port = 8080 # the public one
+host = "localhost"
+
+[database]
+# Connection string
+url = "postgres://localhost"
+"#;
+
+#[test]
+fn comments_and_formatting_are_preserved() {
+    let mut layer = ConfigLayer::new();
+    layer.port = Some(3000);
+    layer.database.url = Some("postgres://db".to_owned());
+
+    let updated = apply_layer_to_str(DOCUMENT, &layer).unwrap();
+
+    assert_eq!(
+        updated,
+        r#"# Server settings
+port = 3000 # the public one
+host = "localhost"
+
+[database]
+# Connection string
+url = "postgres://db"
+"#
+    );
+}
+
+#[test]
+fn new_keys_are_appended() {
+    let mut layer = ConfigLayer::new();
+    layer.database.pool = Some(16);
+
+    let updated = apply_layer_to_str("port = 8080\n", &layer).unwrap();
+
+    assert!(updated.starts_with("port = 8080\n"), "{updated}");
+    assert!(updated.contains("[database]\npool = 16\n"), "{updated}");
+}
@@ -0,0 +1,43 @@
+use soukousei::builder::ConfigBuilder;
+use soukousei::{include_config, Layer};
+
+#[derive(Debug, Layer)]
+struct App {
+    port: u16,
+    #[layer(nested)]
+    database: Database,
+}
+
+#[derive(Debug, Layer)]
+struct Database {
+    host: String,
+    #[layer(default = "5432")]
+    port: u16,
+}
+
+const DEFAULTS: soukousei::source::Embedded<AppLayer> =
+    include_config!(App, "tests/data/defaults.toml");
+
+#[test]
+fn embedded_defaults_are_merged() {
+    let app = ConfigBuilder::<AppLayer>::new()
+        .with_defaults()
+        .with_embedded(DEFAULTS)
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(app.port, 8080);
+    assert_eq!(app.database.host, "localhost");
+    assert_eq!(app.database.port, 5432);
+}
+
+#[test]
+fn nested_paths_are_known() {
+    use soukousei::meta::has_path;
+
+    assert!(has_path(AppLayer::FIELDS, "database.host"));
+    assert!(has_path(AppLayer::FIELDS, "database"));
+    assert!(!has_path(AppLayer::FIELDS, "database.hots"));
+    assert!(!has_path(AppLayer::FIELDS, "port.foo"));
+}
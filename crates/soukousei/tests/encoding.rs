@@ -0,0 +1,55 @@
+mod util;
+
+use soukousei::builder::{BuildWarning, ConfigBuilder};
+use soukousei::source::SourceError;
+use soukousei::Layer;
+use util::TempFile;
+
+#[derive(Debug, Layer)]
+struct Config {
+    name: String,
+}
+
+#[test]
+fn byte_order_mark_is_skipped() {
+    let file = TempFile::new("bom.toml", b"\xEF\xBB\xBFname = \"app\"");
+    let path = file.path();
+
+    let builder = ConfigBuilder::<ConfigLayer>::new().with_file(path).unwrap();
+
+    assert!(builder.warnings().is_empty());
+    assert_eq!(builder.build().unwrap().name, "app");
+}
+
+#[test]
+fn non_utf8_file_fails_by_default() {
+    let file = TempFile::new("latin1-strict.toml", b"name = \"caf\xE9\"");
+    let path = file.path();
+
+    let err = ConfigBuilder::<ConfigLayer>::new()
+        .with_file(path)
+        .err()
+        .unwrap();
+
+    let Some(SourceError::Io { source, .. }) = err.source_error() else {
+        panic!("expected an IO error, got {err:?}")
+    };
+    assert_eq!(source.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn non_utf8_file_is_read_as_latin1_with_a_warning() {
+    let file = TempFile::new("latin1.toml", b"name = \"caf\xE9\"");
+    let path = file.path();
+
+    let builder = ConfigBuilder::<ConfigLayer>::new()
+        .allow_latin1()
+        .with_file(path)
+        .unwrap();
+
+    let [BuildWarning::NotUtf8 { source_name }] = builder.warnings() else {
+        panic!("expected a warning, got {:?}", builder.warnings())
+    };
+    assert_eq!(source_name, &path.display().to_string());
+    assert_eq!(builder.build().unwrap().name, "café");
+}
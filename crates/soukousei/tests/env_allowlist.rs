@@ -0,0 +1,88 @@
+#![allow(dead_code)]
+
+mod util;
+
+use soukousei::builder::{BuildError, ConfigBuilder};
+use soukousei::source::Format;
+use soukousei::Layer;
+use util::TestEnv;
+
+#[derive(Debug, Layer)]
+struct Config {
+    #[layer(env = "PORT")]
+    port: u16,
+    #[layer(nested)]
+    db: Database,
+    #[layer(nested)]
+    log: Log,
+}
+
+#[derive(Debug, Layer)]
+struct Database {
+    #[layer(env = "DB_HOST")]
+    host: String,
+    #[layer(env = "DB_PASSWORD", sensitive_file)]
+    password: String,
+}
+
+#[derive(Debug, Layer)]
+struct Log {
+    #[layer(env = "LOG_LEVEL")]
+    level: String,
+}
+
+const FILE: &str = r#"
+port = 8080
+
+[db]
+host = "localhost"
+password = "file"
+
+[log]
+level = "info"
+"#;
+
+fn builder() -> ConfigBuilder<ConfigLayer> {
+    ConfigBuilder::<ConfigLayer>::new()
+        .env_allowlist(["db.password", "log"])
+        .with_str("config.toml", Format::Toml, FILE)
+        .unwrap()
+}
+
+#[test]
+fn allowed_fields_are_overridden() {
+    let env = TestEnv::new()
+        .add("DB_PASSWORD", "env")
+        .add("LOG_LEVEL", "debug");
+
+    let config = builder().with_env(&env).unwrap().build().unwrap();
+
+    assert_eq!(config.db.password, "env");
+    assert_eq!(config.log.level, "debug");
+    assert_eq!(config.port, 8080);
+}
+
+#[test]
+fn other_fields_are_rejected() {
+    let env = TestEnv::new()
+        .add("PORT", "80")
+        .add("DB_HOST", "db.internal")
+        .add("LOG_LEVEL", "debug");
+
+    let Err(BuildError::EnvNotAllowed(err)) = builder().with_env(&env) else {
+        panic!("expected ENV to be rejected")
+    };
+
+    assert_eq!(err.fields().collect::<Vec<_>>(), ["port", "db.host"]);
+    assert_eq!(
+        err.to_string(),
+        "ENV vars are not allowed to set `port` (with PORT), `db.host` (with DB_HOST)"
+    );
+}
+
+#[test]
+fn path_file_of_allowed_field_is_allowed() {
+    let env = TestEnv::new().add("DB_PASSWORD_FILE", "/nonexistent");
+
+    assert!(builder().with_env(&env).is_ok());
+}
@@ -0,0 +1,41 @@
+mod util;
+
+use soukousei::env::FromEnv;
+use soukousei::{EnvEnum, Layer};
+use util::TestEnv;
+
+#[derive(Debug, Layer)]
+struct Logger {
+    #[layer(env = "LOG_FORMAT", env_enum, default = "LogFormat::Pretty")]
+    format: LogFormat,
+}
+
+#[derive(Debug, PartialEq, EnvEnum, serde::Deserialize, serde::Serialize)]
+enum LogFormat {
+    Json,
+    Pretty,
+}
+
+#[test]
+fn parsed_case_insensitively() {
+    let env = TestEnv::new().add("LOG_FORMAT", "JSON");
+
+    let logger = LoggerLayer::from_env(&env).unwrap().complete().unwrap();
+
+    assert_eq!(logger.format, LogFormat::Json);
+}
+
+#[test]
+fn unknown_value_lists_allowed() {
+    let env = TestEnv::new().add("LOG_FORMAT", "yaml");
+
+    let Err(err) = LoggerLayer::from_env(&env) else {
+        panic!("expected an error")
+    };
+
+    let message = err.iter().next().unwrap().value().to_string();
+    assert_eq!(
+        message,
+        "Failed to read ENV var `LOG_FORMAT`: unknown value `yaml`, expected one of: json, pretty"
+    );
+}
@@ -0,0 +1,97 @@
+mod util;
+
+use soukousei::env::FromEnv;
+use soukousei::env_export::EnvExport;
+use soukousei::{EnvEnum, Layer};
+use util::TestEnv;
+
+#[derive(Debug, Layer)]
+#[layer(env_export)]
+struct Config {
+    #[layer(env = ["PORT", "APP_PORT"], default = "8080")]
+    port: u16,
+    #[layer(env = "NAME")]
+    name: Option<String>,
+    #[layer(env = "LOG_FORMAT", env_enum, default = "LogFormat::Pretty")]
+    log_format: LogFormat,
+    #[layer(env = "DB_PASSWORD", secret)]
+    db_password: String,
+    retries: u32,
+    #[layer(nested)]
+    cache: Cache,
+}
+
+#[derive(Debug, Layer)]
+#[layer(env_export)]
+struct Cache {
+    #[layer(env = "CACHE_URL")]
+    url: String,
+}
+
+#[derive(Debug, EnvEnum, serde::Deserialize, serde::Serialize)]
+enum LogFormat {
+    Json,
+    Pretty,
+}
+
+fn config() -> Config {
+    let mut layer = ConfigLayer {
+        log_format: Some(LogFormat::Json),
+        db_password: Some("hunter2".to_owned()),
+        retries: Some(3),
+        ..ConfigLayer::default()
+    };
+    layer.cache.url = Some("redis://localhost".to_owned());
+    layer.complete().unwrap()
+}
+
+fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+#[test]
+fn secrets_are_skipped_by_default() {
+    assert_eq!(
+        config().to_env_vars(""),
+        vars(&[
+            ("PORT", "8080"),
+            ("LOG_FORMAT", "json"),
+            ("CACHE_URL", "redis://localhost"),
+        ])
+    );
+}
+
+#[test]
+fn secrets_are_included_on_request() {
+    let exported = config().to_env_vars_with_secrets("");
+
+    assert!(exported.contains(&("DB_PASSWORD".to_owned(), "hunter2".to_owned())));
+}
+
+#[test]
+fn names_are_prefixed() {
+    let exported = config().to_env_vars("Environment=");
+
+    assert_eq!(
+        exported[0],
+        ("Environment=PORT".to_owned(), "8080".to_owned())
+    );
+}
+
+#[test]
+fn export_reproduces_config() {
+    let exported = config().to_env_vars_with_secrets("");
+    let env = exported
+        .iter()
+        .fold(TestEnv::new(), |env, (key, value)| env.add(key, value));
+
+    let mut layer = ConfigLayer::from_env(&env).unwrap();
+    layer.retries = Some(3);
+    let restored = layer.complete().unwrap();
+
+    assert_eq!(restored.retries, 3);
+    assert_eq!(restored.to_env_vars_with_secrets(""), exported);
+}
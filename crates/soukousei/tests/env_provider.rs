@@ -0,0 +1,130 @@
+mod util;
+
+use soukousei::env::{Chain, EnvProvider, FileSuffix, StdEnv};
+use util::{TempFile, TestEnv};
+
+#[test]
+fn std_env_iter_prefixed() {
+    std::env::set_var("SOUKOUSEI_ITER_TEST_B", "2");
+    std::env::set_var("SOUKOUSEI_ITER_TEST_A", "1");
+
+    let vars = StdEnv::new().iter_prefixed("SOUKOUSEI_ITER_TEST_").unwrap();
+
+    assert_eq!(
+        vars,
+        vec![
+            ("SOUKOUSEI_ITER_TEST_A".to_owned(), "1".to_owned()),
+            ("SOUKOUSEI_ITER_TEST_B".to_owned(), "2".to_owned()),
+        ]
+    );
+}
+
+#[test]
+fn chain_prefers_first_provider() {
+    let env = Chain(
+        TestEnv::new().add("APP_PORT", "1"),
+        TestEnv::new().add("APP_PORT", "2").add("APP_HOST", "b"),
+    );
+
+    assert_eq!(env.fetch("APP_PORT").unwrap().as_deref(), Some("1"));
+    assert_eq!(env.fetch("APP_HOST").unwrap().as_deref(), Some("b"));
+    assert_eq!(
+        env.iter_prefixed("APP_").unwrap(),
+        vec![
+            ("APP_HOST".to_owned(), "b".to_owned()),
+            ("APP_PORT".to_owned(), "1".to_owned()),
+        ]
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn path_fields_accept_non_unicode() {
+    use soukousei::env::FromEnv;
+    use soukousei::{Layer, Report};
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt;
+    use std::path::PathBuf;
+
+    #[derive(Debug, Layer)]
+    struct Paths {
+        #[layer(env = "DATA_DIR")]
+        data_dir: PathBuf,
+    }
+
+    struct NonUnicodeEnv;
+
+    impl EnvProvider for NonUnicodeEnv {
+        fn fetch(&self, key: impl AsRef<str>) -> Result<Option<String>, Report> {
+            Err(miette::miette!("`{}` is not unicode", key.as_ref()))
+        }
+
+        fn fetch_os(&self, _key: impl AsRef<str>) -> Result<Option<OsString>, Report> {
+            Ok(Some(OsString::from_vec(b"/data/\xff".to_vec())))
+        }
+    }
+
+    let paths = PathsLayer::from_env(&NonUnicodeEnv)
+        .unwrap()
+        .complete()
+        .unwrap();
+
+    assert_eq!(
+        paths.data_dir,
+        PathBuf::from(OsString::from_vec(b"/data/\xff".to_vec()))
+    );
+}
+
+#[test]
+fn file_suffix_reads_value_from_file() {
+    let file = TempFile::new("db-password", "hunter2\n");
+    let path = file.path().display().to_string();
+    let env = FileSuffix(TestEnv::new().add("DB_PASSWORD_FILE", &path));
+
+    assert_eq!(
+        env.fetch("DB_PASSWORD").unwrap().as_deref(),
+        Some("hunter2")
+    );
+    assert_eq!(
+        env.iter_prefixed("DB_").unwrap(),
+        vec![("DB_PASSWORD".to_owned(), "hunter2".to_owned())]
+    );
+}
+
+#[test]
+fn file_suffix_rejects_conflicting_values() {
+    let file = TempFile::new("db-conflict", "hunter2");
+    let path = file.path().display().to_string();
+    let env = FileSuffix(
+        TestEnv::new()
+            .add("DB_PASSWORD", "direct")
+            .add("DB_PASSWORD_FILE", &path),
+    );
+
+    let err = env.fetch("DB_PASSWORD").unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "both `DB_PASSWORD` and `DB_PASSWORD_FILE` are set"
+    );
+}
+
+#[test]
+fn file_suffix_reports_unreadable_file() {
+    let env = FileSuffix(TestEnv::new().add("DB_PASSWORD_FILE", "/nonexistent/secret"));
+
+    let err = env.fetch("DB_PASSWORD").unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "failed to read `/nonexistent/secret` set in `DB_PASSWORD_FILE`"
+    );
+}
+
+#[test]
+fn file_suffix_passes_plain_values_through() {
+    let env = FileSuffix(TestEnv::new().add("DB_HOST", "localhost"));
+
+    assert_eq!(env.fetch("DB_HOST").unwrap().as_deref(), Some("localhost"));
+    assert_eq!(env.fetch("DB_PORT").unwrap(), None);
+}
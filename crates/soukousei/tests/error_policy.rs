@@ -0,0 +1,88 @@
+#![allow(dead_code)]
+
+use soukousei::builder::{BuildError, ConfigBuilder};
+use soukousei::{CompleteError, CompleteErrorDiagnostic, ErrorPolicy, Layer};
+
+#[derive(Debug, Layer)]
+struct Sample {
+    port: u32,
+    #[layer(nested)]
+    nested: Nested,
+    workers: u32,
+}
+
+#[derive(Debug, Layer)]
+struct Nested {
+    timeout: u32,
+    retries: u32,
+}
+
+#[derive(Debug, Layer)]
+#[layer(fail_fast)]
+struct Giant {
+    #[layer(nested)]
+    sections: Vec<Nested>,
+    last: u32,
+}
+
+fn paths(err: CompleteError) -> (Option<ErrorPolicy>, Vec<String>) {
+    let policy = err.policy();
+    let CompleteError::Fields(errors) = err else {
+        panic!("expected field errors")
+    };
+    (policy, errors.iter().map(|x| x.joined_path()).collect())
+}
+
+#[test]
+fn all_errors_are_collected_by_default() {
+    let (policy, paths) = paths(Sample::layer().complete().unwrap_err());
+
+    assert_eq!(policy, Some(ErrorPolicy::CollectAll));
+    assert_eq!(
+        paths,
+        ["port", "nested.timeout", "nested.retries", "workers"]
+    );
+}
+
+#[test]
+fn fail_fast_stops_at_first_error() {
+    let mut layer = Sample::layer();
+    layer.port = Some(1);
+
+    let (policy, paths) = paths(layer.complete_with(ErrorPolicy::FailFast).unwrap_err());
+
+    assert_eq!(policy, Some(ErrorPolicy::FailFast));
+    assert_eq!(paths, ["nested.timeout"]);
+}
+
+#[test]
+fn fail_fast_is_set_with_derive() {
+    let layer = GiantLayer {
+        sections: soukousei::collection::VecLayer(Some(vec![
+            NestedLayer::new(),
+            NestedLayer::new(),
+        ])),
+        last: None,
+    };
+
+    let (policy, paths) = paths(layer.complete().unwrap_err());
+
+    assert_eq!(policy, Some(ErrorPolicy::FailFast));
+    assert_eq!(paths, ["sections[0].timeout"]);
+}
+
+#[test]
+fn fail_fast_is_set_with_builder() {
+    let Err(BuildError::Complete(CompleteErrorDiagnostic::Fields { fields })) =
+        ConfigBuilder::<SampleLayer>::new().fail_fast().build()
+    else {
+        panic!("expected field errors")
+    };
+
+    assert_eq!(fields.len(), 1);
+}
+
+#[test]
+fn single_errors_have_no_policy() {
+    assert_eq!(CompleteError::MissingData.policy(), None);
+}
@@ -0,0 +1,86 @@
+use soukousei::{
+    CompleteError, CompleteErrorDiagnostic, CompleteFieldError, FieldErrorDiagnostic,
+    MissingFieldError, MultipleFieldsError, PathSegment,
+};
+
+fn sample_errors() -> MultipleFieldsError<CompleteFieldError> {
+    let nested = MultipleFieldsError::new()
+        .add(MissingFieldError::new().into(), "foo")
+        .add(MissingFieldError::new().into(), "bar");
+
+    MultipleFieldsError::new()
+        .add(MissingFieldError::new().into(), "baz")
+        .nest(nested, "nested")
+        .add(MissingFieldError::new().into(), "qux")
+}
+
+#[test]
+fn bunch_summary_groups_by_section() {
+    let bunch = sample_errors().into_diagnostic();
+
+    assert_eq!(bunch.len(), 4);
+    assert_eq!(
+        bunch.sections(),
+        vec![("baz", 1), ("nested", 2), ("qux", 1)]
+    );
+    assert_eq!(
+        bunch.to_string(),
+        "4 configuration errors (baz: 1, nested: 2, qux: 1)"
+    );
+}
+
+#[test]
+fn bunch_renders_truncation_marker() {
+    use soukousei::miette::Diagnostic;
+
+    let bunch = sample_errors().into_diagnostic().max_rendered(1);
+
+    let related: Vec<_> = bunch.related().unwrap().map(|x| x.to_string()).collect();
+
+    assert_eq!(related, vec!["baz: Missing field", "… and 3 more"]);
+}
+
+#[test]
+fn missing_field_help_suggests_env() {
+    let nested = MultipleFieldsError::new().add_if_none_with_env(
+        &None::<u32>,
+        "foo",
+        &["SPECIFIC_FOO", "FOO"],
+    );
+    let errors = MultipleFieldsError::new()
+        .add_if_none(&None::<u32>, "bar")
+        .nest(nested, "nested");
+
+    let CompleteErrorDiagnostic::Fields { fields } =
+        CompleteErrorDiagnostic::from(CompleteError::from(errors))
+    else {
+        panic!("expected missing fields");
+    };
+
+    let help: Vec<_> = fields
+        .iter()
+        .map(|x| match x {
+            FieldErrorDiagnostic::Missing(x) => x.help(),
+            other => panic!("unexpected error: {other}"),
+        })
+        .collect();
+    assert_eq!(
+        help,
+        vec![
+            "set `bar` in a config file",
+            "set `nested.foo` in a config file or export `SPECIFIC_FOO` or `FOO`",
+        ]
+    );
+}
+
+#[test]
+fn runtime_path_segments() {
+    let field = format!("replica_{}", 1);
+    let errors = MultipleFieldsError::<CompleteFieldError>::new().nest(
+        MultipleFieldsError::new().add(MissingFieldError::new().into(), PathSegment::field(field)),
+        PathSegment::key("acme"),
+    );
+
+    let paths: Vec<_> = errors.iter().map(|x| x.joined_path()).collect();
+    assert_eq!(paths, vec!["[\"acme\"].replica_1"]);
+}
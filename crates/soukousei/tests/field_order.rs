@@ -0,0 +1,119 @@
+//! Regression tests for the order of fields in generated layers, which serde relies on when
+//! serializing and when a `flatten` field is involved
+
+#![cfg(feature = "json")]
+#![allow(dead_code)]
+
+use soukousei::source::Format;
+use soukousei::Layer;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Layer)]
+struct Tricky {
+    #[layer(nested)]
+    first: Section,
+    #[layer(default = "8080")]
+    port: u16,
+    #[layer(flatten)]
+    extra: BTreeMap<String, String>,
+    #[layer(env = "TOKEN", sensitive_file)]
+    token: String,
+    #[layer(default = "\"info\".to_owned()")]
+    level: Option<String>,
+    #[layer(nested)]
+    last: Section,
+}
+
+#[derive(Debug, Layer)]
+struct Section {
+    name: Option<String>,
+}
+
+fn parse(contents: &str) -> TrickyLayer {
+    Format::Json.parse(contents).unwrap().value
+}
+
+/// Top-level keys of a JSON object, in order of their appearance
+fn keys(json: &str) -> Vec<String> {
+    let value: serde_json::Value = serde_json::from_str(json).unwrap();
+    let mut keys: Vec<_> = value
+        .as_object()
+        .unwrap()
+        .keys()
+        .map(|key| (json.find(&format!("\"{key}\":")).unwrap(), key.clone()))
+        .collect();
+    keys.sort();
+    keys.into_iter().map(|(_, key)| key).collect()
+}
+
+#[test]
+fn fields_are_serialized_in_declaration_order() {
+    let mut layer = TrickyLayer {
+        token_file: Some("/run/secrets/token".into()),
+        ..TrickyLayer::default()
+    };
+    layer.extra.insert("plugin".to_owned(), "on".to_owned());
+
+    let json = serde_json::to_string(&layer).unwrap();
+
+    assert_eq!(
+        keys(&json),
+        [
+            "first",
+            "port",
+            "plugin",
+            "token",
+            "token_file",
+            "level",
+            "last"
+        ]
+    );
+    let names: Vec<_> = TrickyLayer::FIELDS.iter().map(|x| x.name).collect();
+    assert_eq!(
+        names,
+        [
+            "first",
+            "port",
+            "extra",
+            "token",
+            "token_file",
+            "level",
+            "last"
+        ]
+    );
+}
+
+#[test]
+fn order_of_keys_in_source_does_not_matter() {
+    let forward = parse(
+        r#"{ "first": { "name": "a" }, "port": 1, "plugin": "on", "token": "t", "level": null, "last": { "name": "b" } }"#,
+    );
+    let backward = parse(
+        r#"{ "last": { "name": "b" }, "level": null, "token": "t", "plugin": "on", "port": 1, "first": { "name": "a" } }"#,
+    );
+
+    assert_eq!(
+        serde_json::to_string(&forward).unwrap(),
+        serde_json::to_string(&backward).unwrap()
+    );
+}
+
+#[test]
+fn catch_all_takes_only_unknown_keys() {
+    let layer = parse(r#"{ "zzz": "1", "port": 2, "aaa": "3", "token_file": "/t", "mmm": "4" }"#);
+
+    assert_eq!(layer.port, Some(2));
+    assert_eq!(layer.token_file, Some("/t".into()));
+    assert_eq!(
+        layer.extra.keys().collect::<Vec<_>>(),
+        ["aaa", "mmm", "zzz"]
+    );
+}
+
+#[test]
+fn explicit_null_overrides_default_next_to_catch_all() {
+    let layer = TrickyLayer::default().merge(parse(r#"{ "level": null }"#));
+
+    assert_eq!(layer.level, Some(None));
+    assert!(layer.extra.is_empty());
+}
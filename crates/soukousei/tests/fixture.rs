@@ -0,0 +1,73 @@
+#![cfg(feature = "fuzz")]
+
+use soukousei::fixture;
+use soukousei::toggle::Toggle;
+use soukousei::Layer;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+#[derive(Debug, PartialEq, Layer)]
+struct Config {
+    name: String,
+    verbose: bool,
+    workers: Option<u32>,
+    listen: SocketAddr,
+    tags: Vec<String>,
+    #[layer(nested)]
+    db: Db,
+    #[layer(nested)]
+    replicas: HashMap<String, Db>,
+    #[layer(toggle)]
+    metrics: Toggle<Metrics>,
+}
+
+#[derive(Debug, PartialEq, Layer)]
+struct Db {
+    host: String,
+    port: u16,
+}
+
+#[derive(Debug, PartialEq, Layer)]
+struct Metrics {
+    endpoint: String,
+}
+
+#[test]
+fn every_field_is_populated() {
+    let config = ConfigLayer::arbitrary(42).complete().unwrap();
+
+    assert!(config.name.starts_with("name-"));
+    assert!(config.workers.is_some());
+    assert_eq!(config.tags.len(), 1);
+    assert!(config.db.host.starts_with("host-"));
+    assert!(config.db.port > 0);
+    assert_eq!(config.replicas.len(), 1);
+    assert!(matches!(config.metrics, Toggle::Enabled(_)));
+}
+
+#[test]
+fn same_seed_gives_same_layer() {
+    let complete = |seed| ConfigLayer::arbitrary(seed).complete().unwrap();
+
+    assert_eq!(complete(7), complete(7));
+    assert_ne!(complete(7), complete(8));
+}
+
+#[test]
+fn fields_are_set_on_top() {
+    let config = ConfigLayer::arbitrary(1)
+        .merge(soukousei::layer!(Config { db.port: 5432u16 }))
+        .complete()
+        .unwrap();
+
+    assert_eq!(config.db.port, 5432);
+}
+
+#[test]
+fn layers_are_taken_from_unstructured_data() {
+    let mut u = arbitrary::Unstructured::new(&[1, 2, 3, 4, 5, 6, 7, 8]);
+
+    let layer: ConfigLayer = fixture::from_unstructured(&mut u).unwrap();
+
+    assert!(layer.complete().is_ok());
+}
@@ -0,0 +1,87 @@
+#![cfg(feature = "json")]
+
+mod util;
+
+use soukousei::builder::ConfigBuilder;
+use soukousei::source::Format;
+use soukousei::Layer;
+use std::collections::HashMap;
+use util::TestEnv;
+
+#[derive(Debug, Layer)]
+struct Sample {
+    #[layer(env = "PORT")]
+    port: u16,
+    #[layer(nested)]
+    plugins: Plugins,
+}
+
+#[derive(Debug, Layer)]
+struct Plugins {
+    #[layer(default = "true")]
+    enabled: bool,
+    #[layer(flatten)]
+    extra: HashMap<String, String>,
+}
+
+#[test]
+fn unknown_keys_are_captured() {
+    let sample = ConfigBuilder::<SampleLayer>::new()
+        .with_defaults()
+        .with_str(
+            "config.toml",
+            Format::Toml,
+            "port = 8080\n[plugins]\ncache = \"redis\"\nauth = \"oidc\"",
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert!(sample.plugins.enabled);
+    assert_eq!(
+        sample.plugins.extra,
+        HashMap::from([
+            ("cache".to_owned(), "redis".to_owned()),
+            ("auth".to_owned(), "oidc".to_owned()),
+        ])
+    );
+}
+
+#[test]
+fn captured_keys_are_merged_across_sources() {
+    let sample = ConfigBuilder::<SampleLayer>::new()
+        .with_defaults()
+        .with_str(
+            "base.toml",
+            Format::Toml,
+            "port = 8080\n[plugins]\ncache = \"redis\"\nauth = \"oidc\"",
+        )
+        .unwrap()
+        .with_str(
+            "local.json",
+            Format::Json,
+            r#"{ "plugins": { "cache": "memory" } }"#,
+        )
+        .unwrap()
+        .with_env(&TestEnv::new().add("PORT", "3000"))
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(sample.port, 3000);
+    assert_eq!(sample.plugins.extra["cache"], "memory");
+    assert_eq!(sample.plugins.extra["auth"], "oidc");
+}
+
+#[test]
+fn captured_keys_are_listed() {
+    let layer: SampleLayer = Format::Toml
+        .parse("[plugins]\nenabled = false\ncache = \"redis\"")
+        .unwrap()
+        .value;
+
+    assert_eq!(layer.captured_keys(), vec!["plugins.cache"]);
+    assert!(layer
+        .provided_fields()
+        .contains(&"plugins.cache".to_owned()));
+}
@@ -0,0 +1,152 @@
+use soukousei::builder::ConfigBuilder;
+use soukousei::freeze::{Cipher, Freeze, FreezeError, ENCRYPTED_PREFIX};
+use soukousei::source::Format;
+use soukousei::{Layer, Report};
+
+#[derive(Debug, Layer)]
+struct Sample {
+    port: u16,
+    #[layer(secret)]
+    token: String,
+    #[layer(nested)]
+    db: Database,
+}
+
+#[derive(Debug, Layer)]
+struct Database {
+    user: String,
+    #[layer(secret)]
+    password: String,
+}
+
+/// Not a real cipher, but it checks the associated data like one
+struct Reversed;
+
+impl Cipher for Reversed {
+    fn encrypt(&self, plaintext: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Report> {
+        let mut out = associated_data.to_vec();
+        out.push(0);
+        out.extend(plaintext.iter().rev());
+        Ok(out)
+    }
+
+    fn decrypt(&self, ciphertext: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Report> {
+        let (data, plaintext) = ciphertext.split_at(associated_data.len());
+        if data != associated_data || plaintext.first() != Some(&0) {
+            return Err(miette::miette!("authentication failed"));
+        }
+        Ok(plaintext[1..].iter().rev().copied().collect())
+    }
+}
+
+const CONFIG: &str = r#"
+    port = 8080
+    token = "t0ken"
+
+    [db]
+    user = "admin"
+    password = "qwerty"
+"#;
+
+fn builder() -> ConfigBuilder<SampleLayer> {
+    ConfigBuilder::new()
+        .with_str("config.toml", Format::Toml, CONFIG)
+        .unwrap()
+}
+
+#[test]
+fn secrets_are_encrypted() {
+    let frozen = builder()
+        .freeze(&Freeze::new().with_cipher(Reversed))
+        .unwrap();
+
+    assert!(frozen.contains("port = 8080"), "{frozen}");
+    assert!(frozen.contains(r#"user = "admin""#), "{frozen}");
+    assert!(!frozen.contains("t0ken"), "{frozen}");
+    assert!(!frozen.contains("qwerty"), "{frozen}");
+    assert_eq!(frozen.matches(ENCRYPTED_PREFIX).count(), 2, "{frozen}");
+}
+
+#[test]
+fn frozen_layer_is_read_back() {
+    let freeze = Freeze::new().with_cipher(Reversed);
+    let frozen = builder().freeze(&freeze).unwrap();
+
+    let sample = ConfigBuilder::<SampleLayer>::new()
+        .with_named_layer("frozen", freeze.from_str(&frozen).unwrap())
+        .build()
+        .unwrap();
+
+    assert_eq!(sample.port, 8080);
+    assert_eq!(sample.token, "t0ken");
+    assert_eq!(sample.db.user, "admin");
+    assert_eq!(sample.db.password, "qwerty");
+}
+
+#[test]
+fn plaintext_secrets_are_refused() {
+    let err = builder().freeze(&Freeze::new()).unwrap_err();
+
+    let FreezeError::PlaintextSecret { path } = err else {
+        panic!("unexpected error: {err}")
+    };
+    assert!(["token", "db.password"].contains(&path.as_str()), "{path}");
+}
+
+#[test]
+fn plaintext_secrets_might_be_allowed() {
+    let freeze = Freeze::new().allow_plaintext_secrets();
+    let frozen = builder().freeze(&freeze).unwrap();
+
+    assert!(frozen.contains(r#"password = "qwerty""#), "{frozen}");
+    let layer: SampleLayer = freeze.from_str(&frozen).unwrap();
+    assert_eq!(layer.complete().unwrap().token, "t0ken");
+}
+
+#[test]
+fn encrypted_value_is_bound_to_its_field() {
+    let freeze = Freeze::new().with_cipher(Reversed);
+    let frozen = builder().freeze(&freeze).unwrap();
+    let mut value: toml::Table = toml::from_str(&frozen).unwrap();
+    let password = value["db"]["password"].clone();
+    value.insert("token".to_owned(), password);
+
+    let Err(err) = freeze.from_str::<SampleLayer>(&toml::to_string(&value).unwrap()) else {
+        panic!("expected an error")
+    };
+
+    assert!(
+        matches!(&err, FreezeError::Decrypt { path, .. } if path == "token"),
+        "{err}"
+    );
+}
+
+#[test]
+fn reading_encrypted_values_requires_a_cipher() {
+    let frozen = builder()
+        .freeze(&Freeze::new().with_cipher(Reversed))
+        .unwrap();
+
+    let Err(err) = Freeze::new().from_str::<SampleLayer>(&frozen) else {
+        panic!("expected an error")
+    };
+
+    assert!(matches!(err, FreezeError::MissingCipher { .. }), "{err}");
+}
+
+#[test]
+fn plaintext_secrets_are_refused_on_read() {
+    let freeze = Freeze::new().with_cipher(Reversed);
+    let frozen = builder().freeze(&freeze).unwrap();
+    let mut value: toml::Table = toml::from_str(&frozen).unwrap();
+    value.insert("token".to_owned(), "swapped".into());
+
+    let Err(err) = freeze.from_str::<SampleLayer>(&toml::to_string(&value).unwrap()) else {
+        panic!("expected an error")
+    };
+
+    assert!(
+        matches!(&err, FreezeError::UnencryptedSecret { path } if path == "token"),
+        "{err}"
+    );
+}
@@ -0,0 +1,67 @@
+use soukousei::builder::ConfigBuilder;
+use soukousei::source::Format;
+use std::path::Path;
+
+mod config {
+    use soukousei::Layer;
+    use std::path::PathBuf;
+
+    #[derive(Debug, Layer)]
+    #[layer(getters)]
+    #[non_exhaustive]
+    pub struct Config {
+        /// Name of the service
+        name: String,
+        #[layer(default = r#"vec!["a".to_owned()]"#)]
+        tags: Vec<String>,
+        motd: Option<String>,
+        data_dir: PathBuf,
+        #[layer(default = "8080")]
+        port: u16,
+        #[layer(nested)]
+        limits: Limits,
+    }
+
+    #[derive(Debug, Layer)]
+    #[layer(getters)]
+    pub struct Limits {
+        #[layer(default = "10")]
+        connections: u32,
+    }
+}
+
+use config::{Config, ConfigLayer};
+
+fn sample() -> Config {
+    ConfigBuilder::<ConfigLayer>::new()
+        .with_defaults()
+        .with_str(
+            "config.toml",
+            Format::Toml,
+            "name = \"app\"\ndata_dir = \"/var/lib/app\"",
+        )
+        .unwrap()
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn getters_borrow_fields() {
+    let config = sample();
+
+    assert_eq!(config.name(), "app");
+    assert_eq!(config.tags(), ["a"]);
+    assert_eq!(config.motd(), None);
+    assert_eq!(config.data_dir(), Path::new("/var/lib/app"));
+    assert_eq!(*config.port(), 8080);
+    assert_eq!(*config.limits().connections(), 10);
+}
+
+#[test]
+fn setters_replace_fields() {
+    let config = sample().with_motd(Some("hello".to_owned())).with_port(3000);
+
+    assert_eq!(config.motd(), Some("hello"));
+    assert_eq!(*config.port(), 3000);
+    assert_eq!(config.name(), "app");
+}
@@ -1,38 +1,42 @@
 mod util;
 
-use soukousei::env::EnvProvider;
-use soukousei::Config;
-use std::collections::HashMap;
-use std::num::NonZeroU64;
-use toml::toml;
+use soukousei::env::FromEnv;
+use soukousei::Layer;
 use util::TestEnv;
 
-#[derive(Config)]
+#[derive(Debug, Layer)]
 struct Test {
-    #[param(default = "100")]
+    #[layer(default = "100")]
     with_default_foo: u32,
     optional_bar: Option<String>,
     required_baz: bool,
-    #[param(nested)]
+    #[layer(nested)]
     nested: Nested,
 }
 
-#[derive(Config)]
+#[derive(Debug, Layer)]
 struct Nested {
-    #[param(env = "FOO", default = r#""I am default foo!".to_owned()"#)]
+    #[layer(env = "FOO", default = r#""I am default foo!".to_owned()"#)]
     foo_env: String,
-    #[param(envs = ["SPECIFIC_BAR", "BAR"])]
+    #[layer(env = ["SPECIFIC_BAR", "BAR"])]
     bar_env_multiple: Option<u32>,
 }
 
 #[test]
 fn success_build_from_toml() {
-    const input: &str = r#"
+    const INPUT: &str = r#"
     required_baz = false
     "#;
 
-    let config: Test = Test::Partial::default()
-        .parse_and_merge(toml::de::ValueDeserializer::new(input))?
-        .parse_env_and_merge(TestEnv::new().add("FOO", "SELECT foo FROM env"))?
-        .resolve()?;
+    let mut layer = Test::layer()
+        .merge(toml::from_str(INPUT).unwrap())
+        .merge(TestLayer::from_env(&TestEnv::new().add("FOO", "SELECT foo FROM env")).unwrap());
+    layer.fill_defaults();
+    let config = layer.complete().unwrap();
+
+    assert_eq!(config.with_default_foo, 100);
+    assert_eq!(config.optional_bar, None);
+    assert!(!config.required_baz);
+    assert_eq!(config.nested.foo_env, "SELECT foo FROM env");
+    assert_eq!(config.nested.bar_env_multiple, None);
 }
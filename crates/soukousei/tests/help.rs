@@ -0,0 +1,113 @@
+#![allow(dead_code)]
+
+use soukousei::Layer;
+
+#[derive(Layer)]
+struct App {
+    /// Port to listen on
+    #[layer(default = "8080", env = "PORT")]
+    port: u16,
+    /// Database settings
+    #[layer(nested)]
+    database: Database,
+}
+
+#[derive(Layer)]
+struct Database {
+    #[layer(env = ["APP_DATABASE_URL", "DATABASE_URL"], secret)]
+    url: String,
+    pool_size: Option<u32>,
+}
+
+#[test]
+fn fields_metadata() {
+    let fields = AppLayer::fields();
+
+    assert_eq!(fields.len(), 2);
+    assert_eq!(fields[0].name, "port");
+    assert_eq!(fields[0].ty, "u16");
+    assert_eq!(fields[0].doc, Some("Port to listen on"));
+    assert_eq!(fields[0].default, Some("8080"));
+    assert_eq!(fields[0].env, &["PORT"]);
+
+    let nested = fields[1].nested_fields().unwrap();
+    assert_eq!(nested[1].ty, "Option<u32>");
+    assert!(nested[1].optional);
+}
+
+#[test]
+fn render_plain_help() {
+    assert_eq!(
+        AppLayer::render_help(false),
+        "\
+port: u16
+  Port to listen on
+  default: 8080
+  env: PORT
+database:
+  Database settings
+  url: String (secret)
+    env: APP_DATABASE_URL, DATABASE_URL
+  pool_size: Option<u32> (optional)
+"
+    );
+}
+
+#[test]
+fn completion_scripts() {
+    use soukousei::completion::Shell;
+
+    assert_eq!(
+        AppLayer::completion_script(Shell::Bash, "my-app"),
+        r#"_my_app_set() {
+    local cur="${COMP_WORDS[COMP_CWORD]}"
+    local prev="${COMP_WORDS[COMP_CWORD-1]}"
+    if [[ "$prev" == "--set" ]]; then
+        COMPREPLY=($(compgen -S '=' -W "port database.url database.pool_size" -- "$cur"))
+        compopt -o nospace
+    fi
+}
+complete -F _my_app_set my-app
+"#
+    );
+    assert_eq!(
+        AppLayer::completion_script(Shell::Fish, "my-app"),
+        "\
+complete -c my-app -l set -x -a 'port=' -d 'Port to listen on'
+complete -c my-app -l set -x -a 'database.url='
+complete -c my-app -l set -x -a 'database.pool_size='
+"
+    );
+}
+
+#[derive(Layer)]
+struct Server {
+    #[layer(group = "Networking", order = 2)]
+    port: u16,
+    #[layer(group = "Logging")]
+    log_level: String,
+    #[layer(group = "Networking", order = 1)]
+    host: String,
+    name: String,
+}
+
+#[test]
+fn fields_are_grouped_and_ordered() {
+    let groups = soukousei::meta::groups(ServerLayer::fields());
+
+    let names: Vec<_> = groups
+        .iter()
+        .map(|group| {
+            let fields: Vec<_> = group.fields.iter().map(|field| field.name).collect();
+            (group.name, fields)
+        })
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            (Some("Networking"), vec!["host", "port"]),
+            (Some("Logging"), vec!["log_level"]),
+            (None, vec!["name"]),
+        ]
+    );
+}
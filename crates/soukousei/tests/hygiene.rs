@@ -0,0 +1,50 @@
+//! Generated impls must not pick up user items which shadow prelude names, nor panic
+
+#![allow(dead_code)]
+
+#[deny(clippy::unwrap_used, clippy::expect_used)]
+mod app {
+    pub type Result<T> = std::result::Result<T, ()>;
+
+    pub struct Default;
+
+    pub enum Option {
+        Some,
+        None,
+    }
+
+    pub struct String;
+
+    pub struct Vec;
+
+    pub struct Layer;
+
+    #[derive(soukousei::Layer)]
+    pub struct Config {
+        #[layer(default = "8080", env = "PORT")]
+        pub port: u16,
+        pub name: std::option::Option<std::string::String>,
+        pub tags: std::vec::Vec<std::string::String>,
+        #[layer(nested)]
+        pub nested: Nested,
+    }
+
+    #[derive(soukousei::Layer)]
+    pub struct Nested {
+        #[layer(default = "true")]
+        pub flag: bool,
+    }
+}
+
+use soukousei::Layer;
+
+#[test]
+fn compiles_with_shadowed_prelude() {
+    let layer: app::ConfigLayer = toml::from_str("tags = [\"a\"]").unwrap();
+    let config = app::ConfigLayer::default().merge(layer).complete().unwrap();
+
+    assert_eq!(config.port, 8080);
+    assert!(config.name.is_none());
+    assert_eq!(config.tags, vec!["a".to_owned()]);
+    assert!(config.nested.flag);
+}
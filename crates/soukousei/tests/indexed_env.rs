@@ -0,0 +1,120 @@
+mod util;
+
+use soukousei::builder::{ConfigBuilder, UnknownEnv};
+use soukousei::meta;
+use soukousei::source::Format;
+use soukousei::Layer;
+use util::TestEnv;
+
+#[derive(Debug, Layer)]
+struct Sample {
+    #[layer(nested, env_indexed = "MYAPP_UPSTREAMS")]
+    upstreams: Vec<Upstream>,
+}
+
+#[derive(Debug, Layer)]
+struct Upstream {
+    #[layer(env = "ADDR")]
+    addr: String,
+    #[layer(env = "WEIGHT", default = "1")]
+    weight: u32,
+}
+
+#[test]
+fn elements_are_read_by_index() {
+    let sample = ConfigBuilder::<SampleLayer>::new()
+        .with_env(
+            &TestEnv::new()
+                .add("MYAPP_UPSTREAMS_0_ADDR", "10.0.0.1")
+                .add("MYAPP_UPSTREAMS_1_ADDR", "10.0.0.2")
+                .add("MYAPP_UPSTREAMS_1_WEIGHT", "5"),
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+
+    let upstreams: Vec<_> = sample
+        .upstreams
+        .iter()
+        .map(|x| (x.addr.as_str(), x.weight))
+        .collect();
+    assert_eq!(upstreams, [("10.0.0.1", 1), ("10.0.0.2", 5)]);
+}
+
+#[test]
+fn env_replaces_elements_from_files() {
+    let builder = ConfigBuilder::<SampleLayer>::new()
+        .with_str(
+            "config.toml",
+            Format::Toml,
+            "[[upstreams]]\naddr = \"a\"\n[[upstreams]]\naddr = \"b\"",
+        )
+        .unwrap();
+
+    let sample = builder
+        .with_env(&TestEnv::new().add("MYAPP_UPSTREAMS_0_ADDR", "c"))
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(sample.upstreams.len(), 1);
+    assert_eq!(sample.upstreams[0].addr, "c");
+}
+
+#[test]
+fn reading_stops_at_a_gap() {
+    let layer: SampleLayer = soukousei::env::FromEnv::from_env(
+        &TestEnv::new()
+            .add("MYAPP_UPSTREAMS_0_ADDR", "a")
+            .add("MYAPP_UPSTREAMS_2_ADDR", "c"),
+    )
+    .unwrap();
+
+    assert_eq!(layer.upstreams.0.map(|x| x.len()), Some(1));
+}
+
+#[test]
+fn errors_have_indexed_paths() {
+    let Err(errors) = <SampleLayer as soukousei::env::FromEnv>::from_env(
+        &TestEnv::new()
+            .add("MYAPP_UPSTREAMS_0_ADDR", "a")
+            .add("MYAPP_UPSTREAMS_0_WEIGHT", "heavy"),
+    ) else {
+        panic!("expected an error")
+    };
+
+    let paths: Vec<_> = errors.iter().map(|x| x.joined_path()).collect();
+    assert_eq!(paths, ["upstreams[0].weight"]);
+}
+
+#[test]
+fn patterns_are_documented() {
+    assert_eq!(
+        meta::env_patterns(SampleLayer::FIELDS),
+        ["MYAPP_UPSTREAMS_<N>_ADDR", "MYAPP_UPSTREAMS_<N>_WEIGHT"]
+    );
+    assert!(meta::env_vars(SampleLayer::FIELDS).is_empty());
+
+    let help = SampleLayer::render_help(false);
+    assert!(help.contains("env: MYAPP_UPSTREAMS_<N>_ADDR"), "{help}");
+}
+
+#[test]
+fn indexed_vars_are_known_in_strict_mode() {
+    let builder = ConfigBuilder::<SampleLayer>::new()
+        .with_env_strict(
+            &TestEnv::new()
+                .add("MYAPP_UPSTREAMS_0_ADDR", "a")
+                .add("MYAPP_UPSTREAMS_X_ADDR", "b"),
+            "MYAPP_",
+            UnknownEnv::Warn,
+        )
+        .unwrap();
+
+    let warnings: Vec<_> = builder.warnings().iter().map(ToString::to_string).collect();
+    assert_eq!(warnings.len(), 1, "{warnings:?}");
+    assert!(
+        warnings[0].contains("MYAPP_UPSTREAMS_X_ADDR"),
+        "{warnings:?}"
+    );
+}
@@ -0,0 +1,116 @@
+#![cfg(feature = "toml")]
+
+use soukousei::instances::Instances;
+use soukousei::{CompleteError, Layer};
+
+#[derive(Debug, Layer)]
+struct Config {
+    #[layer(nested)]
+    workers: Instances<Worker>,
+}
+
+#[derive(Debug, Layer)]
+struct Worker {
+    name: String,
+    port: u16,
+    #[layer(default = "4")]
+    threads: u32,
+}
+
+#[test]
+fn template_is_expanded() {
+    let layer: ConfigLayer = toml::from_str(
+        r#"
+        [workers]
+        instances = 3
+        vars = { base_port = 9000 }
+
+        [workers.template]
+        name = "worker-${index}"
+        port = "${base_port + index * 2}"
+        "#,
+    )
+    .unwrap();
+
+    let config = layer.complete().unwrap();
+
+    let workers: Vec<_> = config
+        .workers
+        .iter()
+        .map(|x| (x.name.as_str(), x.port, x.threads))
+        .collect();
+    assert_eq!(
+        workers,
+        [
+            ("worker-0", 9000, 4),
+            ("worker-1", 9002, 4),
+            ("worker-2", 9004, 4)
+        ]
+    );
+}
+
+#[test]
+fn explicit_list_is_accepted() {
+    let layer: ConfigLayer = toml::from_str(
+        r#"
+        [[workers]]
+        name = "main"
+        port = 80
+        "#,
+    )
+    .unwrap();
+
+    let config = layer.complete().unwrap();
+
+    assert_eq!(config.workers.len(), 1);
+    assert_eq!(config.workers[0].name, "main");
+}
+
+#[test]
+fn newer_source_replaces_entries() {
+    let file: ConfigLayer = toml::from_str(
+        r#"
+        [workers]
+        instances = 2
+        template = { name = "w${index}", port = "${8000 + index}" }
+        "#,
+    )
+    .unwrap();
+    let other: ConfigLayer = toml::from_str(
+        r#"
+        [workers]
+        instances = 1
+        template = { name = "only", port = 1 }
+        "#,
+    )
+    .unwrap();
+
+    let config = file.merge(other).complete().unwrap();
+
+    assert_eq!(config.workers.len(), 1);
+    assert_eq!(config.workers[0].name, "only");
+}
+
+#[test]
+fn unknown_variable_is_reported() {
+    let layer: ConfigLayer = toml::from_str(
+        r#"
+        [workers]
+        instances = 1
+        template = { name = "w", port = "${base_port}" }
+        "#,
+    )
+    .unwrap();
+
+    let Err(CompleteError::Fields(errors)) = layer.complete() else {
+        panic!("expected field errors")
+    };
+    let error = errors.iter().next().unwrap();
+    assert_eq!(error.joined_path(), "workers");
+    let message = error.value().to_string();
+    assert!(message.contains("instance 0"), "{message}");
+    assert!(
+        message.contains("unknown variable `base_port`"),
+        "{message}"
+    );
+}
@@ -0,0 +1,86 @@
+use soukousei::builder::{BuildError, ConfigBuilder};
+use soukousei::jail::PathJail;
+use soukousei::Layer;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Layer)]
+struct Config {
+    port: u16,
+}
+
+fn jail_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("soukousei-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("conf.d")).unwrap();
+    std::fs::write(dir.join("conf.d/app.toml"), "port = 3000").unwrap();
+    dir
+}
+
+#[test]
+fn files_inside_the_jail_are_read() {
+    let dir = jail_dir("jail-inside");
+
+    let config = ConfigBuilder::<ConfigLayer>::new()
+        .with_path_jail(PathJail::new(&dir))
+        .with_file("conf.d/../conf.d/./app.toml")
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(config.port, 3000);
+}
+
+#[test]
+fn traversal_is_rejected() {
+    let jail = PathJail::new(jail_dir("jail-traversal"));
+
+    for path in [
+        "../../etc/passwd",
+        "conf.d/../../secret.toml",
+        "/etc/passwd",
+    ] {
+        let err = jail.resolve(Path::new(path)).unwrap_err();
+        assert_eq!(err.path(), Path::new(path));
+    }
+
+    let err = ConfigBuilder::<ConfigLayer>::new()
+        .with_path_jail(jail.clone())
+        .with_file("../../etc/passwd")
+        .err()
+        .expect("the path is outside of the jail");
+    let BuildError::PathEscape(err) = err else {
+        panic!("expected a path escape, got {err:?}")
+    };
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Refused to read `../../etc/passwd`: it is outside of `{}`",
+            jail.base().display()
+        )
+    );
+}
+
+#[test]
+fn absolute_paths_inside_the_jail_might_be_allowed() {
+    let dir = jail_dir("jail-absolute");
+    let jail = PathJail::new(&dir).allow_absolute();
+
+    assert!(jail.resolve(&dir.join("conf.d/app.toml")).is_ok());
+    assert!(jail.resolve(Path::new("/etc/passwd")).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks_out_of_the_jail_are_rejected() {
+    let dir = jail_dir("jail-symlink");
+    let outside =
+        std::env::temp_dir().join(format!("soukousei-{}-outside.toml", std::process::id()));
+    std::fs::write(&outside, "port = 4000").unwrap();
+    std::os::unix::fs::symlink(&outside, dir.join("conf.d/link.toml")).unwrap();
+
+    let err = PathJail::new(&dir)
+        .resolve(Path::new("conf.d/link.toml"))
+        .unwrap_err();
+
+    assert!(err.to_string().contains("it links outside of"));
+}
@@ -0,0 +1,44 @@
+use soukousei::lazy::{Lazy, Load};
+use soukousei::{CompleteError, HasLayer, Layer, Report};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static LOADS: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug)]
+struct Cert(&'static str);
+
+impl Load for Cert {
+    type Output = String;
+
+    fn validate(&self) -> Result<(), Report> {
+        if self.0.is_empty() {
+            return Err(Report::msg("empty path"));
+        }
+        Ok(())
+    }
+
+    fn load(&self) -> Result<Self::Output, Report> {
+        LOADS.fetch_add(1, Ordering::SeqCst);
+        Ok(format!("contents of {}", self.0))
+    }
+}
+
+#[test]
+fn loads_once_on_first_access() {
+    let cert = <Lazy<Cert> as HasLayer>::Layer::new()
+        .merge(soukousei::lazy::LazyLayer(Some(Cert("cert.pem"))))
+        .complete()
+        .unwrap();
+
+    assert_eq!(LOADS.load(Ordering::SeqCst), 0);
+    assert_eq!(cert.get().unwrap(), "contents of cert.pem");
+    assert_eq!(cert.get().unwrap(), "contents of cert.pem");
+    assert_eq!(LOADS.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn validated_at_completion() {
+    let result = soukousei::lazy::LazyLayer(Some(Cert(""))).complete();
+
+    assert!(matches!(result, Err(CompleteError::Invalid(_))));
+}
@@ -0,0 +1,118 @@
+#![allow(dead_code)]
+
+use soukousei::builder::{BuildError, ConfigBuilder};
+use soukousei::limits::{LimitError, Limits};
+use soukousei::lint::LintIssue;
+use soukousei::source::Format;
+use soukousei::Layer;
+
+#[derive(Debug, Layer)]
+struct Config {
+    #[layer(nested)]
+    server: Server,
+}
+
+#[derive(Debug, Layer)]
+struct Server {
+    port: u16,
+    hosts: Vec<String>,
+}
+
+const CONFIG: &str = r#"
+[server]
+port = 8080
+hosts = ["a", "b", "c"]
+"#;
+
+fn parse(limits: Limits, contents: &str) -> Result<ConfigBuilder<ConfigLayer>, BuildError> {
+    ConfigBuilder::<ConfigLayer>::new()
+        .with_limits(limits)
+        .with_str("upload", Format::Toml, contents)
+}
+
+fn assert_limit(result: Result<ConfigBuilder<ConfigLayer>, BuildError>, expected: &str) {
+    let Err(BuildError::Source(report)) = result else {
+        panic!("expected a source error")
+    };
+    let [LintIssue::Limit(err)] = report.issues() else {
+        panic!("expected a limit error, got {:?}", report.issues())
+    };
+    assert_eq!(err.to_string(), expected);
+}
+
+#[test]
+fn documents_within_limits_are_accepted() {
+    let limits = Limits::new()
+        .max_size(CONFIG.len())
+        .max_depth(2)
+        .max_array_len(3);
+
+    let config = parse(limits, CONFIG).unwrap().build().unwrap();
+
+    assert_eq!(config.server.hosts.len(), 3);
+}
+
+#[test]
+fn large_documents_are_rejected() {
+    assert_limit(
+        parse(Limits::new().max_size(16), CONFIG),
+        "The document exceeds the limit of 16 bytes",
+    );
+}
+
+#[test]
+fn deep_documents_are_rejected() {
+    assert_limit(
+        parse(Limits::new().max_depth(0), CONFIG),
+        "`server` is nested deeper than the limit of 0 levels",
+    );
+
+    let nested = "[server]\nport = 8080\nhosts = [\"a\"]\n[server.extra.deeper]\nkey = 1";
+    let err = Limits::new()
+        .max_depth(2)
+        .check_str(Format::Toml, nested)
+        .unwrap_err();
+    assert!(
+        matches!(&err, LimitError::Depth { path, .. } if path == "server.extra.deeper"),
+        "{err:?}"
+    );
+}
+
+#[test]
+fn long_arrays_are_rejected() {
+    assert_limit(
+        parse(Limits::new().max_array_len(2), CONFIG),
+        "`server.hosts` has more items than the limit of 2",
+    );
+}
+
+#[test]
+fn unknown_keys_are_checked() {
+    let nested = "[server]\nport = 8080\nhosts = []\n[server.extra.deeper]\nkey = 1";
+
+    assert_limit(
+        parse(Limits::new().max_depth(2), nested),
+        "`server.extra.deeper` is nested deeper than the limit of 2 levels",
+    );
+}
+
+#[test]
+fn datetimes_are_not_tables() {
+    let dated = "[server]\nport = 8080\nstarted = 1979-05-27T07:32:00Z";
+
+    Limits::new()
+        .max_depth(1)
+        .check_str(Format::Toml, dated)
+        .unwrap();
+}
+
+#[test]
+fn endless_streams_are_cut_at_the_limit() {
+    let endless = std::io::repeat(b'#');
+
+    let result = ConfigBuilder::<ConfigLayer>::new()
+        .with_limits(Limits::new().max_size(1024))
+        .with_reader("upload", Format::Toml, endless);
+
+    assert_limit(result, "The document exceeds the limit of 1024 bytes");
+}
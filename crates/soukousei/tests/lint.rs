@@ -0,0 +1,82 @@
+#![allow(dead_code)]
+
+use soukousei::lint::{validate_str, LintIssue};
+use soukousei::source::Format;
+use soukousei::Layer;
+
+#[derive(Layer)]
+struct Sample {
+    port: u16,
+    #[layer(nested)]
+    nested: Nested,
+}
+
+#[derive(Layer)]
+struct Nested {
+    host: Option<String>,
+}
+
+#[test]
+fn valid_file_has_no_issues() {
+    validate_str::<Sample>(
+        "config.toml",
+        Format::Toml,
+        "port = 8080\n[nested]\nhost = \"localhost\"",
+    )
+    .unwrap();
+}
+
+#[test]
+fn unknown_keys_are_reported() {
+    let report = validate_str::<Sample>(
+        "config.toml",
+        Format::Toml,
+        "prot = 8080\n[nested]\nhost = \"localhost\"\nbaz = 1",
+    )
+    .unwrap_err();
+
+    let keys: Vec<_> = report
+        .issues()
+        .iter()
+        .map(|issue| match issue {
+            LintIssue::UnknownKey { key } => key.as_str(),
+            other => panic!("unexpected issue: {other}"),
+        })
+        .collect();
+    assert_eq!(keys, vec!["prot", "nested.baz"]);
+}
+
+#[test]
+fn type_errors_are_reported_with_span() {
+    let report =
+        validate_str::<Sample>("config.toml", Format::Toml, "port = \"eighty\"").unwrap_err();
+
+    match report.issues() {
+        [LintIssue::Source(err)] => assert!(err.span().is_some()),
+        other => panic!("unexpected issues: {other:?}"),
+    }
+}
+
+#[test]
+fn captured_keys_are_reported() {
+    #[derive(Layer)]
+    struct WithExtra {
+        port: u16,
+        #[layer(flatten)]
+        extra: std::collections::HashMap<String, String>,
+    }
+
+    let report =
+        validate_str::<WithExtra>("config.toml", Format::Toml, "port = 8080\nplugin = \"on\"")
+            .unwrap_err();
+
+    let keys: Vec<_> = report
+        .issues()
+        .iter()
+        .map(|issue| match issue {
+            LintIssue::UnknownKey { key } => key.as_str(),
+            other => panic!("unexpected issue: {other}"),
+        })
+        .collect();
+    assert_eq!(keys, vec!["plugin"]);
+}
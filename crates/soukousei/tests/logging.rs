@@ -0,0 +1,57 @@
+#![cfg(feature = "logging")]
+
+use soukousei::contrib::logging::{init_tracing, Level, LogFormat, LoggingConfig, LoggingError};
+use soukousei::Layer;
+
+#[derive(Debug, Layer)]
+struct Config {
+    #[layer(nested)]
+    logging: LoggingConfig,
+}
+
+#[test]
+fn section_has_defaults() {
+    let config = ConfigLayer::default().complete().unwrap();
+
+    assert_eq!(config.logging.level, Level::Info);
+    assert_eq!(config.logging.format, LogFormat::Pretty);
+    assert_eq!(config.logging.file, None);
+}
+
+#[test]
+fn parsed_from_toml() {
+    let layer: ConfigLayer = toml::from_str(
+        r#"
+        [logging]
+        level = "debug"
+        format = "json"
+
+        [logging.targets]
+        hyper = "warn"
+        "#,
+    )
+    .unwrap();
+
+    let config = ConfigLayer::default().merge(layer).complete().unwrap();
+
+    assert_eq!(config.logging.level, Level::Debug);
+    assert_eq!(config.logging.format, LogFormat::Json);
+    assert_eq!(config.logging.targets().to_string(), "hyper=warn,debug");
+}
+
+#[test]
+fn global_subscriber_is_installed_once() {
+    let path = std::env::temp_dir().join(format!("soukousei-{}.log", std::process::id()));
+    let mut layer = ConfigLayer::default();
+    layer.logging.file = Some(path.clone());
+    layer.logging.format = Some(LogFormat::Compact);
+    let config = layer.complete().unwrap();
+
+    init_tracing(&config.logging).unwrap();
+
+    assert!(path.exists());
+    assert!(matches!(
+        init_tracing(&config.logging),
+        Err(LoggingError::Init(_))
+    ));
+}
@@ -2,16 +2,10 @@ extern crate core;
 
 mod util;
 
-use miette::{miette, IntoDiagnostic, Report, WrapErr};
+use miette::{Report, WrapErr};
 use serde::{Deserialize, Serialize};
 use soukousei::env::{FieldFromEnvError, FromEnv};
-use soukousei::{
-    env::EnvProvider, CompleteError, HasLayer, Layer, MissingFieldError, MultipleFieldsError,
-    ResultExt,
-};
-use std::collections::HashMap;
-use std::str::FromStr;
-use util::TestEnv;
+use soukousei::{env::EnvProvider, CompleteError, HasLayer, Layer, MultipleFieldsError, ResultExt};
 
 #[derive(Debug)]
 // #[derive(Layer)]
@@ -31,15 +25,9 @@ struct Sample {
 }
 
 // we only want to override how merge works
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 struct CustomLayer(Option<u32>);
 
-impl Default for CustomLayer {
-    fn default() -> Self {
-        Self(None)
-    }
-}
-
 impl FromEnv for CustomLayer {
     fn from_env(
         _provider: &impl EnvProvider,
@@ -229,7 +217,7 @@ impl FromEnv for NestedLayer {
         let (bar_env_multiple, errors) = errors.add_if_err(
             "bar_env_multiple",
             provider.try_fetch_multiple_and_parse(
-                BAR_ENV_MULTIPLE_VARIABLES.iter().map(|x| *x),
+                BAR_ENV_MULTIPLE_VARIABLES.iter().copied(),
                 soukousei::env::default_env_parse,
             ),
         );
@@ -291,7 +279,11 @@ impl Layer for NestedLayer {
 #[test]
 fn success_build_from_toml() -> Result<(), Report> {
     const INPUT: &str = r#"
-    # required_baz = false
+    required_baz = false
+    custom = 5
+
+    [nested]
+    custom_nested = 7
     "#;
 
     let sample = <Sample as HasLayer>::Layer::default()
@@ -302,7 +294,13 @@ fn success_build_from_toml() -> Result<(), Report> {
         .complete_and_report()
         .wrap_err("Failed to build Sample configuration")?;
 
-    dbg!(&sample);
+    assert_eq!(sample.with_default_foo, 100);
+    assert_eq!(sample.optional_bar, None);
+    assert!(!sample.required_baz);
+    assert_eq!(sample.custom, 5);
+    assert_eq!(sample.nested.foo_env, "I am default foo!");
+    assert_eq!(sample.nested.bar_env_multiple, None);
+    assert_eq!(sample.nested.custom_nested, 7);
 
     Ok(())
 }
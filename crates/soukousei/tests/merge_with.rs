@@ -0,0 +1,65 @@
+use soukousei::builder::ConfigBuilder;
+use soukousei::source::Format;
+use soukousei::Layer;
+
+#[derive(Debug, Layer)]
+struct Sample {
+    #[layer(merge_with = "soukousei::merge::max")]
+    workers: u32,
+    #[layer(merge_with = "soukousei::merge::sum")]
+    budget: u64,
+    #[layer(merge_with = "soukousei::merge::concat")]
+    plugins: Vec<String>,
+    #[layer(merge_with = "keep_first")]
+    name: String,
+    port: u16,
+}
+
+fn keep_first(older: Option<String>, newer: Option<String>) -> Option<String> {
+    older.or(newer)
+}
+
+#[test]
+fn fields_are_merged_with_functions() {
+    let sample = ConfigBuilder::<SampleLayer>::new()
+        .with_str(
+            "base.toml",
+            Format::Toml,
+            "workers = 8\nbudget = 100\nplugins = [\"a\"]\nname = \"base\"\nport = 80",
+        )
+        .unwrap()
+        .with_str(
+            "local.toml",
+            Format::Toml,
+            "workers = 4\nbudget = 50\nplugins = [\"b\"]\nname = \"local\"\nport = 8080",
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(sample.workers, 8);
+    assert_eq!(sample.budget, 150);
+    assert_eq!(sample.plugins, ["a", "b"]);
+    assert_eq!(sample.name, "base");
+    assert_eq!(sample.port, 8080);
+}
+
+#[test]
+fn missing_value_is_taken_from_the_other_layer() {
+    let sample = ConfigBuilder::<SampleLayer>::new()
+        .with_str(
+            "base.toml",
+            Format::Toml,
+            "workers = 8\nbudget = 100\nplugins = [\"a\"]\nport = 80",
+        )
+        .unwrap()
+        .with_str("local.toml", Format::Toml, "name = \"local\"")
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(sample.workers, 8);
+    assert_eq!(sample.budget, 100);
+    assert_eq!(sample.plugins, ["a"]);
+    assert_eq!(sample.name, "local");
+}
@@ -0,0 +1,121 @@
+#![allow(dead_code)]
+
+mod util;
+
+use soukousei::builder::ConfigBuilder;
+use soukousei::metrics::{fingerprint, ConfigMetricsSink};
+use soukousei::reload::{ReloadTrigger, Reloader};
+use soukousei::source::Format;
+use soukousei::Layer;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use util::TestEnv;
+
+#[derive(Debug, Layer)]
+#[layer(render_tree)]
+struct Config {
+    #[layer(env = "PORT")]
+    port: u16,
+    #[layer(secret)]
+    token: String,
+}
+
+#[derive(Default)]
+struct Recorder {
+    events: Mutex<Vec<String>>,
+}
+
+impl Recorder {
+    fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.events.lock().unwrap())
+    }
+
+    fn push(&self, event: String) {
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+impl ConfigMetricsSink for Recorder {
+    fn source_loaded(&self, source: &str, _duration: Duration) {
+        self.push(format!("loaded {source}"));
+    }
+
+    fn reload_attempted(&self, trigger: ReloadTrigger) {
+        self.push(format!("attempted {trigger}"));
+    }
+
+    fn reload_failed(&self, trigger: ReloadTrigger) {
+        self.push(format!("failed {trigger}"));
+    }
+
+    fn config_activated(&self, fingerprint: u64) {
+        self.push(format!("activated {fingerprint:x}"));
+    }
+}
+
+#[test]
+fn source_loads_are_reported() {
+    let recorder = Arc::new(Recorder::default());
+
+    ConfigBuilder::<ConfigLayer>::new()
+        .with_metrics(recorder.clone())
+        .with_str("config.toml", Format::Toml, "port = 3000\ntoken = 'abc'")
+        .unwrap()
+        .with_env(&TestEnv::new().add("PORT", "4000"))
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(recorder.take(), ["loaded config.toml", "loaded env"]);
+}
+
+#[test]
+fn reloads_are_reported() {
+    let port = Arc::new(Mutex::new(Some("3000")));
+    let recorder = Arc::new(Recorder::default());
+    let reloader = Reloader::new({
+        let port = port.clone();
+        move || {
+            let env = match *port.lock().unwrap() {
+                Some(port) => TestEnv::new().add("PORT", port),
+                None => TestEnv::new(),
+            };
+            ConfigBuilder::<ConfigLayer>::new()
+                .with_str("config.toml", Format::Toml, "token = 'abc'")?
+                .with_env(&env)?
+                .build()
+        }
+    })
+    .unwrap()
+    .with_metrics(recorder.clone());
+    let initial = fingerprint(&*reloader.shared().get());
+    assert_eq!(recorder.take(), [format!("activated {initial:x}")]);
+
+    *port.lock().unwrap() = Some("4000");
+    reloader.reload().unwrap();
+    let updated = fingerprint(&*reloader.shared().get());
+    assert_ne!(initial, updated);
+
+    *port.lock().unwrap() = None;
+    reloader.reload().unwrap_err();
+
+    assert_eq!(
+        recorder.take(),
+        [
+            "attempted manual".to_owned(),
+            format!("activated {updated:x}"),
+            "attempted manual".to_owned(),
+            "failed manual".to_owned(),
+        ]
+    );
+}
+
+#[test]
+fn fingerprint_ignores_secrets() {
+    let config = |token: &str| Config {
+        port: 3000,
+        token: token.to_owned(),
+    };
+
+    assert_eq!(fingerprint(&config("abc")), fingerprint(&config("xyz")));
+}
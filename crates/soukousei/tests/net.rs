@@ -0,0 +1,58 @@
+mod util;
+
+use soukousei::env::FromEnv;
+use soukousei::net::CidrList;
+use soukousei::Layer;
+use util::TestEnv;
+
+#[derive(Debug, Layer)]
+struct Server {
+    #[layer(env = "ALLOWED_IPS", default = "CidrList::default()")]
+    allowed_ips: CidrList,
+}
+
+#[test]
+fn parsed_from_array() {
+    let layer: ServerLayer =
+        toml::from_str(r#"allowed_ips = ["10.0.0.0/8", "192.168.1.0/24", "::1"]"#).unwrap();
+
+    let server = layer.complete().unwrap();
+
+    assert!(server.allowed_ips.contains("10.20.30.40".parse().unwrap()));
+    assert!(server.allowed_ips.contains("192.168.1.7".parse().unwrap()));
+    assert!(!server.allowed_ips.contains("192.168.2.7".parse().unwrap()));
+    assert!(server.allowed_ips.contains("::1".parse().unwrap()));
+    assert!(!server.allowed_ips.contains("::2".parse().unwrap()));
+}
+
+#[test]
+fn parsed_from_comma_separated_env() {
+    let env = TestEnv::new().add("ALLOWED_IPS", "10.0.0.0/8, 127.0.0.1");
+
+    let server = ServerLayer::from_env(&env).unwrap().complete().unwrap();
+
+    assert_eq!(server.allowed_ips.to_string(), "10.0.0.0/8,127.0.0.1/32");
+}
+
+#[test]
+fn invalid_prefix_is_rejected() {
+    let env = TestEnv::new().add("ALLOWED_IPS", "10.0.0.0/33");
+
+    let Err(err) = ServerLayer::from_env(&env) else {
+        panic!("expected an error")
+    };
+
+    let message = err.iter().next().unwrap().value().to_string();
+    assert!(
+        message.contains("invalid network `10.0.0.0/33`"),
+        "{message}"
+    );
+}
+
+#[test]
+fn empty_by_default() {
+    let server = ServerLayer::default().complete().unwrap();
+
+    assert!(server.allowed_ips.is_empty());
+    assert!(!server.allowed_ips.contains("10.0.0.1".parse().unwrap()));
+}
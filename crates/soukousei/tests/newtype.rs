@@ -0,0 +1,84 @@
+#![allow(dead_code)]
+
+#[cfg(feature = "json")]
+use soukousei::builder::ConfigBuilder;
+use soukousei::{CompleteError, Layer};
+
+#[derive(Debug, Layer)]
+struct Rules(#[layer(nested)] Vec<Rule>);
+
+#[derive(Debug, Layer)]
+struct Rule {
+    pattern: String,
+    #[layer(default = "true")]
+    allow: bool,
+}
+
+#[derive(Debug, PartialEq, Layer)]
+struct Port(u16);
+
+#[cfg(feature = "json")]
+#[test]
+fn top_level_array_is_loaded() {
+    use soukousei::source::Format;
+
+    let rules = ConfigBuilder::<RulesLayer>::new()
+        .with_str(
+            "rules.json",
+            Format::Json,
+            r#"[{"pattern": "/admin"}, {"pattern": "/private", "allow": false}]"#,
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+
+    let rules: Vec<_> = rules
+        .0
+        .iter()
+        .map(|x| (x.pattern.as_str(), x.allow))
+        .collect();
+    assert_eq!(rules, [("/admin", true), ("/private", false)]);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn top_level_scalar_is_loaded() {
+    use soukousei::source::Format;
+
+    let port = ConfigBuilder::<PortLayer>::new()
+        .with_str("port.json", Format::Json, "8080")
+        .unwrap()
+        .with_str("empty.json", Format::Json, "null")
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(port, Port(8080));
+}
+
+#[test]
+fn newer_value_wins() {
+    let port = PortLayer(Some(1))
+        .merge(PortLayer(Some(2)))
+        .complete()
+        .unwrap();
+
+    assert_eq!(port, Port(2));
+    assert!(matches!(
+        Port::layer().complete(),
+        Err(CompleteError::MissingData)
+    ));
+}
+
+#[test]
+fn nested_newtype_reports_element_paths() {
+    let layer = Rules::layer().merge(RulesLayer(soukousei::collection::VecLayer(Some(vec![
+        RuleLayer::new(),
+    ]))));
+
+    let Err(CompleteError::Fields(errors)) = layer.complete() else {
+        panic!("expected field errors")
+    };
+    let paths: Vec<_> = errors.iter().map(|x| x.joined_path()).collect();
+    assert_eq!(paths, ["[0].pattern"]);
+}
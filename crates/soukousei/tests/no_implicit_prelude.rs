@@ -0,0 +1,59 @@
+mod util;
+
+mod app {
+    #![no_implicit_prelude]
+
+    #[derive(::soukousei::Layer)]
+    #[layer(render_tree, degradable, env_export)]
+    pub struct Config {
+        /// Port to listen on
+        #[layer(default = "8080", env = "PORT", telemetry)]
+        pub port: u16,
+        #[layer(env = "NAME")]
+        pub name: ::std::option::Option<::std::string::String>,
+        #[layer(env = "LEVEL", env_enum)]
+        pub level: Level,
+        #[layer(env = "TOKEN", secret, sensitive_file)]
+        pub token: ::std::string::String,
+        #[layer(default = "3")]
+        pub retries: ::std::option::Option<u32>,
+        #[layer(nested, telemetry)]
+        pub database: Database,
+    }
+
+    #[derive(::soukousei::Layer)]
+    #[layer(render_tree, env_export)]
+    pub struct Database {
+        #[layer(default = "5")]
+        pub pool: u32,
+    }
+
+    #[derive(
+        ::soukousei::EnvEnum, ::core::fmt::Debug, ::serde::Serialize, ::serde::Deserialize,
+    )]
+    pub enum Level {
+        Info,
+        Debug,
+    }
+}
+
+use soukousei::env::FromEnv;
+use soukousei::Layer;
+use util::TestEnv;
+
+#[test]
+fn derive_works_without_prelude() {
+    let env = TestEnv::new().add("LEVEL", "debug").add("TOKEN", "secret");
+
+    let config = app::ConfigLayer::default()
+        .merge(app::ConfigLayer::from_env(&env).unwrap())
+        .complete()
+        .unwrap();
+
+    assert_eq!(config.port, 8080);
+    assert!(config.name.is_none());
+    assert!(matches!(config.level, app::Level::Debug));
+    assert_eq!(config.token, "secret");
+    assert_eq!(config.retries, Some(3));
+    assert_eq!(config.database.pool, 5);
+}
@@ -0,0 +1,89 @@
+use soukousei::builder::ConfigBuilder;
+use soukousei::normalize::{KeyNormalizer, SnakeCase};
+use soukousei::source::Format;
+use soukousei::Layer;
+
+#[derive(Debug, Layer)]
+struct Config {
+    max_connections: u32,
+    #[layer(nested)]
+    http_server: HttpServer,
+}
+
+#[derive(Debug, Layer)]
+struct HttpServer {
+    listen_port: u16,
+}
+
+#[test]
+fn snake_case_conversions() {
+    for (key, expected) in [
+        ("max-connections", "max_connections"),
+        ("maxConnections", "max_connections"),
+        ("MAX_CONNECTIONS", "max_connections"),
+        ("HTTPServer", "http_server"),
+        ("listenPort2", "listen_port2"),
+        ("already_snake", "already_snake"),
+    ] {
+        assert_eq!(SnakeCase.normalize(key), expected);
+    }
+}
+
+#[test]
+fn kebab_case_toml_keys_are_normalized() {
+    let config = ConfigBuilder::<ConfigLayer>::new()
+        .with_key_normalizer(SnakeCase)
+        .with_str(
+            "config.toml",
+            Format::Toml,
+            "max-connections = 10\n\n[http-server]\nlisten-port = 8080\n",
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(config.max_connections, 10);
+    assert_eq!(config.http_server.listen_port, 8080);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn camel_case_json_keys_are_normalized() {
+    let config = ConfigBuilder::<ConfigLayer>::new()
+        .with_key_normalizer(SnakeCase)
+        .with_str(
+            "config.json",
+            Format::Json,
+            r#"{"maxConnections": 5, "httpServer": {"listenPort": 80}}"#,
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(config.max_connections, 5);
+    assert_eq!(config.http_server.listen_port, 80);
+}
+
+#[test]
+fn normalizer_is_set_per_source() {
+    let builder = ConfigBuilder::<ConfigLayer>::new()
+        .with_key_normalizer(|key: &str| key.trim_start_matches("app.").to_owned())
+        .with_str("a.toml", Format::Toml, "\"app.max_connections\" = 1")
+        .unwrap()
+        .without_key_normalizer()
+        .with_str("b.toml", Format::Toml, "[http_server]\nlisten_port = 1")
+        .unwrap();
+
+    let config = builder.build().unwrap();
+    assert_eq!(config.max_connections, 1);
+    assert_eq!(config.http_server.listen_port, 1);
+}
+
+#[test]
+fn unknown_keys_are_reported_normalized() {
+    let parsed = Format::Toml
+        .parse_with::<ConfigLayer>("max-connections = 1\nmax-conns = 2", Some(&SnakeCase))
+        .unwrap();
+
+    assert_eq!(parsed.unknown_keys, ["max_conns"]);
+}
@@ -0,0 +1,72 @@
+use soukousei::pointer::ArcLayer;
+use soukousei::{CompleteError, HasLayer, Layer};
+use std::sync::Arc;
+
+#[derive(Debug, PartialEq)]
+struct Port(u16);
+
+struct PortLayer(Option<u16>);
+
+impl Layer for PortLayer {
+    type Complete = Port;
+
+    fn new() -> Self {
+        Self(None)
+    }
+
+    fn merge(self, other: Self) -> Self {
+        Self(other.0.or(self.0))
+    }
+
+    fn complete(self) -> Result<Self::Complete, CompleteError> {
+        self.0.map(Port).ok_or(CompleteError::MissingData)
+    }
+}
+
+impl HasLayer for Port {
+    type Layer = PortLayer;
+}
+
+#[test]
+fn box_layer_completes_boxed() {
+    let port = <Box<PortLayer> as Layer>::new()
+        .merge(Box::new(PortLayer(Some(8080))))
+        .complete()
+        .unwrap();
+
+    assert_eq!(port, Box::new(Port(8080)));
+}
+
+#[test]
+fn arc_layer_completes_shared() {
+    let port = <Arc<Port> as HasLayer>::Layer::new()
+        .merge(ArcLayer(PortLayer(Some(8080))))
+        .merge(ArcLayer(PortLayer(None)))
+        .complete()
+        .unwrap();
+
+    assert_eq!(port, Arc::new(Port(8080)));
+}
+
+#[derive(Debug, Layer)]
+struct Server {
+    #[layer(nested)]
+    tls: Box<Tls>,
+}
+
+#[derive(Debug, Layer)]
+struct Tls {
+    cert: String,
+    #[layer(default = "true")]
+    verify: bool,
+}
+
+#[test]
+fn boxed_nested_field_is_derived() {
+    let layer: ServerLayer = toml::from_str("[tls]\ncert = \"server.pem\"").unwrap();
+
+    let server = ServerLayer::default().merge(layer).complete().unwrap();
+
+    assert_eq!(server.tls.cert, "server.pem");
+    assert!(server.tls.verify);
+}
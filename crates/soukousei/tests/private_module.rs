@@ -0,0 +1,65 @@
+use soukousei::builder::ConfigBuilder;
+use soukousei::source::Format;
+use soukousei::{HasLayer, Layer};
+
+mod api {
+    use soukousei::Layer;
+
+    #[derive(Debug, Layer)]
+    #[layer(private_module)]
+    pub struct Config {
+        pub port: u16,
+        token: Option<String>,
+        #[layer(nested)]
+        pub db: Database,
+    }
+
+    #[derive(Debug, Layer)]
+    #[layer(private_module)]
+    pub struct Database {
+        pub url: String,
+    }
+
+    impl Config {
+        pub fn token(&self) -> Option<&str> {
+            self.token.as_deref()
+        }
+    }
+
+    /// Private fields of the layer are accessible next to the struct
+    pub fn with_token(token: &str) -> <Config as soukousei::HasLayer>::Layer {
+        let mut layer = Config::layer();
+        layer.token = Some(token.to_owned());
+        layer
+    }
+}
+
+use api::Config;
+
+type ConfigLayer = <Config as HasLayer>::Layer;
+
+#[test]
+fn layer_is_reachable_through_has_layer() {
+    let config = ConfigBuilder::<ConfigLayer>::new()
+        .with_str(
+            "config.toml",
+            Format::Toml,
+            "port = 8080\n[db]\nurl = \"postgres://localhost\"",
+        )
+        .unwrap()
+        .with_layer(api::with_token("secret"))
+        .build()
+        .unwrap();
+
+    assert_eq!(config.port, 8080);
+    assert_eq!(config.db.url, "postgres://localhost");
+    assert_eq!(config.token(), Some("secret"));
+}
+
+#[test]
+fn public_fields_of_the_layer_are_accessible() {
+    let mut layer = Config::layer();
+    layer.port = Some(3000);
+
+    assert_eq!(layer.provided_fields(), ["port"]);
+}
@@ -0,0 +1,70 @@
+use soukousei::reference::Ref;
+use soukousei::{CompleteErrorDiagnostic, Layer};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Layer)]
+#[layer(reference(field = "server.tls", section = "tls_profiles"))]
+#[layer(reference(field = "admin_tls", section = "tls_profiles"))]
+// both references point into the same section, which clippy takes for a repeated attribute
+#[allow(clippy::duplicated_attributes)]
+struct Config {
+    #[layer(nested)]
+    server: Server,
+    admin_tls: Option<Ref<TlsProfile>>,
+    #[layer(nested)]
+    tls_profiles: BTreeMap<String, TlsProfile>,
+}
+
+#[derive(Debug, Layer)]
+struct Server {
+    tls: Ref<TlsProfile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Layer)]
+struct TlsProfile {
+    cert: String,
+}
+
+#[test]
+fn resolved_on_complete() {
+    let layer: ConfigLayer = toml::from_str(
+        r#"
+        server.tls = "corp-tls"
+
+        [tls_profiles.corp-tls]
+        cert = "corp.pem"
+        "#,
+    )
+    .unwrap();
+
+    let config = layer.complete().unwrap();
+
+    assert_eq!(config.server.tls.name(), "corp-tls");
+    assert_eq!(config.server.tls.cert, "corp.pem");
+    assert!(config.admin_tls.is_none());
+}
+
+#[test]
+fn dangling_reference() {
+    let layer: ConfigLayer = toml::from_str(
+        r#"
+        server.tls = "corp-tls"
+        admin_tls = "admin"
+
+        [tls_profiles.corp-tls]
+        cert = "corp.pem"
+        "#,
+    )
+    .unwrap();
+
+    let CompleteErrorDiagnostic::Fields { fields } = layer.complete_and_report().unwrap_err()
+    else {
+        panic!("expected field errors")
+    };
+    let fields: Vec<_> = fields.iter().map(ToString::to_string).collect();
+
+    assert_eq!(
+        fields,
+        vec!["`admin_tls`: invalid value: `admin` is not defined in `tls_profiles`"]
+    );
+}
@@ -0,0 +1,195 @@
+mod util;
+
+use soukousei::builder::{BuildError, ConfigBuilder};
+use soukousei::reload::{ReloadTrigger, Reloader, WatchOptions};
+use soukousei::Layer;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use util::TempFile;
+
+#[derive(Debug, Layer)]
+#[layer(render_tree)]
+struct Config {
+    port: u16,
+    #[layer(default = "\"localhost\".to_owned()")]
+    host: String,
+}
+
+fn reloader(path: PathBuf) -> Reloader<Config> {
+    Reloader::new(move || {
+        ConfigBuilder::<ConfigLayer>::new()
+            .with_defaults()
+            .with_file(&path)?
+            .build()
+    })
+    .unwrap()
+}
+
+#[test]
+fn reload_swaps_the_config() {
+    let file = TempFile::new("reload.toml", "port = 3000");
+    let path = file.path();
+    let reloader = reloader(path.to_owned());
+    let events = reloader.subscribe();
+    assert_eq!(reloader.shared().get().port, 3000);
+
+    std::fs::write(path, "port = 4000").unwrap();
+    reloader.reload().unwrap();
+
+    assert_eq!(reloader.shared().get().port, 4000);
+    let event = events.try_recv().unwrap();
+    assert_eq!(event.trigger, ReloadTrigger::Manual);
+    assert!(event.outcome.is_ok());
+}
+
+#[test]
+fn failed_reload_is_delivered_to_subscribers() {
+    let file = TempFile::new("reload-failed.toml", "port = 3000");
+    let path = file.path();
+    let reloader = reloader(path.to_owned());
+    let events = reloader.subscribe();
+
+    std::fs::write(path, "port = \"nope\"").unwrap();
+    assert!(reloader.reload().is_err());
+
+    assert_eq!(reloader.shared().get().port, 3000);
+    assert!(events.try_recv().unwrap().outcome.is_err());
+}
+
+#[test]
+fn previous_config_is_kept_on_failure() {
+    let file = TempFile::new("reload-rollback.toml", "port = 3000");
+    let path = file.path();
+    let hook_calls = Arc::new(AtomicUsize::new(0));
+    let reloader = reloader(path.to_owned()).on_failure({
+        let hook_calls = hook_calls.clone();
+        move |_| {
+            hook_calls.fetch_add(1, Ordering::Relaxed);
+        }
+    });
+    let config = reloader.handle();
+
+    std::fs::write(path, "").unwrap();
+    assert!(reloader.reload().is_err());
+    assert!(reloader.reload().is_err());
+
+    assert_eq!(config.get().port, 3000);
+    assert_eq!(config.failures(), 2);
+    assert_eq!(hook_calls.load(Ordering::Relaxed), 2);
+    let err = config.last_error().expect("reload has failed");
+    assert!(matches!(*err, BuildError::Complete(_)));
+
+    std::fs::write(path, "port = 4000").unwrap();
+    reloader.reload().unwrap();
+
+    assert_eq!(config.get().port, 4000);
+    assert!(config.last_error().is_none());
+    assert_eq!(config.failures(), 2);
+}
+
+#[cfg(all(unix, feature = "signal"))]
+#[test]
+fn sighup_triggers_reload() {
+    let file = TempFile::new("reload-sighup.toml", "port = 3000");
+    let path = file.path();
+    let reloader = reloader(path.to_owned());
+    let events = reloader.subscribe();
+    let listener = reloader.reload_on_sighup().unwrap();
+
+    std::fs::write(path, "port = 5000").unwrap();
+    signal_hook::low_level::raise(signal_hook::consts::SIGHUP).unwrap();
+
+    let event = events.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(event.trigger, ReloadTrigger::Signal);
+    assert!(event.outcome.is_ok());
+    assert_eq!(reloader.shared().get().port, 5000);
+    listener.close();
+}
+
+#[test]
+fn dry_run_does_not_swap_the_config() {
+    let file = TempFile::new("reload-dry-run.toml", "port = 3000");
+    let path = file.path();
+    let reloader = reloader(path.to_owned());
+    let events = reloader.subscribe();
+    let config = reloader.handle();
+
+    assert!(reloader.dry_run().unwrap().is_empty());
+
+    std::fs::write(path, "port = 4000\nhost = \"example.com\"").unwrap();
+    let diff = reloader.dry_run().unwrap();
+    assert_eq!(
+        diff.to_string(),
+        "`port`: 3000 -> 4000\n`host`: \"localhost\" -> \"example.com\""
+    );
+
+    std::fs::write(path, "").unwrap();
+    assert!(matches!(reloader.dry_run(), Err(BuildError::Complete(_))));
+
+    assert_eq!(config.get().port, 3000);
+    assert_eq!(config.failures(), 0);
+    assert!(config.last_error().is_none());
+    assert!(events.try_recv().is_err());
+}
+
+fn fast_watch() -> WatchOptions {
+    WatchOptions {
+        interval: Duration::from_millis(20),
+        debounce: Duration::from_millis(100),
+    }
+}
+
+#[test]
+fn burst_of_changes_is_reloaded_once() {
+    let file = TempFile::new("reload-watch.toml", "port = 3000");
+    let path = file.path();
+    let reloader = reloader(path.to_owned());
+    let events = reloader.subscribe();
+    let watcher = reloader.watch_with([&path], fast_watch()).unwrap();
+
+    for port in 4000..4005 {
+        std::fs::write(path, format!("port = {port}")).unwrap();
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    let event = events.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(event.trigger, ReloadTrigger::Watch);
+    assert!(event.outcome.is_ok());
+    assert_eq!(reloader.shared().get().port, 4004);
+    assert!(events.recv_timeout(Duration::from_millis(300)).is_err());
+    watcher.stop();
+}
+
+#[cfg(unix)]
+#[test]
+fn kubernetes_configmap_swap_is_followed() {
+    use std::os::unix::fs::symlink;
+
+    let dir = std::env::temp_dir().join(format!("soukousei-{}-configmap", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    for (version, port) in [("..v1", 3000), ("..v2", 4000)] {
+        std::fs::create_dir_all(dir.join(version)).unwrap();
+        std::fs::write(
+            dir.join(version).join("config.toml"),
+            format!("port = {port}"),
+        )
+        .unwrap();
+    }
+    symlink("..v1", dir.join("..data")).unwrap();
+    symlink("..data/config.toml", dir.join("config.toml")).unwrap();
+
+    let reloader = reloader(dir.join("config.toml"));
+    let events = reloader.subscribe();
+    let _watcher = reloader.watch_with([&dir], fast_watch()).unwrap();
+    assert_eq!(reloader.shared().get().port, 3000);
+
+    // the way kubelet swaps the data: a new link is renamed over the old one
+    symlink("..v2", dir.join("..data_tmp")).unwrap();
+    std::fs::rename(dir.join("..data_tmp"), dir.join("..data")).unwrap();
+
+    let event = events.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(event.outcome.is_ok());
+    assert_eq!(reloader.shared().get().port, 4000);
+}
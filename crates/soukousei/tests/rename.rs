@@ -0,0 +1,140 @@
+mod util;
+
+use soukousei::builder::ConfigBuilder;
+use soukousei::source::Format;
+use soukousei::{CompleteError, Layer};
+use util::TestEnv;
+
+#[derive(Debug, Layer)]
+#[layer(
+    file_rename_all = "kebab-case",
+    env_rename_all = "SCREAMING_SNAKE_CASE"
+)]
+struct Sample {
+    max_connections: u32,
+    #[layer(env = "APP_LOG_LEVEL")]
+    log_level: String,
+    #[layer(nested)]
+    tls_settings: Tls,
+}
+
+#[derive(Debug, Layer)]
+#[layer(file_rename_all = "camelCase")]
+struct Tls {
+    cert_path: Option<String>,
+}
+
+#[test]
+fn file_keys_follow_file_convention() {
+    let sample = ConfigBuilder::<SampleLayer>::new()
+        .with_str(
+            "config.toml",
+            Format::Toml,
+            "max-connections = 10\nlog-level = \"info\"\n[tls-settings]\ncertPath = \"/cert\"",
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(sample.max_connections, 10);
+    assert_eq!(sample.log_level, "info");
+    assert_eq!(sample.tls_settings.cert_path.as_deref(), Some("/cert"));
+}
+
+#[test]
+fn env_names_follow_env_convention() {
+    let sample = ConfigBuilder::<SampleLayer>::new()
+        .with_env(
+            &TestEnv::new()
+                .add("MAX_CONNECTIONS", "20")
+                .add("APP_LOG_LEVEL", "debug"),
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(sample.max_connections, 20);
+    assert_eq!(sample.log_level, "debug");
+}
+
+#[test]
+fn metadata_and_errors_use_file_keys() {
+    let names: Vec<_> = SampleLayer::FIELDS.iter().map(|x| x.name).collect();
+    assert_eq!(names, ["max-connections", "log-level", "tls-settings"]);
+    assert_eq!(SampleLayer::FIELDS[0].env, ["MAX_CONNECTIONS"]);
+
+    let Err(CompleteError::Fields(errors)) = SampleLayer::new().complete() else {
+        panic!("expected field errors")
+    };
+    let paths: Vec<_> = errors.iter().map(|x| x.joined_path()).collect();
+    assert_eq!(paths, ["max-connections", "log-level"]);
+}
+
+#[derive(Debug, Layer)]
+struct Opted {
+    #[layer(env)]
+    max_connections: u32,
+    #[layer(default = "30")]
+    timeout: u32,
+}
+
+#[test]
+fn bare_env_opts_in_a_single_field() {
+    let opted = ConfigBuilder::<OptedLayer>::new()
+        .with_defaults()
+        .with_env(
+            &TestEnv::new()
+                .add("MAX_CONNECTIONS", "20")
+                .add("TIMEOUT", "10"),
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(opted.max_connections, 20);
+    assert_eq!(opted.timeout, 30);
+    assert_eq!(OptedLayer::FIELDS[0].env, ["MAX_CONNECTIONS"]);
+    assert!(OptedLayer::FIELDS[1].env.is_empty());
+}
+
+#[derive(Debug, Layer)]
+struct App {
+    #[layer(nested)]
+    server: Server,
+    #[layer(nested)]
+    db: Db,
+}
+
+#[derive(Debug, Layer)]
+#[layer(env_rename_all = "SCREAMING_SNAKE_CASE", env_prefix = "SERVER_")]
+struct Server {
+    port: u16,
+}
+
+#[derive(Debug, Layer)]
+#[layer(env_rename_all = "SCREAMING_SNAKE_CASE", env_prefix = "DB_")]
+struct Db {
+    port: u16,
+    #[layer(env)]
+    host: String,
+}
+
+#[test]
+fn env_prefix_separates_nested_sections() {
+    let app = ConfigBuilder::<AppLayer>::new()
+        .with_env(
+            &TestEnv::new()
+                .add("PORT", "1")
+                .add("SERVER_PORT", "8080")
+                .add("DB_PORT", "5432")
+                .add("DB_HOST", "db"),
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(app.server.port, 8080);
+    assert_eq!(app.db.port, 5432);
+    assert_eq!(app.db.host, "db");
+    assert_eq!(DbLayer::FIELDS[0].env, ["DB_PORT"]);
+}
@@ -0,0 +1,42 @@
+#![allow(dead_code)]
+
+use soukousei::testing::RoundtripError;
+use soukousei::Layer;
+
+#[derive(Debug, Layer)]
+struct Sample {
+    #[layer(default = "8080", range = "1..=65535")]
+    port: u16,
+    #[layer(default = r#""localhost".to_owned()"#)]
+    host: String,
+    #[layer(default = "3")]
+    retries: Option<u32>,
+    /// No default, so it stays missing
+    token: String,
+    #[layer(nested)]
+    db: Database,
+}
+
+#[derive(Debug, Layer)]
+struct Database {
+    #[layer(default = r#"vec!["primary".to_owned()]"#)]
+    replicas: Vec<String>,
+}
+
+#[derive(Debug, Layer)]
+struct Invalid {
+    #[layer(default = "0", range = "1..=65535")]
+    port: u16,
+}
+
+#[test]
+fn default_layer_survives_roundtrip() {
+    SampleLayer::verify_roundtrip().unwrap();
+}
+
+#[test]
+fn invalid_default_is_reported() {
+    let err = InvalidLayer::verify_roundtrip().unwrap_err();
+
+    assert!(matches!(err, RoundtripError::Complete(_)), "{err}");
+}
@@ -0,0 +1,107 @@
+#![allow(dead_code)]
+
+use soukousei::builder::{BuildError, ConfigBuilder};
+use soukousei::schema::{fingerprint, verify, RemoteDocument, RemoteSource};
+use soukousei::source::Format;
+use soukousei::{Layer, Report};
+
+#[derive(Layer)]
+struct V1 {
+    port: u16,
+}
+
+#[derive(Layer)]
+struct V2 {
+    /// Docs don't matter
+    #[layer(default = "8080", env = "PORT")]
+    port: u16,
+}
+
+#[derive(Layer)]
+struct V3 {
+    port: u32,
+}
+
+#[derive(Layer)]
+struct WithPath {
+    port: ::core::primitive::u16,
+}
+
+#[test]
+fn fingerprint_depends_on_structure_only() {
+    assert_eq!(fingerprint(V1Layer::FIELDS), fingerprint(V2Layer::FIELDS));
+    assert_ne!(fingerprint(V1Layer::FIELDS), fingerprint(V3Layer::FIELDS));
+}
+
+#[test]
+fn fingerprint_ignores_type_paths() {
+    assert_eq!(
+        fingerprint(V1Layer::FIELDS),
+        fingerprint(WithPathLayer::FIELDS)
+    );
+}
+
+#[test]
+fn mismatch_is_reported() {
+    let remote = fingerprint(V3Layer::FIELDS);
+
+    assert!(verify::<V1Layer>(fingerprint(V1Layer::FIELDS)).is_ok());
+    let err = verify::<V1Layer>(remote).unwrap_err();
+    assert!(err.to_string().starts_with("Config server schema mismatch"));
+}
+
+/// Config server which serves `port = 80`, produced for the schema of `V`
+struct Server<V>(std::marker::PhantomData<V>);
+
+impl<V: Layer> RemoteSource for Server<V> {
+    fn fetch(&self, _fingerprint: u64) -> Result<RemoteDocument, Report> {
+        Ok(RemoteDocument {
+            format: Format::Toml,
+            contents: "port = 80".to_owned(),
+            fingerprint: fingerprint(V::fields()),
+        })
+    }
+}
+
+struct Unreachable;
+
+impl RemoteSource for Unreachable {
+    fn fetch(&self, _fingerprint: u64) -> Result<RemoteDocument, Report> {
+        Err(Report::msg("connection refused"))
+    }
+}
+
+#[test]
+fn remote_document_of_the_same_schema_is_merged() {
+    let config = ConfigBuilder::<V1Layer>::new()
+        .with_remote("config server", &Server::<V2Layer>(Default::default()))
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(config.port, 80);
+}
+
+#[test]
+fn remote_document_of_another_schema_is_rejected() {
+    let Err(BuildError::SchemaMismatch(err)) = ConfigBuilder::<V1Layer>::new()
+        .with_remote("config server", &Server::<V3Layer>(Default::default()))
+    else {
+        panic!("expected a schema mismatch")
+    };
+
+    assert!(err.to_string().starts_with("Config server schema mismatch"));
+}
+
+#[test]
+fn remote_fetch_error_names_the_source() {
+    let Err(err) = ConfigBuilder::<V1Layer>::new().with_remote("config server", &Unreachable)
+    else {
+        panic!("expected a fetch error")
+    };
+
+    assert_eq!(
+        err.to_string(),
+        "Failed to fetch `config server`: connection refused"
+    );
+}
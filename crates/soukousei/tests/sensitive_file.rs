@@ -0,0 +1,93 @@
+mod util;
+
+use soukousei::env::FromEnv;
+use soukousei::{HasLayer, Layer};
+use util::{TempFile, TestEnv};
+
+#[derive(Debug, Layer)]
+struct Database {
+    #[layer(env = "DB_PASSWORD", sensitive_file)]
+    password: String,
+    #[layer(sensitive_file)]
+    token: Option<Vec<u8>>,
+}
+
+#[test]
+fn set_directly() {
+    let db = <Database as HasLayer>::Layer::new()
+        .merge(toml::from_str(r#"password = "qwerty""#).unwrap())
+        .complete()
+        .unwrap();
+
+    assert_eq!(db.password, "qwerty");
+    assert_eq!(db.token, None);
+}
+
+#[test]
+fn read_from_file() {
+    let file = TempFile::new("password", "qwerty\n");
+    let path = file.path();
+
+    let db = <Database as HasLayer>::Layer::new()
+        .merge(
+            toml::from_str(&format!("password_file = {:?}", path.display().to_string())).unwrap(),
+        )
+        .complete()
+        .unwrap();
+
+    assert_eq!(db.password, "qwerty");
+}
+
+#[test]
+fn read_path_from_env() {
+    let file = TempFile::new("env", "from env");
+    let path = file.path();
+    let env = TestEnv::new().add("DB_PASSWORD_FILE", path.display().to_string());
+
+    let db = DatabaseLayer::from_env(&env).unwrap().complete().unwrap();
+
+    assert_eq!(db.password, "from env");
+}
+
+#[test]
+fn newer_layer_switches_to_file() {
+    let file = TempFile::new("switch", "from file");
+    let path = file.path();
+    let env = TestEnv::new().add("DB_PASSWORD_FILE", path.display().to_string());
+
+    let db = <Database as HasLayer>::Layer::new()
+        .merge(toml::from_str(r#"password = "qwerty""#).unwrap())
+        .merge(DatabaseLayer::from_env(&env).unwrap())
+        .complete()
+        .unwrap();
+
+    assert_eq!(db.password, "from file");
+}
+
+#[test]
+fn both_set_is_an_error() {
+    let layer: DatabaseLayer =
+        toml::from_str("password = \"qwerty\"\npassword_file = \"/run/secrets/db\"").unwrap();
+
+    let err = layer.complete_and_report().unwrap_err();
+
+    assert!(format!("{err:?}").contains("password_file"), "{err:?}");
+}
+
+#[test]
+fn missing_suggests_file_env() {
+    let err = <Database as HasLayer>::Layer::new()
+        .complete_and_report()
+        .unwrap_err();
+
+    let soukousei::CompleteErrorDiagnostic::Fields { fields } = err else {
+        panic!("expected field errors")
+    };
+    let soukousei::FieldErrorDiagnostic::Missing(missing) = &fields[0] else {
+        panic!("expected a missing field")
+    };
+    assert_eq!(
+        missing.help(),
+        "set `password` in a config file or export `DB_PASSWORD` or `DB_PASSWORD_FILE`"
+    );
+}
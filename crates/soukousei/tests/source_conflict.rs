@@ -0,0 +1,156 @@
+#![cfg(all(feature = "toml", feature = "json"))]
+
+use soukousei::builder::{BuildError, ConfigBuilder};
+use soukousei::conflict::SourceShapes;
+use soukousei::source::Format;
+use soukousei::value::Value;
+use soukousei::Layer;
+
+#[derive(Debug, Layer)]
+struct Config {
+    #[layer(nested)]
+    db: Db,
+    /// Accepts any shape, so that a conflict is not caught by decoding
+    extra: Option<Value>,
+}
+
+#[derive(Debug, Layer)]
+struct Db {
+    host: String,
+}
+
+const BASE: &str = r#"
+    extra = { level = 1 }
+
+    [db]
+    host = "localhost"
+"#;
+
+fn shapes() -> SourceShapes {
+    let mut shapes = SourceShapes::new();
+    shapes.add("base.toml", Format::Toml, BASE).unwrap();
+    shapes
+}
+
+#[test]
+fn conflict_names_both_sources() {
+    let err = shapes()
+        .add(
+            "override.json",
+            Format::Json,
+            r#"{ "db": "postgres://db" }"#,
+        )
+        .unwrap_err();
+
+    assert_eq!(err.source_name(), "override.json");
+    assert_eq!(err.fields().collect::<Vec<_>>(), ["db"]);
+    assert_eq!(
+        err.to_string(),
+        "`override.json` disagrees with earlier sources on `db` \
+         (table in `base.toml`, string in `override.json`)"
+    );
+}
+
+#[test]
+fn nested_keys_are_compared() {
+    let err = shapes()
+        .add("override.toml", Format::Toml, "db.host = [\"a\", \"b\"]")
+        .unwrap_err();
+
+    assert_eq!(err.fields().collect::<Vec<_>>(), ["db.host"]);
+}
+
+#[test]
+fn latest_source_setting_the_key_is_named() {
+    let mut shapes = shapes();
+    shapes
+        .add("second.toml", Format::Toml, "extra = { level = 2 }")
+        .unwrap();
+    let err = shapes
+        .add("third.toml", Format::Toml, "extra = 3")
+        .unwrap_err();
+
+    assert!(err.to_string().contains("table in `second.toml`"), "{err}");
+}
+
+#[test]
+fn null_in_between_unsets_the_key() {
+    let mut shapes = shapes();
+    shapes
+        .add("reset.json", Format::Json, r#"{ "extra": null }"#)
+        .unwrap();
+
+    shapes
+        .add("override.toml", Format::Toml, "extra = 3")
+        .unwrap();
+}
+
+#[test]
+fn same_shapes_and_scalars_of_other_types_are_not_conflicts() {
+    let mut shapes = shapes();
+
+    shapes
+        .add("override.json", Format::Json, r#"{ "db": { "host": 1 } }"#)
+        .unwrap();
+}
+
+#[test]
+fn rejected_source_is_not_compared_with() {
+    let mut shapes = shapes();
+    shapes
+        .add("bad.toml", Format::Toml, "extra = 3")
+        .unwrap_err();
+
+    shapes
+        .add("override.toml", Format::Toml, "extra = { level = 2 }")
+        .unwrap();
+}
+
+fn builder() -> ConfigBuilder<ConfigLayer> {
+    ConfigBuilder::new()
+        .deny_source_conflicts()
+        .with_str("base.toml", Format::Toml, BASE)
+        .unwrap()
+}
+
+#[test]
+fn builder_fails_on_conflicting_source() {
+    let Err(BuildError::SourceConflict(err)) =
+        builder().with_str("override.toml", Format::Toml, "extra = [1, 2]")
+    else {
+        panic!("expected a source conflict")
+    };
+
+    assert_eq!(
+        err.to_string(),
+        "`override.toml` disagrees with earlier sources on `extra` \
+         (table in `base.toml`, array in `override.toml`)"
+    );
+}
+
+#[test]
+fn builder_accepts_source_after_null_reset() {
+    let config = builder()
+        .with_str("reset.json", Format::Json, r#"{ "extra": null }"#)
+        .unwrap()
+        .with_str("override.toml", Format::Toml, "extra = 3")
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(config.db.host, "localhost");
+    assert_eq!(config.extra, Some(Value::Integer(3)));
+}
+
+#[test]
+fn conflicts_are_allowed_by_default() {
+    let config = ConfigBuilder::<ConfigLayer>::new()
+        .with_str("base.toml", Format::Toml, BASE)
+        .unwrap()
+        .with_str("override.toml", Format::Toml, "extra = 3")
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(config.extra, Some(Value::Integer(3)));
+}
@@ -0,0 +1,80 @@
+#![allow(dead_code)]
+
+use soukousei::builder::ConfigBuilder;
+use soukousei::source::{Format, SourceError};
+use soukousei::Layer;
+
+#[derive(Debug, Layer)]
+struct Config {
+    #[layer(nested)]
+    db: Db,
+}
+
+#[derive(Debug, Layer)]
+struct Db {
+    port: u16,
+}
+
+#[test]
+fn missing_file_is_an_io_error() {
+    let path = std::env::temp_dir().join(format!("soukousei-{}-missing.toml", std::process::id()));
+
+    let err = ConfigBuilder::<ConfigLayer>::new()
+        .with_file(&path)
+        .err()
+        .unwrap();
+
+    let Some(SourceError::Io { path: name, source }) = err.source_error() else {
+        panic!("expected an IO error, got {err:?}")
+    };
+    assert_eq!(name, &path.display().to_string());
+    assert_eq!(source.kind(), std::io::ErrorKind::NotFound);
+}
+
+#[test]
+fn invalid_syntax_is_a_parse_error() {
+    let err = ConfigBuilder::<ConfigLayer>::new()
+        .with_str("config.toml", Format::Toml, "[db\nport = 80")
+        .err()
+        .unwrap();
+
+    let Some(SourceError::Parse { path, span, .. }) = err.source_error() else {
+        panic!("expected a parse error, got {err:?}")
+    };
+    assert_eq!(path, "config.toml");
+    assert!(span.is_some());
+}
+
+#[test]
+fn type_mismatch_is_a_decode_error() {
+    let contents = "[db]\nport = \"eighty\"";
+
+    let err = ConfigBuilder::<ConfigLayer>::new()
+        .with_str("config.toml", Format::Toml, contents)
+        .err()
+        .unwrap();
+
+    let Some(SourceError::Decode {
+        path, field, span, ..
+    }) = err.source_error()
+    else {
+        panic!("expected a decode error, got {err:?}")
+    };
+    assert_eq!(path, "config.toml");
+    assert_eq!(field.as_deref(), Some("db.port"));
+    assert!(contents[span.clone().unwrap()].contains("eighty"));
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn json_type_mismatch_is_a_decode_error() {
+    let err = ConfigBuilder::<ConfigLayer>::new()
+        .with_str("config.json", Format::Json, r#"{"db": {"port": true}}"#)
+        .err()
+        .unwrap();
+
+    let Some(SourceError::Decode { field, .. }) = err.source_error() else {
+        panic!("expected a decode error, got {err:?}")
+    };
+    assert_eq!(field.as_deref(), Some("port"));
+}
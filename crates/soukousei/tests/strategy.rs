@@ -0,0 +1,80 @@
+#![cfg(feature = "proptest")]
+#![allow(dead_code)]
+
+use proptest::prelude::*;
+use proptest::strategy::ValueTree;
+use soukousei::strategy;
+use soukousei::{CompleteError, Layer};
+
+#[derive(Debug, Layer)]
+#[layer(derive(Debug, Clone, PartialEq))]
+struct Config {
+    name: String,
+    port: u16,
+    #[layer(nested)]
+    db: Db,
+}
+
+#[derive(Debug, Layer)]
+#[layer(derive(Debug, Clone, PartialEq))]
+struct Db {
+    host: String,
+    pool: Option<u32>,
+}
+
+proptest! {
+    #[test]
+    fn merge_laws_hold_for_derived_layers(
+        a in strategy::layer::<ConfigLayer>(),
+        b in strategy::layer::<ConfigLayer>(),
+        c in strategy::layer::<ConfigLayer>(),
+    ) {
+        strategy::check_merge_laws(a, b, c)?;
+    }
+}
+
+#[test]
+fn fields_are_set_independently() {
+    let mut runner = proptest::test_runner::TestRunner::deterministic();
+    let strategy = strategy::layer::<ConfigLayer>();
+
+    let layers: Vec<_> = (0..64)
+        .map(|_| strategy.new_tree(&mut runner).unwrap().current())
+        .collect();
+
+    assert!(layers.iter().any(|x| x.port.is_some() && x.name.is_none()));
+    assert!(layers.iter().any(|x| x.port.is_none() && x.name.is_some()));
+    assert!(layers.iter().any(|x| x.db.host.is_some()));
+    assert!(layers.iter().any(|x| x.db.host.is_none()));
+}
+
+#[test]
+fn derived_layers_obey_merge_laws() {
+    strategy::assert_merge_laws(strategy::layer::<ConfigLayer>());
+}
+
+/// Prefers the first provided value, so `new` is not a left identity
+#[derive(Debug, Clone, PartialEq)]
+struct FirstLayer(Option<u8>);
+
+impl Layer for FirstLayer {
+    type Complete = u8;
+
+    fn new() -> Self {
+        Self(Some(0))
+    }
+
+    fn merge(self, other: Self) -> Self {
+        Self(self.0.or(other.0))
+    }
+
+    fn complete(self) -> Result<Self::Complete, CompleteError> {
+        self.0.ok_or(CompleteError::MissingData)
+    }
+}
+
+#[test]
+#[should_panic(expected = "merge laws do not hold")]
+fn broken_custom_layers_are_caught() {
+    strategy::assert_merge_laws(any::<Option<u8>>().prop_map(FirstLayer));
+}
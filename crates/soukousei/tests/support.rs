@@ -0,0 +1,64 @@
+#![cfg(feature = "json")]
+#![allow(dead_code)]
+
+mod util;
+
+use soukousei::builder::ConfigBuilder;
+use soukousei::source::Format;
+use soukousei::Layer;
+use util::TestEnv;
+
+#[derive(Layer)]
+struct App {
+    #[layer(default = "8080")]
+    port: u16,
+    host: String,
+    #[layer(env = "TOKEN", secret)]
+    token: String,
+    #[layer(nested)]
+    tenants: std::collections::BTreeMap<String, Tenant>,
+}
+
+#[derive(Layer)]
+struct Tenant {
+    name: String,
+    enabled: bool,
+}
+
+#[test]
+fn support_bundle_is_anonymized() {
+    let bundle = ConfigBuilder::<AppLayer>::new()
+        .with_defaults()
+        .with_str(
+            "config.toml",
+            Format::Toml,
+            r#"
+            host = "internal.example.com"
+
+            [tenants.acme]
+            name = "ACME Corp"
+            enabled = true
+            "#,
+        )
+        .unwrap()
+        .with_env(&TestEnv::new().add("TOKEN", "hunter2"))
+        .unwrap()
+        .export_support_bundle()
+        .unwrap();
+
+    let config = &bundle["config"];
+    assert_eq!(config["port"], 8080);
+    assert_eq!(config["token"], "<redacted>");
+    assert!(config["host"].as_str().unwrap().starts_with("<hash:"));
+    assert!(config["tenants"]["acme"]["name"]
+        .as_str()
+        .unwrap()
+        .starts_with("<hash:"));
+    assert_eq!(config["tenants"]["acme"]["enabled"], true);
+    assert_eq!(bundle["provenance"]["host"], "config.toml");
+    assert_eq!(bundle["provenance"]["token"], "env");
+
+    let text = bundle.to_string();
+    assert!(!text.contains("hunter2"), "{text}");
+    assert!(!text.contains("example.com"), "{text}");
+}
@@ -0,0 +1,48 @@
+#![allow(dead_code)]
+
+use soukousei::telemetry::Telemetry;
+use soukousei::Layer;
+use std::collections::HashMap;
+
+#[derive(Debug, Layer)]
+struct Config {
+    #[layer(telemetry)]
+    region: String,
+    #[layer(telemetry)]
+    zone: Option<String>,
+    #[layer(secret)]
+    token: String,
+    #[layer(nested, telemetry)]
+    db: Database,
+    #[layer(nested)]
+    cache: Database,
+}
+
+#[derive(Debug, Layer)]
+struct Database {
+    #[layer(telemetry, default = "16")]
+    pool_size: u32,
+    host: String,
+}
+
+#[test]
+fn exports_opted_in_fields() {
+    let config: ConfigLayer = toml::from_str(
+        r#"
+        region = "eu-1"
+        token = "qwerty"
+        db.host = "db"
+        cache.host = "cache"
+        "#,
+    )
+    .unwrap();
+    let config = ConfigLayer::default().merge(config).complete().unwrap();
+
+    assert_eq!(
+        config.telemetry_attributes(),
+        HashMap::from([
+            ("region".to_owned(), "eu-1".to_owned()),
+            ("db.pool_size".to_owned(), "16".to_owned()),
+        ])
+    );
+}
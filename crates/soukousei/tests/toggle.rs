@@ -0,0 +1,72 @@
+use soukousei::toggle::Toggle;
+use soukousei::{meta, CompleteErrorDiagnostic, Layer};
+
+#[derive(Debug, Layer)]
+struct Config {
+    #[layer(toggle)]
+    metrics: Toggle<Metrics>,
+}
+
+#[derive(Debug, PartialEq, Layer)]
+struct Metrics {
+    endpoint: String,
+    #[layer(default = "10")]
+    interval: u32,
+}
+
+fn complete(input: &str) -> Result<Config, CompleteErrorDiagnostic> {
+    let layer: ConfigLayer = toml::from_str(input).unwrap();
+    ConfigLayer::default().merge(layer).complete_and_report()
+}
+
+#[test]
+fn disabled_unless_enabled() {
+    let config = complete("metrics.endpoint = \"http://localhost\"").unwrap();
+
+    assert_eq!(config.metrics, Toggle::Disabled);
+}
+
+#[test]
+fn disabled_section_is_not_validated() {
+    let config = complete("metrics.enabled = false").unwrap();
+
+    assert!(!config.metrics.is_enabled());
+}
+
+#[test]
+fn enabled_section_is_complete() {
+    let config = complete(
+        r#"
+        [metrics]
+        enabled = true
+        endpoint = "http://localhost"
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        config.metrics.enabled(),
+        Some(&Metrics {
+            endpoint: "http://localhost".to_owned(),
+            interval: 10,
+        })
+    );
+}
+
+#[test]
+fn enabled_but_misconfigured() {
+    let CompleteErrorDiagnostic::Fields { fields } =
+        complete("metrics.enabled = true").unwrap_err()
+    else {
+        panic!("expected field errors")
+    };
+
+    let fields: Vec<_> = fields.iter().map(ToString::to_string).collect();
+    assert_eq!(fields, vec!["`metrics.endpoint`: missing field"]);
+}
+
+#[test]
+fn enabled_key_is_known() {
+    assert!(meta::has_path(ConfigLayer::FIELDS, "metrics.enabled"));
+    assert!(meta::has_path(ConfigLayer::FIELDS, "metrics.endpoint"));
+}
@@ -0,0 +1,115 @@
+#![allow(dead_code)]
+
+use soukousei::tree::{Audience, RenderTree};
+use soukousei::Layer;
+
+#[derive(Layer)]
+#[layer(render_tree)]
+struct Server {
+    host: String,
+    port: u16,
+    #[layer(secret)]
+    token: String,
+    #[layer(nested)]
+    tls: Tls,
+}
+
+#[derive(Layer)]
+#[layer(render_tree)]
+struct Tls {
+    cert: Option<String>,
+}
+
+fn server() -> Server {
+    Server {
+        host: "localhost".to_owned(),
+        port: 8080,
+        token: "hunter2".to_owned(),
+        tls: Tls { cert: None },
+    }
+}
+
+#[test]
+fn render_tree_redacts_secrets() {
+    assert_eq!(
+        server().render_tree(),
+        "host = \"localhost\"\nport = 8080\ntoken = <redacted>\ntls:\n  cert = None\n"
+    );
+}
+
+#[test]
+fn render_tree_with_annotations() {
+    let tree = server().render_tree_with(&|path| match path {
+        "port" => Some("env: PORT".to_owned()),
+        "tls.cert" => Some("default".to_owned()),
+        _ => None,
+    });
+
+    assert_eq!(
+        tree,
+        "host = \"localhost\"\nport = 8080 [env: PORT]\ntoken = <redacted>\ntls:\n  cert = None [default]\n"
+    );
+}
+
+#[derive(Layer)]
+#[layer(render_tree)]
+struct Service {
+    name: String,
+    #[layer(secret)]
+    api_key: String,
+    #[layer(visibility = "admin")]
+    pool_size: u32,
+    #[layer(nested, visibility = "ops")]
+    tuning: Tuning,
+}
+
+#[derive(Layer)]
+#[layer(render_tree)]
+struct Tuning {
+    backlog: u32,
+}
+
+fn service() -> Service {
+    Service {
+        name: "api".to_owned(),
+        api_key: "hunter2".to_owned(),
+        pool_size: 16,
+        tuning: Tuning { backlog: 128 },
+    }
+}
+
+#[test]
+fn dump_for_audience() {
+    assert_eq!(
+        service().dump_for(&Audience::public().without_secrets()),
+        "name = \"api\"\n"
+    );
+    assert_eq!(
+        service().dump_for(&Audience::new(["admin"])),
+        "name = \"api\"\napi_key = <redacted>\npool_size = 16\n"
+    );
+    assert_eq!(
+        service().dump_for(&Audience::new(["admin", "ops"])),
+        "name = \"api\"\napi_key = <redacted>\npool_size = 16\ntuning:\n  backlog = 128\n"
+    );
+}
+
+#[test]
+fn render_tree_shows_everything() {
+    assert_eq!(
+        service().render_tree(),
+        "name = \"api\"\napi_key = <redacted>\npool_size = 16\ntuning:\n  backlog = 128\n"
+    );
+    assert_eq!(
+        ServiceLayer::fields()
+            .iter()
+            .map(|x| (x.name, x.visibility))
+            .collect::<Vec<_>>(),
+        [
+            ("name", None),
+            ("api_key", None),
+            ("pool_size", Some("admin")),
+            ("tuning", Some("ops"))
+        ]
+    );
+}
@@ -0,0 +1,43 @@
+use soukousei::builder::ConfigBuilder;
+use soukousei::source::Format;
+use soukousei::Layer;
+
+#[derive(Debug, Layer)]
+struct Sample {
+    port: u16,
+    /// Reserved for plugin settings
+    #[layer(nested)]
+    plugins: Plugins,
+}
+
+#[derive(Debug, PartialEq, Layer)]
+struct Plugins;
+
+#[test]
+fn placeholder_section_might_be_included() {
+    let sample = ConfigBuilder::<SampleLayer>::new()
+        .with_str("config.toml", Format::Toml, "port = 8080\n[plugins]")
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(sample.port, 8080);
+    assert_eq!(sample.plugins, Plugins);
+}
+
+#[test]
+fn placeholder_section_might_be_absent() {
+    let sample = ConfigBuilder::<SampleLayer>::new()
+        .with_str("config.toml", Format::Toml, "port = 8080")
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(sample.plugins, Plugins);
+}
+
+#[test]
+fn unit_layer_is_trivially_complete() {
+    assert_eq!(PluginsLayer::new().complete().unwrap(), Plugins);
+    assert!(PluginsLayer::FIELDS.is_empty());
+}
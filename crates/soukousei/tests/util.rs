@@ -1,6 +1,10 @@
-use soukousei::{env::EnvProvider, miette::Report};
+#![allow(dead_code)]
+
+use soukousei::{env::EnvProvider, Report};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
+#[derive(Default)]
 pub struct TestEnv {
     map: HashMap<String, String>,
 }
@@ -23,4 +27,38 @@ impl EnvProvider for TestEnv {
     fn fetch(&self, key: impl AsRef<str>) -> Result<Option<String>, Report> {
         Ok(self.map.get(key.as_ref()).cloned())
     }
+
+    fn iter_prefixed(&self, prefix: &str) -> Result<Vec<(String, String)>, Report> {
+        let mut vars: Vec<_> = self
+            .map
+            .iter()
+            .filter(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        vars.sort();
+        Ok(vars)
+    }
+}
+
+/// File in the temp dir, unique to the test process, which is removed on drop
+pub struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    pub fn new(name: &str, contents: impl AsRef<[u8]>) -> Self {
+        let path = std::env::temp_dir().join(format!("soukousei-{}-{name}", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
 }
@@ -0,0 +1,102 @@
+use soukousei::value::Value;
+use soukousei::Layer;
+
+#[derive(Debug, Layer)]
+struct Config {
+    port: u16,
+    limit: u64,
+    ratio: f64,
+    name: Option<String>,
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn toml_scalars_keep_their_types() {
+    let value: Value = toml::from_str(
+        r#"
+        port = 8080
+        quoted = "8080"
+        ratio = 0.5
+        enabled = true
+        at = 1979-05-27T07:32:00Z
+        "#,
+    )
+    .unwrap();
+
+    let types: Vec<_> = ["port", "quoted", "ratio", "enabled", "at"]
+        .into_iter()
+        .map(|x| value.get(x).unwrap().type_name())
+        .collect();
+    assert_eq!(types, ["integer", "string", "float", "boolean", "datetime"]);
+    assert_eq!(
+        value.get("at"),
+        Some(&Value::Datetime("1979-05-27T07:32:00Z".to_owned()))
+    );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn large_integers_are_lossless() {
+    let value: Value = serde_json::from_str(r#"{"max": 18446744073709551615, "min": -1}"#).unwrap();
+
+    assert_eq!(value.get("max"), Some(&Value::Integer(u64::MAX.into())));
+    assert_eq!(value.get("min"), Some(&Value::Integer(-1)));
+    assert_eq!(
+        serde_json::to_string(&value).unwrap(),
+        r#"{"max":18446744073709551615,"min":-1}"#
+    );
+}
+
+#[test]
+fn display_shows_the_type() {
+    assert_eq!(Value::Integer(8080).to_string(), "integer 8080");
+    assert_eq!(
+        Value::String("8080".to_owned()).to_string(),
+        "string \"8080\""
+    );
+    assert_eq!(Value::Float(1.0).to_string(), "float 1.0");
+    assert_eq!(
+        Value::Array(vec![Value::Null]).to_string(),
+        "array of 1 items"
+    );
+}
+
+#[test]
+fn layer_is_deserialized_from_the_tree() {
+    let value = Value::Table(
+        [
+            ("port", Value::Integer(80)),
+            ("limit", Value::Integer(u64::MAX.into())),
+            ("ratio", Value::Float(0.25)),
+            ("name", Value::Null),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_owned(), v))
+        .collect(),
+    );
+
+    let config = value
+        .deserialize_into::<ConfigLayer>()
+        .unwrap()
+        .complete()
+        .unwrap();
+
+    assert_eq!(config.port, 80);
+    assert_eq!(config.limit, u64::MAX);
+    assert_eq!(config.ratio, 0.25);
+    assert_eq!(config.name, None);
+}
+
+#[test]
+fn mismatched_types_are_reported() {
+    let value = Value::Table([("port".to_owned(), Value::String("80".to_owned()))].into());
+
+    let Err(err) = value.deserialize_into::<ConfigLayer>() else {
+        panic!("expected an error")
+    };
+
+    assert!(
+        err.to_string().contains("invalid type: string \"80\""),
+        "{err}"
+    );
+}
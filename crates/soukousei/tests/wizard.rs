@@ -0,0 +1,109 @@
+#![cfg(feature = "wizard")]
+#![allow(dead_code)]
+
+use soukousei::toggle::Toggle;
+use soukousei::wizard::{Terminal, Wizard};
+use soukousei::Layer;
+use std::collections::VecDeque;
+
+#[derive(Debug, Layer)]
+struct Config {
+    /// Port to listen on
+    #[layer(default = "8080")]
+    port: u16,
+    name: String,
+    #[layer(secret)]
+    token: String,
+    #[layer(nested)]
+    database: Database,
+    #[layer(toggle)]
+    metrics: Toggle<Metrics>,
+}
+
+#[derive(Debug, Layer)]
+struct Database {
+    url: String,
+}
+
+#[derive(Debug, Layer)]
+struct Metrics {
+    endpoint: String,
+}
+
+#[derive(Default)]
+struct Scripted {
+    answers: VecDeque<&'static str>,
+    output: String,
+    secrets_read: usize,
+}
+
+impl Scripted {
+    fn new(answers: &[&'static str]) -> Self {
+        Self {
+            answers: answers.iter().copied().collect(),
+            ..Self::default()
+        }
+    }
+}
+
+impl Terminal for Scripted {
+    fn print(&mut self, text: &str) -> std::io::Result<()> {
+        self.output.push_str(text);
+        Ok(())
+    }
+
+    fn read_line(&mut self) -> std::io::Result<String> {
+        Ok(self
+            .answers
+            .pop_front()
+            .expect("no more answers")
+            .to_owned())
+    }
+
+    fn read_secret(&mut self) -> std::io::Result<String> {
+        self.secrets_read += 1;
+        self.read_line()
+    }
+}
+
+#[test]
+fn produces_layer_and_config() {
+    let mut terminal = Scripted::new(&[
+        "not a port",
+        "",
+        "",
+        "app",
+        "hunter2",
+        "postgres://localhost",
+        "n",
+    ]);
+
+    let answers = Wizard::new(&mut terminal).run::<ConfigLayer>().unwrap();
+
+    let document = answers.to_toml();
+    for line in [
+        "name = \"app\"",
+        "token = \"hunter2\"",
+        "[database]\nurl = \"postgres://localhost\"",
+        "[metrics]\nenabled = false",
+    ] {
+        assert!(document.contains(line), "{document}");
+    }
+    assert!(!document.contains("port"), "{document}");
+    let config = ConfigLayer::default()
+        .merge(answers.into_layer())
+        .complete()
+        .unwrap();
+    assert_eq!(config.port, 8080);
+    assert_eq!(config.name, "app");
+    assert_eq!(config.token, "hunter2");
+    assert_eq!(config.database.url, "postgres://localhost");
+    assert!(!config.metrics.is_enabled());
+
+    assert!(terminal
+        .output
+        .contains("Port to listen on\nport (u16) [default: 8080]: "));
+    assert!(terminal.output.contains("Invalid value: "));
+    assert!(terminal.output.contains("A value is required"));
+    assert_eq!(terminal.secrets_read, 1);
+}
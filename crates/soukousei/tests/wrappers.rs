@@ -0,0 +1,125 @@
+#![allow(dead_code)]
+
+mod util;
+
+use soukousei::builder::ConfigBuilder;
+use soukousei::env::FromEnv;
+use soukousei::source::Format;
+use soukousei::{CompleteError, Layer};
+use util::TestEnv;
+
+#[derive(Debug, Layer)]
+struct Config {
+    #[layer(nested)]
+    primary: Database,
+    #[layer(nested)]
+    replica: Option<Database>,
+    #[layer(nested)]
+    cache: Box<Cache>,
+}
+
+#[derive(Debug, Layer)]
+struct Database {
+    url: String,
+    #[layer(default = "10")]
+    pool: u32,
+}
+
+#[derive(Debug, Layer)]
+struct Cache {
+    #[layer(env = "CACHE_TTL", default = "60")]
+    ttl: u32,
+}
+
+#[test]
+fn absent_optional_section_is_none() {
+    let config = ConfigBuilder::<ConfigLayer>::new()
+        .with_defaults()
+        .with_str("config.toml", Format::Toml, "[primary]\nurl = \"pg://a\"")
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert!(config.replica.is_none());
+    assert_eq!(config.cache.ttl, 60);
+}
+
+#[test]
+fn present_optional_section_is_merged_over_defaults() {
+    let config = ConfigBuilder::<ConfigLayer>::new()
+        .with_defaults()
+        .with_str(
+            "config.toml",
+            Format::Toml,
+            "[primary]\nurl = \"pg://a\"\n\n[replica]\nurl = \"pg://b\"",
+        )
+        .unwrap()
+        .with_str("override.toml", Format::Toml, "[replica]\npool = 2")
+        .unwrap()
+        .build()
+        .unwrap();
+
+    let replica = config.replica.unwrap();
+    assert_eq!(replica.url, "pg://b");
+    assert_eq!(replica.pool, 2);
+}
+
+#[test]
+fn present_optional_section_must_be_complete() {
+    let layer: ConfigLayer =
+        toml::from_str("[primary]\nurl = \"pg://a\"\n\n[replica]\npool = 2").unwrap();
+
+    let Err(CompleteError::Fields(errors)) = ConfigLayer::default().merge(layer).complete() else {
+        panic!("expected field errors")
+    };
+    let paths: Vec<_> = errors.iter().map(|x| x.joined_path()).collect();
+    assert_eq!(paths, ["replica.url"]);
+}
+
+#[test]
+fn tuples_of_layers_are_merged_together() {
+    let env = TestEnv::new().add("CACHE_TTL", "5");
+    let mut database = DatabaseLayer::new();
+    database.url = Some("pg://a".to_owned());
+
+    let (database, cache) = ConfigBuilder::<(DatabaseLayer, CacheLayer)>::new()
+        .with_defaults()
+        .with_layer((database, CacheLayer::new()))
+        .with_env(&env)
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(database.url, "pg://a");
+    assert_eq!(database.pool, 10);
+    assert_eq!(cache.ttl, 5);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn tuples_are_deserialized_from_arrays() {
+    let (database, cache) = ConfigBuilder::<(DatabaseLayer, CacheLayer)>::new()
+        .with_defaults()
+        .with_str(
+            "config.json",
+            Format::Json,
+            r#"[{"url": "pg://a"}, {"ttl": 1}]"#,
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(database.url, "pg://a");
+    assert_eq!(cache.ttl, 1);
+}
+
+#[test]
+fn tuple_errors_are_indexed() {
+    let Err(CompleteError::Fields(errors)) = <(DatabaseLayer, CacheLayer)>::new().complete() else {
+        panic!("expected field errors")
+    };
+
+    let paths: Vec<_> = errors.iter().map(|x| x.joined_path()).collect();
+    assert_eq!(paths, ["[0].url", "[0].pool", "[1].ttl"]);
+    assert!(<(DatabaseLayer, CacheLayer)>::from_env(&TestEnv::new()).is_ok());
+}
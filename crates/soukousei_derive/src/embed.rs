@@ -0,0 +1,94 @@
+//! `include_config!` implementation
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+
+pub struct IncludeConfigInput {
+    ty: syn::Type,
+    path: syn::LitStr,
+}
+
+impl Parse for IncludeConfigInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ty = input.parse()?;
+        input.parse::<syn::Token![,]>()?;
+        let path = input.parse()?;
+        input.parse::<Option<syn::Token![,]>>()?;
+        Ok(Self { ty, path })
+    }
+}
+
+pub fn expand(IncludeConfigInput { ty, path }: IncludeConfigInput) -> syn::Result<TokenStream> {
+    let name = path.value();
+    let full_path = std::env::var("CARGO_MANIFEST_DIR")
+        .map(std::path::PathBuf::from)
+        .unwrap_or_default()
+        .join(&name);
+
+    let contents = std::fs::read_to_string(&full_path).map_err(|err| {
+        syn::Error::new_spanned(
+            &path,
+            format!("failed to read `{}`: {err}", full_path.display()),
+        )
+    })?;
+    let table: toml::Table = contents.parse().map_err(|err| {
+        syn::Error::new_spanned(&path, format!("`{name}` is not a valid TOML: {err}"))
+    })?;
+
+    let layer_ty = quote! { <#ty as ::soukousei::HasLayer>::Layer };
+    let checks = collect_keys(&table).into_iter().map(|key| {
+        // the message is a format string
+        let message = format!("unknown key `{key}` in `{name}`")
+            .replace('{', "{{")
+            .replace('}', "}}");
+        quote! {
+            ::core::assert!(
+                ::soukousei::meta::has_path(<#layer_ty as ::soukousei::Layer>::FIELDS, #key),
+                #message
+            );
+        }
+    });
+    let full_path = full_path.display().to_string();
+
+    Ok(quote! {
+        {
+            const _: () = {
+                #(#checks)*
+            };
+
+            ::soukousei::source::Embedded::<#layer_ty>::new(#name, ::core::include_str!(#full_path))
+        }
+    })
+}
+
+/// Dot-separated paths of all keys with values. Elements of arrays of tables are checked
+/// against the same fields.
+pub fn collect_keys(table: &toml::Table) -> Vec<String> {
+    let mut keys = Vec::new();
+    collect_keys_into(&mut keys, table, "");
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+fn collect_keys_into(keys: &mut Vec<String>, table: &toml::Table, prefix: &str) {
+    for (key, value) in table {
+        let path = format!("{prefix}{key}");
+        match value {
+            toml::Value::Table(nested) if !nested.is_empty() => {
+                collect_keys_into(keys, nested, &format!("{path}."))
+            }
+            toml::Value::Array(items)
+                if !items.is_empty() && items.iter().all(toml::Value::is_table) =>
+            {
+                for item in items {
+                    if let toml::Value::Table(nested) = item {
+                        collect_keys_into(keys, nested, &format!("{path}."))
+                    }
+                }
+            }
+            _ => keys.push(path),
+        }
+    }
+}
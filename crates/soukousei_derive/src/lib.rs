@@ -2,80 +2,382 @@ use darling::{FromDeriveInput, FromField, FromMeta};
 use proc_macro::TokenStream;
 use syn::{parse_macro_input, Expr, Lit};
 
+mod embed;
+mod newtype;
+mod rename;
+
+/// A unit struct derives a layer without fields, e.g. a placeholder section kept for forward
+/// compatibility, which files might already include
 #[derive(Debug, FromDeriveInput, Eq, PartialEq)]
-#[darling(attributes(layer), supports(struct_named))]
+#[darling(attributes(layer), supports(struct_named, struct_unit))]
 struct LayerArgs {
     ident: syn::Ident,
+    vis: syn::Visibility,
     data: darling::ast::Data<darling::util::Ignored, LayerFieldArgs>,
+    /// Do not derive `Serialize` and `Deserialize` for the generated layer
+    #[darling(default)]
+    no_serde: bool,
+    /// Extra derives for the generated layer, e.g. `#[layer(derive(Clone, PartialEq))]`
+    ///
+    /// Nested layers should derive the same traits.
+    #[darling(default)]
+    derive: darling::util::PathList,
+    /// Implement `RenderTree` for the complete type
+    #[darling(default)]
+    render_tree: bool,
+    /// Do not implement `FromEnv` for the generated layer
+    #[darling(default)]
+    no_env: bool,
+    /// Implement `EnvExport` for the complete type
+    #[darling(default)]
+    env_export: bool,
+    /// Cross-section references resolved on completion, e.g.
+    /// `#[layer(reference(field = "server.tls", section = "tls_profiles"))]`
+    #[darling(multiple, rename = "reference")]
+    references: Vec<LayerReferenceArgs>,
+    /// Generate `complete_partial`, which completes nested sections independently, and a
+    /// `<Struct>Degraded` type with optional sections
+    #[darling(default)]
+    degradable: bool,
+    /// Assert at compile time that the complete type is `Send + Sync + 'static`, so that it
+    /// might be stored in a shared application state
+    #[darling(default)]
+    assert_send_sync: bool,
+    /// Generate getters on the complete type, which return `&str` for `String`, slices for
+    /// `Vec` and references otherwise, and `with_*` setters, so that fields might become private
+    /// or the struct `#[non_exhaustive]` without breaking downstream code
+    #[darling(default)]
+    getters: bool,
+    /// Put the generated layer into a private module, so that it is reachable only as
+    /// `<T as HasLayer>::Layer` and doesn't appear in the public API of a library
+    #[darling(default)]
+    private_module: bool,
+    /// Stop `complete` at the first missing or invalid field instead of reporting all of them,
+    /// e.g. for giant configs where the full traversal is expensive
+    #[darling(default)]
+    fail_fast: bool,
+    /// Naming convention of keys in files, e.g. `"kebab-case"`, the same as of
+    /// `#[serde(rename_all = "...")]`. Applies to this struct only, not to nested ones.
+    file_rename_all: Option<String>,
+    /// Naming convention of ENV vars, e.g. `"SCREAMING_SNAKE_CASE"`. Fields without `env`
+    /// are read from the renamed field name, e.g. `MAX_CONNECTIONS` for `max_connections`.
+    /// Applies to this struct only, so fields of the same name in nested structs read the same
+    /// var, unless the structs have different `env_prefix`.
+    env_rename_all: Option<String>,
+    /// Prefix of ENV vars derived from field names, e.g. `"DB_"` for `DB_PORT`, see
+    /// `env_rename_all`
+    env_prefix: Option<String>,
+    /// Path to `soukousei` in generated code, e.g. `#[layer(crate = "framework::soukousei")]`,
+    /// so that a framework re-exporting it doesn't require a direct dependency
+    #[darling(default, rename = "crate")]
+    krate: Option<syn::Path>,
     // TODO: how to collect all struct-level serde attributes? So that we can pass them to the Partial
-    // TODO: inherit visibility?
+}
+
+#[derive(Debug, FromMeta, Eq, PartialEq)]
+struct LayerReferenceArgs {
+    /// Dot-separated path to a `Ref<T>` field
+    field: String,
+    /// Dot-separated path to a map section with referenced entries
+    section: String,
 }
 
 #[derive(Debug, FromField, Eq, PartialEq)]
-#[darling(attributes(layer))]
+#[darling(attributes(layer), forward_attrs(doc))]
 struct LayerFieldArgs {
     ident: Option<syn::Ident>,
+    vis: syn::Visibility,
     ty: syn::Type,
+    attrs: Vec<syn::Attribute>,
 
     /// Associated default value
+    ///
+    /// On an `Option` field, the default applies when the key is absent, while an explicit null
+    /// overrides it and leaves the field empty.
     default: Option<String>,
-    /// Associated ENV var(s)
+    /// Associated ENV var(s). A bare `#[layer(env)]` derives the name from the field, e.g.
+    /// `MAX_CONNECTIONS` for `max_connections`, following `env_rename_all` if it is set.
     env: Option<LayerParamEnv>,
     /// Flag that indicates that there is a nested configuration
     ///
-    /// TODO: can we validate that the type of the nested field has `::Partial`?
+    /// The layer type is resolved as `<Type as HasLayer>::Layer`, so type aliases and generic
+    /// instantiations work, and the compiler validates that the type implements `HasLayer`.
+    ///
+    /// A struct nesting itself, even through `Box` or `Vec`, is rejected, as its layer and
+    /// metadata would be infinite. Cycles through other structs can't be detected here and fail
+    /// with "recursive type has infinite size" or a cycle in `FIELDS`; break them with `opaque`.
     #[darling(default)]
     nested: bool,
+    /// Flag that indicates that the value should never be displayed
+    #[darling(default)]
+    secret: bool,
+    /// Flag that indicates that the value might also be read from a file, which path is set with
+    /// a sibling `<field>_file` key or a `<ENV>_FILE` var
+    #[darling(default)]
+    sensitive_file: bool,
+    /// Flag that indicates that the type is deserialized and merged as a whole, e.g. a type from
+    /// another crate that implements `Deserialize` and `Default`, but not `HasLayer`
+    #[darling(default)]
+    opaque: bool,
+    /// Flag that indicates that the value is parsed from ENV with `EnvEnum` instead of `FromStr`
+    #[darling(default)]
+    env_enum: bool,
+    /// Flag that indicates that a `String` is borrowed from the source while deserializing the
+    /// layer, which then has a lifetime, and is converted to owned only on completion
+    #[darling(default)]
+    borrow: bool,
+    /// Flag that indicates that the value is exported as a telemetry attribute, see
+    /// `soukousei::telemetry`. On a nested field, its own telemetry fields are exported.
+    #[darling(default)]
+    telemetry: bool,
+    /// Flag that indicates that there is a nested `Toggle<T>` section, which might be switched
+    /// with `enabled = true/false`. Implies `nested`.
+    #[darling(default)]
+    toggle: bool,
+    /// Flag that indicates that the field is a map capturing all unknown keys of file sources,
+    /// e.g. `HashMap<String, String>` for plugins reading extra keys from the same file. The
+    /// captured keys are still reported by `soukousei::lint`.
+    #[darling(default)]
+    flatten: bool,
+    /// Prefix of ENV vars of `nested` collection elements, e.g. `"MYAPP_UPSTREAMS"`, so that
+    /// the `ADDR` var of the first element is read from `MYAPP_UPSTREAMS_0_ADDR`
+    env_indexed: Option<String>,
+    /// Type implementing `FieldParser`, which parses the value from ENV and from strings in
+    /// files instead of `FromStr` and `Deserialize`, e.g. `soukousei::datetime::ChronoUtc`
+    parse: Option<syn::Path>,
+    /// Flag that indicates that the `env` var is read at build time with `option_env!` and
+    /// baked into the default, e.g. a git sha stamped by the build script. A baked-in value
+    /// which fails to parse is reported on completion, unless a source sets the field.
+    #[darling(default)]
+    compile_time: bool,
+    /// Range the value is checked against on completion, e.g. `"1..=65535"` or `"0.0..1.0"`
+    range: Option<String>,
+    /// Function merging values of the field instead of overriding the older one with the newer
+    /// one, e.g. to take the maximum or to concatenate lists. It is
+    /// `fn(Option<T>, Option<T>) -> Option<T>` of the layer field, with the older value first.
+    merge_with: Option<syn::Path>,
+    /// Group of the field for settings editors generated from the metadata, e.g. `"Networking"`
+    group: Option<String>,
+    /// Position of the field for settings editors, fields without it keep the declaration
+    /// order
+    order: Option<i32>,
+    /// Audience which may see the field in dumps, e.g. `"admin"`, see
+    /// `soukousei::tree::Audience`. Fields without it are visible to everyone.
+    visibility: Option<String>,
     // TODO how to collect all field-level serde attributes? So that we can pass them to the Partial
 }
 
-trait IsIdentOption {
+trait IsOption {
     fn is_option_already(&self) -> bool;
 }
 
-impl IsIdentOption for syn::Ident {
+impl IsOption for syn::Type {
+    /// Syntactic check, i.e. `type Alias = Option<T>` is not considered as an option
     fn is_option_already(&self) -> bool {
-        todo!()
+        match self {
+            syn::Type::Path(syn::TypePath { qself: None, path }) => path
+                .segments
+                .last()
+                .map(|segment| {
+                    segment.ident == "Option"
+                        && matches!(segment.arguments, syn::PathArguments::AngleBracketed(_))
+                })
+                .unwrap_or(false),
+            _ => false,
+        }
     }
 }
 
 struct LayerFieldBase {
     ident: syn::Ident,
+    vis: syn::Visibility,
     ty: syn::Type,
+    doc: Option<String>,
+    telemetry: bool,
+    group: Option<String>,
+    order: Option<i32>,
+    visibility: Option<String>,
+}
+
+/// Collects `#[doc = "..."]` attributes into a single string
+fn collect_doc(attrs: &[syn::Attribute]) -> Option<String> {
+    let lines: Vec<_> = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            syn::Meta::NameValue(syn::MetaNameValue {
+                value:
+                    Expr::Lit(syn::ExprLit {
+                        lit: Lit::Str(lit), ..
+                    }),
+                ..
+            }) => Some(lit.value().trim().to_owned()),
+            _ => None,
+        })
+        .collect();
+
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n"))
+    }
+}
+
+/// Renders a type as a compact string, e.g. `Option<String>` instead of `Option < String >`
+fn type_name(ty: &syn::Type) -> String {
+    quote::quote!(#ty)
+        .to_string()
+        .replace(" < ", "<")
+        .replace("< ", "<")
+        .replace(" >", ">")
+        .replace(" :: ", "::")
+        .replace(":: ", "::")
+        .replace(" ,", ",")
+        .replace("& ", "&")
 }
 
 enum LayerField {
     Nested {
         base: LayerFieldBase,
+        toggle: bool,
+        env_indexed: Option<String>,
+    },
+    CatchAll {
+        base: LayerFieldBase,
     },
     Field {
         base: LayerFieldBase,
         // TODO: should be not a string, but a parsed expression, like `Default::default()`
         default: Option<String>,
         env: Option<LayerParamEnv>,
+        secret: bool,
+        sensitive_file: bool,
+        opaque: bool,
+        env_enum: bool,
+        borrow: bool,
+        parse: Option<syn::Path>,
+        compile_time: bool,
+        range: Option<String>,
+        merge_with: Option<syn::Path>,
     },
 }
 
+/// Attributes of a field which cannot be used together
+#[derive(Debug, PartialEq, Eq)]
+enum LayerFieldError {
+    /// The field of a tuple struct, which is handled by `newtype`
+    Unnamed,
+    Conflict(&'static str, &'static str),
+    /// The first attribute is set without the second one
+    Requires(&'static str, &'static str),
+}
+
+impl std::fmt::Display for LayerFieldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unnamed => write!(f, "fields should be named"),
+            Self::Conflict(a, b) => write!(f, "`{a}` cannot be combined with `{b}`"),
+            Self::Requires(a, b) => write!(f, "`{a}` requires `{b}`"),
+        }
+    }
+}
+
 impl TryFrom<LayerFieldArgs> for LayerField {
-    type Error = ();
+    type Error = LayerFieldError;
 
     fn try_from(
         LayerFieldArgs {
             ident,
+            vis,
             ty,
+            attrs,
             default,
             env,
             nested,
+            secret,
+            sensitive_file,
+            opaque,
+            env_enum,
+            borrow,
+            telemetry,
+            toggle,
+            flatten,
+            env_indexed,
+            parse,
+            compile_time,
+            range,
+            merge_with,
+            group,
+            order,
+            visibility,
         }: LayerFieldArgs,
     ) -> Result<Self, Self::Error> {
-        let ident = ident.ok_or(())?;
-        let base = LayerFieldBase { ident, ty };
-        let param = match (nested, default, env) {
-            (true, None, None) => LayerField::Nested { base },
-            (false, default, env) => LayerField::Field { base, default, env },
-            _ => return Err(()),
+        let ident = ident.ok_or(LayerFieldError::Unnamed)?;
+        let doc = collect_doc(&attrs);
+        let base = LayerFieldBase {
+            ident,
+            vis,
+            ty,
+            doc,
+            telemetry,
+            group,
+            order,
+            visibility,
+        };
+        let first = |attrs: &[(&'static str, bool)]| {
+            attrs.iter().find(|(_, set)| *set).map(|(name, _)| *name)
         };
-        Ok(param)
+        let structural = first(&[("nested", nested), ("toggle", toggle), ("flatten", flatten)]);
+        let value = first(&[
+            ("default", default.is_some()),
+            ("env", env.is_some()),
+            ("secret", secret),
+            ("sensitive_file", sensitive_file),
+            ("opaque", opaque),
+            ("env_enum", env_enum),
+            ("borrow", borrow),
+            ("parse", parse.is_some()),
+            ("compile_time", compile_time),
+            ("range", range.is_some()),
+            ("merge_with", merge_with.is_some()),
+        ]);
+        if let (Some(structural), Some(value)) = (structural, value) {
+            return Err(LayerFieldError::Conflict(structural, value));
+        }
+        if flatten {
+            if let Some(other) = first(&[
+                ("nested", nested),
+                ("toggle", toggle),
+                ("env_indexed", env_indexed.is_some()),
+            ]) {
+                return Err(LayerFieldError::Conflict("flatten", other));
+            }
+            return Ok(LayerField::CatchAll { base });
+        }
+        if nested || toggle {
+            return Ok(LayerField::Nested {
+                base,
+                toggle,
+                env_indexed,
+            });
+        }
+        if env_indexed.is_some() {
+            return Err(LayerFieldError::Requires("env_indexed", "nested"));
+        }
+        Ok(LayerField::Field {
+            base,
+            default,
+            env,
+            secret,
+            sensitive_file,
+            opaque,
+            env_enum,
+            borrow,
+            parse,
+            compile_time,
+            range,
+            merge_with,
+        })
     }
 }
 
@@ -83,22 +385,40 @@ impl TryFrom<LayerFieldArgs> for LayerField {
 enum LayerParamEnv {
     Single(String),
     Multiple(Vec<String>),
+    /// A bare `#[layer(env)]`, replaced with `Single` by `Ir::from_args`
+    Auto,
+}
+
+impl LayerParamEnv {
+    fn names(&self) -> Vec<&str> {
+        match self {
+            Self::Single(name) => vec![name.as_str()],
+            Self::Multiple(names) => names.iter().map(String::as_str).collect(),
+            Self::Auto => unreachable!("the name is derived before codegen"),
+        }
+    }
 }
 
 impl FromMeta for LayerParamEnv {
+    fn from_word() -> darling::Result<Self> {
+        Ok(Self::Auto)
+    }
+
     fn from_expr(expr: &Expr) -> darling::Result<Self> {
-        use syn::{ExprArray, ExprLit, Lit, LitStr};
+        use syn::{ExprArray, ExprLit, Lit};
 
         match expr {
             // TODO: is there a less verbose way to parse expr as `["A", "B"]`?
-            Expr::Array(ExprArray { attrs, elems, .. }) if attrs.len() == 0 && elems.len() > 0 => {
+            Expr::Array(ExprArray { attrs, elems, .. })
+                if attrs.is_empty() && !elems.is_empty() =>
+            {
                 let literals = elems
                     .into_iter()
                     .map(|lit_expr| match lit_expr {
                         Expr::Lit(ExprLit {
                             attrs,
                             lit: Lit::Str(lit),
-                        }) if attrs.len() == 0 => Ok(lit.value()),
+                        }) if attrs.is_empty() => Ok(lit.value()),
                         _ => Err(darling::Error::unexpected_expr_type(lit_expr)),
                     })
                     .collect::<Result<Vec<_>, _>>()?;
@@ -108,7 +428,7 @@ impl FromMeta for LayerParamEnv {
             Expr::Lit(ExprLit {
                 attrs,
                 lit: Lit::Str(lit),
-            }) if attrs.len() == 0 => Ok(Self::Single(lit.value())),
+            }) if attrs.is_empty() => Ok(Self::Single(lit.value())),
             _ => Err(darling::Error::unexpected_expr_type(expr)),
         }
     }
@@ -117,168 +437,2320 @@ impl FromMeta for LayerParamEnv {
 mod codegen {
     use super::LayerField;
     use super::LayerParamEnv;
-    use crate::LayerArgs;
-    use darling::FromMeta;
-    use miette::{miette, Result};
+    use crate::rename::RenameRule;
+    use crate::{type_name, IsOption, LayerArgs, LayerFieldBase};
+    use miette::{miette, IntoDiagnostic, Report, Result};
     use proc_macro2::TokenStream;
-    use quote::quote;
+    use quote::{format_ident, quote};
+    use syn::spanned::Spanned;
 
-    struct Ir {
+    pub struct Ir {
+        vis: syn::Visibility,
         ident_main: syn::Ident,
         ident_layer: syn::Ident,
+        derives: Vec<syn::Path>,
+        impl_serde: bool,
         impl_default: bool,
         impl_from_env: bool,
+        impl_render_tree: bool,
+        impl_env_export: bool,
+        impl_degradable: bool,
+        assert_send_sync: bool,
+        impl_getters: bool,
+        private_module: bool,
+        /// `complete` stops at the first error, see `#[layer(fail_fast)]`
+        fail_fast: bool,
+        /// See `#[layer(file_rename_all = "...")]`
+        file_rename_all: Option<String>,
         fields: Vec<IrField>,
+        references: Vec<IrReference>,
+        /// The layer has a lifetime, see `#[layer(borrow)]`
+        borrowed: bool,
+        /// Path to the crate, `::soukousei` unless overridden with `#[layer(crate = "...")]`
+        krate: syn::Path,
+    }
+
+    struct IrReference {
+        field: Vec<syn::Ident>,
+        section: Vec<syn::Ident>,
+        section_name: String,
     }
 
+    // built once per field while expanding, so the size doesn't matter
+    #[allow(clippy::large_enum_variant)]
     enum IrField {
         Plain {
             id: syn::Ident,
+            vis: syn::Visibility,
             ty: syn::Type,
             default: Option<syn::Expr>,
+            /// Default expression as written in the attribute
+            default_src: Option<String>,
             env: Option<LayerParamEnv>,
             is_optional: bool,
+            /// An optional field with a default. It is `Option<Option<T>>` in the layer, so that
+            /// an explicit null overrides the default, while an absent key does not.
+            nullable: bool,
+            secret: bool,
+            /// Also loaded from a file, see `#[layer(sensitive_file)]`
+            sensitive_file: bool,
+            /// Replaced as a whole on merge, see `#[layer(opaque)]`
+            opaque: bool,
+            /// Parsed from ENV with `EnvEnum`, see `#[layer(env_enum)]`
+            env_enum: bool,
+            /// `Cow<'a, str>` in the layer, see `#[layer(borrow)]`
+            borrow: bool,
+            /// Custom parser, see `#[layer(parse = "...")]`
+            parse: Option<syn::Path>,
+            /// `env` is read at build time, see `#[layer(compile_time)]`
+            compile_time: bool,
+            /// Checked on completion, see `#[layer(range = "...")]`
+            range: Option<syn::Expr>,
+            /// Range as written in the attribute
+            range_src: Option<String>,
+            /// Merged with a custom function, see `#[layer(merge_with = "...")]`
+            merge_with: Option<syn::Path>,
+            /// Exported as a telemetry attribute, see `#[layer(telemetry)]`
+            telemetry: bool,
+            doc: Option<String>,
+            ui: IrFieldUi,
+            key: String,
+            /// Key of the sibling field with a path to a file, see `#[layer(sensitive_file)]`
+            key_file: String,
         },
         NestedLayer {
             id: syn::Ident,
-            layer_ty: syn::Type,
+            vis: syn::Visibility,
+            ty: syn::Type,
+            telemetry: bool,
+            /// A `Toggle<T>` section, see `#[layer(toggle)]`
+            toggle: bool,
+            /// Prefix of ENV vars of elements, see `#[layer(env_indexed = "...")]`
+            env_indexed: Option<String>,
+            doc: Option<String>,
+            ui: IrFieldUi,
+            key: String,
+        },
+        /// A map capturing unknown keys, see `#[layer(flatten)]`
+        CatchAll {
+            id: syn::Ident,
+            vis: syn::Visibility,
+            ty: syn::Type,
+            doc: Option<String>,
+            ui: IrFieldUi,
+            key: String,
         },
     }
 
-    impl IrField {
-        fn codegen_new(&self) -> TokenStream {
-            match self {
-                Self::Plain { id, .. } => quote! { #id: None },
-                Self::NestedLayer { id, .. } => quote! { #id: ::soukousei::Layer::new() },
+    /// Presentation hints, see `#[layer(group = "...", order = ..., visibility = "...")]`
+    struct IrFieldUi {
+        group: Option<String>,
+        order: Option<i32>,
+        visibility: Option<String>,
+    }
+
+    impl IrFieldUi {
+        fn codegen(&self) -> TokenStream {
+            let group = match &self.group {
+                Some(group) => quote! { ::core::option::Option::Some(#group) },
+                None => quote! { ::core::option::Option::None },
+            };
+            let order = match self.order {
+                Some(order) => quote! { ::core::option::Option::Some(#order) },
+                None => quote! { ::core::option::Option::None },
+            };
+            let visibility = match &self.visibility {
+                Some(visibility) => quote! { ::core::option::Option::Some(#visibility) },
+                None => quote! { ::core::option::Option::None },
+            };
+            quote! {
+                group: #group,
+                order: #order,
+                visibility: #visibility,
             }
         }
+    }
 
-        fn codegen_merge(&self) -> TokenStream {
-            match self {
-                Self::Plain { id, .. } => quote! { #id: other.#id.or(self.#id) },
-                Self::NestedLayer { id, .. } => {
-                    quote! { #id: ::soukousei::Layer::merge(self.#id, other.#id) }
+    impl TryFrom<LayerField> for IrField {
+        type Error = miette::Report;
+
+        fn try_from(value: LayerField) -> Result<Self> {
+            let field = match value {
+                LayerField::Nested {
+                    base:
+                        LayerFieldBase {
+                            ident,
+                            vis,
+                            ty,
+                            doc,
+                            telemetry,
+                            group,
+                            order,
+                            visibility,
+                        },
+                    toggle,
+                    env_indexed,
+                } => {
+                    if toggle && env_indexed.is_some() {
+                        return Err(miette!(
+                            "`{ident}`: `env_indexed` is only supported for collections, not for `toggle` sections"
+                        ));
+                    }
+                    Self::NestedLayer {
+                        key: ident.to_string(),
+                        id: ident,
+                        vis,
+                        ty,
+                        telemetry,
+                        toggle,
+                        env_indexed,
+                        doc,
+                        ui: IrFieldUi {
+                            group,
+                            order,
+                            visibility,
+                        },
+                    }
                 }
-            }
+                LayerField::CatchAll {
+                    base:
+                        LayerFieldBase {
+                            ident,
+                            vis,
+                            ty,
+                            doc,
+                            telemetry,
+                            group,
+                            order,
+                            visibility,
+                        },
+                } => {
+                    if telemetry {
+                        return Err(miette!(
+                            "`{ident}`: `flatten` fields cannot be exported with `telemetry`"
+                        ));
+                    }
+                    if !is_map(&ty) {
+                        return Err(miette!(
+                            "`{ident}`: `flatten` is only supported for `HashMap` and `BTreeMap`"
+                        ));
+                    }
+                    Self::CatchAll {
+                        key: ident.to_string(),
+                        id: ident,
+                        vis,
+                        ty,
+                        doc,
+                        ui: IrFieldUi {
+                            group,
+                            order,
+                            visibility,
+                        },
+                    }
+                }
+                LayerField::Field {
+                    base:
+                        LayerFieldBase {
+                            ident,
+                            vis,
+                            ty,
+                            doc,
+                            telemetry,
+                            group,
+                            order,
+                            visibility,
+                        },
+                    default,
+                    env,
+                    secret,
+                    sensitive_file,
+                    opaque,
+                    env_enum,
+                    borrow,
+                    parse,
+                    compile_time,
+                    range,
+                    merge_with,
+                } => {
+                    let is_optional = ty.is_option_already();
+                    if merge_with.is_some() && sensitive_file {
+                        return Err(miette!(
+                            "`{ident}`: `merge_with` cannot be combined with `sensitive_file`"
+                        ));
+                    }
+                    if telemetry && secret {
+                        return Err(miette!(
+                            "`{ident}`: `secret` fields cannot be exported with `telemetry`"
+                        ));
+                    }
+                    if borrow {
+                        let ty_name = type_name(&ty);
+                        if ty_name != "String" && ty_name != "Option<String>" {
+                            return Err(miette!(
+                                "`{ident}`: `borrow` is only supported for `String` and `Option<String>`"
+                            ));
+                        }
+                        if sensitive_file || opaque || env_enum {
+                            return Err(miette!(
+                                "`{ident}`: `borrow` cannot be combined with `sensitive_file`, `opaque` or `env_enum`"
+                            ));
+                        }
+                        if is_optional && default.is_some() {
+                            return Err(miette!(
+                                "`{ident}`: `borrow` cannot be combined with `default` on an `Option` field"
+                            ));
+                        }
+                    }
+                    if parse.is_some() {
+                        if env_enum || borrow || opaque {
+                            return Err(miette!(
+                                "`{ident}`: `parse` cannot be combined with `env_enum`, `borrow` or `opaque`"
+                            ));
+                        }
+                        if is_optional && default.is_some() {
+                            return Err(miette!(
+                                "`{ident}`: `parse` cannot be combined with `default` on an `Option` field"
+                            ));
+                        }
+                    }
+                    if compile_time {
+                        if !matches!(env, Some(LayerParamEnv::Single(_))) {
+                            return Err(miette!(
+                                "`{ident}`: `compile_time` requires a single `env` var"
+                            ));
+                        }
+                        if borrow || opaque || sensitive_file || secret {
+                            return Err(miette!(
+                                "`{ident}`: `compile_time` cannot be combined with `borrow`, `opaque`, `sensitive_file` or `secret`"
+                            ));
+                        }
+                        if is_optional && default.is_some() {
+                            return Err(miette!(
+                                "`{ident}`: `compile_time` cannot be combined with `default` on an `Option` field"
+                            ));
+                        }
+                    }
+                    if range.is_some() {
+                        if sensitive_file || borrow || opaque || env_enum {
+                            return Err(miette!(
+                                "`{ident}`: `range` cannot be combined with `sensitive_file`, `borrow`, `opaque` or `env_enum`"
+                            ));
+                        }
+                        if is_optional && default.is_some() {
+                            return Err(miette!(
+                                "`{ident}`: `range` cannot be combined with `default` on an `Option` field"
+                            ));
+                        }
+                    }
+                    let range_src = range;
+                    let range = range_src
+                        .as_deref()
+                        .map(|x| match syn::parse_str(x) {
+                            Ok(expr @ syn::Expr::Range(_)) => Ok(expr),
+                            _ => Err(miette!(
+                                "`{ident}`: `range` should be a range, e.g. `\"1..=65535\"`"
+                            )),
+                        })
+                        .transpose()?;
+                    if env_enum && env.is_none() {
+                        return Err(miette!("`{ident}`: `env_enum` requires `env`"));
+                    }
+                    if opaque && (env.is_some() || sensitive_file) {
+                        return Err(miette!(
+                            "`{ident}`: `opaque` fields are deserialized as a whole and cannot be combined with `env` or `sensitive_file`"
+                        ));
+                    }
+                    let default = match default {
+                        None if opaque && !is_optional => Some("Default::default()".to_owned()),
+                        default => default,
+                    };
+                    if is_optional {
+                        match default.as_deref().map(str::trim) {
+                            Some("None") => {
+                                return Err(miette!(
+                                    "`{ident}`: `default = \"None\"` is redundant, as `Option` fields are empty unless set"
+                                ))
+                            }
+                            Some(_) if sensitive_file => {
+                                return Err(miette!(
+                                    "`{ident}`: `default` cannot be combined with `sensitive_file` on an `Option` field"
+                                ))
+                            }
+                            _ => {}
+                        }
+                    }
+
+                    Self::Plain {
+                        default: default
+                            .as_ref()
+                            .map(|x| syn::parse_str(x))
+                            .transpose()
+                            .into_diagnostic()?,
+                        nullable: is_optional && default.is_some(),
+                        default_src: match (compile_time, env.as_ref(), default) {
+                            (true, Some(env), Some(default)) => {
+                                Some(format!("`{}` at build time, or {default}", env.names()[0]))
+                            }
+                            (true, Some(env), None) => {
+                                Some(format!("`{}` at build time", env.names()[0]))
+                            }
+                            (_, _, default) => default,
+                        },
+                        env,
+                        is_optional,
+                        secret,
+                        sensitive_file,
+                        opaque,
+                        env_enum,
+                        borrow,
+                        parse,
+                        compile_time,
+                        range,
+                        range_src,
+                        merge_with,
+                        telemetry,
+                        doc,
+                        ui: IrFieldUi {
+                            group,
+                            order,
+                            visibility,
+                        },
+                        key: ident.to_string(),
+                        key_file: file_id(&ident).to_string(),
+                        id: ident,
+                        vis,
+                        ty,
+                    }
+                }
+            };
+            Ok(field)
         }
+    }
 
-        fn codegen_default(&self) -> TokenStream {
-            match self {
-                Self::Plain {
-                    id,
-                    default: Some(default),
-                    ..
-                } => quote! { #id: Some(#default) },
-                Self::Plain {
-                    id,
-                    // FIXME: how to match for `None`, and not bind to variable `None`?
-                    default: Option::None,
-                    ..
-                } => quote! { #id: None },
-                Self::NestedLayer { id, .. } => quote! { #id: Default::default() },
-            }
+    /// Whether the type of a nested field of `ident` mentions it, e.g. `Self`, `Box<Config>`
+    /// or `Vec<Self>`. Such layers would be infinite, and so would be their `FIELDS`.
+    fn nests_itself(ty: &syn::Type, ident: &syn::Ident) -> bool {
+        let syn::Type::Path(syn::TypePath { qself: None, path }) = ty else {
+            return false;
+        };
+        if path.segments.len() == 1 && path.segments[0].ident == *ident {
+            return true;
         }
+        path.segments.iter().any(|segment| {
+            segment.ident == "Self"
+                || match &segment.arguments {
+                    syn::PathArguments::AngleBracketed(args) => {
+                        args.args.iter().any(|arg| match arg {
+                            syn::GenericArgument::Type(ty) => nests_itself(ty, ident),
+                            _ => false,
+                        })
+                    }
+                    _ => false,
+                }
+        })
     }
 
-    impl Ir {
-        pub fn from_args(args: LayerArgs) -> Result<Self> {
-            let ident_main = args.ident.clone();
-            let ident_layer = paste::paste! { [<#ident_main Layer>] };
+    /// Syntactic check of a `#[layer(flatten)]` field type, e.g. `HashMap<String, String>`
+    fn is_map(ty: &syn::Type) -> bool {
+        let syn::Type::Path(syn::TypePath { qself: None, path }) = ty else {
+            return false;
+        };
+        path.segments
+            .last()
+            .map(|segment| segment.ident == "HashMap" || segment.ident == "BTreeMap")
+            .unwrap_or(false)
+    }
 
-            let fields = args
-                .data
-                .take_struct()
-                .ok_or_else(|| miette!("not a struct"))?
-                .fields
-                .into_iter()
-                .map(|field_args| todo!());
+    /// Layer type of a nested field, i.e. `<T as HasLayer>::Layer`
+    fn nested_layer_ty(ty: &syn::Type, krate: &syn::Path) -> syn::Type {
+        syn::parse_quote_spanned! {ty.span()=>
+            <#ty as #krate::HasLayer>::Layer
+        }
+    }
 
-            todo!()
+    /// The single type argument of `ty`, if it is `name<T>`, e.g. `T` of `Vec<T>`
+    fn type_arg<'a>(ty: &'a syn::Type, name: &str) -> Option<&'a syn::Type> {
+        let syn::Type::Path(syn::TypePath { qself: None, path }) = ty else {
+            return None;
+        };
+        let segment = path.segments.last().filter(|x| x.ident == name)?;
+        let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+            return None;
+        };
+        match args.args.iter().collect::<Vec<_>>().as_slice() {
+            [syn::GenericArgument::Type(arg)] => Some(arg),
+            _ => None,
         }
+    }
 
-        pub fn codegen(&self) -> TokenStream {
-            let fields_new = self.codegen_new_fields();
+    /// Borrowed form of an owned type and the method to get it, e.g. `&str` and `as_str` for
+    /// `String`, for getters
+    fn borrowed_type(ty: &syn::Type) -> Option<(TokenStream, TokenStream)> {
+        if let Some(item) = type_arg(ty, "Vec") {
+            return Some((quote! { &[#item] }, quote! { as_slice }));
+        }
+        match type_name(ty).as_str() {
+            "String" | "std::string::String" | "::std::string::String" => {
+                Some((quote! { &str }, quote! { as_str }))
+            }
+            "PathBuf" | "std::path::PathBuf" | "::std::path::PathBuf" => {
+                Some((quote! { &::std::path::Path }, quote! { as_path }))
+            }
+            _ => None,
+        }
+    }
 
-            let fields_merge = self.codegen_fields_merge();
+    /// Crate path as a string, for serde attributes
+    fn path_str(path: &syn::Path) -> String {
+        quote!(#path).to_string().replace(' ', "")
+    }
 
-            let mut tokens = quote! {
-                impl ::soukousei::HasLayer for #self.ident_main {
-                    type Layer = #self.ident_layer;
-                }
+    /// Construct a struct with `fields`, matching required values out of their options, so that
+    /// generated code doesn't panic. Errors are accumulated before, so the `fallback` arm is
+    /// never reached in practice.
+    fn construct(
+        ctor: TokenStream,
+        fields: Vec<(Option<(TokenStream, syn::Ident)>, TokenStream)>,
+        fallback: TokenStream,
+    ) -> TokenStream {
+        let mut scrutinees = Vec::new();
+        let mut patterns = Vec::new();
+        let mut values = Vec::new();
+        for (scrutinee, value) in fields {
+            if let Some((scrutinee, id)) = scrutinee {
+                scrutinees.push(scrutinee);
+                patterns.push(quote! { ::core::option::Option::Some(#id) });
+            }
+            values.push(value);
+        }
 
-                impl ::soukousei::Layer for #self.ident_layer {
-                    type Complete = #self.ident_main;
+        if scrutinees.is_empty() {
+            return quote! { #ctor { #(#values),* } };
+        }
+        quote! {
+            match (#(#scrutinees,)*) {
+                (#(#patterns,)*) => #ctor { #(#values),* },
+                _ => #fallback,
+            }
+        }
+    }
 
-                    fn new() -> Self {
-                        Self {
-                            #fields_new
-                        }
-                    }
+    /// Ident of the sibling field with a path to a file, see `#[layer(sensitive_file)]`
+    fn file_id(id: &syn::Ident) -> syn::Ident {
+        format_ident!("{}_file", id)
+    }
 
-                    fn merge(self, other: Self) -> Self {
-                        Self {
-                            #fields_merge
-                        }
-                    }
+    /// Field idents of a dot-separated path, see `#[layer(reference(...))]`
+    fn parse_path(path: &str) -> Result<Vec<syn::Ident>> {
+        path.split('.')
+            .map(|seg| {
+                syn::parse_str(seg).map_err(|_| miette!("`{path}` is not a valid field path"))
+            })
+            .collect()
+    }
 
-                    fn complete(self) -> Result<Self::Complete, ::soukousei::CompleteError> {
-                        // TODO
-                    }
-                }
-            };
+    /// `PathBuf` and `OsString` fields are fetched from ENV without requiring UTF-8
+    fn is_os_string(ty: &syn::Type) -> bool {
+        let name = type_name(ty);
+        let name = name
+            .strip_prefix("Option<")
+            .and_then(|x| x.strip_suffix('>'))
+            .unwrap_or(&name);
+        let last = name.rsplit("::").next().unwrap_or(name);
+        last == "PathBuf" || last == "OsString"
+    }
 
-            if self.impl_default {
-                let fields_default = self.codegen_fields_default();
+    /// ENV vars with a path to a file, see `#[layer(sensitive_file)]`
+    fn file_env(env: &Option<LayerParamEnv>) -> Vec<String> {
+        env.as_ref()
+            .map(|x| x.names())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|x| format!("{x}_FILE"))
+            .collect()
+    }
 
-                tokens.extend(quote! {
-                   impl Default for #self.ident_layer {
-                        fn default() -> Self {
-                            Self {
-                                #fields_default
-                            }
-                        }
-                    }
-                });
+    impl IrField {
+        /// Name of the field in files, metadata and error paths, see
+        /// `#[layer(file_rename_all = "...")]`
+        fn key(&self) -> String {
+            let (Self::Plain { key, .. }
+            | Self::NestedLayer { key, .. }
+            | Self::CatchAll { key, .. }) = self;
+            key.clone()
+        }
+
+        /// Same as [`Self::key`], but of the `sensitive_file` sibling
+        fn key_file(&self) -> String {
+            match self {
+                Self::Plain { key_file, .. } => key_file.clone(),
+                Self::NestedLayer { key, .. } | Self::CatchAll { key, .. } => format!("{key}_file"),
             }
+        }
 
-            if self.impl_from_env {
-                tokens.extend(quote! {
-                    impl ::soukousei::FromEnv for #self.ident_layer {
-                        // TODO
+        fn id(&self) -> &syn::Ident {
+            let (Self::Plain { id, .. } | Self::NestedLayer { id, .. } | Self::CatchAll { id, .. }) =
+                self;
+            id
+        }
+
+        /// Apply naming conventions of the struct, see `#[layer(file_rename_all = "...")]`,
+        /// `#[layer(env_rename_all = "...")]` and `#[layer(env_prefix = "...")]`
+        fn rename(
+            &mut self,
+            file: Option<RenameRule>,
+            env_rule: Option<RenameRule>,
+            env_prefix: &str,
+        ) {
+            let name = self.id().to_string();
+            if let Some(file) = file {
+                let renamed = file.apply(&name);
+                match self {
+                    Self::Plain { key, key_file, .. } => {
+                        *key = renamed;
+                        *key_file = file.apply(&format!("{name}_file"));
                     }
-                })
+                    Self::NestedLayer { key, .. } | Self::CatchAll { key, .. } => *key = renamed,
+                }
+            }
+            if let (
+                Some(rule),
+                Self::Plain {
+                    env: field_env @ None,
+                    opaque: false,
+                    ..
+                },
+            ) = (env_rule, self)
+            {
+                *field_env = Some(LayerParamEnv::Single(format!(
+                    "{env_prefix}{}",
+                    rule.apply(&name)
+                )));
             }
-
-            tokens
         }
 
-        fn codegen_new_fields(&self) -> TokenStream {
-            let fields: Vec<_> = self.fields.iter().map(|x| x.codegen_new()).collect();
+        /// A getter and a `with_*` setter of the field on the complete type, see
+        /// `#[layer(getters)]`
+        fn codegen_accessors(&self, vis: &syn::Visibility) -> TokenStream {
+            let (Self::Plain { id, ty, doc, .. }
+            | Self::NestedLayer { id, ty, doc, .. }
+            | Self::CatchAll { id, ty, doc, .. }) = self;
 
+            let (ret, value) = match type_arg(ty, "Option") {
+                Some(inner) => match borrowed_type(inner) {
+                    Some((ret, _)) => (
+                        quote! { ::core::option::Option<#ret> },
+                        quote! { self.#id.as_deref() },
+                    ),
+                    None => (
+                        quote! { ::core::option::Option<&#inner> },
+                        quote! { self.#id.as_ref() },
+                    ),
+                },
+                None => match borrowed_type(ty) {
+                    Some((ret, method)) => (ret, quote! { self.#id.#method() }),
+                    None => (quote! { &#ty }, quote! { &self.#id }),
+                },
+            };
+            let doc = doc.iter();
+            let with = format_ident!("with_{}", id);
+            let with_doc = format!("Replace `{id}`");
             quote! {
-                #(#fields),*
+                #(#[doc = #doc])*
+                #vis fn #id(&self) -> #ret {
+                    #value
+                }
+
+                #[doc = #with_doc]
+                #vis fn #with(mut self, value: #ty) -> Self {
+                    self.#id = value;
+                    self
+                }
             }
         }
 
-        fn codegen_fields_merge(&self) -> TokenStream {
-            let fields: Vec<_> = self.fields.iter().map(|x| x.codegen_merge()).collect();
-
-            quote! {
-                #(#fields),*
+        fn codegen_layer_field(&self, impl_serde: bool, krate: &syn::Path) -> TokenStream {
+            match self {
+                Self::Plain {
+                    id,
+                    vis,
+                    ty,
+                    nullable: true,
+                    ..
+                } => {
+                    let serde_attrs = impl_serde.then(|| {
+                        let deserialize_nullable =
+                            format!("{}::source::deserialize_nullable", path_str(krate));
+                        quote! {
+                            #[serde(
+                                default,
+                                deserialize_with = #deserialize_nullable
+                            )]
+                        }
+                    });
+                    quote! {
+                        #serde_attrs
+                        #vis #id: ::core::option::Option<#ty>
+                    }
+                }
+                Self::Plain {
+                    id,
+                    vis,
+                    borrow: true,
+                    ..
+                } => {
+                    let serde_attrs = impl_serde.then(|| {
+                        let deserialize_borrowed =
+                            format!("{}::source::deserialize_borrowed", path_str(krate));
+                        quote! {
+                            #[serde(
+                                borrow,
+                                default,
+                                deserialize_with = #deserialize_borrowed
+                            )]
+                        }
+                    });
+                    quote! {
+                        #serde_attrs
+                        #vis #id: ::core::option::Option<::std::borrow::Cow<'a, str>>
+                    }
+                }
+                Self::Plain {
+                    id,
+                    vis,
+                    ty,
+                    is_optional,
+                    parse: Some(parse),
+                    ..
+                } => {
+                    let serde_attrs = impl_serde.then(|| {
+                        let parse = path_str(parse);
+                        let krate = path_str(krate);
+                        let deserialize =
+                            format!("{krate}::parse::deserialize_option::<{parse}, _>");
+                        let serialize = format!("{krate}::parse::serialize_option::<{parse}, _>");
+                        quote! {
+                            #[serde(
+                                default,
+                                deserialize_with = #deserialize,
+                                serialize_with = #serialize
+                            )]
+                        }
+                    });
+                    let ty = if *is_optional {
+                        quote! { #ty }
+                    } else {
+                        quote! { ::core::option::Option<#ty> }
+                    };
+                    quote! {
+                        #serde_attrs
+                        #vis #id: #ty
+                    }
+                }
+                Self::Plain {
+                    id,
+                    vis,
+                    ty,
+                    is_optional,
+                    sensitive_file,
+                    ..
+                } => {
+                    let ty = if *is_optional {
+                        quote! { #ty }
+                    } else {
+                        quote! { ::core::option::Option<#ty> }
+                    };
+                    let file = sensitive_file.then(|| {
+                        let id_file = file_id(id);
+                        quote! { , #vis #id_file: ::core::option::Option<::std::path::PathBuf> }
+                    });
+                    quote! { #vis #id: #ty #file }
+                }
+                Self::NestedLayer { id, vis, ty, .. } if impl_serde => {
+                    let layer_ty = nested_layer_ty(ty, krate);
+                    let new = format!("{}::Layer::new", path_str(krate));
+                    quote! {
+                        #[serde(default = #new)]
+                        #vis #id: #layer_ty
+                    }
+                }
+                Self::NestedLayer { id, vis, ty, .. } => {
+                    let layer_ty = nested_layer_ty(ty, krate);
+                    quote! { #vis #id: #layer_ty }
+                }
+                Self::CatchAll { id, vis, ty, .. } if impl_serde => quote! {
+                    #[serde(flatten)]
+                    #vis #id: #ty
+                },
+                Self::CatchAll { id, vis, ty, .. } => quote! { #vis #id: #ty },
             }
         }
 
-        fn codegen_fields_default(&self) -> TokenStream {
-            let fields: Vec<_> = self.fields.iter().map(|x| x.codegen_default()).collect();
+        fn codegen_new(&self, krate: &syn::Path) -> TokenStream {
+            match self {
+                Self::Plain {
+                    id,
+                    sensitive_file: true,
+                    ..
+                } => {
+                    let id_file = file_id(id);
+                    quote! { #id: ::core::option::Option::None, #id_file: ::core::option::Option::None }
+                }
+                Self::Plain { id, .. } => quote! { #id: ::core::option::Option::None },
+                Self::NestedLayer { id, .. } => quote! { #id: #krate::Layer::new() },
+                Self::CatchAll { id, .. } => quote! { #id: ::core::default::Default::default() },
+            }
+        }
 
-            quote! {
-                #(#fields),*
+        /// Merges `other` into `self` in place. A value and a path to a file are merged as a
+        /// pair, so that a newer layer can switch from one to another.
+        fn codegen_merge_from(&self, krate: &syn::Path) -> TokenStream {
+            match self {
+                Self::Plain {
+                    id,
+                    sensitive_file: true,
+                    ..
+                } => {
+                    let id_file = file_id(id);
+                    quote! {
+                        if other.#id.is_some() || other.#id_file.is_some() {
+                            self.#id = other.#id;
+                            self.#id_file = other.#id_file;
+                        }
+                    }
+                }
+                Self::Plain {
+                    id,
+                    merge_with: Some(merge_with),
+                    ..
+                } => quote! {
+                    self.#id = #merge_with(self.#id.take(), other.#id);
+                },
+                Self::Plain { id, .. } => quote! {
+                    if other.#id.is_some() {
+                        self.#id = other.#id;
+                    }
+                },
+                Self::NestedLayer { id, .. } => {
+                    quote! { #krate::Layer::merge_from(&mut self.#id, other.#id); }
+                }
+                // keys of a newer layer override the same keys of an older one
+                Self::CatchAll { id, .. } => {
+                    quote! { ::core::iter::Extend::extend(&mut self.#id, other.#id); }
+                }
             }
         }
-    }
 
-    impl Ir {
-        // fn codegen(self) {
+        fn codegen_complete_check(&self, krate: &syn::Path) -> TokenStream {
+            let check = self.codegen_complete_check_value(krate);
+            let range = match self {
+                Self::Plain {
+                    id,
+                    range: Some(range),
+                    range_src: Some(range_src),
+                    ..
+                } => {
+                    let loc = self.key();
+                    quote! {
+                        let errors = match &self.#id {
+                            ::core::option::Option::Some(value) => {
+                                match #krate::bounded::check_range(value, #range, #range_src) {
+                                    ::core::result::Result::Ok(()) => errors,
+                                    ::core::result::Result::Err(report) => errors
+                                        .add(#krate::CompleteFieldError::Invalid(report), #loc),
+                                }
+                            }
+                            ::core::option::Option::None => errors,
+                        };
+                    }
+                }
+                _ => quote! {},
+            };
+            quote! {
+                #check
+                #range
+            }
+        }
+
+        fn codegen_complete_check_value(&self, krate: &syn::Path) -> TokenStream {
+            match self {
+                Self::Plain {
+                    id,
+                    env,
+                    is_optional,
+                    sensitive_file: true,
+                    ..
+                } => {
+                    let loc = self.key();
+                    let id_file = file_id(id);
+                    let file_key = self.key_file();
+                    let check_missing = (!is_optional).then(|| {
+                        let env = env
+                            .as_ref()
+                            .map(|x| x.names())
+                            .unwrap_or_default()
+                            .into_iter()
+                            .map(ToOwned::to_owned)
+                            .chain(file_env(env));
+                        quote! {
+                            let errors = errors.add_if_none_with_env(&value, #loc, &[#(#env),*]);
+                        }
+                    });
+                    quote! {
+                        let (#id, errors) = match #krate::sensitive_file::resolve(
+                            self.#id,
+                            self.#id_file,
+                            #file_key,
+                        ) {
+                            ::core::result::Result::Ok(value) => {
+                                #check_missing
+                                (value, errors)
+                            }
+                            ::core::result::Result::Err(report) => (
+                                ::core::option::Option::None,
+                                errors.add(#krate::CompleteFieldError::Invalid(report), #loc),
+                            ),
+                        };
+                    }
+                }
+                Self::Plain {
+                    id,
+                    env: Some(env),
+                    is_optional,
+                    compile_time: true,
+                    ..
+                } => {
+                    let loc = self.key();
+                    let name = env.names()[0];
+                    let parse = self.compile_time_parse(krate);
+                    let check_missing = (!is_optional).then(|| {
+                        quote! {
+                            let errors = errors.add_if_none_with_env(&self.#id, #loc, &[#name]);
+                        }
+                    });
+                    // the default leaves the field unset if the baked-in value fails to parse
+                    quote! {
+                        let errors = match #krate::env::compile_time_error(
+                            &self.#id,
+                            ::core::option_env!(#name),
+                            #parse,
+                        ) {
+                            ::core::option::Option::Some(report) => {
+                                errors.add(#krate::CompleteFieldError::Invalid(report), #loc)
+                            }
+                            ::core::option::Option::None => {
+                                #check_missing
+                                errors
+                            }
+                        };
+                    }
+                }
+                Self::Plain {
+                    is_optional: true, ..
+                } => quote! {},
+                Self::Plain { id, env, .. } => {
+                    let loc = self.key();
+                    let env = env.as_ref().map(|x| x.names()).unwrap_or_default();
+                    quote! {
+                        let errors = errors.add_if_none_with_env(&self.#id, #loc, &[#(#env),*]);
+                    }
+                }
+                Self::NestedLayer { id, .. } => {
+                    let loc = self.key();
+                    quote! {
+                        let (#id, errors) = #krate::ResultExt::nest_if_err(
+                            #krate::Layer::complete_with(self.#id, errors.policy()),
+                            errors,
+                            #loc,
+                        );
+                    }
+                }
+                Self::CatchAll { .. } => quote! {},
+            }
+        }
+
+        /// Field of the complete value. A required value is matched out of its option, which is
+        /// given as the first element along with the ident it is bound to, see [`construct`].
+        fn codegen_complete(&self) -> (Option<(TokenStream, syn::Ident)>, TokenStream) {
+            match self {
+                Self::Plain {
+                    id,
+                    is_optional: true,
+                    sensitive_file: true,
+                    ..
+                } => (None, quote! { #id }),
+                Self::Plain {
+                    id,
+                    sensitive_file: true,
+                    ..
+                } => (Some((quote! { #id }, id.clone())), quote! { #id }),
+                Self::Plain {
+                    id, nullable: true, ..
+                } => (None, quote! { #id: self.#id.flatten() }),
+                Self::Plain {
+                    id,
+                    is_optional: true,
+                    borrow: true,
+                    ..
+                } => (
+                    None,
+                    quote! { #id: self.#id.map(::std::borrow::Cow::into_owned) },
+                ),
+                Self::Plain {
+                    id, borrow: true, ..
+                } => (
+                    Some((quote! { self.#id }, id.clone())),
+                    quote! { #id: ::std::borrow::Cow::into_owned(#id) },
+                ),
+                Self::Plain {
+                    id,
+                    is_optional: true,
+                    ..
+                } => (None, quote! { #id: self.#id }),
+                Self::Plain { id, .. } => (Some((quote! { self.#id }, id.clone())), quote! { #id }),
+                Self::NestedLayer { id, .. } => {
+                    (Some((quote! { #id }, id.clone())), quote! { #id })
+                }
+                Self::CatchAll { id, .. } => (None, quote! { #id: self.#id }),
+            }
+        }
+
+        fn codegen_meta(&self, krate: &syn::Path) -> TokenStream {
+            let quote_option = |value: &Option<String>| match value {
+                Some(value) => quote! { ::core::option::Option::Some(#value) },
+                None => quote! { ::core::option::Option::None },
+            };
+
+            match self {
+                Self::Plain {
+                    ty,
+                    default_src,
+                    env,
+                    is_optional,
+                    secret,
+                    sensitive_file,
+                    opaque,
+                    doc,
+                    ui,
+                    compile_time,
+                    ..
+                } => {
+                    let name = self.key();
+                    let ty = type_name(ty);
+                    let doc_quoted = quote_option(doc);
+                    let ui = ui.codegen();
+                    let default = quote_option(default_src);
+                    // a compile-time var is not read at runtime
+                    let env_names = env
+                        .as_ref()
+                        .filter(|_| !compile_time)
+                        .map(|x| x.names())
+                        .unwrap_or_default();
+                    let file = sensitive_file.then(|| {
+                        let name_file = self.key_file();
+                        let doc_file = format!("Path to a file with the contents of `{name}`");
+                        let env_file = file_env(env);
+                        quote! {
+                            , #krate::meta::FieldMeta {
+                                name: #name_file,
+                                ty: "PathBuf",
+                                doc: ::core::option::Option::Some(#doc_file),
+                                default: ::core::option::Option::None,
+                                env: &[#(#env_file),*],
+                                secret: false,
+                                optional: true,
+                                opaque: false,
+                                toggle: false,
+                                catch_all: false,
+                                nested: ::core::option::Option::None,
+                                env_indexed: ::core::option::Option::None,
+                                #ui
+                            }
+                        }
+                    });
+                    quote! {
+                        #krate::meta::FieldMeta {
+                            name: #name,
+                            ty: #ty,
+                            doc: #doc_quoted,
+                            default: #default,
+                            env: &[#(#env_names),*],
+                            secret: #secret,
+                            optional: #is_optional,
+                            opaque: #opaque,
+                            toggle: false,
+                            catch_all: false,
+                            nested: ::core::option::Option::None,
+                            env_indexed: ::core::option::Option::None,
+                            #ui
+                        }
+                        #file
+                    }
+                }
+                Self::NestedLayer {
+                    ty,
+                    toggle,
+                    env_indexed,
+                    doc,
+                    ui,
+                    ..
+                } => {
+                    let layer_ty = nested_layer_ty(ty, krate);
+                    let name = self.key();
+                    let ty = type_name(ty);
+                    let doc = quote_option(doc);
+                    let env_indexed = quote_option(env_indexed);
+                    let ui = ui.codegen();
+                    quote! {
+                        #krate::meta::FieldMeta {
+                            name: #name,
+                            ty: #ty,
+                            doc: #doc,
+                            default: ::core::option::Option::None,
+                            env: &[],
+                            secret: false,
+                            optional: false,
+                            opaque: false,
+                            toggle: #toggle,
+                            catch_all: false,
+                            nested: ::core::option::Option::Some(<#layer_ty as #krate::Layer>::FIELDS),
+                            env_indexed: #env_indexed,
+                            #ui
+                        }
+                    }
+                }
+                Self::CatchAll { ty, doc, ui, .. } => {
+                    let name = self.key();
+                    let ty = type_name(ty);
+                    let doc = quote_option(doc);
+                    let ui = ui.codegen();
+                    quote! {
+                        #krate::meta::FieldMeta {
+                            name: #name,
+                            ty: #ty,
+                            doc: #doc,
+                            default: ::core::option::Option::None,
+                            env: &[],
+                            secret: false,
+                            optional: true,
+                            opaque: false,
+                            toggle: false,
+                            catch_all: true,
+                            nested: ::core::option::Option::None,
+                            env_indexed: ::core::option::Option::None,
+                            #ui
+                        }
+                    }
+                }
+            }
+        }
+
+        fn codegen_provided(&self, krate: &syn::Path) -> TokenStream {
+            match self {
+                Self::Plain {
+                    id, sensitive_file, ..
+                } => {
+                    let name = self.key();
+                    let file = sensitive_file.then(|| {
+                        let id_file = file_id(id);
+                        let name_file = self.key_file();
+                        quote! {
+                            if self.#id_file.is_some() {
+                                provided.push(::std::borrow::ToOwned::to_owned(#name_file));
+                            }
+                        }
+                    });
+                    quote! {
+                        if self.#id.is_some() {
+                            provided.push(::std::borrow::ToOwned::to_owned(#name));
+                        }
+                        #file
+                    }
+                }
+                Self::NestedLayer { id, .. } => {
+                    let name = self.key();
+                    quote! {
+                        ::core::iter::Extend::extend(&mut provided, #krate::provenance::nest(
+                            #name,
+                            #krate::Layer::provided_fields(&self.#id),
+                        ));
+                    }
+                }
+                // captured keys are siblings of the fields
+                Self::CatchAll { .. } => self.codegen_captured(krate, quote! { provided }),
+            }
+        }
+
+        /// Collects keys captured by `#[layer(flatten)]` fields into `out`, see
+        /// `Layer::captured_keys`
+        fn codegen_captured(&self, krate: &syn::Path, out: TokenStream) -> TokenStream {
+            match self {
+                Self::Plain { .. } => quote! {},
+                Self::NestedLayer { id, .. } => {
+                    let name = self.key();
+                    quote! {
+                        ::core::iter::Extend::extend(&mut #out, #krate::provenance::nest(
+                            #name,
+                            #krate::Layer::captured_keys(&self.#id),
+                        ));
+                    }
+                }
+                Self::CatchAll { id, .. } => quote! {
+                    ::core::iter::Extend::extend(
+                        &mut #out,
+                        self.#id.keys().map(::std::string::ToString::to_string),
+                    );
+                },
+            }
+        }
+
+        fn codegen_render_tree(&self) -> TokenStream {
+            let render = self.codegen_render_tree_line();
+            let (Self::Plain { ui, .. } | Self::NestedLayer { ui, .. } | Self::CatchAll { ui, .. }) =
+                self;
+            match &ui.visibility {
+                Some(visibility) => quote! {
+                    if f.reveals(#visibility) {
+                        #render
+                    }
+                },
+                None => render,
+            }
+        }
+
+        fn codegen_render_tree_line(&self) -> TokenStream {
+            match self {
+                Self::Plain { secret: true, .. } => {
+                    let name = self.key();
+                    quote! { f.secret(#name); }
+                }
+                Self::Plain { id, .. } => {
+                    let name = self.key();
+                    quote! { f.field(#name, &self.#id); }
+                }
+                Self::NestedLayer { id, .. } => {
+                    let name = self.key();
+                    quote! { f.section(#name, &self.#id); }
+                }
+                Self::CatchAll { id, .. } => {
+                    let name = self.key();
+                    quote! { f.field(#name, &self.#id); }
+                }
+            }
+        }
+
+        fn codegen_telemetry(&self, krate: &syn::Path) -> TokenStream {
+            match self {
+                Self::Plain {
+                    id,
+                    telemetry: true,
+                    is_optional: true,
+                    ..
+                } => {
+                    let name = self.key();
+                    quote! {
+                        if let ::core::option::Option::Some(value) = &self.#id {
+                            out.insert(::std::format!("{prefix}{}", #name), ::std::string::ToString::to_string(value));
+                        }
+                    }
+                }
+                Self::Plain {
+                    id,
+                    telemetry: true,
+                    ..
+                } => {
+                    let name = self.key();
+                    quote! { out.insert(::std::format!("{prefix}{}", #name), ::std::string::ToString::to_string(&self.#id)); }
+                }
+                Self::NestedLayer {
+                    id,
+                    telemetry: true,
+                    ..
+                } => {
+                    let name = self.key();
+                    quote! {
+                        #krate::telemetry::Telemetry::telemetry_attributes_into(
+                            &self.#id,
+                            &::std::format!("{prefix}{}.", #name),
+                            out,
+                        );
+                    }
+                }
+                _ => quote! {},
+            }
+        }
+
+        fn codegen_env_export(&self, krate: &syn::Path) -> TokenStream {
+            match self {
+                Self::Plain {
+                    id,
+                    env: Some(env),
+                    is_optional,
+                    secret,
+                    env_enum,
+                    parse,
+                    compile_time: false,
+                    ..
+                } => {
+                    // the first name is the one read first
+                    let name = env.names()[0];
+                    let value = if let Some(parse) = parse {
+                        quote! { <#parse as #krate::parse::FieldParser>::format(value) }
+                    } else if *env_enum {
+                        quote! { #krate::env_export::enum_value(value) }
+                    } else {
+                        quote! { #krate::env_export::EnvValue::to_env_value(value) }
+                    };
+                    let push = quote! {
+                        out.push((::std::format!("{prefix}{}", #name), #value));
+                    };
+                    let push = if *is_optional {
+                        quote! {
+                            if let ::core::option::Option::Some(value) = &self.#id {
+                                #push
+                            }
+                        }
+                    } else {
+                        quote! {
+                            let value = &self.#id;
+                            #push
+                        }
+                    };
+                    if *secret {
+                        quote! { if secrets { #push } }
+                    } else {
+                        quote! { { #push } }
+                    }
+                }
+                Self::Plain { .. } | Self::CatchAll { .. } => quote! {},
+                Self::NestedLayer { id, .. } => {
+                    quote! {
+                        #krate::env_export::EnvExport::env_vars_into(&self.#id, prefix, secrets, out);
+                    }
+                }
+            }
+        }
+
+        fn codegen_from_env(&self, krate: &syn::Path) -> TokenStream {
+            // without a parser, the value is fetched as `OsString` and converted with `From`
+            let fetch = |id: &syn::Ident,
+                         loc: String,
+                         env: Vec<String>,
+                         parse: Option<TokenStream>| {
+                if env.is_empty() {
+                    return quote! { let #id = ::core::option::Option::None; };
+                }
+                let result = match parse {
+                    Some(parse) => quote! {
+                        provider.try_fetch_multiple_and_parse(::core::iter::IntoIterator::into_iter([#(#env),*]), #parse)
+                    },
+                    None => {
+                        quote! { provider.try_fetch_multiple_os(::core::iter::IntoIterator::into_iter([#(#env),*])) }
+                    }
+                };
+                quote! {
+                    let (#id, errors) = errors.add_if_err(#loc, #result);
+                }
+            };
+
+            match self {
+                Self::Plain {
+                    id,
+                    ty,
+                    env,
+                    nullable,
+                    sensitive_file,
+                    env_enum,
+                    borrow,
+                    parse,
+                    compile_time,
+                    ..
+                } => {
+                    // a compile-time var is not read at runtime
+                    let names = env
+                        .as_ref()
+                        .filter(|_| !compile_time)
+                        .map(|x| x.names())
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(ToOwned::to_owned)
+                        .collect();
+                    let parse = if let Some(parse) = parse {
+                        Some(quote! { <#parse as #krate::parse::FieldParser>::parse })
+                    } else if *env_enum {
+                        Some(quote! { #krate::env::parse_enum })
+                    } else if is_os_string(ty) {
+                        None
+                    } else {
+                        Some(quote! { #krate::env::default_env_parse })
+                    };
+                    let value = fetch(id, self.key(), names, parse);
+                    // ENV cannot be null
+                    let nullable = nullable
+                        .then(|| quote! { let #id = #id.map(::core::option::Option::Some); });
+                    let borrow = borrow.then(|| {
+                        quote! { let #id = #id.map(|x: ::std::string::String| ::std::borrow::Cow::Owned(x)); }
+                    });
+                    let file = sensitive_file
+                        .then(|| fetch(&file_id(id), self.key_file(), file_env(env), None));
+                    quote! {
+                        #value
+                        #nullable
+                        #borrow
+                        #file
+                    }
+                }
+                Self::NestedLayer {
+                    id,
+                    env_indexed: Some(prefix),
+                    ..
+                } => {
+                    let loc = self.key();
+                    quote! {
+                        let (#id, errors) = errors.nest_if_err(
+                            #krate::collection::IndexedFromEnv::from_env_indexed(provider, #prefix),
+                            #loc,
+                        );
+                    }
+                }
+                Self::NestedLayer { id, .. } => {
+                    let loc = self.key();
+                    quote! {
+                        let (#id, errors) = errors.nest_if_err(
+                            #krate::env::FromEnv::from_env(provider),
+                            #loc,
+                        );
+                    }
+                }
+                // ENV has no unknown keys to capture
+                Self::CatchAll { id, .. } => {
+                    quote! { let #id = ::core::default::Default::default(); }
+                }
+            }
+        }
+
+        /// Same as [`Self::codegen_complete`], but for the layer read from ENV
+        fn codegen_from_env_field(&self) -> (Option<(TokenStream, syn::Ident)>, TokenStream) {
+            match self {
+                Self::Plain {
+                    id,
+                    sensitive_file: true,
+                    ..
+                } => {
+                    let id_file = file_id(id);
+                    (None, quote! { #id, #id_file })
+                }
+                Self::Plain { id, .. } | Self::CatchAll { id, .. } => (None, quote! { #id }),
+                Self::NestedLayer { id, .. } => {
+                    (Some((quote! { #id }, id.clone())), quote! { #id })
+                }
+            }
+        }
+
+        /// Value of a plain field in the default layer, if it has a default
+        fn default_value(&self, krate: &syn::Path) -> Option<TokenStream> {
+            match self {
+                Self::Plain {
+                    default,
+                    env: Some(env),
+                    compile_time: true,
+                    ..
+                } => {
+                    let name = env.names()[0];
+                    let parse = self.compile_time_parse(krate);
+                    let fallback = match default {
+                        Some(default) => quote! { ::core::option::Option::Some(#default) },
+                        None => quote! { ::core::option::Option::None },
+                    };
+                    // a value which fails to parse is left unset, and reported at completion
+                    // unless a source sets the field, see `codegen_complete_check_value`
+                    Some(quote! {
+                        match ::core::option_env!(#name) {
+                            ::core::option::Option::Some(raw) => #parse(raw).ok(),
+                            ::core::option::Option::None => #fallback,
+                        }
+                    })
+                }
+                Self::Plain {
+                    default: Some(default),
+                    nullable,
+                    borrow,
+                    ..
+                } => Some(if *borrow {
+                    quote! { ::core::option::Option::Some(::std::borrow::Cow::Owned(#default)) }
+                } else if *nullable {
+                    quote! { ::core::option::Option::Some(::core::option::Option::Some(#default)) }
+                } else {
+                    quote! { ::core::option::Option::Some(#default) }
+                }),
+                _ => None,
+            }
+        }
+
+        /// Parser of the value of a `#[layer(compile_time)]` var
+        fn compile_time_parse(&self, krate: &syn::Path) -> TokenStream {
+            match self {
+                Self::Plain {
+                    parse: Some(parse), ..
+                } => quote! { <#parse as #krate::parse::FieldParser>::parse },
+                Self::Plain { env_enum: true, .. } => quote! { #krate::env::parse_enum },
+                _ => quote! { #krate::env::default_env_parse },
+            }
+        }
+
+        fn codegen_default(&self, krate: &syn::Path) -> TokenStream {
+            match self {
+                Self::Plain {
+                    id, sensitive_file, ..
+                } => {
+                    let value = self
+                        .default_value(krate)
+                        .unwrap_or_else(|| quote! { ::core::option::Option::None });
+                    let file = sensitive_file.then(|| {
+                        let id_file = file_id(id);
+                        quote! { , #id_file: ::core::option::Option::None }
+                    });
+                    quote! { #id: #value #file }
+                }
+                Self::NestedLayer { id, .. } | Self::CatchAll { id, .. } => {
+                    quote! { #id: ::core::default::Default::default() }
+                }
+            }
+        }
+
+        /// Sets a missing value to its default, evaluating the default expression only then
+        fn codegen_fill_defaults(&self, krate: &syn::Path) -> TokenStream {
+            match self {
+                Self::NestedLayer { id, .. } => {
+                    quote! { #krate::Layer::fill_defaults(&mut self.#id); }
+                }
+                Self::CatchAll { .. } => quote! {},
+                Self::Plain {
+                    id, sensitive_file, ..
+                } => {
+                    let Some(value) = self.default_value(krate) else {
+                        return quote! {};
+                    };
+                    let file_missing = sensitive_file.then(|| {
+                        let id_file = file_id(id);
+                        quote! { && self.#id_file.is_none() }
+                    });
+                    quote! {
+                        if self.#id.is_none() #file_missing {
+                            self.#id = #value;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    impl Ir {
+        /// Errors are spanned on the field they are about, or on the struct otherwise
+        pub fn from_args(args: LayerArgs) -> syn::Result<Self> {
+            let ident_main = args.ident.clone();
+            let struct_error = |report: Report| syn::Error::new_spanned(&ident_main, report);
+            let ident_layer = format_ident!("{}Layer", ident_main);
+
+            let rename_rule = |rule: &Option<String>, attr: &str| {
+                rule.as_deref()
+                    .map(|rule| {
+                        RenameRule::from_str(rule).ok_or_else(|| {
+                            miette!(
+                                "`{attr}`: unknown naming convention `{rule}`, expected one of: {}",
+                                RenameRule::NAMES.join(", ")
+                            )
+                        })
+                    })
+                    .transpose()
+            };
+            let file_rule =
+                rename_rule(&args.file_rename_all, "file_rename_all").map_err(struct_error)?;
+            let env_rule =
+                rename_rule(&args.env_rename_all, "env_rename_all").map_err(struct_error)?;
+            let env_prefix = args.env_prefix.as_deref().unwrap_or_default();
+
+            let mut fields = args
+                .data
+                .take_struct()
+                .ok_or_else(|| struct_error(miette!("not a struct")))?
+                .fields
+                .into_iter()
+                .map(|mut field_args| {
+                    // a bare `#[layer(env)]`
+                    if let (Some(LayerParamEnv::Auto), Some(ident)) =
+                        (&field_args.env, &field_args.ident)
+                    {
+                        let rule = env_rule.unwrap_or(RenameRule::ScreamingSnake);
+                        field_args.env = Some(LayerParamEnv::Single(format!(
+                            "{env_prefix}{}",
+                            rule.apply(&ident.to_string())
+                        )));
+                    }
+                    let span = match &field_args.ident {
+                        Some(ident) => ident.span(),
+                        None => field_args.ty.span(),
+                    };
+                    let ident = field_args.ident.clone();
+                    LayerField::try_from(field_args)
+                        .map_err(|err| match ident {
+                            Some(ident) => syn::Error::new(span, format!("`{ident}`: {err}")),
+                            None => syn::Error::new(span, err),
+                        })
+                        .and_then(|field| {
+                            IrField::try_from(field).map_err(|report| syn::Error::new(span, report))
+                        })
+                })
+                .collect::<syn::Result<Vec<_>>>()?;
+
+            for field in fields.iter_mut() {
+                field.rename(file_rule, env_rule, env_prefix);
+            }
+
+            if args.private_module {
+                // private fields remain accessible in the module of the struct
+                for field in fields.iter_mut() {
+                    let (IrField::Plain { vis, .. }
+                    | IrField::NestedLayer { vis, .. }
+                    | IrField::CatchAll { vis, .. }) = field;
+                    if matches!(vis, syn::Visibility::Inherited) {
+                        *vis = syn::parse_quote!(pub(super));
+                    }
+                }
+            }
+
+            if args.getters {
+                // `layer()` is generated on the complete type anyway
+                if let Some(field) = fields.iter().find(|x| x.id() == "layer") {
+                    return Err(syn::Error::new_spanned(
+                        field.id(),
+                        format!(
+                            "`{}`: `getters` conflict with the generated `layer()`",
+                            field.id()
+                        ),
+                    ));
+                }
+            }
+
+            for field in &fields {
+                if let IrField::NestedLayer { id, ty, .. } = field {
+                    if nests_itself(ty, &ident_main) {
+                        return Err(syn::Error::new_spanned(
+                            id,
+                            format!("`{id}`: `{ident_main}` cannot nest itself, as its layer would be infinite; use `#[layer(opaque)]` for recursive data"),
+                        ));
+                    }
+                }
+            }
+
+            if let Some(IrField::CatchAll { id, .. }) = fields
+                .iter()
+                .filter(|x| matches!(x, IrField::CatchAll { .. }))
+                .nth(1)
+            {
+                return Err(syn::Error::new_spanned(
+                    id,
+                    format!("`{id}`: only a single field can capture unknown keys with `flatten`"),
+                ));
+            }
+
+            if args.degradable
+                && !fields
+                    .iter()
+                    .any(|x| matches!(x, IrField::NestedLayer { .. }))
+            {
+                return Err(struct_error(miette!(
+                    "`degradable` requires at least one `nested` field"
+                )));
+            }
+
+            let references = args
+                .references
+                .iter()
+                .map(|x| {
+                    Ok(IrReference {
+                        field: parse_path(&x.field)?,
+                        section: parse_path(&x.section)?,
+                        section_name: x.section.clone(),
+                    })
+                })
+                .collect::<Result<Vec<_>>>()
+                .map_err(struct_error)?;
+
+            Ok(Self {
+                // the layer is public within the private module, see `Self::codegen`
+                vis: if args.private_module {
+                    syn::parse_quote!(pub)
+                } else {
+                    args.vis
+                },
+                ident_main,
+                ident_layer,
+                derives: args
+                    .derive
+                    .iter()
+                    .filter(|path| {
+                        // already derived, don't duplicate
+                        args.no_serde
+                            || !path
+                                .segments
+                                .last()
+                                .map(|x| x.ident == "Serialize" || x.ident == "Deserialize")
+                                .unwrap_or(false)
+                    })
+                    .cloned()
+                    .collect(),
+                impl_serde: !args.no_serde,
+                impl_default: true,
+                impl_from_env: !args.no_env,
+                impl_render_tree: args.render_tree,
+                impl_env_export: args.env_export,
+                impl_degradable: args.degradable,
+                assert_send_sync: args.assert_send_sync,
+                impl_getters: args.getters,
+                private_module: args.private_module,
+                fail_fast: args.fail_fast,
+                file_rename_all: args.file_rename_all.clone(),
+                borrowed: fields
+                    .iter()
+                    .any(|x| matches!(x, IrField::Plain { borrow: true, .. })),
+                fields,
+                references,
+                krate: args.krate.unwrap_or_else(|| syn::parse_quote!(::soukousei)),
+            })
+        }
+
+        fn codegen_complete_value(&self) -> TokenStream {
+            let krate = &self.krate;
+            let complete = construct(
+                quote! { Self::Complete },
+                self.fields.iter().map(|x| x.codegen_complete()).collect(),
+                quote! {
+                    return ::core::result::Result::Err(#krate::CompleteError::MissingData)
+                },
+            );
+
+            if self.references.is_empty() {
+                return quote! {
+                    ::core::result::Result::Ok(#complete)
+                };
+            }
+
+            let resolve = self.codegen_resolve(false);
+
+            quote! {
+                let mut value = #complete;
+
+                let errors =
+                    #krate::MultipleFieldsError::<#krate::CompleteFieldError>::new();
+                #(#resolve)*
+                errors.result()?;
+
+                ::core::result::Result::Ok(value)
+            }
+        }
+
+        /// Resolution of references in the complete `value`. In the degraded one, nested
+        /// sections are optional, so references in or to a failed section are skipped.
+        fn codegen_resolve(&self, degraded: bool) -> Vec<TokenStream> {
+            let krate = &self.krate;
+            let is_section = |id: &syn::Ident| {
+                degraded
+                    && self
+                        .fields
+                        .iter()
+                        .any(|x| matches!(x, IrField::NestedLayer { .. }) && x.id() == id)
+            };
+            self.references
+                .iter()
+                .map(
+                    |IrReference {
+                         field,
+                         section,
+                         section_name,
+                     }| {
+                        let (first, rest) = field.split_first().expect("paths are not empty");
+                        let first_key = first.to_string();
+                        let add = match rest.split_last() {
+                            None => quote! { errors.add(err, #first_key) },
+                            Some((last, middle)) => {
+                                let last = last.to_string();
+                                let nested = middle.iter().rev().fold(
+                                    quote! { #krate::MultipleFieldsError::new().add(err, #last) },
+                                    |acc, seg| {
+                                        let seg = seg.to_string();
+                                        quote! { #krate::MultipleFieldsError::new().nest(#acc, #seg) }
+                                    },
+                                );
+                                quote! { errors.nest(#nested, #first_key) }
+                            }
+                        };
+                        let (section_first, section_rest) =
+                            section.split_first().expect("paths are not empty");
+                        if !is_section(first) && !is_section(section_first) {
+                            return quote! {
+                                let errors = match #krate::reference::Resolve::resolve(
+                                    &mut value #(.#field)*,
+                                    &value #(.#section)*,
+                                    #section_name,
+                                ) {
+                                    ::core::result::Result::Ok(()) => errors,
+                                    ::core::result::Result::Err(err) => #add,
+                                };
+                            };
+                        }
+                        let resolve = |field: TokenStream, section: TokenStream| {
+                            quote! {
+                                match #krate::reference::Resolve::resolve(
+                                    &mut (*#field) #(.#rest)*,
+                                    &(*#section) #(.#section_rest)*,
+                                    #section_name,
+                                ) {
+                                    ::core::result::Result::Ok(()) => errors,
+                                    ::core::result::Result::Err(err) => #add,
+                                }
+                            }
+                        };
+                        if first == section_first {
+                            let resolve = resolve(quote! { root }, quote! { root });
+                            return quote! {
+                                let errors = match value.#first.as_mut() {
+                                    ::core::option::Option::Some(root) => #resolve,
+                                    ::core::option::Option::None => errors,
+                                };
+                            };
+                        }
+                        let field_root = if is_section(first) {
+                            quote! { value.#first.as_mut() }
+                        } else {
+                            quote! { ::core::option::Option::Some(&mut value.#first) }
+                        };
+                        let section_root = if is_section(section_first) {
+                            quote! { value.#section_first.as_ref() }
+                        } else {
+                            quote! { ::core::option::Option::Some(&value.#section_first) }
+                        };
+                        let resolve = resolve(quote! { field }, quote! { section });
+                        quote! {
+                            let errors = match (#field_root, #section_root) {
+                                (
+                                    ::core::option::Option::Some(field),
+                                    ::core::option::Option::Some(section),
+                                ) => #resolve,
+                                _ => errors,
+                            };
+                        }
+                    },
+                )
+                .collect()
+        }
+
+        /// Error policy of `complete`, see `#[layer(fail_fast)]`
+        fn policy(&self) -> TokenStream {
+            let krate = &self.krate;
+            if self.fail_fast {
+                quote! { #krate::ErrorPolicy::FailFast }
+            } else {
+                quote! { #krate::ErrorPolicy::CollectAll }
+            }
+        }
+
+        pub fn codegen(&self) -> TokenStream {
+            let krate = &self.krate;
+            let ident_main = &self.ident_main;
+            let ident_layer = &self.ident_layer;
+            let (lt, static_lt) = if self.borrowed {
+                (quote! { <'a> }, quote! { <'static> })
+            } else {
+                (quote! {}, quote! {})
+            };
+
+            let layer_struct = self.codegen_layer_struct();
+
+            let fields_new = self.codegen_new_fields();
+
+            let fields_merge_from: Vec<_> = self
+                .fields
+                .iter()
+                .map(|x| x.codegen_merge_from(krate))
+                .collect();
+
+            let checks_complete: Vec<_> = self
+                .fields
+                .iter()
+                .map(|x| x.codegen_complete_check(krate))
+                .collect();
+
+            let complete_value = self.codegen_complete_value();
+
+            let policy = self.policy();
+
+            let fields_provided: Vec<_> = self
+                .fields
+                .iter()
+                .map(|x| x.codegen_provided(krate))
+                .collect();
+
+            // only layers which might capture keys, directly or in nested ones, override it
+            let captured_keys = self
+                .fields
+                .iter()
+                .any(|x| !matches!(x, IrField::Plain { .. }))
+                .then(|| {
+                    let fields_captured = self
+                        .fields
+                        .iter()
+                        .map(|x| x.codegen_captured(krate, quote! { captured }));
+                    quote! {
+                        fn captured_keys(&self) -> ::std::vec::Vec<::std::string::String> {
+                            let mut captured = ::std::vec::Vec::new();
+                            #(#fields_captured)*
+                            captured
+                        }
+                    }
+                });
+
+            let fields_fill_defaults: Vec<_> = self
+                .fields
+                .iter()
+                .map(|x| x.codegen_fill_defaults(krate))
+                .collect();
+
+            let fields_meta: Vec<_> = self.fields.iter().map(|x| x.codegen_meta(krate)).collect();
+
+            let mut items = layer_struct;
+            let mut tokens = quote! {
+                impl #krate::HasLayer for #ident_main {
+                    type Layer = #ident_layer #static_lt;
+                }
+
+                impl #ident_main {
+                    /// Empty layer of this config, so that it can be reached without importing
+                    /// `HasLayer`
+                    #[allow(dead_code)]
+                    pub fn layer() -> #ident_layer #static_lt {
+                        <#ident_layer #static_lt as #krate::Layer>::new()
+                    }
+                }
+
+                impl #lt #krate::Layer for #ident_layer #lt {
+                    type Complete = #ident_main;
+
+                    fn new() -> Self {
+                        Self {
+                            #fields_new
+                        }
+                    }
+
+                    #[inline]
+                    fn merge(mut self, other: Self) -> Self {
+                        #krate::Layer::merge_from(&mut self, other);
+                        self
+                    }
+
+                    #[inline]
+                    fn merge_from(&mut self, other: Self) {
+                        #(#fields_merge_from)*
+                    }
+
+                    fn fill_defaults(&mut self) {
+                        #(#fields_fill_defaults)*
+                    }
+
+                    fn complete(self) -> ::core::result::Result<Self::Complete, #krate::CompleteError> {
+                        #krate::Layer::complete_with(self, #policy)
+                    }
+
+                    fn complete_with(
+                        self,
+                        policy: #krate::ErrorPolicy,
+                    ) -> ::core::result::Result<Self::Complete, #krate::CompleteError> {
+                        let errors =
+                            #krate::MultipleFieldsError::<#krate::CompleteFieldError>::new()
+                                .with_policy(policy);
+
+                        #(
+                            #checks_complete
+                            if errors.should_stop() {
+                                return ::core::result::Result::Err(#krate::CompleteError::Fields(errors));
+                            }
+                        )*
+
+                        errors.result()?;
+
+                        #complete_value
+                    }
+
+                    const FIELDS: &'static [#krate::meta::FieldMeta] = &[#(#fields_meta),*];
+
+                    fn provided_fields(&self) -> ::std::vec::Vec<::std::string::String> {
+                        let mut provided = ::std::vec::Vec::new();
+                        #(#fields_provided)*
+                        provided
+                    }
+
+                    #captured_keys
+                }
+            };
+
+            if self.impl_default {
+                let fields_default = self.codegen_fields_default();
+
+                tokens.extend(quote! {
+                   impl #lt ::core::default::Default for #ident_layer #lt {
+                        fn default() -> Self {
+                            Self {
+                                #fields_default
+                            }
+                        }
+                    }
+                });
+            }
+
+            if self.impl_render_tree {
+                let fields_render: Vec<_> = self
+                    .fields
+                    .iter()
+                    .map(|x| x.codegen_render_tree())
+                    .collect();
+
+                tokens.extend(quote! {
+                    impl #krate::tree::RenderTree for #ident_main {
+                        fn render_fields(&self, f: &mut #krate::tree::TreeFormatter<'_>) {
+                            #(#fields_render)*
+                        }
+                    }
+                });
+            }
+
+            if self.assert_send_sync {
+                // spanned, so that the error points at the struct rather than at the generated
+                // code
+                let assert = quote::quote_spanned! {ident_main.span()=>
+                    fn assert_send_sync<
+                        T: ::core::marker::Send + ::core::marker::Sync + 'static,
+                    >() {
+                    }
+                    assert_send_sync::<#ident_main>();
+                };
+                tokens.extend(quote! {
+                    const _: fn() = || {
+                        #assert
+                    };
+                });
+            }
+
+            if self.impl_getters {
+                let vis = &self.vis;
+                let accessors = self.fields.iter().map(|x| x.codegen_accessors(vis));
+                tokens.extend(quote! {
+                    impl #ident_main {
+                        #(#accessors)*
+                    }
+                });
+            }
+
+            if self.impl_degradable {
+                let (degraded_struct, degraded_impl) = self.codegen_degradable();
+                items.extend(degraded_struct);
+                tokens.extend(degraded_impl);
+            }
+
+            if self.impl_env_export {
+                let fields_env_export: Vec<_> = self
+                    .fields
+                    .iter()
+                    .map(|x| x.codegen_env_export(krate))
+                    .collect();
+                tokens.extend(quote! {
+                    impl #krate::env_export::EnvExport for #ident_main {
+                        #[allow(unused_variables)]
+                        fn env_vars_into(
+                            &self,
+                            prefix: &str,
+                            secrets: bool,
+                            out: &mut ::std::vec::Vec<(::std::string::String, ::std::string::String)>,
+                        ) {
+                            #(#fields_env_export)*
+                        }
+                    }
+                });
+            }
+
+            let fields_telemetry: Vec<_> = self
+                .fields
+                .iter()
+                .map(|x| x.codegen_telemetry(krate))
+                .collect();
+            tokens.extend(quote! {
+                impl #krate::telemetry::Telemetry for #ident_main {
+                    #[allow(unused_variables)]
+                    fn telemetry_attributes_into(
+                        &self,
+                        prefix: &str,
+                        out: &mut ::std::collections::HashMap<::std::string::String, ::std::string::String>,
+                    ) {
+                        #(#fields_telemetry)*
+                    }
+                }
+            });
+
+            if self.impl_from_env {
+                let fetch_from_env: Vec<_> = self
+                    .fields
+                    .iter()
+                    .map(|x| x.codegen_from_env(krate))
+                    .collect();
+                let from_env = construct(
+                    quote! { Self },
+                    self.fields
+                        .iter()
+                        .map(|x| x.codegen_from_env_field())
+                        .collect(),
+                    quote! {
+                        return ::core::result::Result::Err(#krate::MultipleFieldsError::new())
+                    },
+                );
+
+                tokens.extend(quote! {
+                    impl #lt #krate::env::FromEnv for #ident_layer #lt {
+                        #[allow(unused_variables)]
+                        fn from_env(
+                            provider: &impl #krate::env::EnvProvider,
+                        ) -> ::core::result::Result<
+                            Self,
+                            #krate::MultipleFieldsError<#krate::env::FieldFromEnvError>,
+                        > {
+                            let errors = #krate::MultipleFieldsError::<
+                                #krate::env::FieldFromEnvError,
+                            >::new();
+
+                            #(#fetch_from_env)*
+
+                            errors.result()?;
+
+                            ::core::result::Result::Ok(#from_env)
+                        }
+                    }
+                })
+            }
+
+            // impls are isolated from user items, and all paths are absolute, so that user items
+            // named like `Result` or `Default` are not picked up, even under `no_implicit_prelude`
+            let tokens = quote! {
+                #items
+
+                const _: () = {
+                    #tokens
+                };
+            };
+
+            if !self.private_module {
+                return tokens;
+            }
+            // a child module sees private items of the parent, such as user types and fields,
+            // while the parent sees only the public ones of the child
+            let module = format_ident!("__soukousei_generated_{}", ident_main);
+            quote! {
+                #[doc(hidden)]
+                #[allow(non_snake_case)]
+                mod #module {
+                    #[allow(unused_imports)]
+                    use super::*;
+
+                    #tokens
+                }
+            }
+        }
+
+        /// Nested sections are completed independently, so that a failed one doesn't fail the
+        /// whole struct, see `#[layer(degradable)]`. Returns the degraded struct and the impl
+        /// of `complete_partial`.
+        fn codegen_degradable(&self) -> (TokenStream, TokenStream) {
+            let krate = &self.krate;
+            let vis = &self.vis;
+            let ident_main = &self.ident_main;
+            let ident_layer = &self.ident_layer;
+            let ident_degraded = format_ident!("{}Degraded", ident_main);
+            let lt = self.borrowed.then(|| quote! { <'a> });
+
+            let mut fields = Vec::new();
+            let mut sections = Vec::new();
+            let mut checks = Vec::new();
+            let mut values = Vec::new();
+            for field in self.fields.iter() {
+                match field {
+                    IrField::Plain { vis, id, ty, .. } | IrField::CatchAll { vis, id, ty, .. } => {
+                        fields.push(quote! { #vis #id: #ty });
+                        checks.push(field.codegen_complete_check(krate));
+                        values.push(field.codegen_complete());
+                    }
+                    IrField::NestedLayer { vis, id, ty, .. } => {
+                        let name = field.key();
+                        fields.push(quote! { #vis #id: ::core::option::Option<#ty> });
+                        sections.push(quote! {
+                            let #id = match #krate::Layer::complete_with(self.#id, policy) {
+                                ::core::result::Result::Ok(value) => ::core::option::Option::Some(value),
+                                ::core::result::Result::Err(err) => {
+                                    failed.push(#krate::degraded::FailedSection::new(#name, err));
+                                    ::core::option::Option::None
+                                }
+                            };
+                        });
+                        values.push((None, quote! { #id }));
+                    }
+                }
+            }
+            let doc = format!(
+                "[`{ident_main}`] with optional sections, see [`{ident_layer}::complete_partial`]"
+            );
+
+            let degraded_struct = quote! {
+                #[doc = #doc]
+                #vis struct #ident_degraded {
+                    #(#fields),*
+                }
+            };
+
+            let degraded = construct(
+                quote! { #ident_degraded },
+                values,
+                quote! {
+                    return ::core::result::Result::Err(#krate::CompleteError::MissingData)
+                },
+            );
+
+            let policy = self.policy();
+            let resolve = self.codegen_resolve(true);
+            let value = if resolve.is_empty() {
+                quote! { ::core::result::Result::Ok((#degraded, failed)) }
+            } else {
+                quote! {
+                    let mut value = #degraded;
+
+                    let errors =
+                        #krate::MultipleFieldsError::<#krate::CompleteFieldError>::new();
+                    #(#resolve)*
+                    errors.result()?;
+
+                    ::core::result::Result::Ok((value, failed))
+                }
+            };
+
+            let degraded_impl = quote! {
+                impl #lt #ident_layer #lt {
+                    /// Same as `complete`, but a failed nested section is reported and left empty
+                    /// instead of failing the whole config
+                    #vis fn complete_partial(
+                        self,
+                    ) -> ::core::result::Result<
+                        (#ident_degraded, ::std::vec::Vec<#krate::degraded::FailedSection>),
+                        #krate::CompleteError,
+                    > {
+                        self.complete_partial_with(#policy)
+                    }
+
+                    /// Same as `complete_partial`, but with the given error policy, which
+                    /// applies to nested sections too
+                    #vis fn complete_partial_with(
+                        self,
+                        policy: #krate::ErrorPolicy,
+                    ) -> ::core::result::Result<
+                        (#ident_degraded, ::std::vec::Vec<#krate::degraded::FailedSection>),
+                        #krate::CompleteError,
+                    > {
+                        let mut failed = ::std::vec::Vec::new();
+                        #(#sections)*
+
+                        let errors =
+                            #krate::MultipleFieldsError::<#krate::CompleteFieldError>::new()
+                                .with_policy(policy);
+                        #(
+                            #checks
+                            if errors.should_stop() {
+                                return ::core::result::Result::Err(#krate::CompleteError::Fields(errors));
+                            }
+                        )*
+                        errors.result()?;
+
+                        #value
+                    }
+                }
+            };
+
+            (degraded_struct, degraded_impl)
+        }
+
+        /// Fields are emitted in declaration order, with a `sensitive_file` sibling right after
+        /// its field. Serde serializes them in this order, and a `flatten` field emits the
+        /// captured keys at its position, so the order must not be changed.
+        fn codegen_layer_struct(&self) -> TokenStream {
+            let krate = &self.krate;
+            let vis = &self.vis;
+            let ident_layer = &self.ident_layer;
+            let lt = self.borrowed.then(|| quote! { <'a> });
+            let fields: Vec<_> = self
+                .fields
+                .iter()
+                .map(|x| x.codegen_layer_field(self.impl_serde, krate))
+                .collect();
+
+            let derives = &self.derives;
+            let derive_attrs = if derives.is_empty() {
+                quote! {}
+            } else {
+                quote! { #[derive(#(#derives),*)] }
+            };
+
+            let serde_attrs = if self.impl_serde {
+                let serde_crate = format!("{}::serde", path_str(krate));
+                let rename_all = self
+                    .file_rename_all
+                    .as_ref()
+                    .map(|rule| quote! { #[serde(rename_all = #rule)] });
+                quote! {
+                    #[derive(#krate::serde::Serialize, #krate::serde::Deserialize)]
+                    #[serde(crate = #serde_crate)]
+                    #rename_all
+                }
+            } else {
+                quote! {}
+            };
+
+            quote! {
+                #derive_attrs
+                #serde_attrs
+                #vis struct #ident_layer #lt {
+                    #(#fields),*
+                }
+            }
+        }
+
+        fn codegen_new_fields(&self) -> TokenStream {
+            let krate = &self.krate;
+            let fields: Vec<_> = self.fields.iter().map(|x| x.codegen_new(krate)).collect();
+
+            quote! {
+                #(#fields),*
+            }
+        }
+
+        fn codegen_fields_default(&self) -> TokenStream {
+            let krate = &self.krate;
+            let fields: Vec<_> = self
+                .fields
+                .iter()
+                .map(|x| x.codegen_default(krate))
+                .collect();
+
+            quote! {
+                #(#fields),*
+            }
+        }
+    }
+
+    impl Ir {
+        // fn codegen(self) {
         //     let fields_for_new = self.fields.iter().map(|field| match field {
         //         ConfigField::Field { base, .. } => {
         //             let id = &base.ident;
@@ -335,82 +2807,849 @@ mod codegen {
         //     };
         // }
     }
-}
+}
+
+#[proc_macro_derive(Layer, attributes(layer))]
+pub fn derive_layer(item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as syn::DeriveInput);
+
+    if newtype::is_newtype(&input) {
+        return match newtype::NewtypeArgs::from_derive_input(&input) {
+            Ok(args) => newtype::codegen(args).into(),
+            Err(err) => err.write_errors().into(),
+        };
+    }
+
+    let args = match LayerArgs::from_derive_input(&input) {
+        Ok(args) => args,
+        Err(err) => return err.write_errors().into(),
+    };
+
+    match codegen::Ir::from_args(args) {
+        Ok(ir) => ir.codegen().into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Implement `EnvEnum` for an enum with unit variants, so that it might be parsed from ENV
+/// case-insensitively by variant names in lowercase, see `#[layer(env_enum)]`.
+#[proc_macro_derive(EnvEnum)]
+pub fn derive_env_enum(item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as syn::DeriveInput);
+
+    let syn::Data::Enum(data) = &input.data else {
+        return syn::Error::new_spanned(&input.ident, "`EnvEnum` can only be derived for enums")
+            .to_compile_error()
+            .into();
+    };
+
+    let mut names = Vec::new();
+    let mut arms = Vec::new();
+    let mut index_arms = Vec::new();
+    for (index, variant) in data.variants.iter().enumerate() {
+        if !matches!(variant.fields, syn::Fields::Unit) {
+            return syn::Error::new_spanned(variant, "`EnvEnum` variants cannot have fields")
+                .to_compile_error()
+                .into();
+        }
+        let ident = &variant.ident;
+        names.push(ident.to_string().to_lowercase());
+        arms.push(quote::quote! { #index => Self::#ident });
+        index_arms.push(quote::quote! { Self::#ident => #index });
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    quote::quote! {
+        impl #impl_generics ::soukousei::env::EnvEnum for #ident #ty_generics #where_clause {
+            const NAMES: &'static [&'static str] = &[#(#names),*];
+
+            fn from_index(index: usize) -> Self {
+                match index {
+                    #(#arms,)*
+                    _ => ::core::panic!("variant index is out of bounds"),
+                }
+            }
+
+            fn index(&self) -> usize {
+                match self {
+                    #(#index_arms,)*
+                }
+            }
+        }
+    }
+    .into()
+}
+
+/// Embed a TOML config file into the binary, checking its keys against the layer of a config
+/// type at compile time: `include_config!(Config, "defaults.toml")`.
+///
+/// The path is relative to the crate root. Keys of map sections can't be checked, as they are
+/// dynamic.
+#[proc_macro]
+pub fn include_config(item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as embed::IncludeConfigInput);
+
+    match embed::expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{codegen, LayerArgs, LayerParamEnv};
+    use darling::FromDeriveInput;
+    use quote::quote;
+    use syn::parse_quote;
+
+    #[test]
+    fn parse_all_attributes() {
+        let input = parse_quote! {
+            #[derive(Layer)]
+            struct Test {
+                #[layer(default = "100")]
+                foo: u32,
+                bar: Option<String>,
+                #[layer(env = "ENV")]
+                baz: bool,
+                #[layer(env = ["FOO", "BAR"])]
+                foo_bar: u32,
+                #[layer(nested)]
+                nested: AnotherConfig
+            }
+        };
+
+        let parsed = LayerArgs::from_derive_input(&input).unwrap();
+
+        assert_eq!(parsed.ident.to_string(), "Test");
+
+        let mut fields = parsed.data.take_struct().unwrap().fields.into_iter();
+
+        let foo = fields.next().unwrap();
+        assert_eq!(foo.default, Some("100".to_owned()));
+        assert_eq!(foo.env, None);
+        assert!(!foo.nested);
+
+        let bar = fields.next().unwrap();
+        assert_eq!(bar.default, None);
+        assert_eq!(bar.env, None);
+        assert!(!bar.nested);
+
+        let baz = fields.next().unwrap();
+        assert_eq!(baz.default, None);
+        assert_eq!(baz.env, Some(LayerParamEnv::Single("ENV".to_owned())));
+        assert!(!baz.nested);
+
+        let foo_bar = fields.next().unwrap();
+        assert_eq!(foo_bar.default, None);
+        assert_eq!(
+            foo_bar.env,
+            Some(LayerParamEnv::Multiple(
+                ["FOO", "BAR"].into_iter().map(ToOwned::to_owned).collect()
+            ))
+        );
+        assert!(!foo_bar.nested);
+
+        let nested = fields.next().unwrap();
+        assert!(nested.nested);
+    }
+
+    #[test]
+    fn parse_no_serde() {
+        let input = parse_quote! {
+            #[derive(Layer)]
+            #[layer(no_serde)]
+            struct Test {
+                foo: u32,
+            }
+        };
+
+        let parsed = LayerArgs::from_derive_input(&input).unwrap();
+
+        assert!(parsed.no_serde);
+    }
+
+    #[test]
+    fn parse_extra_derives() {
+        let input = parse_quote! {
+            #[derive(Layer)]
+            #[layer(derive(Clone, PartialEq, serde::Serialize))]
+            struct Test {
+                foo: u32,
+            }
+        };
+
+        let parsed = LayerArgs::from_derive_input(&input).unwrap();
+        assert_eq!(parsed.derive.len(), 3);
+
+        let tokens = codegen::Ir::from_args(parsed)
+            .unwrap()
+            .codegen()
+            .to_string();
+        assert!(
+            tokens.contains(&quote! { #[derive(Clone, PartialEq)] }.to_string()),
+            "{tokens}"
+        );
+    }
+
+    #[test]
+    fn generated_code_does_not_unwrap() {
+        let input = parse_quote! {
+            #[derive(Layer)]
+            #[layer(degradable, reference(field = "tls", section = "profiles"))]
+            struct Test {
+                #[layer(default = "100", env = "FOO")]
+                foo: u32,
+                bar: Option<String>,
+                #[layer(env = "TOKEN", sensitive_file)]
+                token: String,
+                #[layer(borrow)]
+                name: String,
+                tls: Ref<Profile>,
+                #[layer(nested)]
+                profiles: BTreeMap<String, Profile>,
+            }
+        };
+
+        let ir = codegen::Ir::from_args(LayerArgs::from_derive_input(&input).unwrap()).unwrap();
+        let tokens = ir.codegen().to_string();
+
+        assert!(!tokens.contains("unwrap"), "{tokens}");
+        assert!(!tokens.contains("expect"), "{tokens}");
+    }
+
+    #[test]
+    fn crate_path_is_overridden() {
+        let input = parse_quote! {
+            #[derive(Layer)]
+            #[layer(crate = "framework::soukousei")]
+            struct Test {
+                foo: u32,
+                #[layer(nested)]
+                bar: Bar,
+            }
+        };
+
+        let ir = codegen::Ir::from_args(LayerArgs::from_derive_input(&input).unwrap()).unwrap();
+        let tokens = ir.codegen().to_string();
+
+        // the default path is `::soukousei`, so every `:: soukousei` must follow `framework`
+        assert!(
+            tokens
+                .match_indices(":: soukousei")
+                .all(|(i, _)| tokens[..i].ends_with("framework ")),
+            "{tokens}"
+        );
+        for expected in [
+            quote! { impl framework::soukousei::Layer for TestLayer },
+            quote! { bar: <Bar as framework::soukousei::HasLayer>::Layer },
+            quote! { #[serde(crate = "framework::soukousei::serde")] },
+        ] {
+            assert!(tokens.contains(&expected.to_string()), "{tokens}");
+        }
+    }
+
+    #[test]
+    fn custom_parser_is_used_for_env_and_serde() {
+        let input = parse_quote! {
+            #[derive(Layer)]
+            struct Test {
+                #[layer(env = "START", parse = "Rfc3339")]
+                start: DateTime,
+            }
+        };
+
+        let ir = codegen::Ir::from_args(LayerArgs::from_derive_input(&input).unwrap()).unwrap();
+        let tokens = ir.codegen().to_string();
+
+        for expected in [
+            quote! { <Rfc3339 as ::soukousei::parse::FieldParser>::parse },
+            quote! { deserialize_with = "::soukousei::parse::deserialize_option::<Rfc3339, _>" },
+            quote! { serialize_with = "::soukousei::parse::serialize_option::<Rfc3339, _>" },
+        ] {
+            assert!(tokens.contains(&expected.to_string()), "{tokens}");
+        }
+    }
+
+    #[test]
+    fn range_is_checked_on_completion() {
+        let input = parse_quote! {
+            #[derive(Layer)]
+            struct Test {
+                #[layer(range = "1..=65535")]
+                port: u16,
+            }
+        };
+
+        let ir = codegen::Ir::from_args(LayerArgs::from_derive_input(&input).unwrap()).unwrap();
+        let tokens = ir.codegen().to_string();
+
+        let expected = quote! {
+            ::soukousei::bounded::check_range(value, 1..=65535, "1..=65535")
+        };
+        assert!(tokens.contains(&expected.to_string()), "{tokens}");
+    }
+
+    #[test]
+    fn range_should_be_a_range() {
+        let input = parse_quote! {
+            #[derive(Layer)]
+            struct Test {
+                #[layer(range = "65535")]
+                port: u16,
+            }
+        };
+
+        let err = codegen::Ir::from_args(LayerArgs::from_derive_input(&input).unwrap())
+            .err()
+            .unwrap();
+
+        assert!(err.to_string().contains("`port`: `range`"), "{err}");
+    }
+
+    #[test]
+    fn flatten_field_captures_unknown_keys() {
+        let input = parse_quote! {
+            #[derive(Layer)]
+            struct Test {
+                port: u16,
+                #[layer(flatten)]
+                extra: HashMap<String, String>,
+            }
+        };
+
+        let tokens = codegen::Ir::from_args(LayerArgs::from_derive_input(&input).unwrap())
+            .unwrap()
+            .codegen()
+            .to_string();
+
+        for expected in [
+            quote! { #[serde(flatten)] extra: HashMap<String, String> },
+            quote! { ::core::iter::Extend::extend(&mut self.extra, other.extra); },
+            quote! { fn captured_keys(&self) },
+        ] {
+            assert!(tokens.contains(&expected.to_string()), "{tokens}");
+        }
+    }
 
-#[proc_macro_derive(Layer)]
-pub fn derive_layer(_item: TokenStream) -> TokenStream {
-    todo!()
-}
+    #[test]
+    fn unit_struct_is_trivially_complete() {
+        let input = parse_quote! {
+            #[derive(Layer)]
+            struct Reserved;
+        };
 
-#[cfg(test)]
-mod tests {
-    use crate::{LayerArgs, LayerParamEnv};
-    use darling::FromDeriveInput;
-    use expect_test::expect;
-    use quote::quote;
-    use syn::parse_quote;
+        let tokens = codegen::Ir::from_args(LayerArgs::from_derive_input(&input).unwrap())
+            .unwrap()
+            .codegen()
+            .to_string();
+
+        for expected in [
+            quote! { struct ReservedLayer {} },
+            quote! { ::core::result::Result::Ok(Self::Complete {}) },
+            quote! { const FIELDS: &'static [::soukousei::meta::FieldMeta] = &[]; },
+        ] {
+            assert!(tokens.contains(&expected.to_string()), "{tokens}");
+        }
+    }
 
     #[test]
-    fn parse_all_attributes() {
+    fn flatten_should_be_a_single_map() {
+        for input in [
+            parse_quote! {
+                #[derive(Layer)]
+                struct Test {
+                    #[layer(flatten)]
+                    extra: Vec<String>,
+                }
+            },
+            parse_quote! {
+                #[derive(Layer)]
+                struct Test {
+                    #[layer(flatten)]
+                    first: HashMap<String, String>,
+                    #[layer(flatten)]
+                    extra: BTreeMap<String, String>,
+                }
+            },
+        ] {
+            let err = codegen::Ir::from_args(LayerArgs::from_derive_input(&input).unwrap())
+                .err()
+                .unwrap();
+
+            assert!(err.to_string().contains("`extra`: "), "{err}");
+        }
+    }
+
+    #[test]
+    fn indexed_env_reads_elements() {
         let input = parse_quote! {
             #[derive(Layer)]
             struct Test {
-                #[layer(default = "100")]
+                #[layer(nested, env_indexed = "APP_UPSTREAMS")]
+                upstreams: Vec<Upstream>,
+            }
+        };
+
+        let tokens = codegen::Ir::from_args(LayerArgs::from_derive_input(&input).unwrap())
+            .unwrap()
+            .codegen()
+            .to_string();
+
+        for expected in [
+            quote! {
+                ::soukousei::collection::IndexedFromEnv::from_env_indexed(provider, "APP_UPSTREAMS")
+            },
+            quote! { env_indexed: ::core::option::Option::Some("APP_UPSTREAMS"), },
+        ] {
+            assert!(tokens.contains(&expected.to_string()), "{tokens}");
+        }
+    }
+
+    #[test]
+    fn layer_fields_keep_declaration_order() {
+        let input = parse_quote! {
+            #[derive(Layer)]
+            struct Test {
+                #[layer(nested)]
+                zeta: Section,
+                #[layer(flatten)]
+                extra: HashMap<String, String>,
+                #[layer(env = "TOKEN", sensitive_file)]
+                token: String,
+                #[layer(default = "1")]
+                alpha: Option<u32>,
+                beta: u32,
+            }
+        };
+
+        let tokens = codegen::Ir::from_args(LayerArgs::from_derive_input(&input).unwrap())
+            .unwrap()
+            .codegen()
+            .to_string();
+        let layer_struct = &tokens[tokens.find("struct TestLayer").unwrap()..];
+
+        let positions: Vec<_> = [
+            "zeta :",
+            "extra :",
+            "token :",
+            "token_file :",
+            "alpha :",
+            "beta :",
+        ]
+        .iter()
+        .map(|field| layer_struct.find(field).unwrap())
+        .collect();
+        assert!(positions.windows(2).all(|x| x[0] < x[1]), "{layer_struct}");
+    }
+
+    #[test]
+    fn file_and_env_are_renamed_independently() {
+        let input = parse_quote! {
+            #[derive(Layer)]
+            #[layer(file_rename_all = "kebab-case", env_rename_all = "SCREAMING_SNAKE_CASE")]
+            struct Test {
+                max_connections: u32,
+                #[layer(env = "TOKEN", sensitive_file)]
+                api_token: String,
+            }
+        };
+
+        let tokens = codegen::Ir::from_args(LayerArgs::from_derive_input(&input).unwrap())
+            .unwrap()
+            .codegen()
+            .to_string();
+
+        for expected in [
+            quote! { #[serde(rename_all = "kebab-case")] },
+            quote! { name: "max-connections", },
+            quote! { env: &["MAX_CONNECTIONS"], },
+            quote! { name: "api-token-file", },
+            quote! { env: &["TOKEN"], },
+        ] {
+            assert!(tokens.contains(&expected.to_string()), "{tokens}");
+        }
+    }
+
+    #[test]
+    fn unknown_naming_convention_is_an_error() {
+        let input = parse_quote! {
+            #[derive(Layer)]
+            #[layer(env_rename_all = "shouting")]
+            struct Test {
                 foo: u32,
-                bar: Option<String>,
-                #[layer(env = "ENV")]
-                baz: bool,
-                #[layer(env = ["FOO", "BAR"])]
-                foo_bar: u32,
+            }
+        };
+
+        let err = codegen::Ir::from_args(LayerArgs::from_derive_input(&input).unwrap())
+            .err()
+            .unwrap();
+
+        assert!(err.to_string().contains("`env_rename_all`"), "{err}");
+    }
+
+    #[test]
+    fn bare_env_derives_the_name() {
+        let input = parse_quote! {
+            #[derive(Layer)]
+            struct Test {
+                #[layer(env)]
+                max_connections: u32,
+                timeout: u32,
+            }
+        };
+
+        let tokens = codegen::Ir::from_args(LayerArgs::from_derive_input(&input).unwrap())
+            .unwrap()
+            .codegen()
+            .to_string();
+
+        for expected in [
+            quote! { env: &["MAX_CONNECTIONS"], },
+            quote! { name: "timeout", ty: "u32", doc: ::core::option::Option::None, default: ::core::option::Option::None, env: &[], },
+        ] {
+            assert!(tokens.contains(&expected.to_string()), "{tokens}");
+        }
+    }
+
+    #[test]
+    fn merge_with_calls_the_function() {
+        let input = parse_quote! {
+            #[derive(Layer)]
+            struct Test {
+                #[layer(merge_with = "merge::max")]
+                workers: u32,
+            }
+        };
+
+        let tokens = codegen::Ir::from_args(LayerArgs::from_derive_input(&input).unwrap())
+            .unwrap()
+            .codegen()
+            .to_string();
+
+        let expected = quote! { self.workers = merge::max(self.workers.take(), other.workers); };
+        assert!(tokens.contains(&expected.to_string()), "{tokens}");
+    }
+
+    #[test]
+    fn merge_with_is_not_for_nested() {
+        let input = parse_quote! {
+            #[derive(Layer)]
+            struct Test {
+                #[layer(nested, merge_with = "merge::max")]
+                db: Database,
+            }
+        };
+
+        let err = codegen::Ir::from_args(LayerArgs::from_derive_input(&input).unwrap())
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            err.to_string(),
+            "`db`: `nested` cannot be combined with `merge_with`"
+        );
+    }
+
+    #[test]
+    fn getters_return_references() {
+        let input = parse_quote! {
+            #[derive(Layer)]
+            #[layer(getters)]
+            pub struct Test {
+                name: String,
+                tags: Vec<String>,
+                motd: Option<String>,
+                port: u16,
+            }
+        };
+
+        let tokens = codegen::Ir::from_args(LayerArgs::from_derive_input(&input).unwrap())
+            .unwrap()
+            .codegen()
+            .to_string();
+
+        for expected in [
+            quote! { pub fn name(&self) -> &str { self.name.as_str() } },
+            quote! { pub fn tags(&self) -> &[String] { self.tags.as_slice() } },
+            quote! {
+                pub fn motd(&self) -> ::core::option::Option<&str> { self.motd.as_deref() }
+            },
+            quote! { pub fn port(&self) -> &u16 { &self.port } },
+            quote! { pub fn with_port(mut self, value: u16) -> Self { self.port = value; self } },
+        ] {
+            assert!(tokens.contains(&expected.to_string()), "{tokens}");
+        }
+    }
+
+    #[test]
+    fn private_module_hides_the_layer() {
+        let input = parse_quote! {
+            #[derive(Layer)]
+            #[layer(private_module)]
+            struct Test {
+                foo: u32,
+                pub bar: u32,
+            }
+        };
+
+        let tokens = codegen::Ir::from_args(LayerArgs::from_derive_input(&input).unwrap())
+            .unwrap()
+            .codegen()
+            .to_string();
+
+        for expected in [
+            quote! { mod __soukousei_generated_Test },
+            quote! { #[allow(unused_imports)] use super::*; },
+            quote! { pub struct TestLayer },
+            quote! { pub(super) foo: ::core::option::Option<u32> },
+        ] {
+            assert!(tokens.contains(&expected.to_string()), "{tokens}");
+        }
+    }
+
+    #[test]
+    fn newtype_layer_is_transparent() {
+        let input: syn::DeriveInput = parse_quote! {
+            #[derive(Layer)]
+            struct Rules(#[layer(nested)] Vec<Rule>);
+        };
+
+        assert!(crate::newtype::is_newtype(&input));
+        let args = crate::newtype::NewtypeArgs::from_derive_input(&input).unwrap();
+        let tokens = crate::newtype::codegen(args).to_string();
+
+        for expected in [
+            quote! { #[serde(crate = "::soukousei::serde", transparent)] },
+            quote! { struct RulesLayer(pub <Vec<Rule> as ::soukousei::HasLayer>::Layer); },
+        ] {
+            assert!(tokens.contains(&expected.to_string()), "{tokens}");
+        }
+    }
+
+    #[test]
+    fn self_nesting_is_rejected() {
+        for ty in [
+            quote! { Tree },
+            quote! { Self },
+            quote! { Toggle<Tree> },
+            quote! { Box<Self> },
+            quote! { Vec<Tree> },
+        ] {
+            let input = parse_quote! {
+                #[derive(Layer)]
+                struct Tree {
+                    #[layer(nested)]
+                    child: #ty,
+                }
+            };
+
+            let Err(err) = codegen::Ir::from_args(LayerArgs::from_derive_input(&input).unwrap())
+            else {
+                panic!("`{ty}` should be rejected");
+            };
+            assert!(err.to_string().contains("cannot nest itself"), "{err}");
+        }
+    }
+
+    #[test]
+    fn opaque_self_reference_is_accepted() {
+        let input = parse_quote! {
+            #[derive(Layer)]
+            struct Tree {
+                #[layer(opaque)]
+                children: Vec<Tree>,
                 #[layer(nested)]
-                nested: AnotherConfig
+                meta: TreeMeta,
             }
         };
 
-        let parsed = LayerArgs::from_derive_input(&input).unwrap();
+        assert!(codegen::Ir::from_args(LayerArgs::from_derive_input(&input).unwrap()).is_ok());
+    }
 
-        assert_eq!(parsed.ident.to_string(), "Test");
+    #[test]
+    fn nested_layer_type_is_projected() {
+        let input = parse_quote! {
+            #[derive(Layer)]
+            struct Test {
+                #[layer(nested)]
+                pool: Pool<Postgres>,
+                #[layer(nested)]
+                alias: NestedAlias,
+            }
+        };
 
-        let mut fields = parsed.data.take_struct().unwrap().fields.into_iter();
+        let ir = codegen::Ir::from_args(LayerArgs::from_derive_input(&input).unwrap()).unwrap();
+        let tokens = ir.codegen().to_string();
 
-        let foo = fields.next().unwrap();
-        assert_eq!(foo.default, Some("100".to_owned()));
-        assert_eq!(foo.env, None);
-        assert_eq!(foo.nested, false);
+        for expected in [
+            quote! { pool: <Pool<Postgres> as ::soukousei::HasLayer>::Layer },
+            quote! { alias: <NestedAlias as ::soukousei::HasLayer>::Layer },
+        ] {
+            assert!(tokens.contains(&expected.to_string()), "{tokens}");
+        }
+    }
 
-        let bar = fields.next().unwrap();
-        assert_eq!(bar.default, None);
-        assert_eq!(bar.env, None);
-        assert_eq!(bar.nested, false);
+    #[test]
+    fn sensitive_file_adds_sibling_field() {
+        let input = parse_quote! {
+            #[derive(Layer)]
+            struct Test {
+                #[layer(env = "PASSWORD", sensitive_file)]
+                password: String,
+            }
+        };
 
-        let baz = fields.next().unwrap();
-        assert_eq!(baz.default, None);
-        assert_eq!(baz.env, Some(LayerParamEnv::Single("ENV".to_owned())));
-        assert_eq!(baz.nested, false);
+        let ir = codegen::Ir::from_args(LayerArgs::from_derive_input(&input).unwrap()).unwrap();
+        let tokens = ir.codegen().to_string();
+
+        for expected in [
+            quote! { password_file: ::core::option::Option<::std::path::PathBuf> },
+            quote! { ["PASSWORD_FILE"] },
+        ] {
+            assert!(tokens.contains(&expected.to_string()), "{tokens}");
+        }
+    }
+
+    #[test]
+    fn redundant_default_on_option_is_an_error() {
+        let input = parse_quote! {
+            #[derive(Layer)]
+            struct Test {
+                #[layer(default = "None")]
+                foo: Option<u32>,
+            }
+        };
+
+        let err = codegen::Ir::from_args(LayerArgs::from_derive_input(&input).unwrap())
+            .err()
+            .unwrap();
+
+        assert!(err.to_string().contains("`foo`"), "{err}");
+    }
+
+    #[test]
+    fn send_sync_assertion_is_opt_in() {
+        let codegen = |input| {
+            codegen::Ir::from_args(LayerArgs::from_derive_input(&input).unwrap())
+                .unwrap()
+                .codegen()
+                .to_string()
+        };
+        let assertion = quote! { assert_send_sync::<Test>(); }.to_string();
+
+        let asserted = codegen(parse_quote! {
+            #[derive(Layer)]
+            #[layer(assert_send_sync)]
+            struct Test {
+                foo: u32,
+            }
+        });
+        let plain = codegen(parse_quote! {
+            #[derive(Layer)]
+            struct Test {
+                foo: u32,
+            }
+        });
+
+        assert!(asserted.contains(&assertion), "{asserted}");
+        assert!(!plain.contains(&assertion), "{plain}");
+    }
+
+    #[test]
+    fn collect_embedded_keys() {
+        let table: toml::Table = r#"
+            port = 8080
+
+            [nested]
+            foo = "bar"
+
+            [[servers]]
+            host = "a"
+
+            [[servers]]
+            port = 1
+        "#
+        .parse()
+        .unwrap();
 
-        let foo_bar = fields.next().unwrap();
-        assert_eq!(foo_bar.default, None);
         assert_eq!(
-            foo_bar.env,
-            Some(LayerParamEnv::Multiple(
-                ["FOO", "BAR"].into_iter().map(ToOwned::to_owned).collect()
-            ))
+            crate::embed::collect_keys(&table),
+            vec!["nested.foo", "port", "servers.host", "servers.port"]
         );
-        assert_eq!(foo_bar.nested, false);
+    }
 
-        let nested = fields.next().unwrap();
-        assert_eq!(nested.nested, true);
+    #[test]
+    fn detect_option_type() {
+        use crate::IsOption;
+
+        let optional: syn::Type = parse_quote! { Option<String> };
+        let qualified: syn::Type = parse_quote! { std::option::Option<String> };
+        let required: syn::Type = parse_quote! { String };
+
+        assert!(optional.is_option_already());
+        assert!(qualified.is_option_already());
+        assert!(!required.is_option_already());
     }
 
     #[test]
-    #[should_panic]
     fn nested_with_env_is_not_allowed() {
-        todo!()
+        let input = parse_quote! {
+            #[derive(Layer)]
+            struct Test {
+                #[layer(nested, env = "DB")]
+                db: Database,
+            }
+        };
+
+        let err = codegen::Ir::from_args(LayerArgs::from_derive_input(&input).unwrap())
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            err.to_string(),
+            "`db`: `nested` cannot be combined with `env`"
+        );
     }
 
     #[test]
-    #[should_panic]
     fn nested_with_default_is_not_allowed() {
-        todo!()
+        let input = parse_quote! {
+            #[derive(Layer)]
+            struct Test {
+                #[layer(toggle, default = "Database::new()")]
+                db: Toggle<Database>,
+            }
+        };
+
+        let err = codegen::Ir::from_args(LayerArgs::from_derive_input(&input).unwrap())
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            err.to_string(),
+            "`db`: `toggle` cannot be combined with `default`"
+        );
+    }
+
+    #[test]
+    fn env_indexed_requires_nested() {
+        let input = parse_quote! {
+            #[derive(Layer)]
+            struct Test {
+                #[layer(env_indexed = "SERVER")]
+                servers: Vec<String>,
+            }
+        };
+
+        let err = codegen::Ir::from_args(LayerArgs::from_derive_input(&input).unwrap())
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            err.to_string(),
+            "`servers`: `env_indexed` requires `nested`"
+        );
     }
 }
@@ -0,0 +1,227 @@
+//! `#[derive(Layer)]` for newtype structs, whose layers are serialized as their single field,
+//! e.g. a top-level JSON array of rules or a scalar
+
+use darling::{FromDeriveInput, FromField};
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+
+#[derive(Debug, FromDeriveInput)]
+#[darling(attributes(layer), supports(struct_newtype))]
+pub struct NewtypeArgs {
+    ident: syn::Ident,
+    vis: syn::Visibility,
+    data: darling::ast::Data<darling::util::Ignored, NewtypeFieldArgs>,
+    /// Do not derive `Serialize` and `Deserialize` for the generated layer
+    #[darling(default)]
+    no_serde: bool,
+    /// Extra derives for the generated layer
+    #[darling(default)]
+    derive: darling::util::PathList,
+    /// Do not implement `FromEnv` for the generated layer
+    #[darling(default)]
+    no_env: bool,
+    #[darling(default, rename = "crate")]
+    krate: Option<syn::Path>,
+}
+
+#[derive(Debug, FromField)]
+#[darling(attributes(layer))]
+pub struct NewtypeFieldArgs {
+    ty: syn::Type,
+    /// The field is a nested layer, e.g. `Vec<Rule>`, otherwise it is a single value
+    #[darling(default)]
+    nested: bool,
+}
+
+/// Whether the struct has a single unnamed field
+pub fn is_newtype(input: &syn::DeriveInput) -> bool {
+    matches!(
+        &input.data,
+        syn::Data::Struct(syn::DataStruct {
+            fields: syn::Fields::Unnamed(fields),
+            ..
+        }) if fields.unnamed.len() == 1
+    )
+}
+
+pub fn codegen(args: NewtypeArgs) -> TokenStream {
+    let NewtypeArgs {
+        ident,
+        vis,
+        data,
+        no_serde,
+        derive,
+        no_env,
+        krate,
+    } = args;
+    let krate = krate.unwrap_or_else(|| syn::parse_quote!(::soukousei));
+    let field = data
+        .take_struct()
+        .and_then(|x| x.fields.into_iter().next())
+        .expect("darling checks that it is a newtype struct");
+    let ident_layer = format_ident!("{}Layer", ident);
+    let ty = &field.ty;
+
+    let derives: Vec<_> = derive
+        .iter()
+        .filter(|path| {
+            no_serde
+                || !path
+                    .segments
+                    .last()
+                    .map(|x| x.ident == "Serialize" || x.ident == "Deserialize")
+                    .unwrap_or(false)
+        })
+        .collect();
+    let derive_attrs = (!derives.is_empty()).then(|| quote! { #[derive(#(#derives),*)] });
+    let serde_attrs = (!no_serde).then(|| {
+        let serde_crate = format!("{}::serde", quote!(#krate).to_string().replace(' ', ""));
+        quote! {
+            #[derive(#krate::serde::Serialize, #krate::serde::Deserialize)]
+            #[serde(crate = #serde_crate, transparent)]
+        }
+    });
+
+    let (inner_ty, impl_layer, impl_from_env) = if field.nested {
+        let inner_ty = quote! { <#ty as #krate::HasLayer>::Layer };
+        let impl_layer = quote! {
+            fn new() -> Self {
+                Self(#krate::Layer::new())
+            }
+
+            #[inline]
+            fn merge(mut self, other: Self) -> Self {
+                #krate::Layer::merge_from(&mut self, other);
+                self
+            }
+
+            #[inline]
+            fn merge_from(&mut self, other: Self) {
+                #krate::Layer::merge_from(&mut self.0, other.0)
+            }
+
+            fn fill_defaults(&mut self) {
+                #krate::Layer::fill_defaults(&mut self.0)
+            }
+
+            fn complete(self) -> ::core::result::Result<Self::Complete, #krate::CompleteError> {
+                ::core::result::Result::map(#krate::Layer::complete(self.0), #ident)
+            }
+
+            fn complete_with(
+                self,
+                policy: #krate::ErrorPolicy,
+            ) -> ::core::result::Result<Self::Complete, #krate::CompleteError> {
+                ::core::result::Result::map(#krate::Layer::complete_with(self.0, policy), #ident)
+            }
+
+            const FIELDS: &'static [#krate::meta::FieldMeta] = <#inner_ty as #krate::Layer>::FIELDS;
+
+            fn provided_fields(&self) -> ::std::vec::Vec<::std::string::String> {
+                #krate::Layer::provided_fields(&self.0)
+            }
+
+            fn captured_keys(&self) -> ::std::vec::Vec<::std::string::String> {
+                #krate::Layer::captured_keys(&self.0)
+            }
+        };
+        let impl_from_env = quote! {
+            ::core::result::Result::map(#krate::env::FromEnv::from_env(provider), Self)
+        };
+        (inner_ty, impl_layer, impl_from_env)
+    } else {
+        let inner_ty = quote! { ::core::option::Option<#ty> };
+        let impl_layer = quote! {
+            fn new() -> Self {
+                Self(::core::option::Option::None)
+            }
+
+            #[inline]
+            fn merge(mut self, other: Self) -> Self {
+                #krate::Layer::merge_from(&mut self, other);
+                self
+            }
+
+            #[inline]
+            fn merge_from(&mut self, other: Self) {
+                if ::core::option::Option::is_some(&other.0) {
+                    self.0 = other.0;
+                }
+            }
+
+            fn complete(self) -> ::core::result::Result<Self::Complete, #krate::CompleteError> {
+                match self.0 {
+                    ::core::option::Option::Some(value) => ::core::result::Result::Ok(#ident(value)),
+                    ::core::option::Option::None => {
+                        ::core::result::Result::Err(#krate::CompleteError::MissingData)
+                    }
+                }
+            }
+
+            fn provided_fields(&self) -> ::std::vec::Vec<::std::string::String> {
+                match self.0 {
+                    ::core::option::Option::Some(_) => {
+                        ::std::vec![::std::string::String::new()]
+                    }
+                    ::core::option::Option::None => ::std::vec::Vec::new(),
+                }
+            }
+        };
+        // a single value has no ENV var to be read from
+        let impl_from_env = quote! {
+            ::core::result::Result::Ok(Self(::core::option::Option::None))
+        };
+        (inner_ty, impl_layer, impl_from_env)
+    };
+
+    let from_env = (!no_env).then(|| {
+        quote! {
+            impl #krate::env::FromEnv for #ident_layer {
+                #[allow(unused_variables)]
+                fn from_env(
+                    provider: &impl #krate::env::EnvProvider,
+                ) -> ::core::result::Result<
+                    Self,
+                    #krate::MultipleFieldsError<#krate::env::FieldFromEnvError>,
+                > {
+                    #impl_from_env
+                }
+            }
+        }
+    });
+
+    quote! {
+        #derive_attrs
+        #serde_attrs
+        #vis struct #ident_layer(pub #inner_ty);
+
+        const _: () = {
+            impl #krate::HasLayer for #ident {
+                type Layer = #ident_layer;
+            }
+
+            impl #ident {
+                /// Empty layer of this config, so that it can be reached without importing
+                /// `HasLayer`
+                #[allow(dead_code)]
+                pub fn layer() -> #ident_layer {
+                    <#ident_layer as #krate::Layer>::new()
+                }
+            }
+
+            impl #krate::Layer for #ident_layer {
+                type Complete = #ident;
+
+                #impl_layer
+            }
+
+            impl ::core::default::Default for #ident_layer {
+                fn default() -> Self {
+                    Self(::core::default::Default::default())
+                }
+            }
+
+            #from_env
+        };
+    }
+}